@@ -0,0 +1,72 @@
+// Generates a synthetic library to try tap on, such as with:
+//
+//     cargo run --example gen_library -- /tmp/tap-demo --seed 7
+//     tap /tmp/tap-demo
+//
+// The library is the one that the integration tests use. See
+// 'tests/library/mod.rs' for what it contains.
+
+#[path = "../tests/library/mod.rs"]
+#[allow(dead_code)]
+mod library;
+
+use std::{env, path::PathBuf, process};
+
+use library::LibraryConfig;
+
+const USAGE: &str = "usage: gen_library <PATH> [--seed N] [--artists N] [--tracks N] \
+    [--flat N] [--depth N] [--seconds N]";
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("[gen_library error]: {e}\n{USAGE}");
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let mut args = env::args().skip(1);
+    let mut root = None;
+    let mut config = LibraryConfig::default();
+
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            root = Some(PathBuf::from(arg));
+            continue;
+        }
+        let value = args.next().ok_or(format!("'{}' needs a value", arg))?;
+        let number = || {
+            value
+                .parse::<usize>()
+                .map_err(|e| format!("{}: {}", arg, e))
+        };
+        match arg.as_str() {
+            "--seed" => config.seed = number()? as u64,
+            "--artists" => config.artists = number()?,
+            "--tracks" => config.tracks_per_album = number()?,
+            "--flat" => config.flat_tracks = number()?,
+            "--depth" => config.depth = number()?,
+            "--seconds" => config.seconds = number()? as f64,
+            _ => return Err(format!("unknown option '{}'", arg)),
+        }
+    }
+
+    let root = root.ok_or("no path given")?;
+    let is_empty = match root.read_dir() {
+        Ok(mut dir) => dir.next().is_none(),
+        Err(_) => !root.exists(),
+    };
+    if !is_empty {
+        return Err(format!("'{}' isn't an empty directory", root.display()));
+    }
+
+    let library = library::generate(&root, &config).map_err(|e| e.to_string())?;
+    println!(
+        "[gen_library]: {} albums, {} tracks and {} broken files in '{}'",
+        library.albums.len(),
+        library.tracks,
+        library.broken.len(),
+        root.display()
+    );
+    Ok(())
+}
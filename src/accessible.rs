@@ -0,0 +1,299 @@
+use std::{
+    io::{stdin, stdout, BufRead, Write},
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+use anyhow::bail;
+
+use crate::config::args;
+use crate::fuzzy::{self, FuzzyItem};
+use crate::player::{events::Receiver, Player, PlayerBuilder, PlayerEvent};
+
+// The time between polls of the player while waiting for input.
+const POLL_INTERVAL: Duration = Duration::from_millis(60);
+
+// The commands available in accessible mode.
+#[derive(Debug, PartialEq)]
+enum Command {
+    // Print all the items.
+    List,
+    // Print the items that fuzzy match the query.
+    Search(String),
+    // Play the item with the number from the last printed list.
+    Select(usize),
+    // Print the current player info.
+    Info,
+    // Play or pause the current player.
+    Pause,
+    // Stop the current player.
+    Stop,
+    // Print the available commands.
+    Help,
+    Quit,
+}
+
+impl Command {
+    fn parse(line: &str) -> Result<Self, anyhow::Error> {
+        let line = line.trim();
+        let (name, arg) = match line.split_once(char::is_whitespace) {
+            Some((name, arg)) => (name, arg.trim()),
+            None => (line, ""),
+        };
+
+        let command = match (name, arg.is_empty()) {
+            ("list", true) => Self::List,
+            ("search", false) => Self::Search(arg.to_string()),
+            ("select", false) => match arg.parse::<usize>() {
+                Ok(n) if n > 0 => Self::Select(n),
+                _ => bail!("'{arg}' is not an item number"),
+            },
+            ("info", true) => Self::Info,
+            ("pause", true) => Self::Pause,
+            ("stop", true) => Self::Stop,
+            ("help", true) => Self::Help,
+            ("quit", true) => Self::Quit,
+            ("search", true) => bail!("'search' requires a query"),
+            ("select", true) => bail!("'select' requires an item number"),
+            _ => bail!("unknown command '{line}', type 'help' for the available commands"),
+        };
+
+        Ok(command)
+    }
+}
+
+// A line-oriented interface on stdin and stdout, for use with screen
+// readers in place of the TUI.
+pub struct AccessibleMode {
+    // The items to search on.
+    items: Vec<FuzzyItem>,
+    // The items from the last printed list, numbered from one.
+    listed: Vec<FuzzyItem>,
+    // The current player, if an item has been selected.
+    player: Option<Player>,
+    // The playback events from the current player.
+    events: Option<Receiver<PlayerEvent>>,
+}
+
+impl AccessibleMode {
+    pub fn run(items: Vec<FuzzyItem>) -> Result<(), anyhow::Error> {
+        let mut mode = AccessibleMode {
+            listed: items.to_owned(),
+            items,
+            player: None,
+            events: None,
+        };
+
+        // Read stdin on a separate thread so that the player can be polled
+        // while waiting for input.
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for line in stdin().lock().lines().map_while(Result::ok) {
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        println!(
+            "[tap]: {} items, type 'help' for the available commands",
+            mode.items.len()
+        );
+
+        loop {
+            match rx.recv_timeout(POLL_INTERVAL) {
+                Ok(line) if line.trim().is_empty() => (),
+                Ok(line) => match Command::parse(&line) {
+                    Ok(Command::Quit) => return Ok(()),
+                    Ok(command) => mode.execute(command),
+                    Err(e) => println!("[tap error]: {e}"),
+                },
+                Err(RecvTimeoutError::Timeout) => {
+                    if let Some(player) = mode.player.as_mut() {
+                        player.poll();
+                    }
+                    mode.announce();
+                }
+                // Stdin was closed.
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+            stdout().flush()?;
+        }
+    }
+
+    fn execute(&mut self, command: Command) {
+        match command {
+            Command::List => {
+                self.listed = self.items.to_owned();
+                self.print_listed();
+            }
+            Command::Search(query) => self.search(&query),
+            Command::Select(n) => self.select(n),
+            Command::Info => self.info(),
+            Command::Pause => match self.player.as_mut() {
+                Some(player) => {
+                    player.play_or_pause();
+                    self.info();
+                }
+                None => println!("[tap]: nothing is playing"),
+            },
+            Command::Stop => match self.player.as_mut() {
+                Some(player) => {
+                    player.stop();
+                    self.info();
+                }
+                None => println!("[tap]: nothing is playing"),
+            },
+            Command::Help => print_help(),
+            Command::Quit => (),
+        }
+    }
+
+    // Prints the items that fuzzy match `query`, best match first.
+    fn search(&mut self, query: &str) {
+        let mut items = self.items.to_owned();
+        let root = args::match_paths().then(args::search_root);
+        let terms = fuzzy::query::parse(query);
+        let case = fuzzy::query::Case::Smart;
+        let threshold = args::threshold();
+        fuzzy::fuzzy_match(
+            &mut items,
+            &terms,
+            &case.matcher(),
+            case,
+            root.as_deref(),
+            threshold,
+        );
+        items.retain(|item| item.weight != 0);
+        items.sort_by(|a, b| {
+            b.weight
+                .cmp(&a.weight)
+                .then(a.original_index.cmp(&b.original_index))
+        });
+
+        self.listed = items;
+        self.print_listed();
+    }
+
+    // Plays the item numbered `n` in the last printed list.
+    fn select(&mut self, n: usize) {
+        let item = match self.listed.get(n - 1) {
+            Some(item) => item,
+            None => return println!("[tap error]: there is no item {n}"),
+        };
+
+        if !item.has_audio {
+            return println!(
+                "[tap error]: '{}' doesn't contain audio, search for an album instead",
+                item.display
+            );
+        }
+
+        // Drop the current player first so that two players don't overlap.
+        self.player = None;
+
+        match PlayerBuilder::new(item.path.to_owned()) {
+            Ok((mut player, _, _)) => {
+                self.events = Some(player.subscribe());
+                self.player = Some(player);
+                self.info();
+            }
+            Err(e) => println!("[tap error]: {e}"),
+        }
+    }
+
+    // Prints the changes to the current track since the last announcement.
+    fn announce(&self) {
+        let events = match &self.events {
+            Some(events) => events,
+            None => return,
+        };
+
+        while let Some(event) = events.try_recv() {
+            match event {
+                PlayerEvent::TrackStarted { .. } => self.info(),
+                PlayerEvent::AlbumEnded => println!("[tap]: finished playing"),
+                PlayerEvent::Error { msg } => println!("[tap error]: {msg}"),
+                _ => (),
+            }
+        }
+    }
+
+    // Prints the current track, playback status and elapsed time.
+    fn info(&self) {
+        let player = match &self.player {
+            Some(player) => player,
+            None => return println!("[tap]: nothing is playing"),
+        };
+
+        let file = player.file();
+        let status = match (&player.status, file.unplayable) {
+            (_, Some(reason)) => reason.reason(),
+            (status, None) => status.name(),
+        };
+
+        println!(
+            "[tap]: {} '{}' by '{}' from '{}', track {} of {}, {} of {}",
+            status,
+            file.title,
+            file.artist,
+            file.album,
+            player.index + 1,
+            player.playlist.len(),
+            mins_and_secs(player.elapsed().as_secs() as usize),
+            mins_and_secs(file.duration),
+        );
+    }
+
+    fn print_listed(&self) {
+        for (i, item) in self.listed.iter().enumerate() {
+            println!("{}: {}", i + 1, item.display);
+        }
+        println!("[tap]: {} items", self.listed.len());
+    }
+}
+
+fn print_help() {
+    println!(
+        "[tap]: commands:\n\
+        list: print all items\n\
+        search <query>: print the items that match the query\n\
+        select <n>: play item n from the last list\n\
+        info: print the current track\n\
+        pause: play or pause\n\
+        stop: stop playback\n\
+        quit: exit tap"
+    );
+}
+
+// Formats the playback time as spoken text.
+fn mins_and_secs(secs: usize) -> String {
+    format!("{} minutes {} seconds", secs / 60, secs % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!(Command::parse("list").unwrap(), Command::List);
+        assert_eq!(Command::parse("  info \n").unwrap(), Command::Info);
+        assert_eq!(
+            Command::parse("search  the  beatles").unwrap(),
+            Command::Search("the  beatles".to_string())
+        );
+        assert_eq!(Command::parse("select 12").unwrap(), Command::Select(12));
+        assert_eq!(Command::parse("quit").unwrap(), Command::Quit);
+    }
+
+    #[test]
+    fn test_parse_invalid_commands() {
+        assert!(Command::parse("select 0").is_err());
+        assert!(Command::parse("select two").is_err());
+        assert!(Command::parse("search").is_err());
+        assert!(Command::parse("list all").is_err());
+        assert!(Command::parse("play").is_err());
+    }
+}
@@ -0,0 +1,401 @@
+use std::{
+    cell::RefCell,
+    path::PathBuf,
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail};
+use arboard::Clipboard;
+
+use crate::config::args;
+use crate::utils;
+
+// The time after which a command is killed.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// The time between checks on whether a running command has exited.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+// The name of the log for commands that are run, or would be run.
+const LOG: &str = "command";
+
+thread_local! {
+    // The system clipboard, which is kept open once it has been used, since
+    // on Linux the copied text is only available while it is open.
+    static CLIPBOARD: RefCell<Option<Clipboard>> = RefCell::new(None);
+}
+
+// A user-configurable command, such as 'xdg-open {path}', where fields
+// like `{path}` are substituted before the command is run. Braces are
+// escaped as `{{` and `}}`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommandTemplate {
+    // The template, as written.
+    template: String,
+    // The words of the template, split as a shell would split them.
+    words: Vec<String>,
+    // Whether or not to run the template with 'sh -c'.
+    shell: bool,
+    // Whether or not to log the command instead of running it.
+    dry_run: bool,
+    // The time after which the command is killed.
+    timeout: Duration,
+}
+
+impl CommandTemplate {
+    pub fn parse(template: &str) -> Result<Self, anyhow::Error> {
+        let words = split_words(template)?;
+
+        if words.is_empty() {
+            bail!("the command can't be empty")
+        }
+
+        Ok(CommandTemplate {
+            template: template.to_string(),
+            words,
+            shell: false,
+            dry_run: false,
+            timeout: DEFAULT_TIMEOUT,
+        })
+    }
+
+    // Runs the template with 'sh -c', quoting the substituted fields.
+    pub fn shell(mut self, shell: bool) -> Self {
+        self.shell = shell;
+        self
+    }
+
+    // Logs the command instead of running it.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    // The program and arguments with the `fields` substituted.
+    pub fn render(&self, fields: &[(&str, &str)]) -> Result<Vec<String>, anyhow::Error> {
+        if self.shell {
+            let script = substitute(&self.template, fields, true)?;
+            return Ok(vec!["sh".into(), "-c".into(), script]);
+        }
+
+        self.words
+            .iter()
+            .map(|word| substitute(word, fields, false))
+            .collect()
+    }
+
+    // Runs the command with the `fields` substituted, waiting for it to
+    // exit. Failures are logged as well as returned.
+    pub fn run(&self, fields: &[(&str, &str)]) -> Result<(), anyhow::Error> {
+        let argv = self.render(fields)?;
+        let line = argv
+            .iter()
+            .map(|arg| quote(arg))
+            .collect::<Vec<String>>()
+            .join(" ");
+
+        if self.dry_run {
+            utils::log(LOG, &format!("dry run: {line}"));
+            return Ok(());
+        }
+
+        let result = self.execute(&argv);
+
+        if let Err(e) = &result {
+            utils::log(LOG, &format!("{line}: {e}"));
+        }
+
+        result
+    }
+
+    fn execute(&self, argv: &Vec<String>) -> Result<(), anyhow::Error> {
+        let mut child = Command::new(&argv[0])
+            .args(&argv[1..])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| anyhow!("could not run '{}'\n- `{}`", argv[0], e))?;
+
+        let start = Instant::now();
+
+        loop {
+            match child.try_wait()? {
+                Some(status) if status.success() => return Ok(()),
+                Some(status) => bail!("'{}' failed with {}", argv[0], status),
+                None if start.elapsed() >= self.timeout => {
+                    _ = child.kill();
+                    _ = child.wait();
+                    bail!(
+                        "'{}' was killed after {} seconds",
+                        argv[0],
+                        self.timeout.as_secs_f32()
+                    )
+                }
+                None => thread::sleep(POLL_INTERVAL),
+            }
+        }
+    }
+}
+
+// Copies `text` to the system clipboard.
+pub fn copy_to_clipboard(text: &str) -> Result<(), anyhow::Error> {
+    if args::dry_run() {
+        utils::log(LOG, &format!("dry run: copy {}", quote(text)));
+        return Ok(());
+    }
+
+    CLIPBOARD.with(|clipboard| {
+        let mut clipboard = clipboard.borrow_mut();
+        if clipboard.is_none() {
+            *clipboard = Some(Clipboard::new()?);
+        }
+        let clipboard = clipboard.as_mut().expect("should be open");
+        Ok(clipboard.set_text(text)?)
+    })
+}
+
+// Attempts to open the path with the default file manager.
+// Requires 'xdg-open' on linux systems. Uses 'open' on macos.
+pub fn open_file_manager(path: PathBuf) -> Result<(), anyhow::Error> {
+    let p = match std::fs::metadata(&path) {
+        Ok(meta) => match meta.is_dir() {
+            true => path,
+            // false => path.parent().unwrap().to_path_buf(),
+            false => match path.parent() {
+                Some(parent) => parent.to_path_buf(),
+                None => bail!("No parent"),
+            },
+        },
+        Err(err) => bail!(err),
+    };
+
+    let s = p
+        .as_os_str()
+        .to_str()
+        .expect("should be a valid UTF-8 path");
+
+    #[cfg(target_os = "macos")]
+    let template = "open {path}";
+
+    #[cfg(target_os = "linux")]
+    let template = "xdg-open {path}";
+
+    CommandTemplate::parse(template)?
+        .dry_run(args::dry_run())
+        .run(&[("path", s)])
+}
+
+// Splits `s` into words, handling single quotes, double quotes and
+// backslash escapes like a shell would.
+fn split_words(s: &str) -> Result<Vec<String>, anyhow::Error> {
+    let mut words = vec![];
+    let mut word: Option<String> = None;
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if let Some(word) = word.take() {
+                    words.push(word);
+                }
+            }
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => bail!("unterminated quote in '{s}'"),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => bail!("unterminated quote in '{s}'"),
+                        },
+                        Some(c) => word.push(c),
+                        None => bail!("unterminated quote in '{s}'"),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some(c) => word.get_or_insert_with(String::new).push(c),
+                None => bail!("trailing backslash in '{s}'"),
+            },
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if let Some(word) = word {
+        words.push(word);
+    }
+
+    Ok(words)
+}
+
+// Replaces the fields in `s` with their values, quoting the values
+// for the shell if `shell_quote` is true.
+fn substitute(
+    s: &str,
+    fields: &[(&str, &str)],
+    shell_quote: bool,
+) -> Result<String, anyhow::Error> {
+    let mut out = String::new();
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => bail!("unterminated field in '{s}'"),
+                    }
+                }
+
+                let value = match fields.iter().find(|(field, _)| *field == name) {
+                    Some((_, value)) => *value,
+                    None => bail!("unknown field '{{{name}}}' in '{s}'"),
+                };
+
+                match shell_quote {
+                    true => out.push_str(&quote(value)),
+                    false => out.push_str(value),
+                }
+            }
+            c => out.push(c),
+        }
+    }
+
+    Ok(out)
+}
+
+// Quotes `s` for the shell, if needed.
+fn quote(s: &str) -> String {
+    let is_safe = !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c));
+
+    match is_safe {
+        true => s.to_string(),
+        false => format!("'{}'", s.replace('\'', "'\\''")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_splits_words() {
+        let template =
+            CommandTemplate::parse(r#"open -a 'Finder App' "a \"b\"" c\ d {path}"#).unwrap();
+        assert_eq!(
+            template.words,
+            vec!["open", "-a", "Finder App", "a \"b\"", "c d", "{path}"]
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(CommandTemplate::parse("").is_err());
+        assert!(CommandTemplate::parse("   ").is_err());
+        assert!(CommandTemplate::parse("open 'path").is_err());
+        assert!(CommandTemplate::parse("open \"path").is_err());
+        assert!(CommandTemplate::parse("open path\\").is_err());
+    }
+
+    #[test]
+    fn test_render_keeps_fields_as_single_arguments() {
+        let template = CommandTemplate::parse("xdg-open {path}").unwrap();
+        let argv = template
+            .render(&[("path", "/music/a b; rm -rf ~")])
+            .unwrap();
+        assert_eq!(argv, vec!["xdg-open", "/music/a b; rm -rf ~"]);
+    }
+
+    #[test]
+    fn test_render_shell_quotes_fields() {
+        let template = CommandTemplate::parse("notify-send {title} | cat")
+            .unwrap()
+            .shell(true);
+        let argv = template.render(&[("title", "it's $HOME")]).unwrap();
+        assert_eq!(argv, vec!["sh", "-c", "notify-send 'it'\\''s $HOME' | cat"]);
+    }
+
+    #[test]
+    fn test_render_fields() {
+        let template = CommandTemplate::parse("echo {{literal}} {artist}-{title}").unwrap();
+        let fields = [("artist", "a"), ("title", "t")];
+        assert_eq!(
+            template.render(&fields).unwrap(),
+            vec!["echo", "{literal}", "a-t"]
+        );
+
+        let template = CommandTemplate::parse("echo {album}").unwrap();
+        assert!(template.render(&fields).is_err());
+
+        let template = CommandTemplate::parse("echo {album").unwrap();
+        assert!(template.render(&fields).is_err());
+    }
+
+    #[test]
+    fn test_dry_run_does_not_execute() {
+        let template = CommandTemplate::parse("this-command-does-not-exist")
+            .unwrap()
+            .dry_run(true);
+        assert!(template.run(&[]).is_ok());
+    }
+
+    #[test]
+    fn test_run_reports_failures() {
+        let template = CommandTemplate::parse("this-command-does-not-exist").unwrap();
+        assert!(template.run(&[]).is_err());
+
+        let template = CommandTemplate::parse("false").unwrap();
+        assert!(template.run(&[]).is_err());
+
+        let template = CommandTemplate::parse("true").unwrap();
+        assert!(template.run(&[]).is_ok());
+    }
+
+    #[test]
+    fn test_run_kills_after_timeout() {
+        let template = CommandTemplate::parse("sleep 5")
+            .unwrap()
+            .timeout(Duration::from_millis(100));
+
+        let start = Instant::now();
+        let err = template.run(&[]).unwrap_err();
+
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(err.to_string().contains("killed"));
+    }
+}
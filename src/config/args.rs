@@ -3,13 +3,38 @@ use std::path::PathBuf;
 use anyhow::bail;
 use clap::Parser;
 
-use super::theme;
+use super::{config_file, theme};
 use crate::data::persistent_data;
+use crate::utils::{self, PathDisplay, PathStyle};
 
 type Color = cursive::theme::Color;
 
+// The number of directories that a scan finds before asking whether to
+// continue, unless the config file sets another.
+const SCAN_LIMIT: usize = 200_000;
+
 lazy_static::lazy_static! {
     static ref ARGS: Args = Args::parse();
+    // Whether or not the search root is on a read-only mount.
+    static ref ROOT_READ_ONLY: bool = utils::is_read_only(&search_root());
+}
+
+// When items that are too long for one row are wrapped in the fuzzy view.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SoftWrap {
+    // Wrap when the terminal is narrower than the width.
+    Auto(usize),
+    Always,
+    Never,
+}
+
+// What the player does when another application starts playing audio.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AutoPause {
+    Off,
+    Pause,
+    // Lower the volume.
+    Duck,
 }
 
 #[derive(PartialEq)]
@@ -53,6 +78,71 @@ pub struct Args {
     #[arg(short, long, default_value_t = false)]
     exclude: bool,
 
+    /// Scan hidden directories, such as '.stfolder', for the fuzzy-finder
+    #[arg(long, default_value_t = false)]
+    hidden_dirs: bool,
+
+    /// Never write to the library. Enabled when the library is mounted read-only
+    #[arg(long, default_value_t = false)]
+    read_only_library: bool,
+
+    /// Fuzzy match on the paths relative to the search root instead of the folder names
+    #[arg(long, default_value_t = false)]
+    match_paths: bool,
+
+    /// The number of directory levels listed by the fuzzy search. Deeper directories are
+    /// found by selecting the directory that they are in
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    depth: Option<u64>,
+
+    /// Hide the fuzzy matches that weigh less than N, where 0 shows every match
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    threshold: u64,
+
+    /// Shuffle the albums, continuing the previous shuffle if there is one
+    #[arg(long, default_value_t = false)]
+    shuffle_albums: bool,
+
+    /// Use a line-oriented interface for screen readers instead of the TUI.
+    /// Also enabled when ACCESSIBILITY=1 is set
+    #[arg(long, default_value_t = false)]
+    accessible: bool,
+
+    /// Wrap long items onto two rows in the fuzzy view: auto, always or never [default: auto].
+    /// With auto, items are wrapped when the terminal is narrower than '--soft-wrap-width'
+    #[arg(long, value_name = "WHEN", value_parser = ["auto", "always", "never"])]
+    soft_wrap: Option<String>,
+
+    /// The terminal width, in columns, below which '--soft-wrap auto' wraps items [default: 80]
+    #[arg(long, value_name = "COLS")]
+    soft_wrap_width: Option<usize>,
+
+    /// Pause or lower the volume while another application plays audio: off, pause or duck [default: off].
+    /// Requires 'pactl', from PulseAudio or PipeWire
+    #[arg(long, value_name = "MODE", value_parser = ["off", "pause", "duck"])]
+    auto_pause: Option<String>,
+
+    /// Exit if the config file has errors, instead of asking how to continue
+    #[arg(long, default_value_t = false)]
+    strict_config: bool,
+
+    /// Log the external commands that would be run instead of running them.
+    /// With '--profile-startup', quit before the event loop
+    #[arg(long, default_value_t = false)]
+    dry_run: bool,
+
+    /// Print the time taken by each startup phase to stderr after exiting
+    #[arg(long, default_value_t = false)]
+    profile_startup: bool,
+
+    /// Restart tap with the last session if it crashes
+    #[arg(long, default_value_t = false)]
+    watchdog: bool,
+
+    /// Restore the last player from the session <FILE>
+    #[arg(long, value_name = "FILE")]
+    session_restore: Option<PathBuf>,
+
     /// Use the terminal background color
     #[arg(short = 'b', long, default_value_t = false)]
     term_bg: bool,
@@ -78,15 +168,143 @@ pub fn parse() -> Result<(PathBuf, Opts), anyhow::Error> {
 }
 
 pub fn audio_only() -> bool {
-    ARGS.exclude
+    ARGS.exclude || config_file::get().exclude
+}
+
+pub fn hidden_dirs() -> bool {
+    ARGS.hidden_dirs
+}
+
+pub fn read_only_library() -> bool {
+    ARGS.read_only_library || config_file::get().read_only_library || *ROOT_READ_ONLY
+}
+
+pub fn match_paths() -> bool {
+    ARGS.match_paths || config_file::get().match_paths
+}
+
+pub fn depth() -> Option<usize> {
+    ARGS.depth.map(|depth| depth as usize)
+}
+
+pub fn threshold() -> i64 {
+    ARGS.threshold.min(i64::MAX as u64) as i64
+}
+
+pub fn shuffle_albums() -> bool {
+    ARGS.shuffle_albums
 }
 
+pub fn accessible() -> bool {
+    ARGS.accessible || std::env::var("ACCESSIBILITY").map_or(false, |v| v == "1")
+}
+
+pub fn soft_wrap() -> SoftWrap {
+    let config = config_file::get();
+    let soft_wrap = ARGS.soft_wrap.as_ref().or(config.soft_wrap.as_ref());
+    let width = ARGS.soft_wrap_width.or(config.soft_wrap_width).unwrap_or(80);
+
+    match soft_wrap.map(|s| s.as_str()) {
+        Some("always") => SoftWrap::Always,
+        Some("never") => SoftWrap::Never,
+        _ => SoftWrap::Auto(width),
+    }
+}
+
+pub fn wrap_around() -> bool {
+    config_file::get().wrap_around
+}
+
+pub fn downmix() -> bool {
+    config_file::get().downmix
+}
+
+// The number of directories that a scan finds before asking whether to
+// continue, or `None` if it never asks.
+pub fn scan_limit() -> Option<usize> {
+    match config_file::get().scan_limit.unwrap_or(SCAN_LIMIT) {
+        0 => None,
+        limit => Some(limit),
+    }
+}
+
+pub fn auto_pause() -> AutoPause {
+    let config = config_file::get();
+    let auto_pause = ARGS.auto_pause.as_ref().or(config.auto_pause.as_ref());
+
+    match auto_pause.map(|s| s.as_str()) {
+        Some("pause") => AutoPause::Pause,
+        Some("duck") => AutoPause::Duck,
+        _ => AutoPause::Off,
+    }
+}
+
+// How paths are shown, relative to the search root and the named roots.
+pub fn path_display() -> PathDisplay {
+    let config = config_file::get();
+    let style = match config.paths.as_deref() {
+        Some("relative") => PathStyle::Relative,
+        Some("short") => PathStyle::Short,
+        _ => PathStyle::Absolute,
+    };
+    let home = std::env::var("HOME").ok().map(PathBuf::from);
+
+    let mut roots = config
+        .root_names
+        .iter()
+        .map(|(nickname, path)| {
+            let path = match (path.strip_prefix("~"), &home) {
+                (Ok(rest), Some(home)) => home.join(rest),
+                _ => path.to_owned(),
+            };
+            (path, Some(nickname.to_owned()))
+        })
+        .collect::<Vec<(PathBuf, Option<String>)>>();
+
+    // The search root is shown without a prefix, unless it has a name.
+    let search_root = search_root();
+    let is_named = roots
+        .iter()
+        .any(|(path, _)| path.canonicalize().map_or(false, |path| path == search_root));
+    if !is_named {
+        roots.push((search_root, None));
+    }
+
+    PathDisplay::new(style, roots, home)
+}
+
+pub fn strict_config() -> bool {
+    ARGS.strict_config
+}
+
+pub fn dry_run() -> bool {
+    ARGS.dry_run
+}
+
+pub fn profile_startup() -> bool {
+    ARGS.profile_startup
+}
+
+pub fn watchdog() -> bool {
+    ARGS.watchdog
+}
+
+pub fn session_restore() -> Option<PathBuf> {
+    ARGS.session_restore.to_owned()
+}
+
+// The colors from the config file followed by the colors from the command
+// line, so that the command line takes precedence.
 pub fn user_colors() -> (Vec<(String, Color)>, bool) {
-    (ARGS.color.to_owned(), ARGS.term_bg)
+    let config = config_file::get();
+    let mut colors = config.colors.to_owned();
+    colors.extend(ARGS.color.to_owned());
+
+    (colors, ARGS.term_bg || config.term_bg)
 }
 
 pub fn term_color() -> bool {
-    ARGS.term_color
+    ARGS.term_color || config_file::get().term_color
 }
 
 pub fn search_root() -> PathBuf {
@@ -164,6 +382,8 @@ fn exclude_multiple() -> Result<(), anyhow::Error> {
         bail!("'--automate' cannot be used with '--print-default'")
     } else if ARGS.automate && ARGS.set_default {
         bail!("'--automate' cannot be used with '--set-default'")
+    } else if ARGS.automate && ARGS.shuffle_albums {
+        bail!("'--automate' cannot be used with '--shuffle-albums'")
     } else if ARGS.print_default && ARGS.set_default {
         bail!("'--print-default' cannot be used with '--set-default'")
     }
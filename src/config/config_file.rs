@@ -0,0 +1,511 @@
+use std::{
+    io::{stderr, stdin, BufRead, IsTerminal, Write},
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{Arc, RwLock},
+};
+
+use anyhow::{anyhow, bail};
+
+use crate::utils::{self, AppDir};
+
+use super::theme;
+
+type Color = cursive::theme::Color;
+
+static CONFIG: RwLock<Option<Arc<ConfigFile>>> = RwLock::new(None);
+
+// The options that can be set in the config file. Options given on the
+// command line are combined with, or take precedence over, these.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigFile {
+    pub exclude: bool,
+    pub read_only_library: bool,
+    pub match_paths: bool,
+    pub term_bg: bool,
+    pub term_color: bool,
+    pub soft_wrap: Option<String>,
+    pub soft_wrap_width: Option<usize>,
+    // Whether or not moving past either end of the fuzzy matches continues
+    // from the other end.
+    pub wrap_around: bool,
+    pub auto_pause: Option<String>,
+    pub paths: Option<String>,
+    // The nicknames of the library roots, shown in place of their paths.
+    pub root_names: Vec<(String, PathBuf)>,
+    // Whether or not a session is restored when its album was played to the end.
+    pub resume_finished_album: bool,
+    // Whether or not the config file is reloaded when it changes.
+    pub watch_config: bool,
+    // Whether or not the library is watched for albums that are added or
+    // removed while tap is running.
+    pub watch_library: bool,
+    // The number of directories that a scan finds before asking whether to
+    // continue, where `0` never asks.
+    pub scan_limit: Option<usize>,
+    // Whether or not tracks with more than two channels are mixed down to
+    // stereo.
+    pub downmix: bool,
+    pub colors: Vec<(String, Color)>,
+}
+
+impl Default for ConfigFile {
+    fn default() -> Self {
+        ConfigFile {
+            exclude: false,
+            read_only_library: false,
+            match_paths: false,
+            term_bg: false,
+            term_color: false,
+            soft_wrap: None,
+            soft_wrap_width: None,
+            wrap_around: false,
+            auto_pause: None,
+            paths: None,
+            root_names: vec![],
+            resume_finished_album: false,
+            watch_config: true,
+            watch_library: false,
+            scan_limit: None,
+            downmix: true,
+            colors: vec![],
+        }
+    }
+}
+
+// An error on a line of the config file.
+#[derive(Debug, PartialEq)]
+pub struct ConfigError {
+    pub line: usize,
+    pub msg: String,
+}
+
+impl ConfigFile {
+    // Parses the contents of a config file. Each line is either blank, a
+    // comment starting with '#', or an 'option = value' pair. All of the
+    // errors are returned, rather than just the first.
+    pub fn parse(contents: &str) -> Result<Self, Vec<ConfigError>> {
+        let mut config = ConfigFile::default();
+        let mut errors = vec![];
+
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let result = match line.split_once('=') {
+                Some((name, value)) => config.set(name.trim(), unquote(value.trim())),
+                None => Err(format!("expected 'option = value', found '{line}'")),
+            };
+
+            if let Err(msg) = result {
+                errors.push(ConfigError { line: i + 1, msg });
+            }
+        }
+
+        match errors.is_empty() {
+            true => Ok(config),
+            false => Err(errors),
+        }
+    }
+
+    fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        match name {
+            "exclude" => self.exclude = parse_bool(name, value)?,
+            "read-only-library" => self.read_only_library = parse_bool(name, value)?,
+            "match-paths" => self.match_paths = parse_bool(name, value)?,
+            "term-bg" => self.term_bg = parse_bool(name, value)?,
+            "term-color" => self.term_color = parse_bool(name, value)?,
+            "resume-finished-album" => self.resume_finished_album = parse_bool(name, value)?,
+            "watch-config" => self.watch_config = parse_bool(name, value)?,
+            "watch-library" => self.watch_library = parse_bool(name, value)?,
+            "wrap-around" => self.wrap_around = parse_bool(name, value)?,
+            "downmix" => self.downmix = parse_bool(name, value)?,
+            "soft-wrap" => match value {
+                "auto" | "always" | "never" => self.soft_wrap = Some(value.to_string()),
+                _ => {
+                    return Err(format!(
+                        "invalid value '{value}' for '{name}', expected 'auto', 'always' or 'never'"
+                    ))
+                }
+            },
+            "auto-pause" => match value {
+                "off" | "pause" | "duck" => self.auto_pause = Some(value.to_string()),
+                _ => {
+                    return Err(format!(
+                        "invalid value '{value}' for '{name}', expected 'off', 'pause' or 'duck'"
+                    ))
+                }
+            },
+            "paths" => match value {
+                "relative" | "absolute" | "short" => self.paths = Some(value.to_string()),
+                _ => {
+                    return Err(format!(
+                        "invalid value '{value}' for '{name}', expected 'relative', 'absolute' or 'short'"
+                    ))
+                }
+            },
+            "root-names" => {
+                for pair in value.split(',').map(str::trim) {
+                    match pair.split_once('=') {
+                        Some((nickname, path)) if !nickname.trim().is_empty() => self
+                            .root_names
+                            .push((nickname.trim().to_string(), PathBuf::from(path.trim()))),
+                        _ => return Err(format!("invalid root name '{pair}', expected '<NAME>=<PATH>'")),
+                    }
+                }
+            }
+            "soft-wrap-width" => match value.parse::<usize>() {
+                Ok(width) => self.soft_wrap_width = Some(width),
+                Err(_) => {
+                    return Err(format!(
+                        "invalid value '{value}' for '{name}', expected a number of columns"
+                    ))
+                }
+            },
+            "scan-limit" => match value.parse::<usize>() {
+                Ok(limit) => self.scan_limit = Some(limit),
+                Err(_) => {
+                    return Err(format!(
+                        "invalid value '{value}' for '{name}', expected a number of directories"
+                    ))
+                }
+            },
+            "color" => {
+                for pair in value.split(',').map(str::trim) {
+                    self.colors.push(parse_color(pair)?);
+                }
+            }
+            _ => return Err(format!("unknown option '{name}'")),
+        }
+        Ok(())
+    }
+
+    // The options that differ between `self` and `other` but only take effect
+    // when tap is restarted.
+    pub fn restart_only_changes(&self, other: &ConfigFile) -> Vec<&'static str> {
+        let mut changes = vec![];
+        if self.exclude != other.exclude {
+            changes.push("exclude");
+        }
+        changes
+    }
+}
+
+// The options from the config file, or the defaults if it wasn't loaded.
+pub fn get() -> Arc<ConfigFile> {
+    let config = CONFIG.read().unwrap_or_else(|e| e.into_inner());
+    config.as_ref().map(Arc::clone).unwrap_or_default()
+}
+
+fn set(config: ConfigFile) {
+    *CONFIG.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(config));
+}
+
+// Parses the config file again and replaces the options with it. Returns the
+// options that changed but need a restart. When the file has errors, the
+// options are kept and the errors are returned.
+pub fn reload() -> Result<Vec<&'static str>, anyhow::Error> {
+    let path = path()?;
+    let config = match ConfigFile::parse(&read(&path)?) {
+        Ok(config) => config,
+        Err(errors) => bail!(summary(&path, &errors)),
+    };
+
+    let changes = get().restart_only_changes(&config);
+    set(config);
+    Ok(changes)
+}
+
+// Loads the config file, if there is one. When the file has errors and
+// `strict` is false, asks whether to continue with the defaults, edit the
+// file or quit, instead of failing.
+pub fn load(strict: bool) -> Result<(), anyhow::Error> {
+    let path = path()?;
+
+    let config = if strict || !stdin().is_terminal() {
+        match ConfigFile::parse(&read(&path)?) {
+            Ok(config) => config,
+            Err(errors) => bail!(summary(&path, &errors)),
+        }
+    } else {
+        recover(
+            &path,
+            || read(&path),
+            || edit(&path),
+            &mut stdin().lock(),
+            &mut stderr(),
+        )?
+    };
+
+    set(config);
+    Ok(())
+}
+
+// The path to the config file, '~/.config/tap/config' by default.
+pub fn path() -> Result<PathBuf, anyhow::Error> {
+    Ok(utils::app_path(AppDir::Config)?.join("config"))
+}
+
+// Parses the config file contents given by `read` until they are valid,
+// prompting on `output` for what to do about any errors. The answers are
+// read from `input` and `edit` is called to edit the file.
+fn recover(
+    path: &Path,
+    mut read: impl FnMut() -> Result<String, anyhow::Error>,
+    mut edit: impl FnMut() -> Result<(), anyhow::Error>,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> Result<ConfigFile, anyhow::Error> {
+    loop {
+        let errors = match ConfigFile::parse(&read()?) {
+            Ok(config) => return Ok(config),
+            Err(errors) => errors,
+        };
+
+        writeln!(output, "[tap error]: {}", summary(path, &errors))?;
+
+        loop {
+            write!(
+                output,
+                "[c]ontinue with defaults, [e]dit the file or [q]uit? "
+            )?;
+            output.flush()?;
+
+            let mut answer = String::new();
+            if input.read_line(&mut answer)? == 0 {
+                bail!("the config file '{}' has errors", path.display())
+            }
+
+            match answer.trim() {
+                "c" | "continue" => return Ok(ConfigFile::default()),
+                "e" | "edit" => match edit() {
+                    Ok(_) => break,
+                    Err(e) => writeln!(output, "[tap error]: {e}")?,
+                },
+                "q" | "quit" => bail!("the config file '{}' has errors", path.display()),
+                _ => (),
+            }
+        }
+    }
+}
+
+// Lists the errors in the config file at `path`, with their line numbers.
+fn summary(path: &Path, errors: &[ConfigError]) -> String {
+    let lines = errors
+        .iter()
+        .map(|e| format!("- line {}: {}", e.line, e.msg))
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    format!("the config file '{}' has errors:\n{lines}", path.display())
+}
+
+// The contents of the config file at `path`, which are empty if there is no file.
+fn read(path: &Path) -> Result<String, anyhow::Error> {
+    if !path.exists() {
+        return Ok(String::new());
+    }
+
+    std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("could not read '{}'\n- `{}`", path.display(), e))
+}
+
+// Opens the file at `path` in the user's editor and waits for it to exit.
+fn edit(path: &Path) -> Result<(), anyhow::Error> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| String::from("vi"));
+
+    let mut words = editor.split_whitespace();
+    let program = match words.next() {
+        Some(program) => program,
+        None => bail!("the editor can't be empty"),
+    };
+
+    // The editor needs the terminal, so it is run directly rather than as
+    // a `CommandTemplate`.
+    let status = Command::new(program)
+        .args(words)
+        .arg(path)
+        .status()
+        .map_err(|e| anyhow!("could not run '{editor}'\n- `{e}`"))?;
+
+    if !status.success() {
+        bail!("'{editor}' failed with {status}")
+    }
+
+    Ok(())
+}
+
+fn parse_bool(name: &str, value: &str) -> Result<bool, String> {
+    match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(format!(
+            "invalid value '{value}' for '{name}', expected 'true' or 'false'"
+        )),
+    }
+}
+
+// Parses a color with the same '<NAME>=<HEX>' format as '--color'.
+fn parse_color(s: &str) -> Result<(String, Color), String> {
+    let (name, hex) = match s.split_once('=') {
+        Some((name, hex)) => (name.trim(), hex.trim()),
+        None => return Err(format!("invalid color '{s}', expected '<NAME>=<HEX>'")),
+    };
+
+    if !theme::COLOR_MAP.contains_key(name) {
+        return Err(format!("invalid color name '{name}'"));
+    }
+
+    match hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        true => match hex.parse::<Color>() {
+            Ok(color) => Ok((name.to_string(), color)),
+            Err(_) => Err(format!("invalid hex value '{hex}' for '{name}'")),
+        },
+        false => Err(format!("invalid hex value '{hex}' for '{name}'")),
+    }
+}
+
+// Removes the quotes around `s`, if any.
+fn unquote(s: &str) -> &str {
+    s.strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .unwrap_or(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    // Runs the recovery loop with the file contents from `versions`, one
+    // per read, and the `answers` to the prompts. Returns the result and
+    // the number of times the file was edited.
+    fn run_recover(versions: &[&str], answers: &str) -> (Result<ConfigFile, anyhow::Error>, usize) {
+        let mut versions = versions.iter();
+        let mut edits = 0;
+        let mut output = vec![];
+
+        let result = recover(
+            Path::new("config"),
+            || {
+                Ok(versions
+                    .next()
+                    .expect("should not be read again")
+                    .to_string())
+            },
+            || {
+                edits += 1;
+                Ok(())
+            },
+            &mut Cursor::new(answers),
+            &mut output,
+        );
+
+        (result, edits)
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let config = ConfigFile::parse(
+            "# tap config\n\
+            \n\
+            exclude = true\n\
+            soft-wrap = \"always\"\n\
+            soft-wrap-width=60\n\
+            wrap-around = true\n\
+            scan-limit = 0\n\
+            resume-finished-album = true\n\
+            auto-pause = duck\n\
+            downmix = false\n\
+            watch-library = true\n\
+            paths = short\n\
+            root-names = Music=/mnt/music, Podcasts=~/podcasts\n\
+            color = fg=268bd2, bg=002b36\n",
+        )
+        .unwrap();
+
+        assert!(config.exclude);
+        assert!(!config.term_bg);
+        assert_eq!(config.soft_wrap.as_deref(), Some("always"));
+        assert_eq!(config.soft_wrap_width, Some(60));
+        assert!(config.wrap_around);
+        assert_eq!(config.scan_limit, Some(0));
+        assert!(config.resume_finished_album);
+        assert_eq!(config.auto_pause.as_deref(), Some("duck"));
+        assert!(!config.downmix);
+        assert!(config.watch_library);
+        assert_eq!(config.paths.as_deref(), Some("short"));
+        assert_eq!(
+            config.root_names,
+            vec![
+                ("Music".to_string(), PathBuf::from("/mnt/music")),
+                ("Podcasts".to_string(), PathBuf::from("~/podcasts"))
+            ]
+        );
+        assert_eq!(config.colors.len(), 2);
+        assert_eq!(config.colors[1].0, "bg");
+    }
+
+    #[test]
+    fn test_restart_only_changes() {
+        let old = ConfigFile::parse("exclude = false\nterm-bg = true").unwrap();
+        let new = ConfigFile::parse("exclude = true\ncolor = fg=268bd2").unwrap();
+
+        assert_eq!(old.restart_only_changes(&new), vec!["exclude"]);
+        assert!(old.restart_only_changes(&old).is_empty());
+
+        // The colors and the background can change while tap is running.
+        let recolored = ConfigFile::parse("color = fg=268bd2").unwrap();
+        assert!(old.restart_only_changes(&recolored).is_empty());
+        assert!(ConfigFile::default().watch_config);
+    }
+
+    #[test]
+    fn test_parse_errors_have_line_numbers() {
+        let errors = ConfigFile::parse(
+            "exclude = true\n\
+            exlude = true\n\
+            \n\
+            term-bg = yes\n\
+            color = fg=12345g\n\
+            soft-wrap\n",
+        )
+        .unwrap_err();
+
+        let lines = errors.iter().map(|e| e.line).collect::<Vec<usize>>();
+        assert_eq!(lines, vec![2, 4, 5, 6]);
+        assert_eq!(errors[0].msg, "unknown option 'exlude'");
+    }
+
+    #[test]
+    fn test_recover_after_edit() {
+        let (result, edits) = run_recover(&["exlude = true", "exclude = true"], "e\n");
+
+        assert!(result.unwrap().exclude);
+        assert_eq!(edits, 1);
+    }
+
+    #[test]
+    fn test_recover_with_defaults() {
+        let (result, edits) = run_recover(&["exclude = maybe"], "what?\nc\n");
+
+        assert_eq!(result.unwrap(), ConfigFile::default());
+        assert_eq!(edits, 0);
+    }
+
+    #[test]
+    fn test_recover_quit() {
+        let (result, _) = run_recover(&["exclude = maybe"], "q\n");
+        assert!(result.is_err());
+
+        // Stdin was closed.
+        let (result, _) = run_recover(&["exclude = maybe"], "");
+        assert!(result.is_err());
+    }
+}
@@ -1,2 +1,4 @@
 pub mod args;
+pub mod config_file;
 pub mod theme;
+pub mod watch;
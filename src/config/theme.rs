@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::RwLock};
 
 use cursive::{
     theme::{
@@ -15,70 +15,142 @@ use super::args;
 
 lazy_static::lazy_static! {
     pub static ref COLOR_MAP: HashMap<String, Color> = default_palette();
-    pub static ref PALETTE: HashMap<String, Color> = create_palette();
+    static ref PALETTE: RwLock<HashMap<String, Color>> = RwLock::new(create_palette());
 }
 
 pub fn custom() -> Theme {
+    with_palette(&PALETTE.read().unwrap_or_else(|e| e.into_inner()))
+}
+
+// Creates the palette again from the options, such as after the config file
+// has been reloaded.
+pub fn reload() {
+    *PALETTE.write().unwrap_or_else(|e| e.into_inner()) = create_palette();
+}
+
+pub fn with_palette(colors: &HashMap<String, Color>) -> Theme {
     Theme {
         shadow: false,
         borders: BorderStyle::Simple,
         palette: Palette::default().with(|palette| {
-            palette[Background] = PALETTE["bg"];
-            palette[View] = PALETTE["bg"];
-            palette[Primary] = PALETTE["hl"];
-            palette[TitlePrimary] = PALETTE["header"];
+            palette[Background] = colors["bg"];
+            palette[View] = colors["bg"];
+            palette[Primary] = colors["hl"];
+            palette[TitlePrimary] = colors["header"];
         }),
     }
 }
 
+// The color modes that tap can be run with, without any user colors.
+#[cfg(test)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Preset {
+    Default,
+    // '--term-bg'
+    TermBg,
+    // '--term-color'
+    TermColor,
+}
+
+#[cfg(test)]
+impl Preset {
+    pub const ALL: [Preset; 3] = [Preset::Default, Preset::TermBg, Preset::TermColor];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Preset::Default => "default",
+            Preset::TermBg => "term_bg",
+            Preset::TermColor => "term_color",
+        }
+    }
+
+    pub fn palette(&self) -> HashMap<String, Color> {
+        palette(*self == Preset::TermColor, vec![], *self == Preset::TermBg)
+    }
+}
+
+#[cfg(test)]
+thread_local! {
+    // The palette used in place of `PALETTE` by the tests on this thread, so
+    // that views can be drawn without parsing the command line.
+    static TEST_PALETTE: std::cell::RefCell<Option<HashMap<String, Color>>> =
+        std::cell::RefCell::new(None);
+}
+
+// Runs `f` with the colors of `preset` on this thread.
+#[cfg(test)]
+pub fn with_preset<T>(preset: Preset, f: impl FnOnce() -> T) -> T {
+    TEST_PALETTE.set(Some(preset.palette()));
+    let result = f();
+    TEST_PALETTE.set(None);
+    result
+}
+
+// The color in the palette with `name`.
+fn color(name: &str) -> Color {
+    #[cfg(test)]
+    if let Some(color) = TEST_PALETTE.with_borrow(|p| p.as_ref().map(|p| p[name])) {
+        return color;
+    }
+    PALETTE.read().unwrap_or_else(|e| e.into_inner())[name]
+}
+
 pub fn fg() -> ColorStyle {
-    ColorStyle::front(PALETTE["fg"])
+    ColorStyle::front(color("fg"))
 }
 
 pub fn hl() -> ColorStyle {
-    ColorStyle::front(PALETTE["hl"])
+    ColorStyle::front(color("hl"))
 }
 
 pub fn prompt() -> ColorStyle {
-    ColorStyle::front(PALETTE["prompt"])
+    ColorStyle::front(color("prompt"))
 }
 
 pub fn header1() -> ColorStyle {
-    ColorStyle::front(PALETTE["header"])
+    ColorStyle::front(color("header"))
 }
 
 pub fn header2() -> ColorStyle {
-    ColorStyle::front(PALETTE["header+"])
+    ColorStyle::front(color("header+"))
 }
 
 pub fn progress() -> ColorStyle {
-    ColorStyle::front(PALETTE["progress"])
+    ColorStyle::front(color("progress"))
 }
 
 pub fn info() -> ColorStyle {
-    ColorStyle::front(PALETTE["info"])
+    ColorStyle::front(color("info"))
 }
 
 pub fn err() -> ColorStyle {
-    ColorStyle::front(PALETTE["err"])
+    ColorStyle::front(color("err"))
 }
 
 pub fn button() -> ColorStyle {
-    ColorStyle::new(PALETTE["bg"], PALETTE["fg"])
+    ColorStyle::new(color("bg"), color("fg"))
 }
 
 fn create_palette() -> HashMap<String, Color> {
+    let (user_colors, term_bg) = args::user_colors();
+    palette(args::term_color(), user_colors, term_bg)
+}
+
+fn palette(
+    term_color: bool,
+    user_colors: Vec<(String, Color)>,
+    term_bg: bool,
+) -> HashMap<String, Color> {
     // Get the default colors.
-    let mut m = COLOR_MAP.to_owned();
+    let mut m = default_palette();
 
-    if args::term_color() {
+    if term_color {
         // Use terminal colors for foreground and background.
         for (_, value) in m.iter_mut() {
             *value = Color::TerminalDefault;
         }
     } else {
         // Update any user-defined colors.
-        let (user_colors, term_bg) = args::user_colors();
         m.extend(user_colors);
 
         // Update background color with terminal color, if using.
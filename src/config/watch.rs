@@ -0,0 +1,131 @@
+use std::{thread, time::Duration, time::SystemTime};
+
+use cursive::{CbSink, Cursive};
+
+use crate::fuzzy::{ErrorView, FuzzyView};
+use crate::layers;
+use crate::player::{audio_focus, output};
+use crate::utils;
+
+use super::{
+    args::{self, AutoPause},
+    config_file, theme,
+};
+
+// The time between checks for changes to the config file.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+// Detects changes to the modification time of the config file. A change is
+// only reported once the time has stayed the same for a poll, so that a file
+// that is still being written isn't read.
+#[derive(Debug, Default)]
+pub struct ChangeDetector {
+    // The modification time that was last reported, or seen on startup.
+    last: Option<SystemTime>,
+    // The modification time that differs from `last`, waiting to settle.
+    pending: Option<Option<SystemTime>>,
+}
+
+impl ChangeDetector {
+    pub fn new(modified: Option<SystemTime>) -> Self {
+        ChangeDetector {
+            last: modified,
+            pending: None,
+        }
+    }
+
+    // Whether or not the file has changed, given its modification time now.
+    pub fn poll(&mut self, modified: Option<SystemTime>) -> bool {
+        if modified == self.last {
+            self.pending = None;
+            return false;
+        }
+        match self.pending == Some(modified) {
+            true => {
+                self.last = modified;
+                self.pending = None;
+                true
+            }
+            false => {
+                self.pending = Some(modified);
+                false
+            }
+        }
+    }
+}
+
+// Reloads the config file whenever it changes, unless 'watch-config' is false.
+pub fn start(cb_sink: CbSink) {
+    thread::spawn(move || {
+        let mut detector = ChangeDetector::new(modified());
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            // The changes are still tracked while not watching, so that turning
+            // watching back on doesn't reload a file that was reloaded by hand.
+            let changed = detector.poll(modified()) && config_file::get().watch_config;
+            if changed && cb_sink.send(Box::new(reload)).is_err() {
+                return;
+            }
+        }
+    });
+}
+
+// The modification time of the config file, if there is one.
+fn modified() -> Option<SystemTime> {
+    config_file::path().ok()?.metadata().ok()?.modified().ok()
+}
+
+// Reloads the config file and applies the options that can change while tap
+// is running. When the file has errors, the options are kept and the errors
+// are shown.
+pub fn reload(siv: &mut Cursive) {
+    let restart_only = match config_file::reload() {
+        Ok(changes) => changes,
+        Err(e) => return ErrorView::load(siv, e),
+    };
+
+    theme::reload();
+    siv.set_theme(theme::custom());
+    utils::set_path_display(args::path_display());
+    siv.call_on_name(FuzzyView::NAME, |fuzzy: &mut FuzzyView| {
+        fuzzy.set_soft_wrap(args::soft_wrap());
+        fuzzy.set_wrap_around(args::wrap_around());
+    });
+    // The listener is otherwise started when the audio output is opened.
+    if args::auto_pause() != AutoPause::Off && output::times_opened() > 0 {
+        audio_focus::start();
+    }
+
+    let msg = match restart_only.is_empty() {
+        true => String::from("config reloaded"),
+        false => format!("'{}' requires restart", restart_only.join("', '")),
+    };
+    layers::notify(siv, &msg);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changes_are_reported_once_settled() {
+        let at = |secs| Some(SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+        let mut detector = ChangeDetector::new(at(1));
+
+        assert!(!detector.poll(at(1)));
+
+        // The file is being written over two polls.
+        assert!(!detector.poll(at(2)));
+        assert!(!detector.poll(at(3)));
+        assert!(detector.poll(at(3)));
+        assert!(!detector.poll(at(3)));
+
+        // Deleting the file is a change too.
+        assert!(!detector.poll(None));
+        assert!(detector.poll(None));
+
+        // A change that is undone before it settles isn't reported.
+        assert!(!detector.poll(at(4)));
+        assert!(!detector.poll(None));
+    }
+}
@@ -0,0 +1,100 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use bincode::{Decode, Encode};
+
+use super::persistent_data;
+
+// The largest boost or cut that can be set for an album, in dB.
+pub const MAX_GAIN: i8 = 12;
+
+// The gain that the user has set for each album, applied on top of the
+// volume whenever the album plays.
+#[derive(Clone, Debug, Default, PartialEq, Encode, Decode)]
+pub struct AlbumGains {
+    // The gain of each album in dB. Albums without any gain aren't kept.
+    gains: HashMap<PathBuf, i8>,
+}
+
+impl AlbumGains {
+    // The cached gains, without the albums that no longer exist.
+    pub fn cached() -> Self {
+        let mut gains: Self = persistent_data::cached_gains().unwrap_or_default();
+        gains.prune(|album| album.exists());
+        gains
+    }
+
+    // Sets the gain of `album` and saves the gains. Returns the gain, which
+    // is limited to `MAX_GAIN` either way.
+    pub fn save(album: &PathBuf, gain: i8) -> i8 {
+        let mut gains = Self::cached();
+        let gain = gains.set(album, gain);
+        _ = persistent_data::save_gains(&gains);
+        gain
+    }
+
+    // The gain of `album` in dB.
+    pub fn get(&self, album: &Path) -> i8 {
+        self.gains.get(album).copied().unwrap_or(0)
+    }
+
+    fn set(&mut self, album: &PathBuf, gain: i8) -> i8 {
+        let gain = gain.clamp(-MAX_GAIN, MAX_GAIN);
+        match gain {
+            0 => self.gains.remove(album),
+            _ => self.gains.insert(album.to_owned(), gain),
+        };
+        gain
+    }
+
+    // Removes the albums for which `exists` is false.
+    fn prune(&mut self, exists: impl Fn(&Path) -> bool) {
+        self.gains.retain(|album, _| exists(album));
+    }
+}
+
+// The factor that the amplitude is multiplied by for `gain` in dB.
+pub fn amplitude(gain: i8) -> f32 {
+    10f32.powf(gain as f32 / 20.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gains_are_limited_and_cleared() {
+        let mut gains = AlbumGains::default();
+        let album = PathBuf::from("/music/quiet");
+
+        assert_eq!(gains.set(&album, 4), 4);
+        assert_eq!(gains.get(&album), 4);
+        assert_eq!(gains.set(&album, 40), MAX_GAIN);
+        assert_eq!(gains.set(&album, -40), -MAX_GAIN);
+
+        // Clearing the gain forgets the album.
+        assert_eq!(gains.set(&album, 0), 0);
+        assert_eq!(gains, AlbumGains::default());
+    }
+
+    #[test]
+    fn test_prune_deleted_albums() {
+        let mut gains = AlbumGains::default();
+        gains.set(&"/music/kept".into(), 2);
+        gains.set(&"/music/deleted".into(), -3);
+
+        gains.prune(|album| album.ends_with("kept"));
+
+        assert_eq!(gains.get(Path::new("/music/kept")), 2);
+        assert_eq!(gains.get(Path::new("/music/deleted")), 0);
+    }
+
+    #[test]
+    fn test_amplitude() {
+        assert_eq!(amplitude(0), 1.0);
+        assert!((amplitude(6) - 1.995).abs() < 0.001);
+        assert!((amplitude(-6) - 0.501).abs() < 0.001);
+    }
+}
@@ -0,0 +1,180 @@
+use std::path::PathBuf;
+
+use bincode::{Decode, Encode};
+use rand::{seq::SliceRandom, thread_rng};
+
+use super::persistent_data;
+
+// A shuffled order of all the albums under the search root, walked
+// through album by album.
+#[derive(Clone, Debug, PartialEq, Encode, Decode)]
+pub struct AlbumShuffle {
+    // The search root that the albums were found in.
+    root: PathBuf,
+    // The album paths, in shuffled order.
+    order: Vec<PathBuf>,
+    // The index of the current album in `order`.
+    position: usize,
+}
+
+impl AlbumShuffle {
+    pub fn new(root: &PathBuf, paths: &Vec<PathBuf>) -> Self {
+        let mut order = paths.to_owned();
+        order.shuffle(&mut thread_rng());
+
+        AlbumShuffle {
+            root: root.to_owned(),
+            order,
+            position: 0,
+        }
+    }
+
+    // Continues the cached shuffle for `root`, if any, otherwise starts a new one.
+    pub fn restore(root: &PathBuf, paths: &Vec<PathBuf>) -> Self {
+        match persistent_data::cached_shuffle() {
+            Ok(mut shuffle) if shuffle.root.eq(root) => {
+                shuffle.reconcile(paths);
+                shuffle
+            }
+            _ => Self::new(root, paths),
+        }
+    }
+
+    // The path of the current album.
+    pub fn current(&self) -> Option<&PathBuf> {
+        self.order.get(self.position)
+    }
+
+    // The position of the current album, starting from one, and the album count.
+    pub fn progress(&self) -> (usize, usize) {
+        (self.position + 1, self.order.len())
+    }
+
+    // Moves to the next album, wrapping around at the end.
+    pub fn next(&mut self) {
+        if !self.order.is_empty() {
+            self.position = (self.position + 1) % self.order.len();
+        }
+    }
+
+    // Moves to the previous album, wrapping around at the start.
+    pub fn previous(&mut self) {
+        if !self.order.is_empty() {
+            self.position = (self.position + self.order.len() - 1) % self.order.len();
+        }
+    }
+
+    // Starts over with a new order.
+    pub fn reshuffle(&mut self) {
+        self.order.shuffle(&mut thread_rng());
+        self.position = 0;
+    }
+
+    // Stores the shuffle so that it can be continued after a restart.
+    pub fn save(&self) {
+        _ = persistent_data::save_shuffle(self);
+    }
+
+    // Updates the order for the albums in `paths`. Albums that no longer
+    // exist are removed and new albums are shuffled into the remaining order.
+    fn reconcile(&mut self, paths: &Vec<PathBuf>) {
+        let current = self.current().cloned();
+
+        self.order.retain(|path| paths.contains(path));
+
+        let mut added = paths
+            .iter()
+            .filter(|path| !self.order.contains(path))
+            .cloned()
+            .collect::<Vec<PathBuf>>();
+
+        added.shuffle(&mut thread_rng());
+
+        // Keep the new albums after the current one, so that they're
+        // played in this pass through the order.
+        let at = match current.and_then(|p| self.order.iter().position(|e| e.eq(&p))) {
+            Some(index) => {
+                self.position = index;
+                index + 1
+            }
+            None => {
+                self.position = self.position.min(self.order.len());
+                self.position
+            }
+        };
+
+        self.order.splice(at..at, added);
+
+        if self.position >= self.order.len() {
+            self.position = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn albums(names: &[&str]) -> Vec<PathBuf> {
+        names
+            .iter()
+            .map(|name| PathBuf::from("/music").join(name))
+            .collect()
+    }
+
+    #[test]
+    fn test_next_and_previous_wrap_around() {
+        let paths = albums(&["a", "b", "c"]);
+        let mut shuffle = AlbumShuffle::new(&"/music".into(), &paths);
+        let first = shuffle.current().cloned();
+
+        shuffle.previous();
+        assert_eq!(shuffle.progress(), (3, 3));
+        shuffle.next();
+        assert_eq!(shuffle.progress(), (1, 3));
+        assert_eq!(shuffle.current().cloned(), first);
+    }
+
+    #[test]
+    fn test_shuffle_contains_every_album_once() {
+        let paths = albums(&["a", "b", "c", "d", "e"]);
+        let mut shuffle = AlbumShuffle::new(&"/music".into(), &paths);
+        let mut visited = vec![];
+
+        for _ in 0..paths.len() {
+            visited.push(shuffle.current().cloned().unwrap());
+            shuffle.next();
+        }
+
+        visited.sort();
+        assert_eq!(visited, paths);
+    }
+
+    #[test]
+    fn test_reconcile_keeps_current_album() {
+        let mut shuffle = AlbumShuffle {
+            root: "/music".into(),
+            order: albums(&["c", "a", "d", "b"]),
+            position: 2,
+        };
+
+        shuffle.reconcile(&albums(&["b", "c", "d", "e"]));
+
+        assert_eq!(shuffle.current(), Some(&"/music/d".into()));
+        assert_eq!(shuffle.order, albums(&["c", "d", "e", "b"]));
+        assert_eq!(shuffle.progress(), (2, 4));
+    }
+
+    #[test]
+    fn test_reconcile_removed_current_album() {
+        let mut shuffle = AlbumShuffle {
+            root: "/music".into(),
+            order: albums(&["a", "b"]),
+            position: 1,
+        };
+
+        shuffle.reconcile(&albums(&["a"]));
+
+        assert_eq!(shuffle.current(), Some(&"/music/a".into()));
+    }
+}
@@ -0,0 +1,115 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use bincode::{Decode, Encode};
+
+use super::persistent_data;
+
+// A position in an audio file that the user has marked.
+#[derive(Clone, Debug, PartialEq, Encode, Decode)]
+pub struct Bookmark {
+    // The position in seconds.
+    pub secs: usize,
+    // The name given to the bookmark, which is empty until it is renamed.
+    pub name: String,
+}
+
+// The bookmarks of each audio file, such as long mixes and lectures.
+#[derive(Clone, Debug, Default, PartialEq, Encode, Decode)]
+pub struct Bookmarks {
+    // The bookmarks of each file, in order of position. Files without any
+    // bookmarks aren't kept.
+    marks: HashMap<PathBuf, Vec<Bookmark>>,
+}
+
+impl Bookmarks {
+    // The cached bookmarks, without the files that no longer exist.
+    pub fn cached() -> Self {
+        let mut bookmarks: Self = persistent_data::cached_bookmarks().unwrap_or_default();
+        bookmarks.marks.retain(|file, _| file.exists());
+        bookmarks
+    }
+
+    // The bookmarks of `file`, in order of position.
+    pub fn of(file: &Path) -> Vec<Bookmark> {
+        Self::cached().get(file).to_vec()
+    }
+
+    // Adds a bookmark at `secs` in `file` and saves the bookmarks. A bookmark
+    // that is already at `secs` is kept, with its name.
+    pub fn save(file: &Path, secs: usize) {
+        Self::update(|bookmarks| bookmarks.add(file, secs))
+    }
+
+    // Renames the bookmark at `secs` in `file` and saves the bookmarks.
+    pub fn save_name(file: &Path, secs: usize, name: &str) {
+        Self::update(|bookmarks| bookmarks.rename(file, secs, name))
+    }
+
+    // Deletes the bookmark at `secs` in `file` and saves the bookmarks.
+    pub fn delete(file: &Path, secs: usize) {
+        Self::update(|bookmarks| bookmarks.remove(file, secs))
+    }
+
+    fn update(f: impl FnOnce(&mut Self)) {
+        let mut bookmarks = Self::cached();
+        f(&mut bookmarks);
+        _ = persistent_data::save_bookmarks(&bookmarks);
+    }
+
+    fn get(&self, file: &Path) -> &[Bookmark] {
+        self.marks.get(file).map_or(&[], Vec::as_slice)
+    }
+
+    fn add(&mut self, file: &Path, secs: usize) {
+        let marks = self.marks.entry(file.to_owned()).or_default();
+        if let Err(i) = marks.binary_search_by_key(&secs, |mark| mark.secs) {
+            let name = String::new();
+            marks.insert(i, Bookmark { secs, name });
+        }
+    }
+
+    fn rename(&mut self, file: &Path, secs: usize, name: &str) {
+        let marks = self.marks.get_mut(file);
+        if let Some(mark) = marks.and_then(|marks| marks.iter_mut().find(|m| m.secs == secs)) {
+            mark.name = name.trim().to_owned();
+        }
+    }
+
+    fn remove(&mut self, file: &Path, secs: usize) {
+        if let Some(marks) = self.marks.get_mut(file) {
+            marks.retain(|mark| mark.secs != secs);
+            if marks.is_empty() {
+                self.marks.remove(file);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bookmarks_are_ordered_and_edited() {
+        let mut bookmarks = Bookmarks::default();
+        let file = Path::new("/music/mix.flac");
+
+        bookmarks.add(file, 3600);
+        bookmarks.add(file, 90);
+        bookmarks.rename(file, 90, " intro ");
+        // Adding a bookmark at the same position keeps its name.
+        bookmarks.add(file, 90);
+
+        let secs: Vec<usize> = bookmarks.get(file).iter().map(|m| m.secs).collect();
+        assert_eq!(secs, vec![90, 3600]);
+        assert_eq!(bookmarks.get(file)[0].name, "intro");
+        assert!(bookmarks.get(Path::new("/music/other.flac")).is_empty());
+
+        bookmarks.remove(file, 90);
+        bookmarks.remove(file, 3600);
+        assert_eq!(bookmarks, Bookmarks::default());
+    }
+}
@@ -0,0 +1,82 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use bincode::{Decode, Encode};
+
+use super::persistent_data;
+
+// The albums and folders that the user has starred, to find them again.
+#[derive(Clone, Debug, Default, PartialEq, Encode, Decode)]
+pub struct Favorites {
+    paths: HashSet<PathBuf>,
+}
+
+impl Favorites {
+    // The saved favorites, or none when the file is missing or can't be read.
+    pub fn saved() -> Self {
+        persistent_data::saved_favorites().unwrap_or_default()
+    }
+
+    // Saves the favorites, which are kept after tap exits.
+    pub fn save(&self) {
+        _ = persistent_data::save_favorites(self);
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        self.paths.contains(path)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    // Stars `path`, or unstars it if it is starred. Returns true if it is
+    // starred now.
+    pub fn toggle(&mut self, path: &Path) -> bool {
+        match self.paths.remove(path) {
+            true => false,
+            false => self.paths.insert(path.to_owned()),
+        }
+    }
+
+    // Moves the favorites inside `old` to `new`, after it has been renamed.
+    pub fn rename(&mut self, old: &Path, new: &Path) -> bool {
+        let renamed: Vec<PathBuf> = self
+            .paths
+            .iter()
+            .filter(|path| path.starts_with(old))
+            .cloned()
+            .collect();
+        for path in renamed.iter() {
+            self.paths.remove(path);
+            let rest = path.strip_prefix(old).unwrap_or(path);
+            self.paths.insert(new.join(rest));
+        }
+        !renamed.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_favorites_are_toggled_and_renamed() {
+        let mut favorites = Favorites::default();
+        let album = Path::new("/music/artist/album");
+
+        assert!(favorites.toggle(album));
+        assert!(favorites.contains(album));
+        assert!(!favorites.contains(Path::new("/music/artist")));
+
+        assert!(favorites.rename(Path::new("/music/artist"), Path::new("/music/band")));
+        assert!(!favorites.contains(album));
+        assert!(favorites.contains(Path::new("/music/band/album")));
+        assert!(!favorites.rename(Path::new("/music/other"), Path::new("/music/else")));
+
+        assert!(!favorites.toggle(Path::new("/music/band/album")));
+        assert!(favorites.is_empty());
+    }
+}
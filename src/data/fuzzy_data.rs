@@ -0,0 +1,227 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use crate::fuzzy::{query::Case, FuzzyItem, MatchMode, SortMode};
+
+// The most queries kept in the history.
+const MAX_HISTORY: usize = 100;
+
+// The search of a fuzzy view when something was selected in it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LastSearch {
+    pub query: String,
+    // The path of the selected item.
+    pub selected: PathBuf,
+    // The index of the match on the bottom row of the list.
+    pub offset_y: usize,
+}
+
+// The fuzzy-finder state that outlives a single FuzzyView.
+#[derive(Debug)]
+pub struct FuzzyData {
+    // The items for the search root.
+    pub items: Vec<FuzzyItem>,
+    // The paths hidden from the fuzzy views and from random selection.
+    // These are kept until tap exits and are never saved.
+    pub hidden: HashSet<PathBuf>,
+    // Whether or not the hidden paths are shown anyway.
+    pub showing_hidden: bool,
+    // Whether or not hidden directories, such as '.stfolder', are scanned
+    // when a directory is browsed or reloaded.
+    pub hidden_dirs: bool,
+    // Whether or not only the favorites are matched.
+    pub favorites_only: bool,
+    // Whether or not the paths are matched instead of the display names,
+    // once this has been toggled.
+    pub match_paths: Option<bool>,
+    // How the case of the letters is matched.
+    pub case: Case,
+    // How the fuzzy matches with equal weights are ordered.
+    pub sort_mode: SortMode,
+    // How the query is matched with the items.
+    pub match_mode: MatchMode,
+    // The weight that the fuzzy matches need at least, once this has been
+    // changed.
+    pub threshold: Option<i64>,
+    // Whether or not the tracks of the selected album are shown beside the
+    // list.
+    pub preview: bool,
+    // Whether or not the size and the date of each item are shown after its
+    // name.
+    pub details: bool,
+    // Whether or not the position of each match is shown before its name.
+    pub show_indices: bool,
+    // The queries that selected something, oldest first and without
+    // duplicates. These are kept until tap exits and are never saved.
+    pub history: Vec<String>,
+    // The directories browsed by selecting folders and going to their
+    // parents, oldest first, and the index of the current one, to go back
+    // and forward through them. These are kept until tap exits.
+    pub dir_history: Vec<PathBuf>,
+    pub dir_history_pos: usize,
+    // The last search that something was selected in, for each directory,
+    // which is restored when the fuzzy-finder is opened for it again. These
+    // are kept until tap exits.
+    pub searches: HashMap<PathBuf, LastSearch>,
+}
+
+impl FuzzyData {
+    pub fn new(items: &Vec<FuzzyItem>) -> Self {
+        FuzzyData {
+            items: items.to_owned(),
+            hidden: HashSet::new(),
+            showing_hidden: false,
+            hidden_dirs: false,
+            favorites_only: false,
+            match_paths: None,
+            case: Case::Smart,
+            sort_mode: SortMode::Name,
+            match_mode: MatchMode::Fuzzy,
+            threshold: None,
+            preview: false,
+            details: false,
+            show_indices: false,
+            history: vec![],
+            dir_history: vec![],
+            dir_history_pos: 0,
+            searches: HashMap::new(),
+        }
+    }
+
+    // Adds `query` to the end of the history, moving it there if it was
+    // submitted before.
+    pub fn remember(&mut self, query: &str) {
+        if query.is_empty() {
+            return;
+        }
+        self.history.retain(|q| q != query);
+        self.history.push(query.to_owned());
+        if self.history.len() > MAX_HISTORY {
+            self.history.remove(0);
+        }
+    }
+
+    // Adds browsing from the directory `from` to `to` to the directory
+    // history, forgetting the directories that were ahead of `from`.
+    pub fn visit(&mut self, from: &Path, to: &Path) {
+        self.go_to(from);
+        if from == to {
+            return;
+        }
+        self.dir_history.truncate(self.dir_history_pos + 1);
+        self.dir_history.push(to.to_owned());
+        if self.dir_history.len() > MAX_HISTORY {
+            self.dir_history.remove(0);
+        }
+        self.dir_history_pos = self.dir_history.len() - 1;
+    }
+
+    // The directory browsed before `current`, going back to it.
+    pub fn back(&mut self, current: &Path) -> Option<PathBuf> {
+        self.go_to(current);
+        self.dir_history_pos = self.dir_history_pos.checked_sub(1)?;
+        Some(self.dir_history[self.dir_history_pos].to_owned())
+    }
+
+    // The directory browsed after `current`, before going back, going
+    // forward to it.
+    pub fn forward(&mut self, current: &Path) -> Option<PathBuf> {
+        self.go_to(current);
+        let dir = self.dir_history.get(self.dir_history_pos + 1)?;
+        self.dir_history_pos += 1;
+        Some(dir.to_owned())
+    }
+
+    // Makes `dir` the current directory of the history. Unless it already
+    // is, such as after opening a new search, it is added after the current
+    // one, in place of those ahead.
+    fn go_to(&mut self, dir: &Path) {
+        if self
+            .dir_history
+            .get(self.dir_history_pos)
+            .map(PathBuf::as_path)
+            == Some(dir)
+        {
+            return;
+        }
+        let len = std::cmp::min(self.dir_history_pos + 1, self.dir_history.len());
+        self.dir_history.truncate(len);
+        self.dir_history.push(dir.to_owned());
+        self.dir_history_pos = self.dir_history.len() - 1;
+    }
+
+    // The `paths` that aren't hidden.
+    pub fn visible(&self, paths: &Vec<PathBuf>) -> Vec<PathBuf> {
+        match self.showing_hidden || self.hidden.is_empty() {
+            true => paths.to_owned(),
+            false => paths
+                .iter()
+                .filter(|path| !is_hidden(&self.hidden, path))
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+// Whether or not `path` is one of the `hidden` paths, or is inside one.
+pub fn is_hidden(hidden: &HashSet<PathBuf>, path: &Path) -> bool {
+    hidden.iter().any(|h| path.starts_with(h))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remember_collapses_duplicates() {
+        let mut data = FuzzyData::new(&vec![]);
+
+        data.remember("beat");
+        data.remember("");
+        data.remember("mingus");
+        data.remember("beat");
+
+        assert_eq!(data.history, vec!["mingus", "beat"]);
+
+        for i in 0..MAX_HISTORY {
+            data.remember(&i.to_string());
+        }
+        assert_eq!(data.history.len(), MAX_HISTORY);
+        assert_eq!(data.history[0], "0");
+    }
+
+    #[test]
+    fn test_dir_history() {
+        let mut data = FuzzyData::new(&vec![]);
+        let (root, artist, album) = (
+            Path::new("/music"),
+            Path::new("/music/artist"),
+            Path::new("/music/artist/album"),
+        );
+        assert_eq!(data.back(root), None);
+        assert_eq!(data.forward(root), None);
+
+        data.visit(root, artist);
+        data.visit(artist, album);
+        assert_eq!(data.back(album).as_deref(), Some(artist));
+        assert_eq!(data.back(artist).as_deref(), Some(root));
+        assert_eq!(data.back(root), None);
+        assert_eq!(data.forward(root).as_deref(), Some(artist));
+        assert_eq!(data.forward(artist).as_deref(), Some(album));
+        assert_eq!(data.forward(album), None);
+
+        // Browsing somewhere else after going back forgets what was ahead.
+        data.back(album);
+        data.visit(artist, root);
+        assert_eq!(data.forward(root), None);
+        assert_eq!(data.back(root).as_deref(), Some(artist));
+
+        // A directory that wasn't browsed to, such as a new search, is added
+        // after the current one.
+        let other = Path::new("/music/other");
+        assert_eq!(data.back(other).as_deref(), Some(artist));
+        assert_eq!(data.forward(artist).as_deref(), Some(other));
+    }
+}
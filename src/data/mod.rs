@@ -1,4 +1,17 @@
+pub mod album_gain;
+pub mod album_shuffle;
+pub mod bookmarks;
+pub mod favorites;
+pub mod fuzzy_data;
 pub mod persistent_data;
+pub mod play_history;
+pub mod scan_limit;
+pub mod schema;
 pub mod session_data;
+pub mod station;
 
-pub use self::session_data::SessionData;
+pub use self::{
+    album_gain::AlbumGains, album_shuffle::AlbumShuffle, bookmarks::Bookmarks,
+    favorites::Favorites, fuzzy_data::FuzzyData, play_history::PlayHistory,
+    session_data::SessionData, station::Station,
+};
@@ -1,15 +1,38 @@
 use std::{
+    collections::HashMap,
     fs::{self, File},
-    io::{Read, Write},
-    path::PathBuf,
+    io::Read,
+    path::{Path, PathBuf},
     time::SystemTime,
 };
 
 use anyhow::bail;
-use bincode::{config, Decode};
+use bincode::{config, Decode, Encode};
 
 use crate::fuzzy::{self, FuzzyItem};
-use crate::utils;
+use crate::utils::{self, AppDir};
+
+use super::{
+    scan_limit,
+    schema::{self, Upgrade},
+    AlbumGains, AlbumShuffle, Bookmarks, Favorites, PlayHistory,
+};
+
+// The name of the log for migrating the saved state.
+const LOG: &str = "state";
+
+// The files that tap saves in the cache directory.
+const FILES: [&str; 9] = [
+    "path",
+    "last_modified",
+    "items",
+    "shuffle",
+    "history",
+    "gains",
+    "bookmarks",
+    "scans",
+    "session",
+];
 
 pub fn cached_path() -> Result<PathBuf, anyhow::Error> {
     // ~/.cache/tap/path
@@ -21,6 +44,86 @@ pub fn cached_items() -> Result<Vec<FuzzyItem>, anyhow::Error> {
     get_cached::<Vec<FuzzyItem>>("items")
 }
 
+pub fn cached_shuffle() -> Result<AlbumShuffle, anyhow::Error> {
+    // ~/.cache/tap/shuffle
+    get_cached::<AlbumShuffle>("shuffle")
+}
+
+pub fn save_shuffle(shuffle: &AlbumShuffle) -> Result<(), anyhow::Error> {
+    set_cached("shuffle", shuffle)
+}
+
+pub fn cached_history() -> Result<PlayHistory, anyhow::Error> {
+    // ~/.cache/tap/history
+    get_cached::<PlayHistory>("history")
+}
+
+pub fn save_history(history: &PlayHistory) -> Result<(), anyhow::Error> {
+    set_cached("history", history)
+}
+
+pub fn cached_gains() -> Result<AlbumGains, anyhow::Error> {
+    // ~/.cache/tap/gains
+    get_cached::<AlbumGains>("gains")
+}
+
+pub fn save_gains(gains: &AlbumGains) -> Result<(), anyhow::Error> {
+    set_cached("gains", gains)
+}
+
+pub fn cached_bookmarks() -> Result<Bookmarks, anyhow::Error> {
+    // ~/.cache/tap/bookmarks
+    get_cached::<Bookmarks>("bookmarks")
+}
+
+pub fn save_bookmarks(bookmarks: &Bookmarks) -> Result<(), anyhow::Error> {
+    set_cached("bookmarks", bookmarks)
+}
+
+// The favorites are kept in the data directory rather than the cache, as
+// they can't be made again.
+pub fn saved_favorites() -> Result<Favorites, anyhow::Error> {
+    // ~/.local/share/tap/favorites
+    let file = File::open(data_dir()?.join("favorites"))?;
+    decode("favorites", file)
+}
+
+pub fn save_favorites(favorites: &Favorites) -> Result<(), anyhow::Error> {
+    save(&data_dir()?.join("favorites"), favorites)
+}
+
+// Whether or not each root that had more directories than the scan limit
+// was scanned anyway.
+pub fn cached_scan_answers() -> Result<HashMap<PathBuf, bool>, anyhow::Error> {
+    // ~/.cache/tap/scans
+    get_cached::<HashMap<PathBuf, bool>>("scans")
+}
+
+pub fn save_scan_answers(answers: &HashMap<PathBuf, bool>) -> Result<(), anyhow::Error> {
+    set_cached("scans", answers)
+}
+
+pub fn session_file() -> Result<PathBuf, anyhow::Error> {
+    // ~/.cache/tap/session
+    Ok(cache_dir()?.join("session"))
+}
+
+// The album path, track index and playback status of the last player.
+pub fn cached_session(file: &PathBuf) -> Result<(PathBuf, usize, u8), anyhow::Error> {
+    match File::open(file) {
+        Ok(file) => decode("session", file),
+        Err(e) => bail!(
+            "could not restore the session from '{}'\n- `{}`",
+            file.display(),
+            e
+        ),
+    }
+}
+
+pub fn save_session(path: &PathBuf, index: usize, status: u8) -> Result<(), anyhow::Error> {
+    set_cached("session", (path, index, status))
+}
+
 fn cached_last_modified() -> Result<SystemTime, anyhow::Error> {
     // ~/.cache/tap/last_modified
     get_cached::<SystemTime>("last_modified")
@@ -39,14 +142,26 @@ pub fn uses_default(path: &PathBuf) -> bool {
 fn get_cached<T: Decode>(file_name: &str) -> Result<T, anyhow::Error> {
     let file_path = cache_dir()?.join(file_name);
 
-    let mut file = match File::open(file_path) {
+    let file = match File::open(file_path) {
         Ok(file) => file,
         Err(_) => {
             bail!("\r[tap error]: use '--set-default' to set a default directory")
         }
     };
-    let mut encoded = Vec::new();
-    file.read_to_end(&mut encoded)?;
+
+    decode(file_name, file)
+}
+
+// Decodes `file`, the file named `file_name`, migrating it in memory if it
+// was saved by an older version of tap.
+fn decode<T: Decode>(file_name: &str, mut file: File) -> Result<T, anyhow::Error> {
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+
+    let encoded = match schema::upgrade(file_name, &bytes)? {
+        Upgrade::Current(data) | Upgrade::Migrated(_, data) => data,
+        Upgrade::Newer(_) => bail!("the file was saved by a newer version of tap"),
+    };
 
     let config = config::standard();
     let (ret, _): (T, _) = bincode::decode_from_slice(&encoded[..], config)?;
@@ -54,54 +169,132 @@ fn get_cached<T: Decode>(file_name: &str) -> Result<T, anyhow::Error> {
     Ok(ret)
 }
 
-fn cache_dir() -> Result<PathBuf, anyhow::Error> {
-    let home_dir = match std::env::var("HOME") {
-        Ok(dir) => PathBuf::from(dir),
-        Err(e) => bail!(e),
-    };
+// Saves `value` to `file_name`, unless a newer version of tap saved it.
+fn set_cached<T: Encode>(file_name: &str, value: T) -> Result<(), anyhow::Error> {
+    save(&cache_dir()?.join(file_name), value)
+}
+
+// Saves `value` to the file at `path`, unless a newer version of tap saved it.
+fn save<T: Encode>(path: &Path, value: T) -> Result<(), anyhow::Error> {
+    if is_newer(path) {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        bail!("'{name}' was saved by a newer version of tap");
+    }
 
-    let cache_dir = home_dir.join(".cache").join("tap");
-    fs::create_dir_all(&cache_dir)?;
-    Ok(cache_dir)
+    let encoded = bincode::encode_to_vec(value, config::standard())?;
+    utils::write_private(path, &schema::with_header(&encoded))?;
+
+    Ok(())
 }
 
-pub fn update_cache(path: &PathBuf) -> Result<Vec<FuzzyItem>, anyhow::Error> {
-    let last_modified = utils::last_modified(path)?;
-    let items = fuzzy::create_items(path)?;
+// Whether or not the file at `path` was saved by a newer version of tap.
+fn is_newer(path: &Path) -> bool {
+    let mut header = [0; 5];
+    match File::open(path).and_then(|mut file| file.read(&mut header)) {
+        Ok(len) => schema::split_header(&header[..len]).0 > schema::VERSION,
+        Err(_) => false,
+    }
+}
 
-    let config = config::standard();
-    let cache_dir = cache_dir()?;
+// Migrates the files saved by older versions of tap, before anything reads
+// them. Returns a message for the user if any were migrated, or if any were
+// saved by a newer version and are left as they are.
+pub fn migrate_state() -> Option<String> {
+    let dir = cache_dir().ok()?;
+    let (migrated, newer) = migrate_dir(&dir);
 
-    let encoded_path = bincode::encode_to_vec(path, config)?;
-    let encoded_modified = bincode::encode_to_vec(last_modified, config)?;
-    let encoded_items = bincode::encode_to_vec(items.clone(), config)?;
+    let msg = match (migrated.is_empty(), newer.is_empty()) {
+        (true, true) => return None,
+        (_, false) => format!(
+            "'{}' in '{}' were saved by a newer version of tap, so changes to them aren't saved",
+            newer.join("', '"),
+            utils::display_path(&dir),
+        ),
+        (false, true) => format!(
+            "updated the saved state to version {}, with backups in '{}'",
+            schema::VERSION,
+            utils::display_path(&dir),
+        ),
+    };
+    utils::log(LOG, &msg);
+    Some(msg)
+}
 
-    let mut path = File::create(cache_dir.join("path"))?;
-    path.write_all(&encoded_path)?;
+// Migrates the files in `dir` that were saved by older versions of tap,
+// keeping a copy of each one as it was. Returns the names of the files that
+// were migrated and of those that were saved by a newer version.
+fn migrate_dir(dir: &Path) -> (Vec<&'static str>, Vec<&'static str>) {
+    let mut migrated = vec![];
+    let mut newer = vec![];
 
-    let mut last_modified = File::create(cache_dir.join("last_modified"))?;
-    last_modified.write_all(&encoded_modified)?;
+    for name in FILES {
+        let path = dir.join(name);
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(_) => continue,
+        };
 
-    let mut items_file = File::create(cache_dir.join("items"))?;
-    items_file.write_all(&encoded_items)?;
+        let result = schema::upgrade(name, &bytes).and_then(|upgrade| {
+            match upgrade {
+                Upgrade::Current(_) => (),
+                Upgrade::Newer(_) => newer.push(name),
+                Upgrade::Migrated(version, data) => {
+                    utils::write_private(&dir.join(format!("{name}.v{version}.bak")), &bytes)?;
+                    utils::write_private(&path, &schema::with_header(&data))?;
+                    migrated.push(name);
+                }
+            }
+            Ok(())
+        });
 
-    Ok(items)
+        if let Err(e) = result {
+            utils::log(LOG, &format!("could not migrate '{name}': {e}"));
+        }
+    }
+    (migrated, newer)
+}
+
+fn cache_dir() -> Result<PathBuf, anyhow::Error> {
+    utils::app_dir(AppDir::Cache)
+}
+
+fn data_dir() -> Result<PathBuf, anyhow::Error> {
+    utils::app_dir(AppDir::Data)
+}
+
+// Scans `path` and caches the items, unless more than `limit` directories
+// are found. The cached items never include the hidden directories.
+pub fn update_cache(
+    path: &PathBuf,
+    limit: Option<usize>,
+) -> Result<Option<Vec<FuzzyItem>>, anyhow::Error> {
+    let last_modified = utils::last_modified(path)?;
+    let items = match fuzzy::create_items_within(path, limit, false)? {
+        Some(items) => items,
+        None => return Ok(None),
+    };
+
+    set_cached("path", path)?;
+    set_cached("last_modified", last_modified)?;
+    set_cached("items", &items)?;
+
+    Ok(Some(items))
 }
 
 pub fn get_cached_items(path: &PathBuf) -> Result<Vec<FuzzyItem>, anyhow::Error> {
     Ok(match needs_update(path)? {
-        true => utils::display_with_spinner(update_cache, path, "updating")?,
+        true => scan_limit::scan(path, "updating", update_cache)?,
         false => match cached_items() {
             Ok(items) => items,
             // Try an update before bailing.
-            Err(_) => utils::display_with_spinner(update_cache, path, "updating")?,
+            Err(_) => scan_limit::scan(path, "updating", update_cache)?,
         },
     })
 }
 
 pub fn set_default_path(path: PathBuf) -> Result<(), anyhow::Error> {
     let msg = "setting default";
-    match utils::display_with_spinner(update_cache, &path, msg) {
+    match scan_limit::scan(&path, msg, update_cache) {
         Ok(_) => {
             println!("\r[tap]: {}...", msg);
             println!("[tap]: done!");
@@ -117,3 +310,33 @@ pub fn print_default_path() -> Result<(), anyhow::Error> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrate_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let fixtures = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/state/v1");
+        let v1 = fs::read(fixtures.join("session")).unwrap();
+        fs::write(dir.path().join("session"), &v1).unwrap();
+
+        // A file from a newer version of tap is left as it is.
+        let mut newer = schema::with_header(&[1, 2, 3]);
+        newer[4] = schema::VERSION + 1;
+        fs::write(dir.path().join("gains"), &newer).unwrap();
+
+        assert_eq!(migrate_dir(dir.path()), (vec!["session"], vec!["gains"]));
+        assert_eq!(fs::read(dir.path().join("session.v1.bak")).unwrap(), v1);
+        assert_eq!(fs::read(dir.path().join("gains")).unwrap(), newer);
+        assert!(is_newer(&dir.path().join("gains")));
+
+        let session: (PathBuf, usize, u8) =
+            decode("session", File::open(dir.path().join("session")).unwrap()).unwrap();
+        assert_eq!(session.1, 3);
+
+        // The files are only migrated once.
+        assert_eq!(migrate_dir(dir.path()), (vec![], vec!["gains"]));
+    }
+}
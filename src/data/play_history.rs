@@ -0,0 +1,91 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use bincode::{Decode, Encode};
+
+use super::persistent_data;
+
+// The number of sessions kept in the history.
+const SESSION_COUNT: usize = 32;
+
+// The albums played in each of the recent sessions, used to find the
+// albums that are often played together.
+#[derive(Clone, Debug, Default, PartialEq, Encode, Decode)]
+pub struct PlayHistory {
+    // The id of each session and the albums played in it, oldest first.
+    sessions: Vec<(u32, Vec<PathBuf>)>,
+}
+
+impl PlayHistory {
+    // The cached history, which is empty if there isn't one.
+    pub fn cached() -> Self {
+        persistent_data::cached_history().unwrap_or_default()
+    }
+
+    // Adds `album` to the history of this session and saves the history.
+    pub fn record(album: &PathBuf) {
+        let mut history = Self::cached();
+        history.push(std::process::id(), album);
+        _ = persistent_data::save_history(&history);
+    }
+
+    // Adds `album` to `session`, starting a new session if `session` isn't
+    // the latest one.
+    fn push(&mut self, session: u32, album: &PathBuf) {
+        match self.sessions.last_mut() {
+            Some((id, albums)) if *id == session => {
+                if !albums.contains(album) {
+                    albums.push(album.to_owned());
+                }
+            }
+            _ => {
+                self.sessions.push((session, vec![album.to_owned()]));
+                if self.sessions.len() > SESSION_COUNT {
+                    self.sessions.remove(0);
+                }
+            }
+        }
+    }
+
+    // The number of sessions that each album was played in together with `album`.
+    pub fn co_plays(&self, album: &PathBuf) -> HashMap<PathBuf, usize> {
+        let mut counts = HashMap::new();
+
+        for (_, albums) in self.sessions.iter().filter(|(_, a)| a.contains(album)) {
+            for other in albums.iter().filter(|a| *a != album) {
+                *counts.entry(other.to_owned()).or_insert(0) += 1;
+            }
+        }
+
+        counts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_co_plays() {
+        let mut history = PlayHistory::default();
+        for (session, album) in [(1, "a"), (1, "b"), (1, "a"), (2, "c"), (3, "a"), (3, "b")] {
+            history.push(session, &PathBuf::from(album));
+        }
+
+        let co_plays = history.co_plays(&"a".into());
+
+        assert_eq!(co_plays.get(&PathBuf::from("b")), Some(&2));
+        assert_eq!(co_plays.get(&PathBuf::from("c")), None);
+        assert_eq!(co_plays.len(), 1);
+    }
+
+    #[test]
+    fn test_oldest_sessions_are_removed() {
+        let mut history = PlayHistory::default();
+        for session in 0..SESSION_COUNT as u32 + 2 {
+            history.push(session, &PathBuf::from("a"));
+        }
+
+        assert_eq!(history.sessions.len(), SESSION_COUNT);
+        assert_eq!(history.sessions[0].0, 2);
+    }
+}
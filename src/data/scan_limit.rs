@@ -0,0 +1,115 @@
+use std::{
+    collections::HashMap,
+    io::{stderr, stdin, BufRead, IsTerminal, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::bail;
+
+use crate::config::args;
+use crate::utils::{self, display_path};
+
+use super::persistent_data;
+
+// Scans `path`, stopping with `None` once more than the limit of directories
+// have been found, if there is a limit.
+pub type Scan<T> = fn(&PathBuf, Option<usize>) -> Result<Option<T>, anyhow::Error>;
+
+// Runs `scan` on `path` with a spinner showing `msg`. When it finds more
+// directories than the scan limit, asks whether to scan all of them, and
+// remembers the answer for `path`.
+pub fn scan<T: Send + 'static>(
+    path: &PathBuf,
+    msg: &'static str,
+    scan: Scan<T>,
+) -> Result<T, anyhow::Error> {
+    let mut answers: HashMap<PathBuf, bool> =
+        persistent_data::cached_scan_answers().unwrap_or_default();
+    let limit = match answers.get(path) {
+        Some(true) => None,
+        _ => args::scan_limit(),
+    };
+
+    if let Some(result) = utils::display_with_spinner(move |p| scan(p, limit), path, msg)? {
+        return Ok(result);
+    }
+
+    let limit = limit.unwrap_or_default();
+    if answers.get(path) == Some(&false) {
+        bail!(
+            "{}, and scanning all of them was declined before. Set 'scan-limit = 0' in the config file to scan it anyway",
+            too_many(path, limit)
+        );
+    }
+    if !stdin().is_terminal() {
+        bail!("{}", too_many(path, limit));
+    }
+
+    let answer = ask(path, limit, &mut stdin().lock(), &mut stderr())?;
+    answers.insert(path.to_owned(), answer);
+    _ = persistent_data::save_scan_answers(&answers);
+
+    if !answer {
+        bail!("{}", too_many(path, limit));
+    }
+    match utils::display_with_spinner(move |p| scan(p, None), path, msg)? {
+        Some(result) => Ok(result),
+        None => bail!("could not scan '{}'", display_path(path)),
+    }
+}
+
+fn too_many(path: &Path, limit: usize) -> String {
+    format!("'{}' has more than {limit} directories", display_path(path))
+}
+
+// Asks on `output` whether to keep scanning `path` after more than `limit`
+// directories were found, reading the answer from `input`.
+fn ask(
+    path: &Path,
+    limit: usize,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> Result<bool, anyhow::Error> {
+    loop {
+        write!(
+            output,
+            "[tap]: {}, which can take a long time to scan. Keep scanning? [y/n] ",
+            too_many(path, limit)
+        )?;
+        output.flush()?;
+
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            return Ok(false);
+        }
+
+        match answer.trim() {
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_ask() {
+        let path = Path::new("/");
+        let mut output = vec![];
+
+        let answer = ask(path, 10, &mut Cursor::new("maybe\ny\n"), &mut output);
+        assert!(answer.unwrap());
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.matches("Keep scanning?").count(), 2);
+        assert!(output.contains("more than 10 directories"));
+
+        assert!(!ask(path, 10, &mut Cursor::new("no\n"), &mut vec![]).unwrap());
+        // Stopping without an answer doesn't scan.
+        assert!(!ask(path, 10, &mut Cursor::new(""), &mut vec![]).unwrap());
+    }
+}
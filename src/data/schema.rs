@@ -0,0 +1,418 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::bail;
+use bincode::{config, Decode, Encode};
+
+use crate::fuzzy::{self, FuzzyItem};
+
+// The version of the format of the files that tap saves in '~/.cache/tap'.
+// Bump it, and add a migration from the previous version, whenever the
+// encoding of a saved type changes.
+pub const VERSION: u8 = 8;
+
+// The start of the header of each file, which is followed by the version.
+// The files from before versioning are version 1 and have no header. They
+// can't start with 0xff, since each saved type starts with a length or other
+// integer, and bincode never starts one with it.
+const MAGIC: &[u8] = b"\xfftap";
+
+// Migrates the data of the file named `file` from one version to the next.
+type Migration = fn(file: &str, data: Vec<u8>) -> Result<Vec<u8>, anyhow::Error>;
+
+// The migration from each version to the next, starting with version 1.
+const MIGRATIONS: [Migration; VERSION as usize - 1] = [
+    v1_to_v2, v2_to_v3, v3_to_v4, v4_to_v5, v5_to_v6, v6_to_v7, v7_to_v8,
+];
+
+#[derive(Debug, PartialEq)]
+pub enum Upgrade {
+    // The data is already the current version.
+    Current(Vec<u8>),
+    // The data has been migrated from an older version.
+    Migrated(u8, Vec<u8>),
+    // The file was saved by a newer version of tap, which this version
+    // can't read or overwrite.
+    Newer(u8),
+}
+
+// The version of `bytes` and the data after the header.
+pub fn split_header(bytes: &[u8]) -> (u8, &[u8]) {
+    match bytes.strip_prefix(MAGIC) {
+        Some([version, data @ ..]) => (*version, data),
+        _ => (1, bytes),
+    }
+}
+
+// Adds the header of the current version to `data`.
+pub fn with_header(data: &[u8]) -> Vec<u8> {
+    [MAGIC, &[VERSION], data].concat()
+}
+
+// Migrates `bytes`, read from the file named `file`, to the current version.
+pub fn upgrade(file: &str, bytes: &[u8]) -> Result<Upgrade, anyhow::Error> {
+    let (version, data) = split_header(bytes);
+    if version == 0 {
+        bail!("'{file}' has an invalid version");
+    }
+    if version > VERSION {
+        return Ok(Upgrade::Newer(version));
+    }
+    if version == VERSION {
+        return Ok(Upgrade::Current(data.to_vec()));
+    }
+
+    let mut data = data.to_vec();
+    for migration in &MIGRATIONS[version as usize - 1..] {
+        data = migration(file, data)?;
+    }
+    Ok(Upgrade::Migrated(version, data))
+}
+
+// Version 2 added the header, and left the data as it was.
+fn v1_to_v2(_file: &str, data: Vec<u8>) -> Result<Vec<u8>, anyhow::Error> {
+    Ok(data)
+}
+
+// A fuzzy item as saved by version 2.
+#[derive(Decode)]
+struct FuzzyItemV2 {
+    path: PathBuf,
+    depth: usize,
+    display: String,
+    key: char,
+    has_audio: bool,
+    child_count: usize,
+    indices: Vec<usize>,
+    weight: i64,
+}
+
+// A fuzzy item as saved by version 3.
+#[derive(Decode, Encode)]
+struct FuzzyItemV3 {
+    path: PathBuf,
+    depth: usize,
+    display: String,
+    key: char,
+    has_audio: bool,
+    child_count: usize,
+    indices: Vec<usize>,
+    weight: i64,
+    original_index: usize,
+}
+
+// Version 3 added the original index to the fuzzy items, which is their
+// position in the saved items.
+fn v2_to_v3(file: &str, data: Vec<u8>) -> Result<Vec<u8>, anyhow::Error> {
+    if file != "items" {
+        return Ok(data);
+    }
+
+    let (items, _): (Vec<FuzzyItemV2>, _) = bincode::decode_from_slice(&data, config::standard())?;
+    let items: Vec<FuzzyItemV3> = items
+        .into_iter()
+        .enumerate()
+        .map(|(original_index, item)| FuzzyItemV3 {
+            path: item.path,
+            depth: item.depth,
+            display: item.display,
+            key: item.key,
+            has_audio: item.has_audio,
+            child_count: item.child_count,
+            indices: item.indices,
+            weight: item.weight,
+            original_index,
+        })
+        .collect();
+    Ok(bincode::encode_to_vec(items, config::standard())?)
+}
+
+// A fuzzy item as saved by version 4.
+#[derive(Decode, Encode)]
+struct FuzzyItemV4 {
+    path: PathBuf,
+    depth: usize,
+    display: String,
+    key: char,
+    has_audio: bool,
+    child_count: usize,
+    indices: Vec<usize>,
+    weight: i64,
+    original_index: usize,
+    modified: u64,
+}
+
+// Version 4 added when the directory of each fuzzy item was last modified,
+// which is read from the directory, or 0 if it no longer exists.
+fn v3_to_v4(file: &str, data: Vec<u8>) -> Result<Vec<u8>, anyhow::Error> {
+    if file != "items" {
+        return Ok(data);
+    }
+
+    let (items, _): (Vec<FuzzyItemV3>, _) = bincode::decode_from_slice(&data, config::standard())?;
+    let items: Vec<FuzzyItemV4> = items
+        .into_iter()
+        .map(|item| FuzzyItemV4 {
+            modified: fs::metadata(&item.path)
+                .and_then(|m| m.modified())
+                .map_or(0, fuzzy::unix_secs),
+            path: item.path,
+            depth: item.depth,
+            display: item.display,
+            key: item.key,
+            has_audio: item.has_audio,
+            child_count: item.child_count,
+            indices: item.indices,
+            weight: item.weight,
+            original_index: item.original_index,
+        })
+        .collect();
+    Ok(bincode::encode_to_vec(items, config::standard())?)
+}
+
+// A fuzzy item as saved by version 5.
+#[derive(Decode, Encode)]
+struct FuzzyItemV5 {
+    path: PathBuf,
+    depth: usize,
+    display: String,
+    key: char,
+    has_audio: bool,
+    child_count: usize,
+    audio_count: usize,
+    indices: Vec<usize>,
+    weight: i64,
+    original_index: usize,
+    modified: u64,
+}
+
+// Version 5 added the number of audio files in the directory of each fuzzy
+// item. Both counts are read from the directory again, since the number of
+// subdirectories was only counted up to two in directories with audio. The
+// items of directories that no longer exist keep their count, with no audio.
+fn v4_to_v5(file: &str, data: Vec<u8>) -> Result<Vec<u8>, anyhow::Error> {
+    if file != "items" {
+        return Ok(data);
+    }
+
+    let (items, _): (Vec<FuzzyItemV4>, _) = bincode::decode_from_slice(&data, config::standard())?;
+    let items: Vec<FuzzyItemV5> = items
+        .into_iter()
+        .map(|item| {
+            let (audio_count, child_count, _, _) =
+                fuzzy::count_entries(&item.path).unwrap_or((0, item.child_count, 0, vec![]));
+            FuzzyItemV5 {
+                path: item.path,
+                depth: item.depth,
+                display: item.display,
+                key: item.key,
+                has_audio: item.has_audio,
+                child_count,
+                audio_count,
+                indices: item.indices,
+                weight: item.weight,
+                original_index: item.original_index,
+                modified: item.modified,
+            }
+        })
+        .collect();
+    Ok(bincode::encode_to_vec(items, config::standard())?)
+}
+
+// Version 6 added whether or not each fuzzy item is marked, which none of the
+// saved items are.
+fn v5_to_v6(file: &str, data: Vec<u8>) -> Result<Vec<u8>, anyhow::Error> {
+    if file != "items" {
+        return Ok(data);
+    }
+
+    let (items, _): (Vec<FuzzyItemV5>, _) = bincode::decode_from_slice(&data, config::standard())?;
+    let items: Vec<FuzzyItemV6> = items
+        .into_iter()
+        .map(|item| FuzzyItemV6 {
+            path: item.path,
+            depth: item.depth,
+            display: item.display,
+            key: item.key,
+            has_audio: item.has_audio,
+            child_count: item.child_count,
+            audio_count: item.audio_count,
+            indices: item.indices,
+            weight: item.weight,
+            original_index: item.original_index,
+            modified: item.modified,
+            selected: false,
+        })
+        .collect();
+    Ok(bincode::encode_to_vec(items, config::standard())?)
+}
+
+// A fuzzy item as saved by version 6.
+#[derive(Decode, Encode)]
+struct FuzzyItemV6 {
+    path: PathBuf,
+    depth: usize,
+    display: String,
+    key: char,
+    has_audio: bool,
+    child_count: usize,
+    audio_count: usize,
+    indices: Vec<usize>,
+    weight: i64,
+    original_index: usize,
+    modified: u64,
+    selected: bool,
+}
+
+// Version 7 added the total size of the audio files of each fuzzy item, which
+// is read from the directory, or 0 if it no longer exists.
+fn v6_to_v7(file: &str, data: Vec<u8>) -> Result<Vec<u8>, anyhow::Error> {
+    if file != "items" {
+        return Ok(data);
+    }
+
+    let (items, _): (Vec<FuzzyItemV6>, _) = bincode::decode_from_slice(&data, config::standard())?;
+    let items: Vec<FuzzyItemV7> = items
+        .into_iter()
+        .map(|item| FuzzyItemV7 {
+            size_bytes: fuzzy::count_entries(&item.path).map_or(0, |(_, _, size, _)| size),
+            path: item.path,
+            depth: item.depth,
+            display: item.display,
+            key: item.key,
+            has_audio: item.has_audio,
+            child_count: item.child_count,
+            audio_count: item.audio_count,
+            indices: item.indices,
+            weight: item.weight,
+            original_index: item.original_index,
+            modified: item.modified,
+            selected: item.selected,
+        })
+        .collect();
+    Ok(bincode::encode_to_vec(items, config::standard())?)
+}
+
+// A fuzzy item as saved by version 7.
+#[derive(Decode, Encode)]
+struct FuzzyItemV7 {
+    path: PathBuf,
+    depth: usize,
+    display: String,
+    key: char,
+    has_audio: bool,
+    child_count: usize,
+    audio_count: usize,
+    size_bytes: u64,
+    indices: Vec<usize>,
+    weight: i64,
+    original_index: usize,
+    modified: u64,
+    selected: bool,
+}
+
+// Version 8 added the extensions of the audio files of each fuzzy item, which
+// are read from the directory, or empty if it no longer exists.
+fn v7_to_v8(file: &str, data: Vec<u8>) -> Result<Vec<u8>, anyhow::Error> {
+    if file != "items" {
+        return Ok(data);
+    }
+
+    let (items, _): (Vec<FuzzyItemV7>, _) = bincode::decode_from_slice(&data, config::standard())?;
+    let items: Vec<FuzzyItem> = items
+        .into_iter()
+        .map(|item| FuzzyItem {
+            extensions: fuzzy::count_entries(&item.path).map_or(vec![], |(_, _, _, ext)| ext),
+            path: item.path,
+            depth: item.depth,
+            display: item.display,
+            key: item.key,
+            has_audio: item.has_audio,
+            child_count: item.child_count,
+            audio_count: item.audio_count,
+            size_bytes: item.size_bytes,
+            indices: item.indices,
+            weight: item.weight,
+            original_index: item.original_index,
+            modified: item.modified,
+            selected: item.selected,
+        })
+        .collect();
+    Ok(bincode::encode_to_vec(items, config::standard())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::AlbumGains;
+
+    // The file named `file` saved by the version `version` of the format.
+    fn fixture(version: u8, file: &str) -> Vec<u8> {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/state")
+            .join(format!("v{version}"))
+            .join(file);
+        fs::read(&path).expect("read state fixture")
+    }
+
+    fn migrated<T: bincode::Decode>(file: &str, bytes: &[u8], from: u8) -> T {
+        match upgrade(file, bytes).unwrap() {
+            Upgrade::Migrated(version, data) => {
+                assert_eq!(version, from);
+                bincode::decode_from_slice(&data, config::standard())
+                    .unwrap()
+                    .0
+            }
+            upgrade => panic!("'{file}' wasn't migrated: {upgrade:?}"),
+        }
+    }
+
+    #[test]
+    fn test_v1_to_v2_session() {
+        let session: (PathBuf, usize, u8) = migrated("session", &fixture(1, "session"), 1);
+        assert_eq!(session, ("/music/Mingus/Ah Um".into(), 3, 1));
+    }
+
+    #[test]
+    fn test_v1_to_v2_gains() {
+        let gains: AlbumGains = migrated("gains", &fixture(1, "gains"), 1);
+        assert_eq!(gains.get(&PathBuf::from("/music/quiet")), 4);
+        assert_eq!(gains.get(&PathBuf::from("/music/loud")), -6);
+    }
+
+    #[test]
+    fn test_v2_to_v8_items() {
+        let items: Vec<FuzzyItem> = migrated("items", &fixture(2, "items"), 2);
+        let paths: Vec<_> = items.iter().map(|item| item.path.to_owned()).collect();
+        assert_eq!(paths, vec![PathBuf::from("/music"), "/music/Mingus".into()]);
+        assert_eq!(items[1].display, "Mingus");
+        assert_eq!(items[1].child_count, 1);
+        let indices: Vec<_> = items.iter().map(|item| item.original_index).collect();
+        assert_eq!(indices, vec![0, 1]);
+        // The directories don't exist, so when they were modified and their
+        // audio files aren't known.
+        assert!(items.iter().all(|item| item.modified == 0));
+        assert!(items.iter().all(|item| item.audio_count == 0));
+        assert!(items.iter().all(|item| item.size_bytes == 0));
+        assert!(items.iter().all(|item| item.extensions.is_empty()));
+        assert!(items.iter().all(|item| !item.selected));
+    }
+
+    #[test]
+    fn test_current_and_newer_versions() {
+        let data = vec![1, 2, 3];
+        let current = with_header(&data);
+        assert_eq!(split_header(&current), (VERSION, &data[..]));
+        assert_eq!(upgrade("items", &current).unwrap(), Upgrade::Current(data));
+
+        let mut newer = current.to_owned();
+        newer[MAGIC.len()] = VERSION + 1;
+        assert_eq!(
+            upgrade("items", &newer).unwrap(),
+            Upgrade::Newer(VERSION + 1)
+        );
+
+        let mut invalid = current;
+        invalid[MAGIC.len()] = 0;
+        assert!(upgrade("items", &invalid).is_err());
+    }
+}
@@ -2,9 +2,12 @@ use std::{collections::VecDeque, path::PathBuf};
 
 use anyhow::bail;
 
+use crate::config::args;
 use crate::fuzzy::{self, FuzzyItem};
 use crate::player::{Player, PlayerOpts};
-use crate::utils::IntoInner;
+use crate::utils::{display_path, IntoInner};
+
+use super::{AlbumShuffle, FuzzyData, Station};
 
 // The path and track number for an audio file.
 type Track = (PathBuf, usize);
@@ -17,6 +20,12 @@ pub struct SessionData {
     // The queue of `track`s that takes one of two forms:
     // [`current_track`] or [`previous_track`, `current_track`, `next_random_track`]
     queue: VecDeque<Track>,
+    // The state shared by the fuzzy views.
+    fuzzy: FuzzyData,
+    // The album shuffle, `Some` when shuffling albums.
+    shuffle: Option<AlbumShuffle>,
+    // The album station, `Some` when playing albums similar to a seed album.
+    station: Option<Station>,
 }
 
 impl SessionData {
@@ -24,13 +33,19 @@ impl SessionData {
         let paths = fuzzy::leaf_paths(&items);
         let queue: VecDeque<Track> = match Player::randomized(&paths) {
             Some(first) => VecDeque::from([first]),
-            None => bail!("no audio files detected in '{}'", path.display()),
+            None => bail!("no audio files detected in '{}'", display_path(path)),
         };
 
         let data = Self {
             opts: PlayerOpts::default(),
             paths,
             queue,
+            fuzzy: FuzzyData {
+                hidden_dirs: args::hidden_dirs(),
+                ..FuzzyData::new(items)
+            },
+            shuffle: None,
+            station: None,
         };
 
         Ok(data)
@@ -42,10 +57,20 @@ impl IntoInner for SessionData {
         (u8, u8, bool, bool),
         Vec<PathBuf>,
         VecDeque<(PathBuf, usize)>,
+        FuzzyData,
+        Option<AlbumShuffle>,
+        Option<Station>,
     );
 
     fn into_inner(self) -> Self::T {
-        (self.opts.into_inner(), self.paths, self.queue)
+        (
+            self.opts.into_inner(),
+            self.paths,
+            self.queue,
+            self.fuzzy,
+            self.shuffle,
+            self.station,
+        )
     }
 }
 
@@ -54,6 +79,9 @@ impl Into<SessionData>
         (u8, u8, bool, bool),
         Vec<PathBuf>,
         VecDeque<(PathBuf, usize)>,
+        FuzzyData,
+        Option<AlbumShuffle>,
+        Option<Station>,
     )
 {
     fn into(self) -> SessionData {
@@ -61,6 +89,9 @@ impl Into<SessionData>
             opts: self.0.into(),
             paths: self.1,
             queue: self.2,
+            fuzzy: self.3,
+            shuffle: self.4,
+            station: self.5,
         }
     }
 }
@@ -0,0 +1,375 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    path::{Path, PathBuf},
+};
+
+use rand::{seq::SliceRandom, thread_rng};
+
+use crate::player::{valid_audio_ext, AudioFile};
+
+use super::PlayHistory;
+
+// The number of albums kept in the queue after the current album.
+pub const STATION_AHEAD: usize = 3;
+// The number of albums played by a station that won't be repeated.
+const RECENT_LEN: usize = 50;
+// The number of random albums considered on each refill, in addition to
+// the albums near the seed and the albums played with it.
+const SAMPLE_LEN: usize = 32;
+
+// The tags used to compare albums, read from the first track of an album.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AlbumTags {
+    pub artist: String,
+    pub genre: Option<String>,
+    pub year: Option<u32>,
+}
+
+impl AlbumTags {
+    // The tags of the first audio file in `album`, if it can be read.
+    pub fn read(album: &Path) -> Option<Self> {
+        let mut paths = album
+            .read_dir()
+            .ok()?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| valid_audio_ext(path))
+            .collect::<Vec<PathBuf>>();
+
+        paths.sort();
+
+        let file = AudioFile::new(paths.into_iter().next()?).ok()?;
+
+        Some(AlbumTags {
+            artist: file.artist,
+            genre: file.genre,
+            year: file.year,
+        })
+    }
+}
+
+// A station keeps a queue of albums that are similar to a seed album,
+// topping it up as the albums are played.
+#[derive(Debug)]
+pub struct Station {
+    // The album that the station was started from.
+    seed: PathBuf,
+    seed_tags: AlbumTags,
+    // The album that is playing.
+    current: PathBuf,
+    // The albums to play next, in order.
+    queue: VecDeque<PathBuf>,
+    // The albums played by the station, most recent last.
+    recent: VecDeque<PathBuf>,
+    // The track index that each album was left at, for the albums that
+    // weren't played to the end.
+    positions: HashMap<PathBuf, usize>,
+    // The tag index, i.e. the tags of the albums read so far. `None` for
+    // albums whose tags couldn't be read.
+    index: HashMap<PathBuf, Option<AlbumTags>>,
+    // The number of sessions that each album was played in with the seed.
+    co_plays: HashMap<PathBuf, usize>,
+}
+
+impl Station {
+    // Starts a station from `seed`, filling the queue from `paths`.
+    pub fn new(seed: &PathBuf, paths: &Vec<PathBuf>) -> Self {
+        let mut station = Station {
+            seed: seed.to_owned(),
+            seed_tags: AlbumTags::read(seed).unwrap_or_default(),
+            current: seed.to_owned(),
+            queue: VecDeque::new(),
+            recent: VecDeque::from([seed.to_owned()]),
+            positions: HashMap::new(),
+            index: HashMap::new(),
+            co_plays: PlayHistory::cached().co_plays(seed),
+        };
+        station.refill(paths);
+        station
+    }
+
+    // The name of the seed album.
+    pub fn name(&self) -> String {
+        self.seed
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+
+    // The path of the current album.
+    pub fn current(&self) -> &PathBuf {
+        &self.current
+    }
+
+    // Moves to the next album in the queue and tops the queue up.
+    pub fn next(&mut self, paths: &Vec<PathBuf>) -> Option<&PathBuf> {
+        self.current = self.queue.pop_front()?;
+        self.recent.push_back(self.current.to_owned());
+        if self.recent.len() > RECENT_LEN {
+            self.recent.pop_front();
+        }
+        self.refill(paths);
+        Some(&self.current)
+    }
+
+    // Moves back to the album played before the current one, which is
+    // queued to play next again. Returns the album and the track that it
+    // was left at, or `None` when the current album is the first.
+    pub fn previous(&mut self) -> Option<(&PathBuf, usize)> {
+        if self.recent.len() < 2 {
+            return None;
+        }
+
+        let current = self.recent.pop_back()?;
+        self.queue.push_front(current);
+        self.current = self.recent.back()?.to_owned();
+
+        let index = self.positions.remove(&self.current).unwrap_or(0);
+        Some((&self.current, index))
+    }
+
+    // Whether or not an album was played before the current one.
+    pub fn has_previous(&self) -> bool {
+        self.recent.len() > 1
+    }
+
+    // Records that the current album was left at the track `index`.
+    pub fn leave_at(&mut self, index: usize) {
+        match index {
+            0 => self.positions.remove(&self.current),
+            _ => self.positions.insert(self.current.to_owned(), index),
+        };
+    }
+
+    // Fills the queue with the best ranked albums that aren't queued and
+    // haven't been played recently.
+    fn refill(&mut self, paths: &Vec<PathBuf>) {
+        if self.queue.len() >= STATION_AHEAD {
+            return;
+        }
+
+        // The albums by the same artist are usually in the same directory.
+        let parent = self.seed.parent();
+        let mut candidates = paths
+            .iter()
+            .filter(|path| path.parent() == parent || self.co_plays.contains_key(*path))
+            .cloned()
+            .collect::<Vec<PathBuf>>();
+
+        candidates.extend(
+            paths
+                .choose_multiple(&mut thread_rng(), SAMPLE_LEN)
+                .cloned(),
+        );
+        candidates.sort();
+        candidates.dedup();
+        candidates.retain(|path| !self.recent.contains(path) && !self.queue.contains(path));
+
+        let candidates = candidates
+            .into_iter()
+            .filter_map(|path| {
+                let tags = self
+                    .index
+                    .entry(path.to_owned())
+                    .or_insert_with(|| AlbumTags::read(&path))
+                    .to_owned()?;
+                Some((path, tags))
+            })
+            .collect::<Vec<(PathBuf, AlbumTags)>>();
+
+        let ranked = rank((&self.seed, &self.seed_tags), &candidates, &self.co_plays);
+        let count = STATION_AHEAD - self.queue.len();
+        self.queue.extend(ranked.into_iter().take(count));
+    }
+}
+
+// Scores how similar the album at `path` with `tags` is to the seed album.
+// Albums by the same artist or in the same genre score highest, followed
+// by albums that were played in the same session as the seed and albums
+// from around the same year.
+pub fn similarity(
+    (seed, seed_tags): (&PathBuf, &AlbumTags),
+    (path, tags): (&PathBuf, &AlbumTags),
+    co_plays: usize,
+) -> u32 {
+    let mut score = 0;
+
+    if !tags.artist.eq_ignore_ascii_case("None")
+        && tags.artist.eq_ignore_ascii_case(&seed_tags.artist)
+    {
+        score += 40;
+    }
+
+    if seed.parent().is_some() && path.parent() == seed.parent() {
+        score += 10;
+    }
+
+    if let (Some(genre), Some(seed_genre)) = (&tags.genre, &seed_tags.genre) {
+        if genre.eq_ignore_ascii_case(seed_genre) {
+            score += 30;
+        }
+    }
+
+    if let (Some(year), Some(seed_year)) = (tags.year, seed_tags.year) {
+        score += 20u32.saturating_sub(2 * year.abs_diff(seed_year));
+    }
+
+    score + 10 * co_plays.min(5) as u32
+}
+
+// Orders the `candidates` by their similarity to the seed, with the most
+// similar first. Ties are ordered by path, so the ranking is deterministic.
+pub fn rank(
+    seed: (&PathBuf, &AlbumTags),
+    candidates: &[(PathBuf, AlbumTags)],
+    co_plays: &HashMap<PathBuf, usize>,
+) -> Vec<PathBuf> {
+    let mut scored = candidates
+        .iter()
+        .filter(|(path, _)| path != seed.0)
+        .map(|(path, tags)| {
+            let count = co_plays.get(path).copied().unwrap_or(0);
+            (similarity(seed, (path, tags), count), path)
+        })
+        .collect::<Vec<(u32, &PathBuf)>>();
+
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(b.1)));
+    scored
+        .into_iter()
+        .map(|(_, path)| path.to_owned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(artist: &str, genre: Option<&str>, year: Option<u32>) -> AlbumTags {
+        AlbumTags {
+            artist: artist.to_string(),
+            genre: genre.map(str::to_string),
+            year,
+        }
+    }
+
+    fn candidates() -> Vec<(PathBuf, AlbumTags)> {
+        vec![
+            ("/music/b/b1".into(), tags("B", Some("Jazz"), Some(1959))),
+            ("/music/a/a2".into(), tags("A", Some("Rock"), Some(1975))),
+            ("/music/c/c1".into(), tags("C", Some("rock"), Some(1971))),
+            ("/music/d/d1".into(), tags("D", None, None)),
+            ("/music/a/a1".into(), tags("A", Some("Rock"), Some(1970))),
+        ]
+    }
+
+    #[test]
+    fn test_rank_by_similarity() {
+        let seed = PathBuf::from("/music/a/a1");
+        let seed_tags = tags("a", Some("Rock"), Some(1970));
+
+        let ranked = rank((&seed, &seed_tags), &candidates(), &HashMap::new());
+
+        assert_eq!(
+            ranked,
+            vec![
+                PathBuf::from("/music/a/a2"),
+                PathBuf::from("/music/c/c1"),
+                PathBuf::from("/music/b/b1"),
+                PathBuf::from("/music/d/d1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_co_plays_raise_rank() {
+        let seed = PathBuf::from("/music/a/a1");
+        let seed_tags = tags("A", Some("Rock"), Some(1970));
+        let co_plays = HashMap::from([(PathBuf::from("/music/d/d1"), 4)]);
+
+        let ranked = rank((&seed, &seed_tags), &candidates(), &co_plays);
+
+        assert_eq!(ranked[2], PathBuf::from("/music/d/d1"));
+    }
+
+    #[test]
+    fn test_rank_is_deterministic() {
+        let seed = PathBuf::from("/music/x/x1");
+        let seed_tags = tags("X", None, None);
+        let mut reversed = candidates();
+        reversed.reverse();
+
+        assert_eq!(
+            rank((&seed, &seed_tags), &candidates(), &HashMap::new()),
+            rank((&seed, &seed_tags), &reversed, &HashMap::new()),
+        );
+    }
+
+    // A station seeded with the first of `paths`, which don't exist.
+    fn test_station(paths: &Vec<PathBuf>) -> Station {
+        Station {
+            seed: paths[0].to_owned(),
+            seed_tags: AlbumTags::default(),
+            current: paths[0].to_owned(),
+            queue: VecDeque::new(),
+            recent: VecDeque::from([paths[0].to_owned()]),
+            positions: HashMap::new(),
+            // Stand in for the tags that would be read from disk.
+            index: paths
+                .iter()
+                .map(|path| (path.to_owned(), Some(AlbumTags::default())))
+                .collect(),
+            co_plays: HashMap::new(),
+        }
+    }
+
+    fn missing_albums(count: usize) -> Vec<PathBuf> {
+        (0..count)
+            .map(|i| PathBuf::from(format!("/missing/album_{i}")))
+            .collect()
+    }
+
+    #[test]
+    fn test_station_avoids_repeats() {
+        let paths = missing_albums(5);
+        let mut station = test_station(&paths);
+
+        station.refill(&paths);
+        assert_eq!(station.queue.len(), STATION_AHEAD);
+
+        let mut played = vec![paths[0].to_owned()];
+        while let Some(next) = station.next(&paths) {
+            assert!(!played.contains(next), "'{}' was repeated", next.display());
+            played.push(next.to_owned());
+        }
+
+        played.sort();
+        assert_eq!(played, paths);
+    }
+
+    #[test]
+    fn test_previous_requeues_current_album() {
+        let paths = missing_albums(8);
+        let mut station = test_station(&paths);
+        station.refill(&paths);
+
+        // There is nothing before the seed.
+        assert!(!station.has_previous());
+        assert_eq!(station.previous(), None);
+
+        let first = station.next(&paths).unwrap().to_owned();
+        station.leave_at(4);
+        let second = station.next(&paths).unwrap().to_owned();
+        let queued = station.queue.to_owned();
+
+        // The first album is resumed where it was left, and the second is
+        // queued to play next again.
+        assert_eq!(station.previous(), Some((&first, 4)));
+        assert_eq!(station.queue.front(), Some(&second));
+        assert_eq!(station.queue.len(), queued.len() + 1);
+
+        // The seed was played to the end.
+        assert_eq!(station.previous(), Some((&paths[0], 0)));
+        assert_eq!(station.next(&paths), Some(&first));
+        assert_eq!(station.next(&paths), Some(&second));
+        assert_eq!(station.queue, queued);
+    }
+}
@@ -0,0 +1,245 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    env,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use cursive::{
+    event::{Event, EventResult},
+    view::ViewWrapper,
+    Printer, View,
+};
+
+use crate::utils;
+
+lazy_static::lazy_static! {
+    // Whether or not the frames are timed, with `TAP_FRAME_STATS=1`.
+    static ref ENABLED: bool = env::var("TAP_FRAME_STATS").map_or(false, |v| v == "1");
+    // The time that a draw or event can take before it is logged, which is
+    // 10 ms unless `TAP_FRAME_BUDGET_MS` is set.
+    static ref BUDGET: Duration = env::var("TAP_FRAME_BUDGET_MS")
+        .ok()
+        .and_then(|ms| ms.parse().ok())
+        .map_or(DEFAULT_BUDGET, Duration::from_millis);
+    static ref STATS: Mutex<HashMap<(&'static str, Kind), Samples>> = Mutex::new(HashMap::new());
+}
+
+// The name of the log that the slow frames and the summary are written to.
+const LOG: &str = "frames";
+
+const DEFAULT_BUDGET: Duration = Duration::from_millis(10);
+
+// The number of the latest durations that the percentiles are taken from.
+const WINDOW: usize = 1000;
+
+// What a view was timed doing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Kind {
+    Draw,
+    Event,
+}
+
+impl Kind {
+    fn name(&self) -> &'static str {
+        match self {
+            Kind::Draw => "draw",
+            Kind::Event => "event",
+        }
+    }
+}
+
+// The durations of the latest draws or events of a view.
+#[derive(Debug, Default)]
+struct Samples {
+    // The latest durations, oldest first, up to `WINDOW` of them.
+    latest: VecDeque<Duration>,
+    // The number of times the view was timed, and of those that were over
+    // the budget.
+    count: usize,
+    over_budget: usize,
+}
+
+impl Samples {
+    // Adds `duration`, returning whether or not it is over `budget`.
+    fn add(&mut self, duration: Duration, budget: Duration) -> bool {
+        if self.latest.len() == WINDOW {
+            self.latest.pop_front();
+        }
+        self.latest.push_back(duration);
+        self.count += 1;
+
+        let is_over = duration > budget;
+        self.over_budget += is_over as usize;
+        is_over
+    }
+
+    // The `p`th percentile of the latest durations, by nearest rank.
+    fn percentile(&self, p: usize) -> Duration {
+        let mut sorted: Vec<Duration> = self.latest.iter().copied().collect();
+        sorted.sort();
+        let rank = (p * sorted.len() + 99) / 100;
+        sorted
+            .get(rank.saturating_sub(1))
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+// Wraps the layer `view`, timing its draws and events as `name` when the
+// frames are timed.
+pub struct Timed<V> {
+    name: &'static str,
+    view: V,
+}
+
+impl<V> Timed<V> {
+    pub fn new(name: &'static str, view: V) -> Self {
+        Timed { name, view }
+    }
+}
+
+impl<V: View> ViewWrapper for Timed<V> {
+    cursive::wrap_impl!(self.view: V);
+
+    fn wrap_draw(&self, printer: &Printer) {
+        time(self.name, Kind::Draw, || self.view.draw(printer))
+    }
+
+    fn wrap_on_event(&mut self, event: Event) -> EventResult {
+        time(self.name, Kind::Event, || self.view.on_event(event))
+    }
+}
+
+// Runs `f`, timing it as `kind` for the view named `name` if the frames are
+// timed. Times over the budget are logged.
+pub fn time<T>(name: &'static str, kind: Kind, f: impl FnOnce() -> T) -> T {
+    if !*ENABLED {
+        return f();
+    }
+
+    let start = Instant::now();
+    let result = f();
+    let duration = start.elapsed();
+
+    let mut stats = STATS.lock().unwrap_or_else(|e| e.into_inner());
+    if stats
+        .entry((name, kind))
+        .or_default()
+        .add(duration, *BUDGET)
+    {
+        utils::log(
+            LOG,
+            &format!(
+                "{name} {} took {}, over the {} budget",
+                kind.name(),
+                ms(duration),
+                ms(*BUDGET)
+            ),
+        );
+    }
+    result
+}
+
+// Prints the percentiles of each view to stderr as a table, and writes them
+// to the log, if the frames were timed.
+pub fn report() {
+    if !*ENABLED {
+        return;
+    }
+
+    let stats = STATS.lock().unwrap_or_else(|e| e.into_inner());
+    let table = table(&stats);
+
+    for line in table.lines() {
+        utils::log(LOG, &format!("summary: {}", line.trim_end()));
+    }
+    eprint!("{table}");
+}
+
+// Formats the `stats` as a table, with a row for the draws and the events of
+// each view, ordered by view.
+fn table(stats: &HashMap<(&'static str, Kind), Samples>) -> String {
+    let mut keys: Vec<_> = stats.keys().collect();
+    keys.sort();
+
+    let mut table = format!(
+        "{: <10}{: <7}{: >8}{: >10}{: >10}{: >6}\n",
+        "view", "kind", "count", "p50", "p99", "slow"
+    );
+    for key in keys {
+        let ((name, kind), samples) = (key, &stats[key]);
+        table.push_str(&format!(
+            "{: <10}{: <7}{: >8}{: >10}{: >10}{: >6}\n",
+            name,
+            kind.name(),
+            samples.count,
+            ms(samples.percentile(50)),
+            ms(samples.percentile(99)),
+            samples.over_budget,
+        ));
+    }
+    table
+}
+
+fn ms(d: Duration) -> String {
+    format!("{:.1} ms", d.as_secs_f64() * 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentiles() {
+        let mut samples = Samples::default();
+        let budget = Duration::from_millis(10);
+        for ms in (1..=100).rev() {
+            samples.add(Duration::from_millis(ms), budget);
+        }
+
+        assert_eq!(samples.percentile(50), Duration::from_millis(50));
+        assert_eq!(samples.percentile(99), Duration::from_millis(99));
+        assert_eq!(samples.over_budget, 90);
+        assert_eq!(Samples::default().percentile(99), Duration::ZERO);
+
+        // Only the latest durations are kept.
+        for _ in 0..WINDOW {
+            samples.add(Duration::from_millis(2), budget);
+        }
+        assert_eq!(samples.percentile(99), Duration::from_millis(2));
+        assert_eq!(samples.count, WINDOW + 100);
+    }
+
+    #[test]
+    fn test_nothing_is_recorded_when_disabled() {
+        // The frames are only timed with `TAP_FRAME_STATS=1`.
+        if *ENABLED {
+            return;
+        }
+        for i in 0..1000 {
+            assert_eq!(time("test", Kind::Draw, || i), i);
+        }
+        assert!(STATS.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_table_has_a_row_for_each_view() {
+        let mut stats: HashMap<(&'static str, Kind), Samples> = HashMap::new();
+        let budget = Duration::from_millis(10);
+        let mut add = |name, kind, duration| {
+            stats.entry((name, kind)).or_default().add(duration, budget);
+        };
+        add("player", Kind::Draw, Duration::from_micros(1_300));
+        add("fuzzy", Kind::Event, Duration::from_millis(12));
+        add("fuzzy", Kind::Draw, Duration::from_millis(3));
+
+        let table = table(&stats);
+        let rows = table.lines().collect::<Vec<&str>>();
+
+        assert_eq!(rows.len(), 4);
+        assert!(rows[1].starts_with("fuzzy     draw") && rows[1].ends_with("3.0 ms     0"));
+        assert!(rows[2].starts_with("fuzzy     event") && rows[2].ends_with("12.0 ms     1"));
+        assert!(rows[3].starts_with("player") && rows[3].contains("1.3 ms"));
+    }
+}
@@ -0,0 +1,259 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+// The minimum time between two flushes of the dirty set.
+pub const FLUSH_INTERVAL: Duration = Duration::from_millis(500);
+
+// The number of dirty directories above which a full rescan is
+// cheaper than applying the changes one by one.
+pub const RESCAN_THRESHOLD: usize = 256;
+
+// A filesystem event reported by the watcher.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FsEvent {
+    // A directory was created.
+    Created(PathBuf),
+    // A directory was removed.
+    Removed(PathBuf),
+    // The entries of a directory changed, without knowing which.
+    Changed(PathBuf),
+}
+
+// The pending state of a dirty directory.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Dirty {
+    Added,
+    Removed,
+    Refreshed,
+}
+
+// The batched changes to apply to the fuzzy items.
+#[derive(Debug, PartialEq)]
+pub enum Changes {
+    Batch {
+        added: Vec<PathBuf>,
+        removed: Vec<PathBuf>,
+        refreshed: Vec<PathBuf>,
+    },
+    Rescan,
+}
+
+impl Changes {
+    // Whether or not the items listed from `dir` may have changed: when
+    // something inside it changed, or when it was removed itself.
+    pub fn affect(&self, dir: &Path) -> bool {
+        match self {
+            Changes::Batch {
+                added,
+                removed,
+                refreshed,
+            } => {
+                added
+                    .iter()
+                    .chain(removed)
+                    .chain(refreshed)
+                    .any(|path| path.starts_with(dir))
+                    || removed.iter().any(|path| dir.starts_with(path))
+            }
+            Changes::Rescan => true,
+        }
+    }
+}
+
+// Accumulates filesystem events into a per-directory dirty set that is
+// flushed at most once per `interval`, so that a burst of events results
+// in a bounded number of updates to the fuzzy view.
+pub struct Coalescer {
+    // The directories that have changed since the last flush.
+    dirty: HashMap<PathBuf, Dirty>,
+    // Whether or not the dirty set exceeded `threshold` since the last flush.
+    overflowed: bool,
+    // The instant of the last flush, if any.
+    last_flush: Option<Instant>,
+    // The minimum time between two flushes.
+    interval: Duration,
+    // The size of the dirty set that triggers a full rescan.
+    threshold: usize,
+}
+
+impl Coalescer {
+    pub fn new(interval: Duration, threshold: usize) -> Self {
+        Coalescer {
+            dirty: HashMap::new(),
+            overflowed: false,
+            last_flush: None,
+            interval,
+            threshold,
+        }
+    }
+
+    // Marks the directory affected by `event` as dirty.
+    pub fn push(&mut self, event: FsEvent) {
+        if self.overflowed {
+            return;
+        }
+
+        let (dir, next) = match event {
+            FsEvent::Created(path) => (path, Dirty::Added),
+            FsEvent::Removed(path) => (path, Dirty::Removed),
+            FsEvent::Changed(path) => (path, Dirty::Refreshed),
+        };
+
+        // A refresh doesn't replace a pending add or remove.
+        let state = match (self.dirty.get(&dir), next) {
+            (Some(&prev), Dirty::Refreshed) => prev,
+            (_, next) => next,
+        };
+
+        self.dirty.insert(dir, state);
+
+        if self.dirty.len() > self.threshold {
+            self.dirty.clear();
+            self.overflowed = true;
+        }
+    }
+
+    // Takes the pending changes, if any, provided that `interval` has
+    // elapsed since the last flush.
+    pub fn flush(&mut self, now: Instant) -> Option<Changes> {
+        if let Some(last) = self.last_flush {
+            if now.duration_since(last) < self.interval {
+                return None;
+            }
+        }
+
+        if self.overflowed {
+            self.overflowed = false;
+            self.last_flush = Some(now);
+            return Some(Changes::Rescan);
+        }
+
+        if self.dirty.is_empty() {
+            return None;
+        }
+
+        let (mut added, mut removed, mut refreshed) = (vec![], vec![], vec![]);
+
+        for (path, state) in self.dirty.drain() {
+            match state {
+                Dirty::Added => added.push(path),
+                Dirty::Removed => removed.push(path),
+                Dirty::Refreshed => refreshed.push(path),
+            }
+        }
+
+        added.sort();
+        removed.sort();
+        refreshed.sort();
+
+        self.last_flush = Some(now);
+
+        Some(Changes::Batch {
+            added,
+            removed,
+            refreshed,
+        })
+    }
+}
+
+impl Default for Coalescer {
+    fn default() -> Self {
+        Coalescer::new(FLUSH_INTERVAL, RESCAN_THRESHOLD)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Replays a burst of `count` events spread over `albums` directories,
+    // with an event every `step`, flushing after every event. Returns the
+    // changes that were flushed.
+    fn replay(
+        coalescer: &mut Coalescer,
+        count: usize,
+        albums: usize,
+        step: Duration,
+    ) -> Vec<Changes> {
+        let start = Instant::now();
+        let mut flushed = vec![];
+
+        for i in 0..count {
+            let path = PathBuf::from(format!("/music/album_{}", i % albums));
+            coalescer.push(FsEvent::Changed(path));
+
+            if let Some(changes) = coalescer.flush(start + step * i as u32) {
+                flushed.push(changes);
+            }
+        }
+
+        if let Some(changes) = coalescer.flush(start + step * count as u32 + FLUSH_INTERVAL) {
+            flushed.push(changes);
+        }
+
+        flushed
+    }
+
+    #[test]
+    fn test_burst_is_flushed_a_bounded_number_of_times() {
+        let mut coalescer = Coalescer::default();
+        // 5000 events over 3 seconds.
+        let flushed = replay(&mut coalescer, 5000, 40, Duration::from_micros(600));
+
+        // At most two flushes per second, plus the first and the last.
+        assert!(flushed.len() <= 8, "flushed {} times", flushed.len());
+
+        for changes in flushed.iter() {
+            match changes {
+                Changes::Batch { refreshed, .. } => assert!(refreshed.len() <= 40),
+                Changes::Rescan => panic!("40 albums should not trigger a rescan"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_large_dirty_set_falls_back_to_rescan() {
+        let mut coalescer = Coalescer::default();
+        let flushed = replay(&mut coalescer, 5000, 1000, Duration::from_micros(600));
+
+        assert!(flushed.contains(&Changes::Rescan));
+        assert!(flushed.len() <= 8, "flushed {} times", flushed.len());
+    }
+
+    #[test]
+    fn test_refresh_keeps_pending_add_and_remove() {
+        let mut coalescer = Coalescer::default();
+        coalescer.push(FsEvent::Created("/music/new".into()));
+        coalescer.push(FsEvent::Changed("/music/new".into()));
+        coalescer.push(FsEvent::Removed("/music/old".into()));
+        coalescer.push(FsEvent::Changed("/music/old".into()));
+        coalescer.push(FsEvent::Changed("/music/other".into()));
+
+        assert_eq!(
+            coalescer.flush(Instant::now()),
+            Some(Changes::Batch {
+                added: vec!["/music/new".into()],
+                removed: vec!["/music/old".into()],
+                refreshed: vec!["/music/other".into()],
+            })
+        );
+    }
+
+    #[test]
+    fn test_changes_affect_the_dirs_they_are_in() {
+        let changes = Changes::Batch {
+            added: vec!["/music/artist/new".into()],
+            removed: vec!["/music/old".into()],
+            refreshed: vec!["/music/artist".into()],
+        };
+        assert!(changes.affect(Path::new("/music")));
+        assert!(changes.affect(Path::new("/music/artist")));
+        assert!(changes.affect(Path::new("/music/old/album")));
+        assert!(!changes.affect(Path::new("/music/other")));
+        assert!(!changes.affect(Path::new("/music/artist/album")));
+        assert!(Changes::Rescan.affect(Path::new("/music/other")));
+    }
+}
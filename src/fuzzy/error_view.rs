@@ -7,6 +7,7 @@ use cursive::{
 };
 
 use crate::config::theme;
+use crate::layers::{self, Role};
 
 pub struct ErrorView {}
 
@@ -26,24 +27,37 @@ impl ErrorView {
                     .full_width(),
             ),
             |layout, size: cursive::XY<usize>| {
-                layout.set_child_position(0, Rect::from_size((0, size.y - 2), (size.x, 2)));
+                layout.set_child_position(
+                    0,
+                    Rect::from_size((0, size.y.saturating_sub(2)), (size.x, 2)),
+                );
                 layout.layout(size);
             },
         )
         .full_screen()
     }
 
+    // Shows an error that has to be acknowledged, such as one from the audio
+    // chain, until a key is pressed. The key only closes the error.
     pub fn load(siv: &mut Cursive, err: anyhow::Error) {
         let content = err.to_string();
-        siv.screen_mut()
-            .add_transparent_layer(OnEventView::new(ErrorView::new(content)).on_event(
-                ErrorView::trigger(),
-                |siv| {
-                    siv.pop_layer();
-                },
-            ));
+        layers::show(
+            siv,
+            Role::Error,
+            OnEventView::new(ErrorView::new(content)).on_event(ErrorView::trigger(), |siv| {
+                layers::close(siv, Role::Error);
+            }),
+        );
     }
 
+    // Shows an error that doesn't need acknowledging, such as a failed file
+    // operation, in place of a toast for a few seconds. The player and the
+    // keys are left alone.
+    pub fn notify(siv: &mut Cursive, err: anyhow::Error) {
+        layers::notify_error(siv, &err.to_string());
+    }
+
+    // The events that acknowledge an error.
     pub fn trigger() -> EventTrigger {
         EventTrigger::from_fn(|event| {
             matches!(
@@ -0,0 +1,419 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail};
+use cursive::{
+    event::Key,
+    view::{Nameable, Resizable},
+    views::{Dialog, EditView, OnEventView},
+    Cursive,
+};
+
+use crate::config::args;
+use crate::data::{Favorites, SessionData};
+use crate::layers::{self, Role};
+use crate::utils::{display_path, InnerType};
+
+use super::{
+    create_items,
+    fuzzy_view::{current_path, scans_hidden_dirs},
+    replace_prefix, ErrorView, FuzzyItem, FuzzyView, TwoPaneView,
+};
+
+// The maximum length of a file name, in bytes, on most filesystems.
+const MAX_NAME_LEN: usize = 255;
+
+// The error number for a rename across filesystems.
+const EXDEV: i32 = 18;
+
+// Checks that `name` can be used as the name of a new directory entry. A name
+// starting with '.' is only allowed while hidden directories are scanned,
+// since the entry wouldn't be listed otherwise.
+pub fn validate_name(name: &str, hidden_dirs: bool) -> Result<(), anyhow::Error> {
+    if name.is_empty() {
+        bail!("the name can't be empty")
+    } else if name.contains('/') {
+        bail!("'{name}' can't contain '/'")
+    } else if name.starts_with('.') && !hidden_dirs {
+        bail!("'{name}' can't start with '.'")
+    } else if name.len() > MAX_NAME_LEN {
+        bail!("the name can't be longer than {MAX_NAME_LEN} bytes")
+    }
+
+    Ok(())
+}
+
+// Shows a dialog asking for the name of a new subdirectory of `root`.
+pub fn create_dir_dialog(root: PathBuf, siv: &mut Cursive) {
+    if let Err(err) = check_writable(&root, args::read_only_library()) {
+        return ErrorView::notify(siv, err);
+    }
+
+    let submit_root = root.to_owned();
+
+    let dialog = Dialog::new()
+        .title("New directory")
+        .content(
+            EditView::new()
+                .on_submit(move |siv, name| confirm_create_dir(&submit_root, name, siv))
+                .with_name("new_dir")
+                .fixed_width(32),
+        )
+        .button("Ok", move |siv| {
+            let name = siv
+                .call_on_name("new_dir", |view: &mut EditView| view.get_content())
+                .unwrap_or_default();
+            confirm_create_dir(&root, &name, siv)
+        })
+        .dismiss_button("Cancel");
+
+    layers::show(siv, Role::Dialog, dialog);
+}
+
+// Asks for confirmation before creating the directory `name` in `root`.
+fn confirm_create_dir(root: &PathBuf, name: &str, siv: &mut Cursive) {
+    if let Err(err) = validate_name(name, scans_hidden_dirs(siv)) {
+        return ErrorView::notify(siv, err);
+    }
+
+    let root = root.to_owned();
+    let path = root.join(name);
+
+    let dialog = Dialog::text(format!("Create '{}'?", display_path(&path)))
+        .button("Create", move |siv| create_dir(&root, &path, siv))
+        .dismiss_button("Cancel");
+
+    layers::show(siv, Role::Dialog, dialog);
+}
+
+// Creates the directory at `path` and reloads the fuzzy view for `root`
+// with the new directory selected.
+fn create_dir(root: &PathBuf, path: &PathBuf, siv: &mut Cursive) {
+    if let Err(err) = make_dir(path, args::read_only_library()) {
+        return ErrorView::notify(siv, err);
+    }
+
+    // Remove the name and confirmation dialogs.
+    layers::close(siv, Role::Dialog);
+
+    // The current list is kept if `root` can't be scanned again, rather than
+    // being replaced by an empty one.
+    let mut items = match create_items(root, scans_hidden_dirs(siv)) {
+        Ok(items) => items,
+        Err(err) => return ErrorView::notify(siv, err),
+    };
+
+    // Empty directories aren't included by `create_items`.
+    if !items.iter().any(|item| item.path.eq(path)) {
+        items.push(FuzzyItem::empty_dir(path.to_owned(), 1));
+    }
+
+    FuzzyView::refresh(items, Some(path), siv);
+}
+
+// Shows a dialog to rename the directory or the file at `path`.
+pub fn rename_dialog(path: PathBuf, siv: &mut Cursive) {
+    if let Err(err) = check_writable(&path, args::read_only_library()) {
+        return ErrorView::notify(siv, err);
+    }
+
+    let name = path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    let submit_path = path.to_owned();
+    let title = match path.is_dir() {
+        true => "Rename directory",
+        false => "Rename file",
+    };
+
+    let dialog = Dialog::new()
+        .title(title)
+        .content(
+            EditView::new()
+                .content(name)
+                .on_submit(move |siv, name| confirm_rename(&submit_path, name, siv))
+                .with_name("rename")
+                .fixed_width(32),
+        )
+        .button("Ok", move |siv| {
+            let name = siv
+                .call_on_name("rename", |view: &mut EditView| view.get_content())
+                .unwrap_or_default();
+            confirm_rename(&path, &name, siv)
+        })
+        .dismiss_button("Cancel");
+
+    layers::show(siv, Role::Dialog, dialog);
+}
+
+// Renames the directory or the file at `path` to `name`, asking for
+// confirmation first if it contains the current player.
+fn confirm_rename(path: &PathBuf, name: &str, siv: &mut Cursive) {
+    if let Err(err) = validate_name(name, scans_hidden_dirs(siv)) {
+        return ErrorView::notify(siv, err);
+    }
+
+    let old = path.to_owned();
+    let new = path.with_file_name(name);
+
+    if new.eq(&old) {
+        layers::close(siv, Role::Dialog);
+        return;
+    }
+
+    if new.exists() {
+        let err = anyhow!("'{}' already exists", display_path(&new));
+        return ErrorView::notify(siv, err);
+    }
+
+    match current_path(siv) {
+        Some(current) if current.starts_with(&old) => {
+            let dialog = Dialog::text(format!(
+                "'{}' contains the current player, which won't be able to \
+                play the next track once renamed. Rename anyway?",
+                display_path(&old)
+            ))
+            .button("Rename", move |siv| rename(&old, &new, siv))
+            .dismiss_button("Cancel");
+
+            layers::show(siv, Role::Dialog, dialog);
+        }
+        _ => rename(&old, &new, siv),
+    }
+}
+
+// Renames the directory or the file at `old` to `new`, updating the paths
+// held by the fuzzy views and the session data.
+fn rename(old: &PathBuf, new: &PathBuf, siv: &mut Cursive) {
+    if let Err(err) = move_dir(old, new, args::read_only_library()) {
+        return ErrorView::notify(siv, err);
+    }
+
+    layers::close(siv, Role::Dialog);
+
+    siv.call_on_all_named(FuzzyView::NAME, |view: &mut FuzzyView| {
+        view.rename(old, new)
+    });
+    siv.call_on_all_named(TwoPaneView::NAME, |view: &mut TwoPaneView| {
+        view.rename(old, new)
+    });
+
+    siv.with_user_data(
+        |(_, paths, queue, data, _, _): &mut InnerType<SessionData>| {
+            for path in paths.iter_mut() {
+                replace_prefix(path, old, new);
+            }
+            for (path, _) in queue.iter_mut() {
+                replace_prefix(path, old, new);
+            }
+            for item in data.items.iter_mut() {
+                item.rename(old, new);
+            }
+        },
+    );
+
+    // The favorites inside the directory are saved with its new path.
+    let mut favorites = Favorites::saved();
+    if favorites.rename(old, new) {
+        favorites.save();
+    }
+}
+
+// Shows a dialog asking whether to delete the directory or the file at
+// `path`.
+pub fn delete_dialog(path: PathBuf, siv: &mut Cursive) {
+    if let Err(err) = check_writable(&path, args::read_only_library()) {
+        return ErrorView::notify(siv, err);
+    }
+
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let mut msg = format!("Delete '{name}'? This cannot be undone.");
+    if current_path(siv).is_some_and(|current| current.starts_with(&path)) {
+        msg.push_str(" It contains the current player, which won't be able to play the next track once deleted.");
+    }
+
+    let dialog = Dialog::text(msg)
+        .button("Yes", move |siv| delete(&path, siv))
+        .dismiss_button("No");
+    // Escape answers no, rather than being passed to the list behind.
+    let dialog =
+        OnEventView::new(dialog).on_event(Key::Esc, |siv| layers::close(siv, Role::Dialog));
+
+    layers::show(siv, Role::Dialog, dialog);
+}
+
+// Deletes the directory or the file at `path`, removing it from the fuzzy
+// views and the session data.
+fn delete(path: &PathBuf, siv: &mut Cursive) {
+    if let Err(err) = remove_path(path, args::read_only_library()) {
+        return ErrorView::notify(siv, err);
+    }
+
+    layers::close(siv, Role::Dialog);
+
+    siv.call_on_all_named(FuzzyView::NAME, |view: &mut FuzzyView| view.remove(path));
+    siv.call_on_all_named(TwoPaneView::NAME, |view: &mut TwoPaneView| {
+        view.remove(path)
+    });
+
+    siv.with_user_data(|(_, paths, _, data, _, _): &mut InnerType<SessionData>| {
+        paths.retain(|p| !p.starts_with(path));
+        data.items.retain(|item| !item.path.starts_with(path));
+    });
+}
+
+// Fails with a "read-only" error for `path` when the library is read-only,
+// so that nothing under the library root is written.
+fn check_writable(path: &Path, read_only: bool) -> Result<(), anyhow::Error> {
+    if read_only {
+        bail!(
+            "the library is read-only\n- '{}' can't be changed",
+            display_path(path)
+        )
+    }
+    Ok(())
+}
+
+// Creates the directory at `path`, unless the library is read-only.
+fn make_dir(path: &Path, read_only: bool) -> Result<(), anyhow::Error> {
+    check_writable(path, read_only)?;
+
+    match std::fs::create_dir(path) {
+        Ok(_) => Ok(()),
+        Err(e) => bail!("could not create '{}'\n- `{}`", display_path(path), e),
+    }
+}
+
+// Renames the directory or the file at `old` to `new`, unless the library is
+// read-only.
+fn move_dir(old: &Path, new: &Path, read_only: bool) -> Result<(), anyhow::Error> {
+    check_writable(old, read_only)?;
+
+    match std::fs::rename(old, new) {
+        Ok(_) => Ok(()),
+        Err(e) => match e.raw_os_error() {
+            Some(EXDEV) => bail!(
+                "could not rename '{}'\n- renaming across filesystems would require a copy and delete",
+                display_path(old)
+            ),
+            _ => bail!("could not rename '{}'\n- `{}`", display_path(old), e),
+        },
+    }
+}
+
+// Deletes the directory, with everything in it, or the file at `path`,
+// unless the library is read-only.
+fn remove_path(path: &Path, read_only: bool) -> Result<(), anyhow::Error> {
+    check_writable(path, read_only)?;
+
+    let result = match path.is_dir() {
+        true => std::fs::remove_dir_all(path),
+        false => std::fs::remove_file(path),
+    };
+    match result {
+        Ok(_) => Ok(()),
+        Err(e) => bail!("could not delete '{}'\n- `{}`", display_path(path), e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    #[cfg(unix)]
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+    use crate::utils;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_read_only_library_is_never_written() {
+        let temp_dir = utils::create_working_dir(&["music/album"], &[], &[]).unwrap();
+        let root = temp_dir.path().join("music");
+        fs::set_permissions(&root, fs::Permissions::from_mode(0o555)).unwrap();
+
+        let read_only = utils::is_read_only(&root);
+        assert!(read_only);
+
+        let err = make_dir(&root.join("new"), read_only).unwrap_err();
+        assert!(err.to_string().contains("read-only"));
+        assert!(!root.join("new").exists());
+
+        let err = move_dir(&root.join("album"), &root.join("renamed"), read_only).unwrap_err();
+        assert!(err.to_string().contains("read-only"));
+        assert!(root.join("album").exists());
+        assert!(!root.join("renamed").exists());
+
+        let err = remove_path(&root.join("album"), read_only).unwrap_err();
+        assert!(err.to_string().contains("read-only"));
+        assert!(root.join("album").exists());
+
+        // Without the check, the filesystem refuses the writes instead, unless
+        // the tests are run as root, which can write to the directory anyway.
+        if fs::File::create(root.join("probe")).is_err() {
+            let err = make_dir(&root.join("new"), false).unwrap_err();
+            assert!(err.to_string().starts_with("could not create"));
+            let err = move_dir(&root.join("album"), &root.join("renamed"), false).unwrap_err();
+            assert!(err.to_string().starts_with("could not rename"));
+            assert!(root.join("album").exists());
+        }
+
+        fs::set_permissions(&root, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn test_writable_library() {
+        let temp_dir = utils::create_working_dir(&["music/album"], &[], &[]).unwrap();
+        let root = temp_dir.path().join("music");
+
+        assert!(!utils::is_read_only(&root));
+        assert!(make_dir(&root.join("new"), false).is_ok());
+        assert!(move_dir(&root.join("album"), &root.join("renamed"), false).is_ok());
+        assert!(root.join("renamed").exists());
+
+        fs::write(root.join("renamed/track.mp3"), "").unwrap();
+        assert!(remove_path(&root.join("renamed/track.mp3"), false).is_ok());
+        assert!(remove_path(&root.join("renamed"), false).is_ok());
+        assert!(!root.join("renamed").exists());
+        assert!(remove_path(&root.join("renamed"), false).is_err());
+    }
+
+    #[test]
+    fn test_read_only_mount() {
+        let temp_dir = utils::create_working_dir(&["music/album"], &[], &[]).unwrap();
+        let root = temp_dir.path().join("music");
+        let mounts = |options: &str| {
+            format!(
+                "/dev/sda1 / ext4 rw,relatime 0 0\n\
+                /dev/sdb1 {} ext4 {} 0 0\n",
+                root.display().to_string().replace(' ', "\\040"),
+                options
+            )
+        };
+
+        // The library is writable, but on a filesystem mounted read-only.
+        let read_only = utils::is_read_only_in(&root, &mounts("ro,relatime"));
+        assert!(read_only);
+        let err = make_dir(&root.join("new"), read_only).unwrap_err();
+        assert!(err.to_string().contains("read-only"));
+        assert!(!root.join("new").exists());
+
+        let read_only = utils::is_read_only_in(&root, &mounts("rw,relatime"));
+        assert!(!read_only);
+        assert!(make_dir(&root.join("new"), read_only).is_ok());
+    }
+
+    #[test]
+    fn test_validate_name() {
+        assert!(validate_name("album", false).is_ok());
+        assert!(validate_name("", false).is_err());
+        assert!(validate_name("a/b", true).is_err());
+        assert!(validate_name(&"a".repeat(MAX_NAME_LEN + 1), false).is_err());
+
+        // A hidden name is only allowed while hidden directories are scanned.
+        let err = validate_name(".stfolder", false).unwrap_err();
+        assert!(err.to_string().contains("can't start with '.'"));
+        assert!(validate_name(".stfolder", true).is_ok());
+    }
+}
@@ -1,13 +1,22 @@
 use std::{
+    borrow::Cow,
     cmp::Ordering,
     path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::SystemTime,
 };
 
 use anyhow::bail;
 use bincode::{Decode, Encode};
+use fuzzy_matcher::skim::SkimMatcherV2;
+use regex::Regex;
 use walkdir::{DirEntry, WalkDir};
 
 use crate::player::valid_audio_ext;
+use crate::utils::display_path;
+
+use super::query::{Case, Term};
 
 #[derive(Clone, Debug, Eq, PartialEq, Ord, Encode, Decode)]
 pub struct FuzzyItem {
@@ -23,21 +32,47 @@ pub struct FuzzyItem {
     pub has_audio: bool,
     // The subdirectory count.
     pub child_count: usize,
-    // The indices of `display` that are fuzzy matched.
+    // The number of audio files in the directory.
+    pub audio_count: usize,
+    // The total size of the audio files in the directory, in bytes.
+    pub size_bytes: u64,
+    // The extensions of the audio files in the directory, sorted and
+    // without duplicates, such as for filtering the matches with ':ext'.
+    pub extensions: Vec<String>,
+    // The indices of the matched text that are fuzzy matched, which is
+    // `display` unless matching on paths.
     pub indices: Vec<usize>,
     // The weight of the fuzzy match. Better matches have higher weight.
     pub weight: i64,
+    // The position of the item in the list that it was created in, which
+    // the items can be sorted by.
+    pub original_index: usize,
+    // When the directory was last modified, in seconds since the Unix
+    // epoch, or 0 if that isn't known.
+    pub modified: u64,
+    // Whether or not the item is marked to be played with the other marked
+    // items.
+    pub selected: bool,
 }
 
 impl FuzzyItem {
     fn new(res: Result<DirEntry, walkdir::Error>) -> Result<Self, anyhow::Error> {
         let dent = res?;
-        let path = dent.path().into();
         let depth = dent.depth();
+        Self::with_depth(&dent, depth)
+    }
+
+    // Creates the item for `dent`, which is `depth` levels below the
+    // directory that the items are created from.
+    fn with_depth(dent: &DirEntry, depth: usize) -> Result<Self, anyhow::Error> {
+        let path = dent.path().into();
 
         // Add the search root as a FuzzyItem iff it contains audio files.
-        let (has_audio, sub_dirs) = match depth {
-            0 => (has_audio(&path)?, 0),
+        let (audio_count, sub_dirs, size_bytes, extensions) = match depth {
+            0 => {
+                let (audio_count, size_bytes, extensions) = count_audio(&path)?;
+                (audio_count, 0, size_bytes, extensions)
+            }
             _ => validate(&path)?,
         };
 
@@ -47,21 +82,28 @@ impl FuzzyItem {
             .into_string()
             .unwrap_or_default();
 
-        let key = display
-            .chars()
-            .next()
-            .unwrap_or_default()
-            .to_ascii_uppercase();
+        let key = first_key(&display);
+        let modified = dent
+            .metadata()
+            .ok()
+            .and_then(|m| m.modified().ok())
+            .map_or(0, unix_secs);
 
         let fuzzy_item = FuzzyItem {
-            has_audio,
+            has_audio: audio_count > 0,
             child_count: sub_dirs,
+            audio_count,
+            size_bytes,
+            extensions,
             indices: vec![],
             // We assign a default weight so that the weights of
             // items are equal before fuzzy matching. The weight
             // should be non-zero since zero weights are excluded
             // from being displayed. So we choose the value one.
             weight: 1,
+            original_index: 0,
+            modified,
+            selected: false,
             path,
             depth,
             display,
@@ -70,6 +112,160 @@ impl FuzzyItem {
 
         Ok(fuzzy_item)
     }
+
+    // Creates an item for a directory that has no audio or subdirectories,
+    // such as one that has just been created.
+    pub fn empty_dir(path: PathBuf, depth: usize) -> Self {
+        let display = file_name(&path);
+        let key = first_key(&display);
+
+        FuzzyItem {
+            has_audio: false,
+            child_count: 0,
+            audio_count: 0,
+            size_bytes: 0,
+            extensions: vec![],
+            indices: vec![],
+            weight: 1,
+            original_index: 0,
+            modified: unix_secs(SystemTime::now()),
+            selected: false,
+            path,
+            depth,
+            display,
+            key,
+        }
+    }
+
+    // Creates an item for the audio file at `path`, such as for a track of an
+    // album.
+    pub fn audio_file(path: PathBuf, depth: usize) -> Self {
+        let metadata = std::fs::metadata(&path).ok();
+
+        FuzzyItem {
+            has_audio: true,
+            audio_count: 1,
+            size_bytes: metadata.as_ref().map_or(0, |m| m.len()),
+            extensions: path
+                .extension()
+                .map(|ext| ext.to_string_lossy().into_owned())
+                .into_iter()
+                .collect(),
+            modified: metadata
+                .and_then(|m| m.modified().ok())
+                .map_or(0, unix_secs),
+            ..Self::empty_dir(path, depth)
+        }
+    }
+
+    // The text that is matched and drawn: the path relative to `root` when
+    // matching on paths, or else the display name.
+    pub fn text(&self, root: Option<&Path>) -> Cow<str> {
+        match root.and_then(|root| self.path.strip_prefix(root).ok()) {
+            Some(path) if !path.as_os_str().is_empty() => path.to_string_lossy(),
+            _ => Cow::from(&self.display),
+        }
+    }
+
+    // Updates the item after the directory at `old` has been renamed to `new`.
+    pub fn rename(&mut self, old: &Path, new: &Path) {
+        if replace_prefix(&mut self.path, old, new) && self.path.eq(new) {
+            self.display = file_name(new);
+            self.key = first_key(&self.display);
+        }
+    }
+}
+
+// How the query is matched with the items.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum MatchMode {
+    // Each term of the query is fuzzy matched.
+    #[default]
+    Fuzzy,
+    // The whole query is matched as a regex.
+    Regex,
+    // The items that start with the whole query are matched.
+    Prefix,
+}
+
+impl MatchMode {
+    // The mode after this one, when cycling through them.
+    pub fn next(self) -> Self {
+        match self {
+            MatchMode::Fuzzy => MatchMode::Regex,
+            MatchMode::Regex => MatchMode::Prefix,
+            MatchMode::Prefix => MatchMode::Fuzzy,
+        }
+    }
+
+    // The symbol of the mode, shown before the match count when it isn't
+    // fuzzy.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            MatchMode::Fuzzy => "~",
+            MatchMode::Regex => "/",
+            MatchMode::Prefix => "^",
+        }
+    }
+
+    // The name of the mode, shown when switching to it.
+    pub fn name(self) -> &'static str {
+        match self {
+            MatchMode::Fuzzy => "fuzzy matching",
+            MatchMode::Regex => "regex matching",
+            MatchMode::Prefix => "prefix matching",
+        }
+    }
+}
+
+// How the items are ordered. The items are always ordered by their weight
+// first, so this orders the items with equal weights, such as all of them
+// when there is no query. Any that are still equal are ordered by name and
+// then by path, so that the order doesn't depend on the order they started in.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SortMode {
+    // By display name, ignoring case.
+    #[default]
+    Name,
+    // The most recently modified first.
+    Modified,
+    // In the order that the items were found in.
+    Found,
+}
+
+impl SortMode {
+    // The mode after this one, when cycling through them.
+    pub fn next(self) -> Self {
+        match self {
+            SortMode::Name => SortMode::Modified,
+            SortMode::Modified => SortMode::Found,
+            SortMode::Found => SortMode::Name,
+        }
+    }
+
+    // The name of the mode, shown with the match count when it isn't the
+    // default.
+    pub fn name(self) -> &'static str {
+        match self {
+            SortMode::Name => "by name",
+            SortMode::Modified => "newest first",
+            SortMode::Found => "as found",
+        }
+    }
+
+    // Orders `a` and `b`, with the better matches first.
+    pub fn compare(self, a: &FuzzyItem, b: &FuzzyItem) -> Ordering {
+        let by_mode = match self {
+            SortMode::Name => Ordering::Equal,
+            SortMode::Modified => b.modified.cmp(&a.modified),
+            SortMode::Found => a.original_index.cmp(&b.original_index),
+        };
+        b.weight
+            .cmp(&a.weight)
+            .then(by_mode)
+            .then_with(|| lowercase(&a.display).cmp(lowercase(&b.display)))
+            .then_with(|| a.path.cmp(&b.path))
+    }
 }
 
 impl<'a> FromIterator<&'a FuzzyItem> for Vec<FuzzyItem> {
@@ -85,14 +281,88 @@ impl PartialOrd for FuzzyItem {
     }
 }
 
-// Creates the list of fuzzy items from the non-hidden subdirectories of `path`.
-pub fn create_items(path: &PathBuf) -> Result<Vec<FuzzyItem>, anyhow::Error> {
-    let items = WalkDir::new(path)
+// Creates the list of fuzzy items from the subdirectories of `path`, including
+// the hidden ones, such as '.stfolder', only if `hidden_dirs` is true.
+pub fn create_items(path: &PathBuf, hidden_dirs: bool) -> Result<Vec<FuzzyItem>, anyhow::Error> {
+    Ok(create_items_within(path, None, hidden_dirs)?.unwrap_or_default())
+}
+
+// Creates the list of fuzzy items from the subdirectories of `path`, or
+// returns `None` once more than `limit` directories are found.
+pub fn create_items_within(
+    path: &PathBuf,
+    limit: Option<usize>,
+    hidden_dirs: bool,
+) -> Result<Option<Vec<FuzzyItem>>, anyhow::Error> {
+    let mut items = vec![];
+    let entries = WalkDir::new(path)
         .into_iter()
-        .filter_entry(is_non_hidden_dir)
-        .filter_map(|res| FuzzyItem::new(res).ok())
-        .collect::<Vec<FuzzyItem>>();
-    Ok(items)
+        .filter_entry(|entry| is_non_hidden_dir(entry, hidden_dirs));
+
+    for (count, res) in entries.enumerate() {
+        if limit.is_some_and(|limit| count >= limit) {
+            return Ok(None);
+        }
+        if let Ok(item) = FuzzyItem::new(res) {
+            let original_index = items.len();
+            items.push(FuzzyItem {
+                original_index,
+                ..item
+            });
+        }
+    }
+    Ok(Some(items))
+}
+
+// Creates the fuzzy items for `dir` and, if `recursive` is true, for its
+// subdirectories, as they would be created from `root`. This updates the
+// items of `root` for the directories that were added or changed since.
+pub fn rescan_items(root: &Path, dir: &Path, recursive: bool, hidden_dirs: bool) -> Vec<FuzzyItem> {
+    let below_root = match dir.strip_prefix(root) {
+        Ok(path) => path.components().count(),
+        Err(_) => return vec![],
+    };
+    let max_depth = match recursive {
+        true => usize::MAX,
+        false => 0,
+    };
+    WalkDir::new(dir)
+        .max_depth(max_depth)
+        .into_iter()
+        .filter_entry(|entry| is_non_hidden_dir(entry, hidden_dirs))
+        .flatten()
+        .filter_map(|dent| FuzzyItem::with_depth(&dent, below_root + dent.depth()).ok())
+        .collect()
+}
+
+// The number of items that `scan_items` sends at a time.
+const SCAN_BATCH: usize = 500;
+
+// Scans the subdirectories of `path` on another thread, sending the fuzzy
+// items in batches as they are found. The sender is dropped once the scan is
+// done.
+pub fn scan_items(path: &PathBuf, hidden_dirs: bool) -> Receiver<Vec<FuzzyItem>> {
+    let (tx, rx) = mpsc::channel();
+    let path = path.to_owned();
+
+    thread::spawn(move || {
+        let items = WalkDir::new(path)
+            .into_iter()
+            .filter_entry(move |entry| is_non_hidden_dir(entry, hidden_dirs))
+            .filter_map(|res| FuzzyItem::new(res).ok());
+
+        let mut batch = Vec::with_capacity(SCAN_BATCH);
+        for item in items {
+            batch.push(item);
+            if batch.len() == SCAN_BATCH && tx.send(std::mem::take(&mut batch)).is_err() {
+                return;
+            }
+        }
+        if !batch.is_empty() {
+            _ = tx.send(batch);
+        }
+    });
+    rx
 }
 
 // Gets all the non-leaf items that start with the letter `key`.
@@ -148,10 +418,10 @@ pub fn only_audio_path(path: &PathBuf, items: &Vec<FuzzyItem>) -> Option<PathBuf
 }
 
 // Returns the path to the first directory that contains audio, if any.
-pub fn first_audio_path(path: &PathBuf) -> Result<PathBuf, anyhow::Error> {
+pub fn first_audio_path(path: &PathBuf, hidden_dirs: bool) -> Result<PathBuf, anyhow::Error> {
     let entries = WalkDir::new(path)
         .into_iter()
-        .filter_entry(is_non_hidden_dir)
+        .filter_entry(|entry| is_non_hidden_dir(entry, hidden_dirs))
         .filter_map(|entry| entry.ok());
 
     for entry in entries {
@@ -159,7 +429,48 @@ pub fn first_audio_path(path: &PathBuf) -> Result<PathBuf, anyhow::Error> {
             return Ok(path.to_owned());
         }
     }
-    bail!("no audio files detected in '{}'", path.display())
+    bail!("no audio files detected in '{}'", display_path(path))
+}
+
+// The file names of the audio files in `path`, sorted, such as for the tracks
+// of an album. Empty if the directory can't be read.
+pub fn audio_file_names(path: &Path) -> Vec<String> {
+    let mut names: Vec<String> = match path.read_dir() {
+        Ok(entries) => entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(valid_audio_ext)
+            .map(|path| file_name(&path))
+            .collect(),
+        Err(_) => vec![],
+    };
+    names.sort();
+    names
+}
+
+// Gets the items that are at most `depth` levels deep, or all of them if
+// there is no depth. The deeper items are found by selecting the folder at
+// `depth` that they are in.
+pub fn top_items(depth: Option<usize>, items: &Vec<FuzzyItem>) -> Vec<FuzzyItem> {
+    match depth {
+        Some(depth) => items.into_iter().filter(|e| e.depth <= depth).collect(),
+        None => items.to_owned(),
+    }
+}
+
+// Gets the items below `dir`, with their depths relative to it, as if they
+// were created from `dir`.
+pub fn items_within(dir: &Path, items: &Vec<FuzzyItem>) -> Vec<FuzzyItem> {
+    items
+        .iter()
+        .filter_map(|item| {
+            let depth = item.path.strip_prefix(dir).ok()?.components().count();
+            (depth > 0).then(|| FuzzyItem {
+                depth,
+                ..item.to_owned()
+            })
+        })
+        .collect()
 }
 
 // Gets all the leaf items, sorted alphabetically.
@@ -181,14 +492,208 @@ pub fn leaf_paths(items: &Vec<FuzzyItem>) -> Vec<PathBuf> {
         .collect::<Vec<PathBuf>>()
 }
 
-// Whether the entry is a directory or not. Excludes hidden directories.
-fn is_non_hidden_dir(entry: &walkdir::DirEntry) -> bool {
+// Computes the weights for the items on matching all of the `terms` with
+// `case`, matching on the paths relative to `root` if there is one. The
+// `matcher` is the matcher of `case`, which is made once and reused, as
+// this runs on every keystroke. The items that weigh less than `threshold`
+// aren't matches. Returns the number of matches.
+pub fn fuzzy_match(
+    items: &mut [FuzzyItem],
+    terms: &[Term],
+    matcher: &SkimMatcherV2,
+    case: Case,
+    root: Option<&Path>,
+    threshold: i64,
+) -> usize {
+    #[cfg(test)]
+    MATCH_RUNS.with(|runs| runs.set(runs.get() + 1));
+
+    let mut count = 0;
+    for item in items.iter_mut() {
+        match match_terms(matcher, case, &item.text(root), terms) {
+            // The items matched by only the operators of the query, with no
+            // letters matched, aren't weak matches.
+            Some((weight, indices)) if weight >= threshold || indices.is_empty() => {
+                item.weight = weight;
+                item.indices = indices;
+                count += 1;
+            }
+            _ => {
+                item.weight = 0;
+                item.indices.clear();
+            }
+        }
+    }
+    count
+}
+
+// Matches the items with `regex`, on the paths relative to `root` if there
+// is one. The matches all have the same weight, and the indices of the first
+// match of each are highlighted. Returns the number of matches.
+pub fn regex_match(items: &mut [FuzzyItem], regex: &Regex, root: Option<&Path>) -> usize {
+    let mut count = 0;
+    for item in items.iter_mut() {
+        let text = item.text(root);
+        if let Some(m) = regex.find(&text) {
+            // The byte offsets of the match, as char indices.
+            let start = text[..m.start()].chars().count();
+            let indices = (start..start + m.as_str().chars().count()).collect();
+            item.weight = 1;
+            item.indices = indices;
+            count += 1;
+        } else {
+            item.weight = 0;
+            item.indices.clear();
+        }
+    }
+    count
+}
+
+// Matches the items that start with `prefix` with `case`, on the paths
+// relative to `root` if there is one. The matches all have the same weight,
+// so that they stay in the sort order. Returns the number of matches.
+pub fn prefix_match(
+    items: &mut [FuzzyItem],
+    prefix: &str,
+    case: Case,
+    root: Option<&Path>,
+) -> usize {
+    let mut count = 0;
+    for item in items.iter_mut() {
+        if case.is_prefix(prefix, &item.text(root)) {
+            item.weight = 1;
+            item.indices = (0..prefix.chars().count()).collect();
+            count += 1;
+        } else {
+            item.weight = 0;
+            item.indices.clear();
+        }
+    }
+    count
+}
+
+#[cfg(test)]
+thread_local! {
+    // The number of times that the items have been matched on this thread.
+    pub static MATCH_RUNS: std::cell::Cell<usize> = std::cell::Cell::new(0);
+}
+
+// How the weight of the match of an item is made up, which is only worked
+// out when it is asked for, to explain the order of the matches.
+#[derive(Debug, PartialEq)]
+pub struct Score {
+    // The weight of the match of each term, in the order of the query.
+    pub terms: Vec<(Term, i64)>,
+    // The total weight, which is 1 more than the sum of the terms.
+    pub weight: i64,
+    // The char indices that are matched by any of the terms.
+    pub indices: Vec<usize>,
+}
+
+// Works out the score of the match of `item` with `terms`, as `fuzzy_match`
+// does, or `None` if it doesn't match.
+pub fn explain(
+    item: &FuzzyItem,
+    terms: &[Term],
+    matcher: &SkimMatcherV2,
+    case: Case,
+    root: Option<&Path>,
+) -> Option<Score> {
+    let mut scored = vec![];
+    let (weight, indices) = score_terms(matcher, case, &item.text(root), terms, |term, w| {
+        scored.push((term.to_owned(), w))
+    })?;
+    Some(Score {
+        terms: scored,
+        weight,
+        indices,
+    })
+}
+
+// The total weight of the matches of `terms` in `text`, with the indices
+// matched by any of them, or `None` if any term doesn't match.
+fn match_terms(
+    matcher: &SkimMatcherV2,
+    case: Case,
+    text: &str,
+    terms: &[Term],
+) -> Option<(i64, Vec<usize>)> {
+    score_terms(matcher, case, text, terms, |_, _| ())
+}
+
+// As `match_terms`, passing the weight of the match of each term to
+// `on_term`.
+fn score_terms(
+    matcher: &SkimMatcherV2,
+    case: Case,
+    text: &str,
+    terms: &[Term],
+    mut on_term: impl FnMut(&Term, i64),
+) -> Option<(i64, Vec<usize>)> {
+    // The items match with only operators in the query, as with an empty one.
+    let mut weight = 1;
+    let mut indices = vec![];
+    for term in terms {
+        let (w, i) = term.find(matcher, case, text)?;
+        on_term(term, w);
+        weight += w;
+        indices.extend(i);
+    }
+    indices.sort_unstable();
+    indices.dedup();
+    Some((weight, indices))
+}
+
+// Replaces the `old` prefix of `path` with `new`. Returns whether or not
+// `path` was changed.
+pub fn replace_prefix(path: &mut PathBuf, old: &Path, new: &Path) -> bool {
+    match path.strip_prefix(old) {
+        Ok(rest) => {
+            *path = new.join(rest);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+// The file name of `path`, or an empty string if there is none.
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned()
+}
+
+// The chars of `text`, lowercased.
+fn lowercase(text: &str) -> impl Iterator<Item = char> + '_ {
+    text.chars().flat_map(char::to_lowercase)
+}
+
+// The seconds from the Unix epoch to `time`, or 0 if it was before then.
+pub fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+// The first character of `display`, uppercased.
+fn first_key(display: &str) -> char {
+    display
+        .chars()
+        .next()
+        .unwrap_or_default()
+        .to_ascii_uppercase()
+}
+
+// Whether the entry is a directory or not. Excludes hidden directories,
+// unless `hidden_dirs` is true.
+pub(super) fn is_non_hidden_dir(entry: &walkdir::DirEntry, hidden_dirs: bool) -> bool {
     entry.file_type().is_dir()
-        && !entry
-            .file_name()
-            .to_str()
-            .map(|s| s.starts_with("."))
-            .unwrap_or(false)
+        && (hidden_dirs
+            || !entry
+                .file_name()
+                .to_str()
+                .map(|s| s.starts_with("."))
+                .unwrap_or(false))
 }
 
 // Whether or not the path is a directory that contains audio.
@@ -203,29 +708,50 @@ fn has_audio<P: AsRef<Path>>(path: P) -> Result<bool, anyhow::Error> {
     bail!("invalid")
 }
 
+// The number of audio files in the search root, which is only an item if
+// it contains audio, their total size and their extensions.
+fn count_audio(path: &PathBuf) -> Result<(usize, u64, Vec<String>), anyhow::Error> {
+    match count_entries(path)? {
+        (0, _, _, _) => bail!("invalid"),
+        (audio_count, _, size_bytes, extensions) => Ok((audio_count, size_bytes, extensions)),
+    }
+}
+
 // Whether or not a directory is a valid FuzzyItem; that is, does
 // the directory contain at least one audio file or child directory.
-fn validate(path: &PathBuf) -> Result<(bool, usize), anyhow::Error> {
-    let mut has_audio = false;
+// Returns the number of each, and the total size and the extensions of the
+// audio files.
+fn validate(path: &PathBuf) -> Result<(usize, usize, u64, Vec<String>), anyhow::Error> {
+    match count_entries(path)? {
+        (0, 0, _, _) => bail!("invalid"),
+        counts => Ok(counts),
+    }
+}
+
+// The number of audio files and of subdirectories in `path`, the total size
+// of the audio files in bytes, and their extensions, sorted and without
+// duplicates.
+pub fn count_entries(path: &Path) -> Result<(usize, usize, u64, Vec<String>), anyhow::Error> {
+    let mut audio_count: usize = 0;
     let mut dir_count: usize = 0;
+    let mut size_bytes: u64 = 0;
+    let mut extensions: Vec<String> = vec![];
 
-    for entry in path.read_dir()? {
-        if let Ok(entry) = entry {
-            if entry.path().is_dir() {
-                dir_count += 1;
-            } else if !has_audio {
-                has_audio = valid_audio_ext(&entry.path());
+    for entry in path.read_dir()?.flatten() {
+        let path = entry.path();
+        // One stat for each entry, which follows symlinks as `is_dir` does.
+        let metadata = std::fs::metadata(&path).ok();
+        if metadata.as_ref().is_some_and(|m| m.is_dir()) {
+            dir_count += 1;
+        } else if valid_audio_ext(&path) {
+            audio_count += 1;
+            size_bytes += metadata.map_or(0, |m| m.len());
+            if let Some(ext) = path.extension() {
+                extensions.push(ext.to_string_lossy().into_owned());
             }
         }
-
-        if has_audio && dir_count > 1 {
-            break;
-        }
-    }
-
-    if !has_audio && dir_count == 0 {
-        bail!("invalid")
     }
-
-    Ok((has_audio, dir_count))
+    extensions.sort();
+    extensions.dedup();
+    Ok((audio_count, dir_count, size_bytes, extensions))
 }
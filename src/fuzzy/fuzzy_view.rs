@@ -1,29 +1,84 @@
-use std::path::PathBuf;
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::Receiver,
+    },
+    thread,
+    time::{Duration, Instant},
+};
 
+use anyhow::anyhow;
 use cursive::{
     event::{Event, EventResult, EventTrigger, Key, MouseButton, MouseEvent},
     theme::Effect,
-    view::Resizable,
-    views::LayerPosition,
-    Cursive, Printer, View, XY,
+    view::{Nameable, Resizable},
+    views::Dialog,
+    CbSink, Cursive, Printer, View, XY,
 };
-use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use fuzzy_matcher::skim::SkimMatcherV2;
 use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
 
-use crate::config::{args, theme};
-use crate::data::session_data::SessionData;
+use crate::command;
+use crate::config::{
+    args::{self, SoftWrap},
+    theme,
+};
+use crate::data::{
+    fuzzy_data::{self, LastSearch},
+    session_data::SessionData,
+    Favorites,
+};
+use crate::layers::{self, Role};
 use crate::player::{PlayerBuilder, PlayerView};
-use crate::utils::{self, InnerType};
+use crate::utils::{self, AppDir, InnerType};
+
+use super::{
+    audio_file_names,
+    changes::Changes,
+    create_items, explain, file_ops, fuzzy_match, items_within,
+    match_worker::{self, MatchWorker, Matches, Query},
+    prefix_match,
+    query::{self, Case},
+    regex_match, replace_prefix, ErrorView, FuzzyItem, MatchMode, Score, SortMode, TwoPaneView,
+};
+
+// How long a toast is shown for.
+const TOAST_TIME: Duration = Duration::from_secs(3);
+
+// The narrowest terminal that the fuzzy finder is drawn on.
+const MIN_WIDTH: usize = 8;
+
+// The narrowest terminal that the preview is drawn on, beside the list.
+const MIN_PREVIEW_WIDTH: usize = 40;
 
-use super::{create_items, ErrorView, FuzzyItem};
+// The fewest columns left for a name for its details to be drawn beside it.
+const MIN_NAME_WIDTH: usize = 8;
+
+// The number of rows that the selection moves for each step of the wheel.
+const WHEEL_ROWS: usize = 3;
+
+// The weight that the threshold is raised or lowered by at a time.
+const THRESHOLD_STEP: i64 = 10;
+
+// The latest scan that a view was loaded from, so that the items of one
+// scan are never added to the view of another.
+static SCANS: AtomicU64 = AtomicU64::new(0);
 
-#[derive(Clone)]
 pub struct FuzzyView {
     // The text input to fuzzy match with.
     query: String,
     // The column of the text input cursor.
     cursor: usize,
+    // The byte index of the first char of the query that is shown, when the
+    // query is too wide for the text input area.
+    query_offset: usize,
+    // The text that was last deleted with 'Ctrl + w' or 'Ctrl + k', which
+    // 'Ctrl + y' inserts again.
+    killed: String,
     // The index of the selected item.
     selected: usize,
     // The vertical offset required to show `selected`.
@@ -34,57 +89,618 @@ pub struct FuzzyView {
     items: Vec<FuzzyItem>,
     // The maximum number of `items` visible per page.
     available_y: usize,
+    // When items that are too long for one row are wrapped onto two rows.
+    soft_wrap: SoftWrap,
+    // Whether or not moving past either end of the matches continues from
+    // the other end.
+    wrap_around: bool,
+    // The queries that selected something this session, oldest first.
+    history: Vec<String>,
+    // The index of the query recalled from `history`, if any.
+    recalled: Option<usize>,
+    // The query that was being typed before one was recalled.
+    draft: String,
+    // Whether or not the paths relative to `root` are matched and drawn,
+    // instead of the display names.
+    match_paths: bool,
+    // How the case of the letters is matched.
+    case: Case,
+    // The fuzzy matcher of `case`.
+    matcher: SkimMatcherV2,
+    // How the items with equal weights are ordered.
+    sort_mode: SortMode,
+    // How the query is matched with the items.
+    match_mode: MatchMode,
+    // The weight that the fuzzy matches need at least, where 0 keeps every
+    // match.
+    threshold: i64,
+    // Why the query isn't a valid regex, in regex mode.
+    regex_error: Option<String>,
+    // Whether or not the tracks of the selected album are shown beside the
+    // list, when there is space.
+    preview: bool,
+    // Whether or not the size and the date of each item are shown after its
+    // name, when there is space.
+    details: bool,
+    // Whether or not the position of each match is shown before its name.
+    show_indices: bool,
+    // The file names of the tracks of each album that has been previewed,
+    // which are read once, when the album is first selected.
+    tracks: HashMap<PathBuf, Vec<String>>,
+    // The paths of the marked items, in the order they were marked, which
+    // are played one after another when one is selected.
+    marked: Vec<PathBuf>,
+    // The search root.
+    root: PathBuf,
+    // The depth of the folders of the depth search that this view was
+    // browsed from, so that the parent of a folder selected in it is that
+    // search again.
+    depth_search: Option<usize>,
+    // The paths hidden from the matches for this session.
+    hidden: HashSet<PathBuf>,
+    // Whether or not the hidden paths are matched anyway.
+    showing_hidden: bool,
+    // Whether or not hidden directories, such as '.stfolder', are scanned
+    // when a directory is browsed or reloaded.
+    hidden_dirs: bool,
+    // The starred paths, which are kept after tap exits.
+    favorites: Favorites,
+    // Whether or not only the favorites are matched.
+    favorites_only: bool,
+    // A message shown above the query for a short time, and when it was shown.
+    toast: Option<(String, Instant)>,
+    // An error shown in place of the toast until a key is pressed, and when
+    // it was shown.
+    error: Option<(String, Instant)>,
+    // Matches the query as it is typed on another thread, in large libraries.
+    worker: Option<MatchWorker>,
+    // The generation of the matches that the worker is matching, which the
+    // previous matches are shown until.
+    pending: Option<u64>,
+    // Spins after the match count while the query is being matched.
+    spinner: utils::Spinner,
+    // How the current matches were matched, when they are all of the items
+    // that match, so that a longer query only needs to match them again.
+    narrowing: Option<Narrowing>,
+    // The item that was last clicked and the offset of the list then, so
+    // that clicking it again without the list moving plays it.
+    click: Option<(usize, usize)>,
+    // Whether or not the current directory is being scanned again.
+    reloading: bool,
+    // The scan that the items are still being added from, if any.
+    scanning: Option<u64>,
+    // Whether or not the items are only some of those in the current
+    // directory, such as the artists of a letter, which the albums added to
+    // the library aren't added to.
+    partial: bool,
+    // The pages that a random page is picked from, in the order they are
+    // picked, and the number of matches and the page length they are pages
+    // of.
+    page_bag: Vec<usize>,
+    page_bag_of: (usize, usize),
+    // What is dragged while the left button is held.
+    drag: Option<Drag>,
     // The size of the view.
     size: XY<usize>,
 }
 
 impl FuzzyView {
-    fn new(items: Vec<FuzzyItem>) -> Self {
+    // The name used to find the fuzzy views in the view stack.
+    pub const NAME: &'static str = "fuzzy";
+
+    pub fn new(mut items: Vec<FuzzyItem>) -> Self {
+        // The order that the items start in, such as alphabetically for the
+        // artist search, is the order they were found in.
+        for (index, item) in items.iter_mut().enumerate() {
+            item.original_index = index;
+        }
+
         FuzzyView {
             query: String::new(),
             cursor: 0,
+            query_offset: 0,
+            killed: String::new(),
             selected: 0,
             offset_y: 0,
             matches: items.len(),
             items,
             available_y: 0,
+            soft_wrap: SoftWrap::Never,
+            wrap_around: false,
+            history: vec![],
+            recalled: None,
+            draft: String::new(),
+            match_paths: false,
+            case: Case::Smart,
+            matcher: Case::Smart.matcher(),
+            sort_mode: SortMode::Name,
+            match_mode: MatchMode::Fuzzy,
+            threshold: 0,
+            regex_error: None,
+            preview: false,
+            details: false,
+            show_indices: false,
+            tracks: HashMap::new(),
+            marked: vec![],
+            root: PathBuf::new(),
+            depth_search: None,
+            hidden: HashSet::new(),
+            showing_hidden: false,
+            hidden_dirs: false,
+            favorites: Favorites::default(),
+            favorites_only: false,
+            toast: None,
+            error: None,
+            worker: None,
+            pending: None,
+            spinner: utils::Spinner::new(utils::supports_unicode()),
+            narrowing: None,
+            click: None,
+            reloading: false,
+            scanning: None,
+            partial: false,
+            page_bag: vec![],
+            page_bag_of: (0, 0),
+            drag: None,
             size: XY { x: 0, y: 0 },
         }
     }
 
+    // Copies the settings of the session, if there is one: whether paths are
+    // matched, the case, sort and match modes, the threshold, the preview,
+    // details and indices, the query history, the hidden paths and whether
+    // they are shown, whether hidden directories are scanned, and whether
+    // only favorites are listed. The list is then matched again with them.
+    fn apply_session(&mut self, siv: &mut Cursive) {
+        if let Some((_, _, _, data, _, _)) = siv.user_data::<InnerType<SessionData>>() {
+            self.match_paths = data.match_paths.unwrap_or(self.match_paths);
+            self.set_case(data.case);
+            self.sort_mode = data.sort_mode;
+            self.match_mode = data.match_mode;
+            self.threshold = data.threshold.unwrap_or(self.threshold);
+            self.preview = data.preview;
+            self.details = data.details;
+            self.show_indices = data.show_indices;
+            self.history = data.history.to_owned();
+            self.hidden = data.hidden.to_owned();
+            self.showing_hidden = data.showing_hidden;
+            self.hidden_dirs = data.hidden_dirs;
+            self.favorites_only = data.favorites_only;
+        }
+        self.update_list(&self.query.to_owned());
+    }
+
+    // A FuzzyView of `items` with the options and the session's settings.
+    pub fn with_session(items: Vec<FuzzyItem>, siv: &mut Cursive) -> Self {
+        let mut fuzzy = FuzzyView::new(items);
+        fuzzy.soft_wrap = args::soft_wrap();
+        fuzzy.wrap_around = args::wrap_around();
+        fuzzy.match_paths = args::match_paths();
+        fuzzy.threshold = args::threshold();
+        fuzzy.root = args::search_root();
+        fuzzy.favorites = Favorites::saved();
+        fuzzy.apply_session(siv);
+        fuzzy
+    }
+
     // Loads a new FuzzyView from the provided items. Providing a `key` will
     // pre-match the results using the char.
     pub fn load(items: Vec<FuzzyItem>, key: Option<char>, siv: &mut Cursive) {
-        let mut fuzzy = FuzzyView::new(items);
+        let mut fuzzy = FuzzyView::with_session(items, siv);
 
         if let Some(key) = key {
             fuzzy.insert(key.to_ascii_lowercase());
         }
+        fuzzy.start_worker(siv.cb_sink().clone());
 
-        siv.add_layer(fuzzy.full_screen());
-        remove_layer(siv);
+        layers::show(siv, Role::Fuzzy, fuzzy.with_name(Self::NAME).full_screen());
     }
 
-    // Moves the selection down one row.
-    fn move_down(&mut self) {
-        if self.selected == 0 {
+    // Loads a new FuzzyView that starts empty, adding the items received from
+    // `scan` as they are found.
+    pub fn load_scanned(scan: Receiver<Vec<FuzzyItem>>, siv: &mut Cursive) {
+        let mut fuzzy = FuzzyView::with_session(vec![], siv);
+
+        let id = SCANS.fetch_add(1, Ordering::SeqCst) + 1;
+        fuzzy.scanning = Some(id);
+        let cb_sink = siv.cb_sink().clone();
+        thread::spawn(move || {
+            for mut batch in scan {
+                let add = move |siv: &mut Cursive| {
+                    let cb_sink = siv.cb_sink().clone();
+                    siv.call_on_all_named(Self::NAME, |fuzzy: &mut FuzzyView| {
+                        fuzzy.add_scanned(id, &mut batch, &cb_sink)
+                    });
+                };
+                if cb_sink.send(Box::new(add)).is_err() {
+                    return;
+                }
+            }
+            let done = move |siv: &mut Cursive| {
+                siv.call_on_all_named(Self::NAME, |fuzzy: &mut FuzzyView| fuzzy.scanned(id));
+            };
+            _ = cb_sink.send(Box::new(done));
+        });
+
+        layers::show(siv, Role::Fuzzy, fuzzy.with_name(Self::NAME).full_screen());
+    }
+
+    // Adds a batch of the items found by the scan `id`, if this view is
+    // loaded from it, and matches the query with them, keeping the selection.
+    fn add_scanned(&mut self, id: u64, batch: &mut Vec<FuzzyItem>, cb_sink: &CbSink) {
+        if self.scanning != Some(id) {
             return;
         }
-        if self.selected == self.offset_y {
-            self.offset_y -= 1;
+        let selected = self
+            .items
+            .get(self.selected)
+            .filter(|item| item.weight != 0)
+            .map(|item| item.path.to_owned());
+
+        let start = self.items.len();
+        for (index, item) in batch.iter_mut().enumerate() {
+            item.original_index = start + index;
+        }
+        self.items.append(batch);
+        self.narrowing = None;
+        match &self.worker {
+            Some(worker) => worker.set_items(self.items.to_owned()),
+            None => self.start_worker(cb_sink.clone()),
+        }
+        self.query_changed();
+
+        if let Some(index) = selected.and_then(|path| {
+            self.items
+                .iter()
+                .position(|item| item.path.eq(&path) && item.weight != 0)
+        }) {
+            self.selected = index;
         }
-        self.selected -= 1;
     }
 
-    // Moves the selection up one row.
-    fn move_up(&mut self) {
-        if self.selected == self.matches - 1 {
+    // Stops showing the scan `id` in the count once all its items are added.
+    fn scanned(&mut self, id: u64) {
+        if self.scanning == Some(id) {
+            self.scanning = None;
+        }
+    }
+
+    // Replaces the current FuzzyView with one for the provided items. Providing
+    // a `path` will select the item with that path, if it exists.
+    pub fn refresh(items: Vec<FuzzyItem>, path: Option<&PathBuf>, siv: &mut Cursive) {
+        Self::browse(items, None, path, siv)
+    }
+
+    // As `refresh`, for a view browsed from the depth search of folders at
+    // `depth_search`, if any.
+    fn browse(
+        items: Vec<FuzzyItem>,
+        depth_search: Option<usize>,
+        path: Option<&PathBuf>,
+        siv: &mut Cursive,
+    ) {
+        let mut fuzzy = FuzzyView::with_session(items, siv);
+        fuzzy.depth_search = depth_search;
+
+        if let Some(index) = path.and_then(|p| fuzzy.items.iter().position(|e| e.path.eq(p))) {
+            fuzzy.selected = index;
+        }
+        fuzzy.start_worker(siv.cb_sink().clone());
+
+        layers::show(siv, Role::Fuzzy, fuzzy.with_name(Self::NAME).full_screen());
+    }
+
+    // Starts matching the query on another thread if there are enough items
+    // for typing to lag, with the matches applied to the view through
+    // `cb_sink`.
+    fn start_worker(&mut self, cb_sink: CbSink) {
+        if self.items.len() < match_worker::MIN_ITEMS {
+            return;
+        }
+        let worker = MatchWorker::start(self.items.to_owned(), move |matches| {
+            let apply = move |siv: &mut Cursive| {
+                siv.call_on_all_named(Self::NAME, |fuzzy: &mut FuzzyView| {
+                    fuzzy.apply_matches(&matches)
+                });
+            };
+            cb_sink.send(Box::new(apply)).is_ok()
+        });
+        self.worker = Some(worker);
+    }
+
+    // Merges a batch of filesystem changes into the items, preserving
+    // the current query and, if it still matches, the selected item.
+    pub fn apply_changes(&mut self, added: Vec<FuzzyItem>, removed: Vec<PathBuf>) {
+        let selected = self.items.get(self.selected).map(|e| e.path.to_owned());
+
+        self.items
+            .retain(|e| !removed.iter().any(|path| e.path.starts_with(path)));
+        self.marked
+            .retain(|p| !removed.iter().any(|path| p.starts_with(path)));
+
+        for item in added {
+            match self.items.iter().position(|e| e.path.eq(&item.path)) {
+                Some(index) => {
+                    let original_index = self.items[index].original_index;
+                    let selected = self.items[index].selected;
+                    self.items[index] = FuzzyItem {
+                        original_index,
+                        selected,
+                        ..item
+                    };
+                }
+                None => {
+                    let original_index = self.items.len();
+                    self.items.push(FuzzyItem {
+                        original_index,
+                        ..item
+                    });
+                }
+            }
+        }
+        // The added items may match queries that the others didn't.
+        self.narrowing = None;
+        if let Some(worker) = &self.worker {
+            worker.set_items(self.items.to_owned());
+        }
+
+        self.update_list(&self.query.to_owned());
+
+        if let Some(index) = selected.and_then(|p| {
+            self.items
+                .iter()
+                .position(|e| e.path.eq(&p) && e.weight != 0)
+        }) {
+            self.selected = index;
+        }
+    }
+
+    // Merges the `library`, scanned again by the watcher after `changes`,
+    // into the items of the current directory, if the changes are in it.
+    pub fn merge_library(&mut self, changes: &Changes, library: &Vec<FuzzyItem>) {
+        if self.scanning.is_some() || self.reloading {
+            return;
+        }
+        let dir = match self.current_dir() {
+            Some(dir) if changes.affect(&dir) => dir,
+            _ => return,
+        };
+
+        let scanned = items_within(&dir, library);
+        let paths: HashSet<&PathBuf> = scanned.iter().map(|item| &item.path).collect();
+        let removed: Vec<PathBuf> = self
+            .items
+            .iter()
+            .filter(|item| item.depth > 0 && !paths.contains(&item.path))
+            .map(|item| item.path.to_owned())
+            .collect();
+        let current: HashMap<&PathBuf, &FuzzyItem> =
+            self.items.iter().map(|item| (&item.path, item)).collect();
+        let added: Vec<FuzzyItem> = scanned
+            .iter()
+            .filter(|item| match current.get(&item.path) {
+                Some(old) => has_changed(old, item),
+                None => !self.partial,
+            })
+            .cloned()
+            .collect();
+
+        if added.is_empty() && removed.is_empty() {
+            return;
+        }
+        for item in added.iter() {
+            self.tracks.remove(&item.path);
+        }
+        self.apply_changes(added, removed);
+    }
+
+    // Removes the items at or inside `path` after it has been deleted. The
+    // item after a deleted one is selected in its place.
+    pub fn remove(&mut self, path: &Path) {
+        let deleted = |item: &FuzzyItem| item.path.starts_with(path);
+        let before = self.items[..self.selected.min(self.items.len())]
+            .iter()
+            .filter(|item| deleted(item))
+            .count();
+        let matched = self.items[..self.matches]
+            .iter()
+            .filter(|item| deleted(item))
+            .count();
+
+        self.items.retain(|item| !deleted(item));
+        self.marked.retain(|p| !p.starts_with(path));
+        self.tracks.retain(|p, _| !p.starts_with(path));
+        self.matches -= matched;
+        self.selected = (self.selected - before).min(self.matches.saturating_sub(1));
+        if let Some(worker) = &self.worker {
+            worker.set_items(self.items.to_owned());
+        }
+    }
+
+    // Sets whether or not the items are only some of those in the current
+    // directory.
+    pub fn set_partial(&mut self, partial: bool) {
+        self.partial = partial;
+    }
+
+    // Updates the items after the directory at `old` has been renamed to `new`.
+    pub fn rename(&mut self, old: &PathBuf, new: &PathBuf) {
+        for item in self.items.iter_mut() {
+            item.rename(old, new);
+        }
+        for path in self.marked.iter_mut() {
+            replace_prefix(path, old, new);
+        }
+        self.favorites.rename(old, new);
+        // The renamed items may match queries that they didn't before.
+        self.narrowing = None;
+        if let Some(worker) = &self.worker {
+            worker.set_items(self.items.to_owned());
+        }
+    }
+
+    // Whether or not items that are too long for one row are wrapped.
+    fn is_wrapping(&self) -> bool {
+        match self.soft_wrap {
+            SoftWrap::Auto(width) => self.size.x < width,
+            SoftWrap::Always => true,
+            SoftWrap::Never => false,
+        }
+    }
+
+    // The number of columns available for an item's display name.
+    fn text_width(&self) -> usize {
+        let width = self.list_width().saturating_sub(3 + self.index_width());
+        std::cmp::max(width, 1)
+    }
+
+    // The number of columns of the position of each match and the space
+    // after it, or 0 when they aren't shown.
+    fn index_width(&self) -> usize {
+        match self.show_indices {
+            true => self.matches.checked_ilog10().unwrap_or(0) as usize + 2,
+            false => 0,
+        }
+    }
+
+    // The number of columns that the list of items is drawn on.
+    fn list_width(&self) -> usize {
+        self.size.x - self.preview_width()
+    }
+
+    // The number of columns of the preview, or 0 when it isn't shown.
+    fn preview_width(&self) -> usize {
+        match self.preview && self.size.x >= MIN_PREVIEW_WIDTH {
+            true => self.size.x / 2,
+            false => 0,
+        }
+    }
+
+    // Reads the tracks of the selected album for the preview, unless they
+    // have been read already.
+    fn load_preview(&mut self) {
+        if self.preview_width() == 0 {
+            return;
+        }
+        if let Some(item) = self.items.get(self.selected) {
+            if item.child_count == 0 && !self.tracks.contains_key(&item.path) {
+                let tracks = audio_file_names(&item.path);
+                self.tracks.insert(item.path.to_owned(), tracks);
+            }
+        }
+    }
+
+    // The number of rows that the item at `index` is drawn on.
+    fn item_rows(&self, index: usize) -> usize {
+        let text = self.items[index].text(self.match_root());
+        match self.is_wrapping() && text.width() > self.text_width() {
+            true => 2,
+            false => 1,
+        }
+    }
+
+    // The root that the paths are matched relative to, when matching paths.
+    fn match_root(&self) -> Option<&Path> {
+        match self.match_paths {
+            true => Some(&self.root),
+            false => None,
+        }
+    }
+
+    // The number of rows available to draw the items on.
+    fn list_rows(&self) -> usize {
+        self.available_y + 1
+    }
+
+    // The number of items, starting from `offset_y`, that fit in the list rows.
+    fn visible_items(&self) -> usize {
+        let mut rows = 0;
+        let mut count = 0;
+        for index in self.offset_y..self.matches {
+            rows += self.item_rows(index);
+            if rows > self.list_rows() {
+                break;
+            }
+            count += 1;
+        }
+        count
+    }
+
+    // The number of items to move by for each page.
+    fn page_len(&self) -> usize {
+        match self.is_wrapping() {
+            true => std::cmp::max(self.visible_items(), 2) - 1,
+            false => self.available_y,
+        }
+    }
+
+    // The index of the item drawn on the list row `from_bottom`, counting
+    // upwards from the bottom row.
+    fn item_at_row(&self, from_bottom: usize) -> Option<usize> {
+        let mut rows = 0;
+        for index in self.offset_y..self.matches {
+            rows += self.item_rows(index);
+            if rows > self.list_rows() {
+                return None;
+            } else if rows > from_bottom {
+                return Some(index);
+            }
+        }
+        None
+    }
+
+    // The index of the match drawn under the mouse at `position`, in a view
+    // at `offset`, or `None` if it isn't over a match. As in the player, the
+    // mouse is one row below the row of the view that it is over.
+    fn item_under_mouse(&self, offset: XY<usize>, position: XY<usize>) -> Option<usize> {
+        let x = position.x.checked_sub(offset.x)?;
+        let y = position.y.checked_sub(offset.y + 1)?;
+        // The list is drawn from the row below the header.
+        if x >= self.list_width() || y == 0 || y > self.list_rows() {
+            return None;
+        }
+        self.item_at_row(self.list_rows() - y)
+    }
+
+    // Adjusts the offset so that all the rows of the selected item are visible.
+    fn scroll_to_selected(&mut self) {
+        if self.selected < self.offset_y || self.matches == 0 {
+            self.offset_y = self.selected;
             return;
         }
-        if self.selected - self.offset_y >= self.available_y {
-            self.offset_y += 1;
+        // Count the rows upwards from the selected item until they don't fit.
+        let mut rows = 0;
+        for index in (self.offset_y..=self.selected).rev() {
+            rows += self.item_rows(index);
+            if rows > self.list_rows() {
+                self.offset_y = std::cmp::min(index + 1, self.selected);
+                return;
+            }
+        }
+    }
+
+    // Moves the selection down one row, or to the top row when wrapping
+    // around from the bottom.
+    fn move_down(&mut self) {
+        match self.selected {
+            0 if self.wrap_around && self.matches > 0 => self.selected = self.matches - 1,
+            0 => return,
+            _ => self.selected -= 1,
+        }
+        self.scroll_to_selected();
+    }
+
+    // Moves the selection up one row, or to the bottom row when wrapping
+    // around from the top.
+    fn move_up(&mut self) {
+        if self.selected + 1 < self.matches {
+            self.selected += 1;
+        } else if self.wrap_around {
+            self.selected = 0;
+        } else {
+            return;
         }
-        self.selected += 1;
+        self.scroll_to_selected();
     }
 
     // Moves the selection up one page.
@@ -92,15 +708,17 @@ impl FuzzyView {
         if self.matches == 0 {
             return;
         }
-        if self.selected + self.available_y <= self.matches - 1 {
-            self.offset_y += self.available_y;
-            self.selected += self.available_y;
+        let page_len = self.page_len();
+        if self.selected + page_len <= self.matches - 1 {
+            self.offset_y += page_len;
+            self.selected += page_len;
         } else {
             self.selected = self.matches - 1;
-            if self.offset_y + self.available_y < self.selected {
-                self.offset_y += self.available_y;
+            if self.offset_y + page_len < self.selected {
+                self.offset_y += page_len;
             }
         }
+        self.scroll_to_selected();
     }
 
     // Moves the selection down one page.
@@ -108,35 +726,137 @@ impl FuzzyView {
         if self.matches == 0 {
             return;
         }
-        if self.selected > self.available_y {
-            self.selected -= self.available_y
+        let page_len = self.page_len();
+        if self.selected > page_len {
+            self.selected -= page_len
         } else {
             self.selected = 0;
         }
-        if self.offset_y > self.available_y {
-            self.offset_y -= self.available_y
+        if self.offset_y > page_len {
+            self.offset_y -= page_len
         } else {
             self.offset_y = 0;
         }
+        self.scroll_to_selected();
     }
 
-    // Moves the selection to a random page.
-    fn random_page(&mut self) {
-        if self.items.len() <= self.available_y {
+    // Moves the selection up half a page.
+    fn half_page_up(&mut self) {
+        if self.matches == 0 {
             return;
         }
+        let selected = std::cmp::min(self.selected + self.half_page_len(), self.matches - 1);
+        self.offset_y = std::cmp::min(self.offset_y + selected - self.selected, self.last_offset());
+        self.selected = selected;
+        self.scroll_to_selected();
+    }
+
+    // Moves the selection down half a page.
+    fn half_page_down(&mut self) {
+        let selected = self.selected.saturating_sub(self.half_page_len());
+        self.offset_y = self.offset_y.saturating_sub(self.selected - selected);
+        self.selected = selected;
+        self.scroll_to_selected();
+    }
 
-        let pages = self.items.len() / (self.available_y) + 1;
-        let page = utils::random(0..pages);
-        let y = page * (self.available_y);
+    // The number of items to move by for half a page.
+    fn half_page_len(&self) -> usize {
+        std::cmp::max(self.page_len() / 2, 1)
+    }
 
-        if y == self.offset_y {
-            self.random_page();
-        } else {
-            self.clear();
-            self.offset_y = y;
-            self.selected = y;
+    // Moves the selection to the first match, at the bottom.
+    fn select_first(&mut self) {
+        self.selected = 0;
+        self.offset_y = 0;
+    }
+
+    // Moves the selection to the last match, at the top.
+    fn select_last(&mut self) {
+        if self.matches == 0 {
+            return;
+        }
+        self.selected = self.matches - 1;
+        self.offset_y = self.last_offset();
+    }
+
+    // The offset that shows the last match at the top of a full list.
+    fn last_offset(&self) -> usize {
+        let mut rows = 0;
+        for index in (0..self.matches).rev() {
+            rows += self.item_rows(index);
+            if rows > self.list_rows() {
+                return std::cmp::min(index + 1, self.matches - 1);
+            }
+        }
+        0
+    }
+
+    // Clears the query and moves the selection to a random page of the
+    // matches. Each page is visited once, in a random order, before any is
+    // visited again, and the page that is shown isn't picked.
+    fn random_page(&mut self) {
+        let page_len = self.page_len();
+        // The page that is shown, unless the query is cleared.
+        let current = match self.query.is_empty() && page_len > 0 {
+            true => Some(self.offset_y / page_len),
+            false => None,
+        };
+        self.clear();
+
+        if page_len == 0 || self.matches <= page_len {
+            return;
         }
+        let pages = self.matches.div_ceil(page_len);
+        // The pages of other matches are all picked again.
+        if self.page_bag_of != (self.matches, page_len) {
+            self.page_bag.clear();
+            self.page_bag_of = (self.matches, page_len);
+        }
+
+        let page = loop {
+            if self.page_bag.is_empty() {
+                self.page_bag = (0..pages).filter(|&page| Some(page) != current).collect();
+                utils::shuffle(&mut self.page_bag);
+            }
+            match self.page_bag.pop() {
+                Some(page) if Some(page) != current => break page,
+                _ => continue,
+            }
+        };
+        self.offset_y = page * page_len;
+        self.selected = self.offset_y;
+    }
+
+    // Moves the cursor to the start of the previous word.
+    fn move_word_left(&mut self) {
+        self.cursor = self.query[..self.cursor]
+            .split_word_bound_indices()
+            .filter(|(_, word)| !word.trim().is_empty())
+            .last()
+            .map_or(0, |(i, _)| i);
+    }
+
+    // Moves the cursor to the end of the next word.
+    fn move_word_right(&mut self) {
+        self.cursor = self.query[self.cursor..]
+            .split_word_bound_indices()
+            .find(|(_, word)| !word.trim().is_empty())
+            .map_or(self.query.len(), |(i, word)| self.cursor + i + word.len());
+    }
+
+    // The part of the query that fits in the text input area.
+    fn query_window(&self) -> QueryWindow {
+        query_window(
+            &self.query,
+            self.cursor,
+            self.query_offset,
+            self.size.x.saturating_sub(2),
+        )
+    }
+
+    // Scrolls the query so that the cursor is visible.
+    fn scroll_query(&mut self) {
+        self.query_offset = self.query_window().start;
     }
 
     // Moves the cursor left one column.
@@ -170,6 +890,89 @@ impl FuzzyView {
         }
     }
 
+    // Deletes the word to the left of the cursor, and the spaces between it
+    // and the cursor, as 'Ctrl + w' does in a shell. The deleted text can be
+    // yanked back.
+    fn delete_word_left(&mut self) {
+        let mut start = self.cursor;
+        let mut graphemes = self.query[..self.cursor].graphemes(true).rev().peekable();
+
+        while let Some(g) = graphemes.next_if(|g| g.trim().is_empty()) {
+            start -= g.len();
+        }
+        while let Some(g) = graphemes.next_if(|g| !g.trim().is_empty()) {
+            start -= g.len();
+        }
+
+        if start < self.cursor {
+            self.killed = self.query.drain(start..self.cursor).collect();
+            self.cursor = start;
+            self.query_changed();
+        }
+    }
+
+    // Deletes the query from the cursor to the end, so that it can be yanked
+    // back.
+    fn kill_to_end(&mut self) {
+        if self.cursor < self.query.len() {
+            self.killed = self.query.split_off(self.cursor);
+            self.query_changed();
+        }
+    }
+
+    // Inserts the text that was last deleted at the cursor.
+    fn yank(&mut self) {
+        self.paste(&self.killed.to_owned());
+    }
+
+    // Inserts `text` at the cursor, without any line breaks or other control
+    // characters, and matches the query once rather than for each character.
+    pub fn paste(&mut self, text: &str) {
+        let text: String = text.chars().filter(|c| !c.is_control()).collect();
+        if !text.is_empty() {
+            self.query.insert_str(self.cursor, &text);
+            self.cursor += text.len();
+            self.query_changed();
+        }
+    }
+
+    // Replaces the query with the one submitted before the recalled one, or
+    // with the last one submitted, keeping what was being typed.
+    fn recall_older(&mut self) {
+        let index = match self.recalled {
+            Some(0) => return,
+            Some(i) => i - 1,
+            None if self.history.is_empty() => return,
+            None => {
+                self.draft = self.query.to_owned();
+                self.history.len() - 1
+            }
+        };
+        self.recall(Some(index));
+    }
+
+    // Replaces the query with the one submitted after the recalled one, or
+    // with what was being typed after the last one.
+    fn recall_newer(&mut self) {
+        match self.recalled {
+            Some(i) if i + 1 < self.history.len() => self.recall(Some(i + 1)),
+            Some(_) => self.recall(None),
+            None => (),
+        }
+    }
+
+    // Sets the query to the history entry at `index`, or back to the draft,
+    // with the cursor at the end.
+    fn recall(&mut self, index: Option<usize>) {
+        self.recalled = index;
+        self.query = match index {
+            Some(i) => self.history[i].to_owned(),
+            None => std::mem::take(&mut self.draft),
+        };
+        self.cursor = self.query.len();
+        self.query_changed();
+    }
+
     // Deletes the character to the right of the cursor.
     fn delete(&mut self) {
         if self.cursor == self.query.len() {
@@ -181,16 +984,33 @@ impl FuzzyView {
                 .unwrap()
                 .len();
             for _ in self.query.drain(self.cursor..self.cursor + len) {}
-            self.update_list(&self.query.clone());
+            self.query_changed();
         }
     }
 
+    // Sets when items that are too long for one row are wrapped.
+    pub fn set_soft_wrap(&mut self, soft_wrap: SoftWrap) {
+        self.soft_wrap = soft_wrap;
+        self.scroll_to_selected();
+    }
+
+    // Sets whether or not moving past either end of the matches continues
+    // from the other end.
+    pub fn set_wrap_around(&mut self, wrap_around: bool) {
+        self.wrap_around = wrap_around;
+    }
+
+    // Whether or not anything has been typed into the query.
+    pub fn has_query(&self) -> bool {
+        !self.query.is_empty()
+    }
+
     // Inserts a character from user input to the right of the cursor.
     fn insert(&mut self, ch: char) {
         self.query.insert(self.cursor, ch);
         let shift = ch.len_utf8();
         self.cursor += shift;
-        self.update_list(&self.query.to_owned());
+        self.query_changed();
     }
 
     // Removes the current fuzzy query.
@@ -200,68 +1020,698 @@ impl FuzzyView {
         self.update_list("");
     }
 
+    // Matches the query after it has been edited, on the worker if there is
+    // one. The previous matches are shown until the worker has matched it.
+    fn query_changed(&mut self) {
+        let (pattern, _) = query::split_extensions(&self.query);
+        match &self.worker {
+            Some(worker) if !pattern.is_empty() => {
+                let query = Query {
+                    pattern: pattern.to_owned(),
+                    case: self.case,
+                    mode: self.match_mode,
+                    root: self.match_root().map(Path::to_path_buf),
+                    threshold: self.threshold,
+                };
+                self.pending = Some(worker.send(query));
+            }
+            _ => self.update_list(&self.query.to_owned()),
+        }
+    }
+
+    // Applies the matches of the worker, if they are of the latest query.
+    pub fn apply_matches(&mut self, matches: &Matches) {
+        if self.pending != Some(matches.generation) {
+            return;
+        }
+        self.pending = None;
+        self.narrowing = None;
+        self.regex_error = matches.regex_error.to_owned();
+
+        for item in self.items.iter_mut() {
+            match matches.matched.get(&item.path) {
+                Some((weight, indices)) => {
+                    item.weight = *weight;
+                    item.indices = indices.to_owned();
+                }
+                None => {
+                    item.weight = 0;
+                    item.indices.clear();
+                }
+            }
+        }
+        self.matches = matches.matched.len();
+        self.hide_items();
+        self.filter_favorites();
+        let (_, extensions) = query::split_extensions(&self.query);
+        self.filter_extensions(&extensions);
+        self.sort();
+        self.selected = 0;
+        self.offset_y = 0;
+    }
+
     // Runs the fuzzy matcher on the query.
     fn update_list(&mut self, pattern: &str) {
-        if self.query.is_empty() {
-            for (i, _) in self.items.clone().into_iter().enumerate() {
-                self.items[i].weight = 1;
-                self.items[i].indices.clear();
+        // The query is matched here instead, so the worker's matches are stale.
+        self.pending = None;
+        self.regex_error = None;
+        let (pattern, extensions) = query::split_extensions(pattern);
+        // Everything matches when there's nothing to match but the extensions.
+        if query::split_extensions(&self.query).0.is_empty() {
+            for item in self.items.iter_mut() {
+                item.weight = 1;
+                item.indices.clear();
             }
             self.matches = self.items.len();
+            // Move the hidden items after the matches, and order the rest.
+            self.hide_items();
+            self.filter_favorites();
+            self.filter_extensions(&extensions);
+            self.sort();
             self.selected = 0;
             self.offset_y = 0;
+            self.narrowing = self.narrowing_of("", &extensions);
             return;
         }
 
-        self.matches = self.fuzzy_match(pattern);
-        self.sort();
+        // The matches of a longer query are among the current matches, which
+        // come before the rest, so only they are matched again.
+        let candidates = match self.narrows_to(pattern, &extensions) {
+            true => self.matches,
+            false => self.items.len(),
+        };
+        let items = &mut self.items[..candidates];
+        // As `match_root`, borrowing only the root, since the items change.
+        let root = self.match_paths.then_some(self.root.as_path());
+        self.matches = match self.match_mode {
+            MatchMode::Fuzzy => {
+                let terms = query::parse(pattern);
+                fuzzy_match(
+                    items,
+                    &terms,
+                    &self.matcher,
+                    self.case,
+                    root,
+                    self.threshold,
+                )
+            }
+            MatchMode::Prefix => prefix_match(items, pattern, self.case, root),
+            MatchMode::Regex => match self.case.regex(pattern) {
+                Ok(regex) => regex_match(items, &regex, root),
+                Err(e) => {
+                    // Nothing matches until the pattern is valid again.
+                    for item in items.iter_mut() {
+                        item.weight = 0;
+                        item.indices.clear();
+                    }
+                    self.regex_error = Some(e);
+                    0
+                }
+            },
+        };
+        self.hide_items();
+        self.filter_favorites();
+        self.filter_extensions(&extensions);
+        // The items after the candidates didn't match before, and still don't.
+        let sort_mode = self.sort_mode;
+        self.items[..candidates].sort_by(|a, b| sort_mode.compare(a, b));
         self.selected = 0;
         self.offset_y = 0;
+        self.narrowing = self.narrowing_of(pattern, &extensions);
     }
 
-    // Sort the items by `weight` in descending order.
-    fn sort(&mut self) {
-        self.items.sort_by(|a, b| b.weight.cmp(&a.weight))
+    // How the matches of `pattern`, filtered by `extensions`, are matched,
+    // unless a longer pattern may match items that it doesn't, as in regex
+    // mode.
+    fn narrowing_of(&self, pattern: &str, extensions: &[String]) -> Option<Narrowing> {
+        (self.match_mode != MatchMode::Regex).then(|| Narrowing {
+            pattern: pattern.to_string(),
+            extensions: extensions.to_vec(),
+            match_mode: self.match_mode,
+            case: self.case,
+            match_paths: self.match_paths,
+            showing_hidden: self.showing_hidden,
+            favorites_only: self.favorites_only,
+        })
     }
 
-    // Computes the weights for the items on fuzzy matching with the query.
-    fn fuzzy_match(&mut self, pattern: &str) -> usize {
-        let mut count = 0;
-        let matcher = Box::new(SkimMatcherV2::default());
-        for (i, item) in self.items.clone().into_iter().enumerate() {
-            if let Some((weight, indices)) = matcher.fuzzy_indices(&item.display, pattern) {
-                self.items[i].weight = weight;
-                self.items[i].indices = indices;
-                count += 1;
-            } else {
-                self.items[i].weight = 0;
-                self.items[i].indices.clear();
+    // Whether or not the matches of `pattern` are all among the current
+    // matches, as it extends the pattern that they were matched with, in the
+    // same way. Text after a '$' can turn a suffix term into another kind of
+    // term, which may match more, so the query is matched again then.
+    fn narrows_to(&self, pattern: &str, extensions: &[String]) -> bool {
+        self.narrowing.as_ref().is_some_and(|narrowing| {
+            pattern.len() > narrowing.pattern.len()
+                && pattern.starts_with(&narrowing.pattern)
+                && !narrowing.pattern.ends_with('$')
+                && self.narrowing_of(&narrowing.pattern, extensions).as_ref() == Some(narrowing)
+        })
+    }
+
+    // Removes the matches without an audio file of one of `extensions` from
+    // the matches, unless there are no extensions. The extensions of the
+    // audio files are found when the items are scanned.
+    fn filter_extensions(&mut self, extensions: &[String]) {
+        if extensions.is_empty() {
+            return;
+        }
+        for item in self.items.iter_mut().filter(|item| item.weight != 0) {
+            if !item.extensions.iter().any(|ext| extensions.contains(ext)) {
+                item.weight = 0;
+                item.indices.clear();
+                self.matches -= 1;
             }
         }
-        count
     }
 
-    // The number of matched items over total items.
-    fn count(&self) -> String {
-        format!("{}/{} ", self.matches, self.items.len())
+    // Removes the hidden items from the matches. Returns true if any were removed.
+    fn hide_items(&mut self) -> bool {
+        if self.showing_hidden || self.hidden.is_empty() {
+            return false;
+        }
+        let matches = self.matches;
+        for item in self.items.iter_mut().filter(|e| e.weight != 0) {
+            if fuzzy_data::is_hidden(&self.hidden, &item.path) {
+                item.weight = 0;
+                self.matches -= 1;
+            }
+        }
+        matches != self.matches
     }
 
-    // Handles a fuzzy match being selected.
-    fn on_select(&mut self) -> EventResult {
-        if self.items.is_empty() {
-            return EventResult::with_cb(|siv| {
+    // Removes the items that aren't favorites from the matches, when only
+    // the favorites are matched.
+    fn filter_favorites(&mut self) {
+        if !self.favorites_only {
+            return;
+        }
+        for item in self.items.iter_mut().filter(|e| e.weight != 0) {
+            if !self.favorites.contains(&item.path) {
+                item.weight = 0;
+                item.indices.clear();
+                self.matches -= 1;
+            }
+        }
+    }
+
+    // Stars the selected item, or unstars it if it is starred, and saves the
+    // favorites.
+    fn toggle_favorite(&mut self) -> EventResult {
+        let path = match self.items.get(self.selected) {
+            Some(item) if item.weight != 0 => item.path.to_owned(),
+            _ => return EventResult::Consumed(None),
+        };
+        match self.favorites.toggle(&path) {
+            true => self.show_toast("starred, Alt + B shows only the favorites"),
+            false => self.show_toast("unstarred"),
+        }
+        // An unstarred item is no longer matched.
+        if self.favorites_only {
+            self.update_list_keeping_selection();
+        }
+
+        let favorites = self.favorites.to_owned();
+        EventResult::with_cb(move |_| favorites.save())
+    }
+
+    // Toggles whether or not only the favorites are matched.
+    fn toggle_favorites_only(&mut self) -> EventResult {
+        self.favorites_only = !self.favorites_only;
+        if self.favorites_only && self.favorites.is_empty() {
+            self.show_toast("nothing is starred yet, Ctrl + b stars an item");
+        }
+        self.update_list(&self.query.to_owned());
+
+        let favorites_only = self.favorites_only;
+        EventResult::with_cb(move |siv| {
+            siv.with_user_data(|(_, _, _, data, _, _): &mut InnerType<SessionData>| {
+                data.favorites_only = favorites_only;
+            });
+        })
+    }
+
+    // Marks the selected album to be played with the other marked albums, or
+    // unmarks it if it is marked.
+    fn toggle_mark(&mut self) {
+        let item = match self.items.get_mut(self.selected) {
+            Some(item) if item.weight != 0 => item,
+            _ => return,
+        };
+        if !item.has_audio {
+            return self.show_toast("only albums can be marked");
+        }
+
+        item.selected = !item.selected;
+        match item.selected {
+            true => self.marked.push(item.path.to_owned()),
+            false => self.marked.retain(|path| path != &item.path),
+        }
+    }
+
+    // Unmarks all of the items.
+    fn clear_marks(&mut self) {
+        for item in self.items.iter_mut() {
+            item.selected = false;
+        }
+        self.marked.clear();
+    }
+
+    // Hides the selected item for the rest of the session, or shows it
+    // again if it is hidden.
+    fn toggle_hidden(&mut self) -> EventResult {
+        let path = match self.items.get(self.selected) {
+            Some(item) if item.weight != 0 => item.path.to_owned(),
+            _ => return EventResult::Consumed(None),
+        };
+
+        let is_hidden = match self.hidden.remove(&path) {
+            true => false,
+            false => {
+                self.hidden.insert(path.to_owned());
+                self.show_toast("hidden until tap exits, Alt + h shows hidden items");
+                true
+            }
+        };
+
+        self.update_list_keeping_selection();
+
+        EventResult::with_cb(move |siv| {
+            siv.with_user_data(|(_, _, _, data, _, _): &mut InnerType<SessionData>| {
+                match is_hidden {
+                    true => data.hidden.insert(path.to_owned()),
+                    false => data.hidden.remove(&path),
+                };
+            });
+        })
+    }
+
+    // Toggles whether or not the hidden items are matched.
+    fn toggle_showing_hidden(&mut self) -> EventResult {
+        self.showing_hidden = !self.showing_hidden;
+        self.update_list_keeping_selection();
+
+        let showing_hidden = self.showing_hidden;
+        EventResult::with_cb(move |siv| {
+            siv.with_user_data(|(_, _, _, data, _, _): &mut InnerType<SessionData>| {
+                data.showing_hidden = showing_hidden;
+            });
+        })
+    }
+
+    // Switches between matching the paths and the display names.
+    fn toggle_match_paths(&mut self) -> EventResult {
+        self.match_paths = !self.match_paths;
+        self.update_list(&self.query.to_owned());
+        self.show_toast(match self.match_paths {
+            true => "matching paths",
+            false => "matching names",
+        });
+
+        let match_paths = self.match_paths;
+        EventResult::with_cb(move |siv| {
+            siv.with_user_data(|(_, _, _, data, _, _): &mut InnerType<SessionData>| {
+                data.match_paths = Some(match_paths);
+            });
+        })
+    }
+
+    // Sets how the case of the letters is matched, making the matcher for it.
+    fn set_case(&mut self, case: Case) {
+        if case != self.case {
+            self.case = case;
+            self.matcher = case.matcher();
+        }
+    }
+
+    // Switches to the next way of matching the case of the letters.
+    fn cycle_case(&mut self) -> EventResult {
+        self.set_case(self.case.next());
+        self.update_list(&self.query.to_owned());
+        self.show_toast(self.case.name());
+
+        let case = self.case;
+        EventResult::with_cb(move |siv| {
+            siv.with_user_data(|(_, _, _, data, _, _): &mut InnerType<SessionData>| {
+                data.case = case;
+            });
+        })
+    }
+
+    // Switches to the next way of matching the query: fuzzy, as a regex or
+    // as a prefix.
+    fn cycle_match_mode(&mut self) -> EventResult {
+        self.match_mode = self.match_mode.next();
+        self.update_list(&self.query.to_owned());
+        self.show_toast(&format!(
+            "{} {}",
+            self.match_mode.symbol(),
+            self.match_mode.name()
+        ));
+
+        let match_mode = self.match_mode;
+        EventResult::with_cb(move |siv| {
+            siv.with_user_data(|(_, _, _, data, _, _): &mut InnerType<SessionData>| {
+                data.match_mode = match_mode;
+            });
+        })
+    }
+
+    // Raises or lowers the weight that the fuzzy matches need at least by
+    // `step`, down to 0, which keeps every match.
+    fn change_threshold(&mut self, step: i64) -> EventResult {
+        let threshold = (self.threshold + step).max(0);
+        if threshold == self.threshold {
+            return EventResult::Consumed(None);
+        }
+        self.threshold = threshold;
+        // The matches that were too weak before may match now.
+        self.narrowing = None;
+        self.query_changed();
+
+        EventResult::with_cb(move |siv| {
+            siv.with_user_data(|(_, _, _, data, _, _): &mut InnerType<SessionData>| {
+                data.threshold = Some(threshold);
+            });
+        })
+    }
+
+    // Shows how the selected match, and the top match if it isn't selected,
+    // are ranked.
+    fn explain_ranking(&mut self) -> EventResult {
+        if self.matches == 0 {
+            self.show_toast("nothing to explain");
+            return EventResult::Consumed(None);
+        }
+        let mut sections = vec![self.explain_item("selected", self.selected)];
+        if self.selected != 0 {
+            sections.push(self.explain_item("top", 0));
+        }
+        let text = sections.join("\n\n");
+
+        EventResult::with_cb(move |siv| {
+            let dialog = Dialog::text(text.to_owned())
+                .title("Ranking")
+                .dismiss_button("Close");
+            layers::show(siv, Role::Dialog, dialog);
+        })
+    }
+
+    // How the match at `index`, labelled with `label`, is ranked: the weight
+    // of each term of the query, the chars they matched and what orders the
+    // items with equal weights.
+    fn explain_item(&self, label: &str, index: usize) -> String {
+        let item = &self.items[index];
+        let text = item.text(self.match_root());
+        let mut lines = vec![format!(
+            "{label}: {} of {}, '{}'",
+            index + 1,
+            self.matches,
+            item.display
+        )];
+
+        let (pattern, _) = query::split_extensions(&self.query);
+        let score = match (pattern.is_empty(), self.match_mode) {
+            (true, _) => None,
+            (false, MatchMode::Fuzzy) => {
+                let terms = query::parse(pattern);
+                explain(item, &terms, &self.matcher, self.case, self.match_root())
+            }
+            // The regex and prefix matches all have the same weight.
+            (false, _) => Some(Score {
+                terms: vec![],
+                weight: item.weight,
+                indices: item.indices.to_owned(),
+            }),
+        };
+        match score {
+            Some(score) => {
+                for (term, weight) in score.terms {
+                    lines.push(format!("  {: <14}{: >6}", term.typed(), weight));
+                }
+                lines.push(format!("  {: <14}{: >6}", "weight", score.weight));
+                lines.push(format!(
+                    "  matched       {}",
+                    bracketed(&text, &score.indices)
+                ));
+            }
+            None => lines.push(format!("  {: <14}{: >6}", "weight", item.weight)),
+        }
+
+        let order = match self.sort_mode {
+            SortMode::Name => String::from("the name"),
+            SortMode::Modified => format!("modified at {}", item.modified),
+            SortMode::Found => format!("found {}", ordinal(item.original_index + 1)),
+        };
+        lines.push(format!("  then by       {order}"));
+        if self.marked.contains(&item.path) {
+            lines.push(String::from("  marked"));
+        }
+        lines.join("\n")
+    }
+
+    // Switches to the next way of ordering the items with equal weights.
+    fn cycle_sort_mode(&mut self) -> EventResult {
+        self.sort_mode = self.sort_mode.next();
+        self.update_list(&self.query.to_owned());
+        self.show_toast(self.sort_mode.name());
+
+        let sort_mode = self.sort_mode;
+        EventResult::with_cb(move |siv| {
+            siv.with_user_data(|(_, _, _, data, _, _): &mut InnerType<SessionData>| {
+                data.sort_mode = sort_mode;
+            });
+        })
+    }
+
+    // Shows or hides the tracks of the selected album beside the list.
+    fn toggle_preview(&mut self) -> EventResult {
+        self.preview = !self.preview;
+        if self.preview && self.size.x < MIN_PREVIEW_WIDTH {
+            self.show_toast("too narrow to preview");
+        }
+
+        let preview = self.preview;
+        EventResult::with_cb(move |siv| {
+            siv.with_user_data(|(_, _, _, data, _, _): &mut InnerType<SessionData>| {
+                data.preview = preview;
+            });
+        })
+    }
+
+    // Shows or hides the size and the date of each item.
+    fn toggle_details(&mut self) -> EventResult {
+        self.details = !self.details;
+
+        let details = self.details;
+        EventResult::with_cb(move |siv| {
+            siv.with_user_data(|(_, _, _, data, _, _): &mut InnerType<SessionData>| {
+                data.details = details;
+            });
+        })
+    }
+
+    // Shows or hides the position of each match.
+    fn toggle_indices(&mut self) -> EventResult {
+        self.show_indices = !self.show_indices;
+
+        let show_indices = self.show_indices;
+        EventResult::with_cb(move |siv| {
+            siv.with_user_data(|(_, _, _, data, _, _): &mut InnerType<SessionData>| {
+                data.show_indices = show_indices;
+            });
+        })
+    }
+
+    // The count drawn at the end of the last row of `item`, followed by its
+    // size and date when the details are shown, if they leave enough of the
+    // `width` columns for the name.
+    fn label(&self, item: &FuzzyItem, width: usize) -> String {
+        let count = count_label(item);
+        if !self.details {
+            return count;
+        }
+        let label = match count.is_empty() {
+            true => details_label(item),
+            false => format!("{count} {}", details_label(item)),
+        };
+        match label.width() + MIN_NAME_WIDTH + 5 <= width {
+            true => label,
+            false => count,
+        }
+    }
+
+    // Draws the tracks of the selected album on `p`, beside the list, or that
+    // the selected item is a directory.
+    fn draw_preview(&self, p: &Printer) {
+        p.with_color(theme::progress(), |p| p.print_vline((0, 0), p.size.y, "│"));
+
+        let item = match self.items.get(self.selected) {
+            Some(item) if item.weight != 0 => item,
+            _ => return,
+        };
+        if item.child_count > 0 {
+            p.with_color(theme::prompt(), |p| p.print((2, 0), "directory"));
+            return;
+        }
+
+        let tracks = self.tracks.get(&item.path).map_or(&[][..], Vec::as_slice);
+        let rows = p.size.y;
+        for (row, track) in tracks.iter().take(rows).enumerate() {
+            if row + 1 == rows && tracks.len() > rows {
+                let more = format!("… {} more", tracks.len() - row);
+                p.with_color(theme::prompt(), |p| p.print((2, row), &more));
+            } else {
+                p.with_color(theme::fg(), |p| p.print((2, row), track));
+            }
+        }
+    }
+
+    // Runs the fuzzy matcher, keeping the selection on the same row.
+    fn update_list_keeping_selection(&mut self) {
+        let selected = self.selected;
+        self.update_list(&self.query.to_owned());
+        self.selected = selected.min(self.matches.saturating_sub(1));
+        self.scroll_to_selected();
+    }
+
+    // Shows `msg` above the query for a few seconds.
+    pub fn show_toast(&mut self, msg: &str) {
+        self.toast = Some((msg.to_string(), Instant::now()));
+    }
+
+    // The message to show above the query, if any.
+    fn toast(&self) -> Option<&str> {
+        match &self.toast {
+            Some((msg, shown)) if shown.elapsed() < TOAST_TIME => Some(msg.as_str()),
+            _ => None,
+        }
+    }
+
+    // Shows the error `msg` above the query, in place of the toast.
+    pub fn show_error(&mut self, msg: &str) {
+        self.error = Some((msg.to_string(), Instant::now()));
+    }
+
+    // The error to show above the query, if any.
+    fn error(&self) -> Option<&str> {
+        match &self.error {
+            Some((msg, shown)) if shown.elapsed() < layers::ERROR_TIME => Some(msg.as_str()),
+            _ => None,
+        }
+    }
+
+    // Sort the items by `weight` in descending order, ordering the items with
+    // equal weights by the sort mode.
+    fn sort(&mut self) {
+        let sort_mode = self.sort_mode;
+        self.items.sort_by(|a, b| sort_mode.compare(a, b))
+    }
+
+    // The number of matched items over total items, the number of hidden
+    // items, and how the case is matched and the items are sorted, unless
+    // these are the defaults, and the number of marked items, if any.
+    fn count(&self) -> String {
+        let mut modes = vec![];
+        if self.case != Case::Smart {
+            modes.push(self.case.name().to_owned());
+        }
+        if self.sort_mode != SortMode::Name {
+            modes.push(self.sort_mode.name().to_owned());
+        }
+        if !self.marked.is_empty() {
+            modes.push(format!("{} marked", self.marked.len()));
+        }
+        if self.favorites_only {
+            modes.push(String::from("favorites"));
+        }
+        let (_, extensions) = query::split_extensions(&self.query);
+        if !extensions.is_empty() {
+            let extensions: Vec<String> = extensions.iter().map(|ext| format!(".{ext}")).collect();
+            modes.push(format!("{} only", extensions.join(", ")));
+        }
+        let count = match (&self.regex_error, modes.is_empty()) {
+            (Some(e), _) => format!("{e} "),
+            (None, true) => self.match_count(),
+            (None, false) => format!("{}[{}] ", self.match_count(), modes.join(", ")),
+        };
+        let count = match self.match_mode {
+            MatchMode::Fuzzy => count,
+            mode => format!("{} {count}", mode.symbol()),
+        };
+        // The query is still being matched, or the directory scanned.
+        match (self.scanning, self.reloading, self.pending) {
+            (Some(_), _, _) => format!("Scanning… {} files ", self.items.len()),
+            (None, true, _) => format!("reloading… {count}"),
+            (None, false, Some(_)) => format!("{}{} ", count.trim_end(), self.spinner.frame()),
+            (None, false, None) => count,
+        }
+    }
+
+    // The number of matched items over total items, the threshold of the
+    // fuzzy matches and the number of hidden items.
+    fn match_count(&self) -> String {
+        let threshold = match self.threshold > 0 && self.match_mode == MatchMode::Fuzzy {
+            true => format!(" (threshold {})", self.threshold),
+            false => String::new(),
+        };
+        let hidden = match self.hidden.len() {
+            0 => String::new(),
+            hidden if self.showing_hidden => format!(" (showing {hidden} hidden)"),
+            hidden => format!(" ({hidden} hidden)"),
+        };
+        format!("{}/{}{threshold}{hidden} ", self.matches, self.items.len())
+    }
+
+    // Handles a fuzzy match being selected.
+    fn on_select(&mut self) -> EventResult {
+        // Select from the matches of the query, rather than the previous ones.
+        if self.pending.is_some() {
+            self.update_list(&self.query.to_owned());
+        }
+        if self.items.is_empty() {
+            return EventResult::with_cb(|siv| {
                 let err = anyhow::Error::msg("Nothing to select!");
-                ErrorView::load(siv, err)
+                ErrorView::notify(siv, err)
             });
         }
 
         let item = self.items[self.selected].to_owned();
+        let query = self.query.to_owned();
+        let marked = self.marked.to_owned();
+        let depth_search = self.depth_search;
+        let hidden_dirs = self.hidden_dirs;
+        let current = self.current_dir();
+        let search = LastSearch {
+            query: self.query.to_owned(),
+            selected: item.path.to_owned(),
+            offset_y: self.offset_y,
+        };
 
         EventResult::with_cb(move |siv| {
-            if item.child_count == 0 {
+            siv.with_user_data(|(_, _, _, data, _, _): &mut InnerType<SessionData>| {
+                data.remember(&query);
+                if let Some(current) = &current {
+                    data.searches.insert(current.to_owned(), search.to_owned());
+                }
+            });
+
+            if !marked.is_empty() {
+                select_albums(marked.to_owned(), siv);
+            } else if item.child_count == 0 {
                 select_player(item.to_owned(), siv);
             } else {
-                let items = create_items(&item.path).expect("should always exist");
+                // The album may be on a drive that has been removed since.
+                let items = match item.path.is_dir() {
+                    true => create_items(&item.path, hidden_dirs),
+                    false => Err(anyhow!(
+                        "'{}' no longer exists",
+                        utils::display_path(&item.path)
+                    )),
+                };
+                let items = match items {
+                    Ok(items) => items,
+                    Err(e) => return ErrorView::notify(siv, e),
+                };
 
                 if items.len() == 1 {
                     let item = items.first().unwrap();
@@ -271,33 +1721,143 @@ impl FuzzyView {
                     }
                 }
 
-                FuzzyView::load(items, None, siv);
+                if let Some(current) = &current {
+                    siv.with_user_data(|(_, _, _, data, _, _): &mut InnerType<SessionData>| {
+                        data.visit(current, &item.path)
+                    });
+                }
+                FuzzyView::browse(items, depth_search, None, siv);
             }
         })
     }
 
+    // Restores the query of the last search that something was selected in,
+    // with the item that was selected, if it still matches, and the scroll
+    // position.
+    fn restore(&mut self, search: &LastSearch) {
+        self.query = search.query.to_owned();
+        self.cursor = self.query.len();
+        self.update_list(&self.query.to_owned());
+
+        if let Some(index) = self
+            .items
+            .iter()
+            .position(|item| item.path.eq(&search.selected) && item.weight != 0)
+        {
+            self.selected = index;
+            self.offset_y = search.offset_y.min(index);
+        }
+    }
+
+    // Clears the marks when the fuzzy-finder is escaped.
+    fn cancel(&mut self) -> EventResult {
+        self.clear_marks();
+        on_cancel()
+    }
+
+    // The row of the scrollbar under the mouse, counted up from the bottom
+    // of the list, when it is drawn.
+    fn scrollbar_under_mouse(&self, offset: XY<usize>, position: XY<usize>) -> Option<usize> {
+        let x = position.x.checked_sub(offset.x)?;
+        let y = position.y.checked_sub(offset.y + 1)?;
+        let list_rows = self.list_rows();
+        scrollbar_thumb(list_rows, self.matches, self.offset_y)?;
+        if x + 1 != self.size.x || y == 0 || y > list_rows {
+            return None;
+        }
+        Some(list_rows - y)
+    }
+
+    // Scrolls the list so that the scrollbar's thumb starts at `row`.
+    fn scroll_to_row(&mut self, row: usize) {
+        let offset_y = row * self.matches / self.list_rows();
+        self.scroll_to(offset_y);
+    }
+
+    // Scrolls the list to `offset_y`, keeping the selection on the list.
+    fn scroll_to(&mut self, offset_y: usize) {
+        self.offset_y = std::cmp::min(offset_y, self.last_offset());
+        let visible = std::cmp::max(self.visible_items(), 1);
+        self.selected = self
+            .selected
+            .clamp(self.offset_y, self.offset_y + visible - 1)
+            .min(self.matches.saturating_sub(1));
+    }
+
     // Handles a selection from mouse input.
-    fn mouse_select(&mut self, position: XY<usize>) -> EventResult {
-        if position.y < 1 || position.y > self.available_y + 1 {
+    fn mouse_select(&mut self, offset: XY<usize>, position: XY<usize>) -> EventResult {
+        if let Some(row) = self.scrollbar_under_mouse(offset, position) {
+            self.click = None;
+            self.drag = Some(Drag::Scrollbar);
+            self.scroll_to_row(row);
             return EventResult::Consumed(None);
         }
+        let next_selected = match self.item_under_mouse(offset, position) {
+            Some(index) => index,
+            None => return EventResult::Consumed(None),
+        };
+        self.drag = Some(Drag::List {
+            row: position.y,
+            offset_y: self.offset_y,
+        });
 
-        let next_selected = self.available_y + 1 + self.offset_y - position.y;
-
-        if next_selected == self.selected {
+        // The second click on an item plays it, unless the list has moved
+        // under the mouse since the first.
+        let click = (next_selected, self.offset_y);
+        if next_selected == self.selected && self.click == Some(click) {
+            self.click = None;
+            self.drag = None;
             return self.on_select();
-        } else {
-            self.selected = next_selected;
-            EventResult::Consumed(None)
+        }
+        self.selected = next_selected;
+        self.click = Some(click);
+        EventResult::Consumed(None)
+    }
+
+    // Scrolls the list with the mouse held at `position`, so that the rows
+    // or the scrollbar's thumb follow it.
+    fn mouse_drag(&mut self, offset: XY<usize>, position: XY<usize>) {
+        match self.drag {
+            // The list starts at the bottom, so dragging up shows the later
+            // items.
+            Some(Drag::List { row, offset_y }) => {
+                self.scroll_to((offset_y + row).saturating_sub(position.y))
+            }
+            Some(Drag::Scrollbar) => {
+                // The thumb follows the mouse up and down, wherever it is
+                // across the view.
+                let y = position.y.saturating_sub(offset.y + 1);
+                let list_rows = self.list_rows();
+                let row = list_rows.saturating_sub(y.clamp(1, list_rows));
+                self.scroll_to_row(row);
+            }
+            None => (),
         }
     }
 
-    // Loads a fuzzy view for the parent of the current directory.
+    // Loads a fuzzy view for the parent of the current directory, or the
+    // depth search that the current directory was selected in.
     fn parent(&self) -> EventResult {
         let mut parent = match self.items.first() {
             Some(parent) => parent.path.to_owned(),
             None => return EventResult::Ignored,
         };
+        let depth_search = self.depth_search;
+        let hidden_dirs = self.hidden_dirs;
+        let current = self.current_dir().unwrap_or_default();
+
+        if let Some(depth) = self.parent_search() {
+            let root = self.root.to_owned();
+            return EventResult::with_cb(move |siv| {
+                let items = siv
+                    .with_user_data(|(_, _, _, data, _, _): &mut InnerType<SessionData>| {
+                        data.visit(&current, &root);
+                        super::depth_items(depth, &data.items)
+                    })
+                    .unwrap_or_default();
+                FuzzyView::browse(items, depth_search, Some(&current), siv);
+            });
+        }
 
         let root = args::search_root();
         if parent != root {
@@ -308,100 +1868,463 @@ impl FuzzyView {
         }
 
         return EventResult::with_cb(move |siv| {
-            if let Ok(items) = create_items(&parent) {
-                FuzzyView::load(items, None, siv);
+            if let Ok(items) = create_items(&parent, hidden_dirs) {
+                siv.with_user_data(|(_, _, _, data, _, _): &mut InnerType<SessionData>| {
+                    data.visit(&current, &parent)
+                });
+                FuzzyView::browse(items, depth_search, None, siv);
             }
         });
     }
 
-    // Opens the current selected item in the preferred file manager.
-    fn open_file_manager(&self) {
-        if self.selected < self.items.len() {
-            let path = self.items[self.selected].path.to_owned();
-            _ = utils::open_file_manager(path);
-        }
+    // Goes back to the directory browsed before the current one, or forward
+    // to the one browsed after it, selecting the folder that was browsed
+    // from, if it is there.
+    fn browse_history(&self, forward: bool) -> EventResult {
+        let current = match self.current_dir() {
+            Some(current) => current,
+            None => return EventResult::Consumed(None),
+        };
+        let depth_search = self.depth_search;
+        let hidden_dirs = self.hidden_dirs;
+
+        EventResult::with_cb(move |siv| {
+            let dir = siv
+                .with_user_data(
+                    |(_, _, _, data, _, _): &mut InnerType<SessionData>| match forward {
+                        true => data.forward(&current),
+                        false => data.back(&current),
+                    },
+                )
+                .flatten();
+            let dir = match dir {
+                Some(dir) => dir,
+                None => return,
+            };
+            match create_items(&dir, hidden_dirs) {
+                Ok(items) => FuzzyView::browse(items, depth_search, Some(&current), siv),
+                Err(e) => ErrorView::notify(siv, e),
+            }
+        })
     }
-}
 
-impl View for FuzzyView {
-    fn layout(&mut self, size: cursive::Vec2) {
-        self.size = size;
-        self.available_y = if size.y > 2 { size.y - 3 } else { 0 };
+    // The depth of the depth search that the current directory is a folder
+    // of, if this view was browsed from one.
+    fn parent_search(&self) -> Option<usize> {
+        let depth = self.depth_search?;
+        let dir = self.current_dir()?;
+        let below_root = dir.strip_prefix(&self.root).ok()?.components().count();
+        (below_root == depth).then_some(depth)
     }
 
-    fn draw(&self, p: &Printer) {
-        // The size of the screen we can draw on.
-        let (w, h) = (p.size.x, p.size.y);
+    // The selected match, if there is one.
+    pub fn selected_item(&self) -> Option<&FuzzyItem> {
+        self.items
+            .get(self.selected)
+            .filter(|item| item.weight != 0)
+    }
 
-        if h > 3 {
-            // The first row of the list.
-            let start_row = h - 3;
-            // The number of visible rows.
-            let visible = std::cmp::min(self.matches - self.offset_y, h - 2);
+    // The directory that the items were created from.
+    fn current_dir(&self) -> Option<PathBuf> {
+        items_dir(&self.items)
+    }
 
-            for y in 0..visible {
-                let index = y + self.offset_y;
-                // The items are drawn in ascending order, starting on third row from bottom.
-                let row = start_row - y;
-                // Only draw items that have matches.
-                if self.items[index].weight != 0 {
-                    // Set the color depending on whether row is currently selected or not.
-                    let (primary, highlight) = if row + self.selected == start_row + self.offset_y {
-                        // Draw the symbol to show the currently selected item.
-                        p.with_color(theme::header2(), |p| p.print((0, row), ">"));
-                        // The colors for the currently selected row.
-                        (theme::hl(), theme::header1())
-                    } else {
-                        // The colors for the not selected row.
-                        (theme::fg(), theme::hl())
-                    };
-                    // Draw the item's display name.
-                    p.with_color(primary, |p| {
-                        p.print((2, row), self.items[index].display.as_str())
-                    });
-                    // Draw the fuzzy matched indices in a highlighting color.
-                    for x in &self.items[index].indices {
-                        let mut chars = self.items[index].display.chars();
-                        p.with_effect(Effect::Bold, |p| {
-                            p.with_color(highlight, |p| {
-                                p.print(
-                                    (x + 2, row),
-                                    chars.nth(*x).unwrap_or_default().to_string().as_str(),
-                                )
-                            });
-                        });
-                    }
-                }
-            }
+    // Scans the current directory again on another thread, for the files
+    // that have been added or removed since the items were created.
+    fn reload(&mut self) -> EventResult {
+        let dir = match self.current_dir() {
+            Some(dir) => dir,
+            None => return EventResult::Consumed(None),
+        };
+        self.reloading = true;
+        let hidden_dirs = self.hidden_dirs;
 
-            // Draw the page count.
-            p.with_color(theme::prompt(), |p| {
-                let page = self.selected / start_row;
-                let pages = self.matches / start_row;
-                let digits = page.checked_ilog10().unwrap_or(0) as usize
-                    + pages.checked_ilog10().unwrap_or(0) as usize
-                    + 2;
-                let column = self.size.x - digits - 2;
-                p.print((column, 0), format!(" {}/{}", page, pages).as_str());
+        EventResult::with_cb(move |siv| {
+            let dir = dir.to_owned();
+            let cb_sink = siv.cb_sink().clone();
+            thread::spawn(move || {
+                let items = create_items(&dir, hidden_dirs);
+                let apply = move |siv: &mut Cursive| {
+                    siv.call_on_name(Self::NAME, |fuzzy: &mut FuzzyView| fuzzy.reloaded(items));
+                };
+                _ = cb_sink.send(Box::new(apply));
             });
-        }
+        })
+    }
 
-        if h > 1 {
-            // The last row we can draw on.
+    // Shows the items in two panes, with the audio files of the selected
+    // folder beside them.
+    fn two_panes(&self) -> EventResult {
+        let items = self.items.to_owned();
+        EventResult::with_cb(move |siv| TwoPaneView::load(items.to_owned(), siv))
+    }
+
+    // Switches whether hidden directories, such as '.stfolder', are scanned,
+    // and scans the current directory again with or without them.
+    fn toggle_hidden_dirs(&mut self) -> EventResult {
+        self.hidden_dirs = !self.hidden_dirs;
+        self.show_toast(match self.hidden_dirs {
+            true => "scanning hidden directories",
+            false => "skipping hidden directories",
+        });
+
+        let hidden_dirs = self.hidden_dirs;
+        let set_session = EventResult::with_cb(move |siv| {
+            siv.with_user_data(|(_, _, _, data, _, _): &mut InnerType<SessionData>| {
+                data.hidden_dirs = hidden_dirs;
+            });
+        });
+        set_session.and(self.reload())
+    }
+
+    // Replaces the items with those scanned again by `reload`, and matches
+    // the query with them from the first match.
+    fn reloaded(&mut self, items: Result<Vec<FuzzyItem>, anyhow::Error>) {
+        self.reloading = false;
+        let mut items = match items {
+            Ok(items) => items,
+            Err(e) => return self.show_error(&format!("can't reload the directory: {e}")),
+        };
+        for (index, item) in items.iter_mut().enumerate() {
+            item.original_index = index;
+        }
+        self.marked
+            .retain(|path| items.iter().any(|item| item.path.eq(path)));
+        self.items = items;
+        // The files of the albums may have changed too.
+        self.tracks.clear();
+        self.narrowing = None;
+        if let Some(worker) = &self.worker {
+            worker.set_items(self.items.to_owned());
+        }
+
+        self.update_list(&self.query.to_owned());
+        self.selected = 0;
+        self.offset_y = 0;
+    }
+
+    // Opens a dialog to create a subdirectory in the current directory.
+    fn create_dir(&self) -> EventResult {
+        match self.current_dir() {
+            Some(root) => {
+                EventResult::with_cb(move |siv| file_ops::create_dir_dialog(root.to_owned(), siv))
+            }
+            None => EventResult::Consumed(None),
+        }
+    }
+
+    // Opens a dialog to rename the selected directory or file.
+    fn rename_item(&self) -> EventResult {
+        match self.selected_item() {
+            Some(item) => {
+                let path = item.path.to_owned();
+                EventResult::with_cb(move |siv| file_ops::rename_dialog(path.to_owned(), siv))
+            }
+            None => EventResult::Consumed(None),
+        }
+    }
+
+    // Opens a dialog to delete the selected directory or file.
+    fn delete_item(&self) -> EventResult {
+        match self.selected_item() {
+            Some(item) => {
+                let path = item.path.to_owned();
+                EventResult::with_cb(move |siv| file_ops::delete_dialog(path.to_owned(), siv))
+            }
+            None => EventResult::Consumed(None),
+        }
+    }
+
+    // Copies the path of the selected item to the clipboard.
+    fn copy_path(&mut self) {
+        let path = match self.items.get(self.selected) {
+            Some(item) if item.weight != 0 => item.path.to_string_lossy().into_owned(),
+            _ => return,
+        };
+        match command::copy_to_clipboard(&path) {
+            Ok(_) => self.show_toast("copied the path"),
+            Err(e) => self.show_error(&format!("can't copy the path: {e}")),
+        }
+    }
+
+    // Saves the paths of the matches to 'results.txt' in the data directory,
+    // to use them outside of tap.
+    fn save_matches(&mut self) {
+        let file = match utils::app_dir(AppDir::Data) {
+            Ok(dir) => dir.join("results.txt"),
+            Err(e) => return self.show_error(&format!("can't save the matches: {e}")),
+        };
+        match self.write_matches(&file) {
+            Ok(1) => self.show_toast(&format!("saved 1 path to {}", utils::display_path(&file))),
+            Ok(count) => self.show_toast(&format!(
+                "saved {count} paths to {}",
+                utils::display_path(&file)
+            )),
+            Err(e) => self.show_error(&format!("can't save the matches: {e}")),
+        }
+    }
+
+    // Writes the paths of the matches to `file`, best first and one per line.
+    // Returns the number of paths.
+    fn write_matches(&self, file: &Path) -> io::Result<usize> {
+        let paths: Vec<String> = self
+            .items
+            .iter()
+            .filter(|item| item.weight != 0)
+            .map(|item| format!("{}\n", item.path.display()))
+            .collect();
+        utils::write_private(file, paths.concat().as_bytes())?;
+        Ok(paths.len())
+    }
+
+    // Opens the current selected item in the preferred file manager.
+    fn open_file_manager(&self) -> EventResult {
+        if self.selected < self.items.len() {
+            let path = self.items[self.selected].path.to_owned();
+            if let Err(err) = command::open_file_manager(path) {
+                let msg = err.to_string();
+                return EventResult::with_cb(move |siv| {
+                    ErrorView::notify(siv, anyhow!(msg.clone()))
+                });
+            }
+        }
+        EventResult::Consumed(None)
+    }
+}
+
+impl View for FuzzyView {
+    fn layout(&mut self, size: cursive::Vec2) {
+        self.size = size;
+        // The first row is the header, and the last two are the query and
+        // the border above it.
+        self.available_y = if size.y > 3 { size.y - 4 } else { 0 };
+        self.scroll_query();
+        // The view is laid out before each frame is drawn.
+        if self.pending.is_some() {
+            self.spinner.tick();
+        }
+
+        // Keep the selection visible, i.e. after a resize or a refresh.
+        self.scroll_to_selected();
+        self.load_preview();
+    }
+
+    fn draw(&self, p: &Printer) {
+        // The size of the screen we can draw on.
+        let (w, h) = (p.size.x, p.size.y);
+
+        // Draw nothing when the terminal is too small, such as after a zero
+        // size resize from a multiplexer.
+        if w < MIN_WIDTH || h == 0 {
+            return;
+        }
+        // The position of the scrollbar's thumb, when not all the matches fit.
+        let thumb = scrollbar_thumb(self.list_rows(), self.matches, self.offset_y);
+
+        if h > 3 {
+            // The first row of the list.
+            let start_row = h - 3;
+            // The column at which wrapped items continue on the next row.
+            let width = self.text_width();
+            // The columns that the list is drawn on, left of the preview and
+            // the scrollbar.
+            let list_w = std::cmp::min(self.list_width(), w - thumb.is_some() as usize);
+            let list = p.cropped((list_w, h));
+            // The number of rows drawn on so far.
+            let mut used = 0;
+
+            for index in self.offset_y..self.matches {
+                let rows = self.item_rows(index);
+                if used + rows > self.list_rows() {
+                    break;
+                }
+                // The items are drawn in ascending order, starting on third row from bottom.
+                // Wrapped items start on the upper of their two rows.
+                let row = start_row - used - (rows - 1);
+                used += rows;
+
+                let item = &self.items[index];
+                // Only draw items that have matches.
+                if item.weight != 0 {
+                    // Set the color depending on whether row is currently selected or not.
+                    let (primary, highlight) = if index == self.selected {
+                        // Draw the symbol to show the currently selected item.
+                        p.with_color(theme::header2(), |p| p.print((0, row), ">"));
+                        // The colors for the currently selected row.
+                        (theme::hl(), theme::header1())
+                    } else if item.child_count > 0 {
+                        // The colors for a directory of directories.
+                        (theme::header2(), theme::hl())
+                    } else {
+                        // The colors for the not selected row.
+                        (theme::fg(), theme::hl())
+                    };
+                    // Draw the symbol to show that the item is marked, or
+                    // else that it is a favorite.
+                    if item.selected {
+                        p.with_color(theme::header2(), |p| p.print((1, row), "+"));
+                    } else if self.favorites.contains(&item.path) {
+                        p.with_color(theme::hl(), |p| p.print((1, row), "*"));
+                    }
+                    // Draw the position of the match, counting from 1.
+                    let indent = self.index_width();
+                    if indent > 0 {
+                        let index = format!("{:>1$}", index + 1, indent - 1);
+                        p.with_color(theme::progress(), |p| p.print((2, row), &index));
+                    }
+                    // The display name, or the path when matching paths.
+                    let text = item.text(self.match_root());
+                    // The byte index at which the text is wrapped.
+                    let split = match rows {
+                        2 => wrap_index(&text, width),
+                        _ => text.len(),
+                    };
+                    // The count and any details at the end of the item's
+                    // last row, which the text is cut short before, if they
+                    // fit.
+                    let label = self.label(item, list_w);
+                    let end = match label.is_empty() || label.width() + 4 > list_w {
+                        true => list_w,
+                        false => list_w - label.width() - 3,
+                    };
+                    // The text is drawn after the position, if it is shown,
+                    // and its last row ends before the label.
+                    let whole_row = list.offset((indent, 0));
+                    let last_row = p.cropped((end, h)).offset((indent, 0));
+                    let (first, second) = match rows {
+                        2 => (whole_row, last_row),
+                        _ => (last_row, whole_row),
+                    };
+                    // The byte index at which the text is cut short, when the
+                    // rest of it doesn't fit on its last row, to end in "…".
+                    let last = if rows == 2 { split } else { 0 };
+                    let room = end.saturating_sub(2 + indent);
+                    let cut = match text[last..].width() > room {
+                        true => last + wrap_index(&text[last..], room.saturating_sub(1)),
+                        false => text.len(),
+                    };
+                    let split = std::cmp::min(split, cut);
+                    // Draw the item's text.
+                    first.with_color(primary, |p| p.print((2, row), &text[..split]));
+                    second.with_color(primary, |p| p.print((2, row + 1), &text[split..cut]));
+                    if cut < text.len() {
+                        let p = if rows == 2 { &second } else { &first };
+                        let column = 2 + text[last..cut].width();
+                        p.with_color(primary, |p| p.print((column, row + rows - 1), "…"));
+                    }
+                    // Draw the matched graphemes in a highlighting color, on
+                    // the second row for the graphemes after the wrap point.
+                    // Those that are cut short aren't drawn.
+                    for (column, offset, grapheme) in highlights(&text[..cut], &item.indices, split)
+                    {
+                        let p = if offset == 0 { &first } else { &second };
+                        p.with_effect(Effect::Bold, |p| {
+                            p.with_color(highlight, |p| p.print((column, row + offset), grapheme));
+                        });
+                    }
+                    // Draw the count in a dim color, apart from the highlights.
+                    if end < list_w {
+                        p.with_color(theme::progress(), |p| {
+                            p.print((end + 1, row + rows - 1), &label)
+                        });
+                    }
+                }
+            }
+
+            // Draw the page count, and the directory that is being browsed
+            // before it, cut from the left when it doesn't fit. The page
+            // count is left out when the list is too narrow for it.
+            p.with_color(theme::prompt(), |p| {
+                let page_len = std::cmp::max(self.page_len(), 1);
+                let page = self.selected / page_len + 1;
+                let pages = std::cmp::max(self.matches.div_ceil(page_len), 1);
+                let count = format!(" {}/{}", page, pages);
+                let column = match list_w >= count.len() + 2 {
+                    true => {
+                        let column = list_w - count.len();
+                        p.print((column, 0), &count);
+                        column
+                    }
+                    false => list_w,
+                };
+
+                if let Some(dir) = self.current_dir() {
+                    let path = utils::display_path(&dir);
+                    p.cropped((column, 1))
+                        .print((2, 0), &breadcrumb(&path, column.saturating_sub(2)));
+                }
+            });
+
+            let preview_w = self.preview_width();
+            if preview_w > 0 {
+                self.draw_preview(&p.offset((w - preview_w, 0)).cropped((preview_w - 1, h - 2)));
+            }
+
+            // Draw the scrollbar in the rightmost column. The list starts at
+            // the bottom, so the thumb moves up as it scrolls.
+            if let Some((top, height)) = thumb {
+                let bottom = start_row - top;
+                let list_rows = self.list_rows();
+                p.with_color(theme::progress(), |p| {
+                    p.print_vline((w - 1, start_row + 1 - list_rows), list_rows, "│")
+                });
+                p.with_color(theme::prompt(), |p| {
+                    p.print_vline((w - 1, bottom + 1 - height), height, "█");
+                });
+            }
+        }
+
+        if h > 1 {
+            // The last row we can draw on.
             let query_row = h - 1;
 
-            // Draw the match count and some borders.
+            // Draw the match count and some borders, with the border on the
+            // right unless the scrollbar is there.
             p.with_color(theme::progress(), |p| {
-                let lines = std::cmp::min(self.matches / 4, h / 4);
-                p.print_vline((w - 1, query_row - 1 - lines), lines, "│");
+                if thumb.is_none() {
+                    let lines = std::cmp::min(self.matches / 4, h / 4);
+                    p.print_vline((w - 1, query_row - 1 - lines), lines, "│");
+                }
                 p.print_hline((2, query_row - 1), w - 3, "─");
                 p.print((2, query_row - 1), &self.count());
             });
 
+            // Draw an error at the end of the border, or over the count when
+            // it doesn't fit, or else the toast, if it fits.
+            if let Some(msg) = self.error() {
+                let msg = format!(" error: {msg} ");
+                let column = match self.count().width() + msg.width() + 5 < w {
+                    true => w - msg.width() - 1,
+                    false => 2,
+                };
+                p.cropped((w - 1, h))
+                    .with_color(theme::err(), |p| p.print((column, query_row - 1), &msg));
+            } else if let Some(msg) = self.toast() {
+                if self.count().width() + msg.width() + 7 < w {
+                    p.with_color(theme::info(), |p| {
+                        p.print((w - msg.width() - 4, query_row - 1), &format!(" {msg} "))
+                    });
+                }
+            }
+
+            // The part of the query that fits, and the column it starts on.
+            let window = self.query_window();
+            let column = 2 + window.clipped_start as usize;
+
             // Draw the text input area that shows the query.
             p.with_color(theme::hl(), |p| {
                 p.print_hline((0, query_row), w, " ");
-                p.print((2, query_row), &self.query);
+                p.print((column, query_row), &self.query[window.start..window.end]);
+            });
+
+            // Draw the indicators for the parts of the query that don't fit.
+            p.with_color(theme::prompt(), |p| {
+                if window.clipped_start {
+                    p.print((2, query_row), "…");
+                }
+                if window.clipped_end {
+                    p.print((w - 1, query_row), "…");
+                }
             });
 
             let c = if self.cursor == self.query.len() {
@@ -412,10 +2335,13 @@ impl View for FuzzyView {
                     .next()
                     .expect("should find a char")
             };
-            let offset = self.query[..self.cursor].width();
-            p.with_effect(Effect::Reverse, |p| {
-                p.print((offset + 2, query_row), c);
-            });
+            let offset = self.query[window.start..self.cursor].width();
+            // The cursor is only drawn while the keys come here.
+            if p.focused {
+                p.with_effect(Effect::Reverse, |p| {
+                    p.print((offset + column, query_row), c);
+                });
+            }
 
             // Draw the symbol to show the start of the text input area.
             p.with_color(theme::prompt(), |p| p.print((0, query_row), ">"));
@@ -424,54 +2350,160 @@ impl View for FuzzyView {
 
     // Keybindings for the fuzzy view.
     fn on_event(&mut self, event: Event) -> EventResult {
+        // Any key dismisses an error, and does what it does as well.
+        if ErrorView::trigger().apply(&event) {
+            self.error = None;
+        }
+        // A click is only played by clicking again straight after it.
+        if !matches!(event, Event::Mouse { .. }) {
+            self.click = None;
+        }
+        let query = self.query.to_owned();
         match event {
             Event::Char(ch) => self.insert(ch),
             Event::Key(Key::Enter) => return self.on_select(),
-            Event::Key(Key::Esc) => return on_cancel(),
+            Event::Key(Key::Esc) => return self.cancel(),
             Event::Key(Key::Down) => self.move_down(),
             Event::Key(Key::Up) => self.move_up(),
             Event::Key(Key::PageUp) | Event::CtrlChar('h') => self.page_up(),
             Event::Key(Key::PageDown) | Event::CtrlChar('l') => self.page_down(),
+            Event::CtrlChar('u') if self.query.is_empty() => self.half_page_up(),
+            Event::CtrlChar('d') => self.half_page_down(),
+            Event::Key(Key::Home) if self.query.is_empty() => self.select_first(),
+            Event::Key(Key::End) if self.query.is_empty() => self.select_last(),
+            Event::Ctrl(Key::Home) | Event::AltChar('g') => self.select_first(),
+            Event::Ctrl(Key::End) | Event::AltChar('G') => self.select_last(),
             Event::CtrlChar('z') => self.random_page(),
+            Event::Key(Key::Tab) => self.toggle_mark(),
+            Event::Shift(Key::Tab) => self.clear_marks(),
             Event::Key(Key::Backspace) => self.backspace(),
             Event::Key(Key::Del) => self.delete(),
             Event::Key(Key::Left) => self.move_left(),
             Event::Key(Key::Right) => self.move_right(),
-            Event::Key(Key::Home) => self.cursor = 0,
-            Event::Key(Key::End) => self.cursor = self.query.len(),
+            Event::Ctrl(Key::Left) | Event::AltChar('b') => self.move_word_left(),
+            Event::Ctrl(Key::Right) | Event::AltChar('f') => self.move_word_right(),
+            Event::Key(Key::Home) | Event::CtrlChar('a') => self.cursor = 0,
+            Event::Key(Key::End) | Event::CtrlChar('e') => self.cursor = self.query.len(),
             Event::CtrlChar('u') => self.clear(),
+            Event::CtrlChar('w') => self.delete_word_left(),
+            Event::CtrlChar('k') => self.kill_to_end(),
+            Event::CtrlChar('y') => self.yank(),
+            Event::Ctrl(Key::Up) | Event::AltChar('p') => self.recall_older(),
+            Event::Ctrl(Key::Down) | Event::AltChar('n') => self.recall_newer(),
             Event::CtrlChar('p') => return self.parent(),
-            Event::CtrlChar('o') => self.open_file_manager(),
+            Event::Alt(Key::Left) => return self.browse_history(false),
+            Event::Alt(Key::Right) => return self.browse_history(true),
+            Event::CtrlChar('o') => return self.open_file_manager(),
+            Event::CtrlChar('n') => return self.create_dir(),
+            Event::CtrlChar('r') => return self.rename_item(),
+            Event::Shift(Key::Del) => return self.delete_item(),
+            Event::AltChar('r') => return self.reload(),
+            Event::AltChar('.') => return self.toggle_hidden_dirs(),
+            Event::AltChar('t') => return self.two_panes(),
+            Event::AltChar('d') => return self.toggle_hidden(),
+            Event::AltChar('h') => return self.toggle_showing_hidden(),
+            Event::CtrlChar('b') => return self.toggle_favorite(),
+            Event::AltChar('B') => return self.toggle_favorites_only(),
+            Event::CtrlChar('f') => return self.toggle_match_paths(),
+            Event::AltChar('c') => return self.cycle_case(),
+            Event::CtrlChar('t') => return self.cycle_sort_mode(),
+            Event::CtrlChar('v') => return self.toggle_preview(),
+            Event::AltChar('i') => return self.toggle_details(),
+            Event::AltChar('#') => return self.toggle_indices(),
+            Event::CtrlChar('x') => return self.cycle_match_mode(),
+            Event::AltChar('?') => return self.explain_ranking(),
+            Event::AltChar('+') | Event::AltChar('=') => {
+                return self.change_threshold(THRESHOLD_STEP)
+            }
+            Event::AltChar('-') => return self.change_threshold(-THRESHOLD_STEP),
+            Event::AltChar('y') => self.copy_path(),
+            Event::AltChar('s') => self.save_matches(),
 
             Event::Mouse {
-                event, position, ..
+                event,
+                offset,
+                position,
             } => match event {
-                MouseEvent::Press(MouseButton::Right) => return on_cancel(),
-                MouseEvent::Press(MouseButton::Left) => return self.mouse_select(position),
-                MouseEvent::WheelDown => self.move_down(),
-                MouseEvent::WheelUp => self.move_up(),
+                MouseEvent::Press(MouseButton::Right) => return self.cancel(),
+                MouseEvent::Press(MouseButton::Left) => return self.mouse_select(offset, position),
+                MouseEvent::Hold(MouseButton::Left) => self.mouse_drag(offset, position),
+                MouseEvent::Release(MouseButton::Left) => self.drag = None,
+                MouseEvent::WheelDown => (0..WHEEL_ROWS).for_each(|_| self.move_down()),
+                MouseEvent::WheelUp => (0..WHEEL_ROWS).for_each(|_| self.move_up()),
                 _ => (),
             },
             _ => (),
         }
+        // Editing a recalled query stops browsing the history, so the next
+        // recall starts from the newest query again.
+        if self.recalled.is_some() && self.query != query && !is_recall(&event) {
+            self.recalled = None;
+            self.draft.clear();
+        }
+        // Keep the cursor visible after editing or moving it.
+        self.scroll_query();
         EventResult::Consumed(None)
     }
 }
 
-pub fn fuzzy_finder(event: &Event, items: &Vec<FuzzyItem>) -> Option<EventResult> {
+pub fn fuzzy_finder(event: &Event) -> Option<EventResult> {
+    let event = event.to_owned();
+    Some(EventResult::with_cb(move |siv| {
+        let (items, key, partial, search) = siv
+            .with_user_data(|(_, _, _, data, _, _): &mut InnerType<SessionData>| {
+                let (items, key) = filter_items(&event, &data.items, args::depth());
+                let partial = args::audio_only() || items.len() < data.items.len();
+                // The fuzzy search returns to the last search of its directory,
+                // rather than the searches of letters and depths.
+                let search = match event.char().is_none() && event.f_num().is_none() {
+                    true => items_dir(&items).and_then(|dir| data.searches.get(&dir).cloned()),
+                    false => None,
+                };
+                (items, key, partial, search)
+            })
+            .unwrap_or_default();
+        match event.f_num() {
+            // The folders selected in a depth search return to it.
+            Some(depth) => FuzzyView::browse(items, Some(depth), None, siv),
+            None => FuzzyView::load(items, key, siv),
+        }
+        siv.call_on_name(FuzzyView::NAME, |fuzzy: &mut FuzzyView| {
+            fuzzy.set_partial(partial);
+            if let Some(search) = &search {
+                fuzzy.restore(search);
+            }
+        });
+    }))
+}
+
+// The directory that `items` were created from, found from the depth of the
+// first one.
+fn items_dir(items: &[FuzzyItem]) -> Option<PathBuf> {
+    let item = items.first()?;
+    let mut path = item.path.to_owned();
+    for _ in 0..item.depth {
+        path.pop();
+    }
+    Some(path)
+}
+
+// Gets the items and the pre-match key for the fuzzy-finder `event`, with
+// the fuzzy search listing the items down to `max_depth`.
+fn filter_items(
+    event: &Event,
+    items: &Vec<FuzzyItem>,
+    max_depth: Option<usize>,
+) -> (Vec<FuzzyItem>, Option<char>) {
     let key = event.char();
-    let (items, key) = match key {
+    match key {
         Some('A'..='Z') => (super::key_items(key, &items), key),
         Some('a') => (super::non_leaf_items(&items), None),
         Some('s') => (super::audio_items(&items), None),
         _ => match event.f_num() {
             Some(depth) => (super::depth_items(depth, &items), None),
-            None => (items.to_owned(), None),
+            None => (super::top_items(max_depth, &items), None),
         },
-    };
-    Some(EventResult::with_cb(move |siv| {
-        FuzzyView::load(items.to_owned(), key, siv)
-    }))
+    }
 }
 
 // Trigger for the fuzzy-finder callbacks.
@@ -495,7 +2527,7 @@ pub fn trigger() -> EventTrigger {
     })
 }
 
-fn select_player(item: FuzzyItem, siv: &mut Cursive) {
+pub fn select_player(item: FuzzyItem, siv: &mut Cursive) {
     let selected = Some(item.path);
     let current = current_path(siv);
 
@@ -503,7 +2535,7 @@ fn select_player(item: FuzzyItem, siv: &mut Cursive) {
         Ok(player) => {
             // Don't reload the player if the selection hasn't changed.
             if selected.eq(&current) {
-                siv.pop_layer();
+                layers::close(siv, Role::Fuzzy);
             } else {
                 PlayerView::load(player, siv);
             }
@@ -512,22 +2544,39 @@ fn select_player(item: FuzzyItem, siv: &mut Cursive) {
     }
 }
 
+// Plays the marked albums at `paths` one after another.
+fn select_albums(paths: Vec<PathBuf>, siv: &mut Cursive) {
+    match PlayerBuilder::albums(paths, siv) {
+        Ok(player) => PlayerView::load(player, siv),
+        Err(e) => ErrorView::load(siv, e),
+    }
+}
+
 // Handle a fuzzy match being escaped.
 fn on_cancel() -> EventResult {
     EventResult::with_cb(|siv| {
         if current_path(siv).is_none() {
             siv.quit()
         } else {
-            siv.pop_layer();
+            layers::close(siv, Role::Fuzzy);
         }
     })
 }
 
+// Whether or not the session scans hidden directories, such as '.stfolder',
+// which is the option until a fuzzy view switches it.
+pub fn scans_hidden_dirs(siv: &mut Cursive) -> bool {
+    match siv.user_data::<InnerType<SessionData>>() {
+        Some((_, _, _, data, _, _)) => data.hidden_dirs,
+        None => args::hidden_dirs(),
+    }
+}
+
 // The path of the current player, if any.
 pub fn current_path(siv: &mut Cursive) -> Option<PathBuf> {
     match siv.user_data::<InnerType<SessionData>>() {
         // match siv.user_data::<InnerType<UserData>>() {
-        Some((_, _, queue)) => match queue.get(1) {
+        Some((_, _, queue, _, _, _)) => match queue.get(1) {
             Some((p, _)) => Some(p.to_owned()),
             None => None,
         },
@@ -535,10 +2584,2322 @@ pub fn current_path(siv: &mut Cursive) -> Option<PathBuf> {
     }
 }
 
-// Pops views from the view stack until there are only two remaining:
-// the current FuzzyView and the underlying PlayerView.
-fn remove_layer(siv: &mut Cursive) {
-    while siv.screen().len() > 2 {
-        siv.screen_mut().remove_layer(LayerPosition::FromFront(1));
+// What the mouse drags while the left button is held.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Drag {
+    // The list, from the row that the button was pressed on, with the offset
+    // of the list then.
+    List { row: usize, offset_y: usize },
+    // The thumb of the scrollbar.
+    Scrollbar,
+}
+
+// The pattern that the matches were matched with, and how.
+#[derive(Debug, PartialEq)]
+struct Narrowing {
+    pattern: String,
+    extensions: Vec<String>,
+    match_mode: MatchMode,
+    case: Case,
+    match_paths: bool,
+    showing_hidden: bool,
+    favorites_only: bool,
+}
+
+// The part of the query that is drawn in the text input area.
+#[derive(Debug, PartialEq)]
+struct QueryWindow {
+    // The byte range of the query that is drawn.
+    start: usize,
+    end: usize,
+    // Whether or not there is more of the query before `start` or after `end`,
+    // shown with an indicator in the first or last column.
+    clipped_start: bool,
+    clipped_end: bool,
+}
+
+// Computes the part of `query` to draw in `width` columns, so that the
+// cursor at the byte index `cursor` is visible. The window starts at
+// `offset` where possible, so that it doesn't jump as the cursor moves.
+// Widths are measured in terminal columns, so wide chars take two.
+fn query_window(query: &str, cursor: usize, offset: usize, width: usize) -> QueryWindow {
+    let cursor_width = query[cursor..]
+        .graphemes(true)
+        .next()
+        .map_or(1, |g| g.width().max(1));
+    // The column taken by the cursor after the last char.
+    let end_cursor = (cursor == query.len()) as usize;
+    // Whether or not the query from `start` overflows, with the indicator
+    // at the start taking a column.
+    let overflows =
+        |start: usize| (start > 0) as usize + query[start..].width() + end_cursor > width;
+
+    let mut start = match query.is_char_boundary(offset) {
+        true => offset.min(cursor),
+        false => cursor,
+    };
+
+    // Show more of the start of the query when the rest fits.
+    while let Some(g) = query[..start].graphemes(true).last() {
+        match overflows(start - g.len()) {
+            true => break,
+            false => start -= g.len(),
+        }
+    }
+
+    // Scroll right until the cursor fits, leaving space for the indicators.
+    while start < cursor {
+        let used = (start > 0) as usize
+            + query[start..cursor].width()
+            + cursor_width
+            + overflows(start) as usize;
+        if used <= width {
+            break;
+        }
+        start += query[start..].graphemes(true).next().map_or(0, |g| g.len());
+    }
+
+    let clipped_start = start > 0;
+    let clipped_end = overflows(start);
+    let available = width.saturating_sub(clipped_start as usize + clipped_end as usize);
+
+    // The end of the last grapheme that fits in the available columns.
+    let mut end = start;
+    let mut used = 0;
+    for g in query[start..].graphemes(true) {
+        if used + g.width() > available {
+            break;
+        }
+        used += g.width();
+        end += g.len();
+    }
+
+    QueryWindow {
+        start,
+        end,
+        clipped_start,
+        clipped_end,
+    }
+}
+
+// Whether or not `event` recalls a query from the history.
+fn is_recall(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::Ctrl(Key::Up) | Event::AltChar('p') | Event::Ctrl(Key::Down) | Event::AltChar('n')
+    )
+}
+
+// `text` with the runs of chars at `indices` in brackets, i.e. `[Al]bum`.
+fn bracketed(text: &str, indices: &[usize]) -> String {
+    let mut bracketed = String::new();
+    let mut in_run = false;
+    for (i, c) in text.chars().enumerate() {
+        let matched = indices.contains(&i);
+        match (in_run, matched) {
+            (false, true) => bracketed.push('['),
+            (true, false) => bracketed.push(']'),
+            _ => (),
+        }
+        bracketed.push(c);
+        in_run = matched;
+    }
+    if in_run {
+        bracketed.push(']');
+    }
+    bracketed
+}
+
+// `n` as an ordinal number, i.e. `2` -> `2nd`.
+fn ordinal(n: usize) -> String {
+    let suffix = match (n % 10, n % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{n}{suffix}")
+}
+
+// Whether or not the directory of `old` has changed in the scan of `new`.
+fn has_changed(old: &FuzzyItem, new: &FuzzyItem) -> bool {
+    (
+        old.has_audio,
+        old.child_count,
+        old.audio_count,
+        old.size_bytes,
+        old.modified,
+    ) != (
+        new.has_audio,
+        new.child_count,
+        new.audio_count,
+        new.size_bytes,
+        new.modified,
+    )
+}
+
+// The count drawn at the end of the row of `item`: the number of audio files
+// in an album, or else the number of subdirectories, followed by a slash.
+fn count_label(item: &FuzzyItem) -> String {
+    match (item.has_audio, item.child_count) {
+        (true, _) => item.audio_count.to_string(),
+        (false, 0) => String::new(),
+        (false, dirs) => format!("{dirs}/"),
+    }
+}
+
+// The size of the audio files of `item` and the date that it was modified,
+// in columns that line up, or blank when they aren't known.
+fn details_label(item: &FuzzyItem) -> String {
+    let size = match item.has_audio {
+        true => utils::human_size(item.size_bytes),
+        false => String::new(),
+    };
+    let date = match item.modified {
+        0 => String::new(),
+        secs => utils::date(secs),
+    };
+    format!("{size:>5} {date:>10}")
+}
+
+// The offset, from the start of the list, and the height of the thumb of the
+// scrollbar of a list of `len` rows, scrolled to the `offset` of `matches`,
+// or `None` when they all fit.
+fn scrollbar_thumb(len: usize, matches: usize, offset: usize) -> Option<(usize, usize)> {
+    if matches <= len {
+        return None;
+    }
+    let height = std::cmp::max(len * len / matches, 1);
+    let top = std::cmp::min(offset * len / matches, len - height);
+    Some((top, height))
+}
+
+// The byte index at which `text` is wrapped onto a second row, after the
+// graphemes that fit in `width` columns.
+fn wrap_index(text: &str, width: usize) -> usize {
+    let mut columns = 0;
+    for (i, grapheme) in text.grapheme_indices(true) {
+        columns += grapheme.width();
+        if columns > width {
+            return i;
+        }
+    }
+    text.len()
+}
+
+// `path` cut from the left to fit in `width` columns, starting with "…/" when
+// it is cut.
+fn breadcrumb(path: &str, width: usize) -> String {
+    if path.width() <= width {
+        return path.to_string();
+    }
+    // Whole directories are cut, unless the last one doesn't fit by itself.
+    let fits = |tail: &&str| tail.width() + 2 <= width;
+    let last = path.rsplit('/').next().unwrap_or(path);
+    let tail = path
+        .match_indices('/')
+        .map(|(i, _)| &path[i + 1..])
+        .find(fits)
+        .or_else(|| {
+            last.grapheme_indices(true)
+                .map(|(i, _)| &last[i..])
+                .find(fits)
+        })
+        .unwrap_or_default();
+    format!("…/{tail}")
+}
+
+// The graphemes of `text` that have a char at one of the char `indices`,
+// with the column and the row offset that each is drawn at, when `text` is
+// drawn from column 2 and wrapped onto a second row at the byte index `split`.
+fn highlights<'a>(text: &'a str, indices: &[usize], split: usize) -> Vec<(usize, usize, &'a str)> {
+    let mut highlights = vec![];
+    let (mut chars, mut column) = (0, 2);
+    for (i, grapheme) in text.grapheme_indices(true) {
+        if i == split {
+            column = 2;
+        }
+        let len = grapheme.chars().count();
+        if (chars..chars + len).any(|c| indices.binary_search(&c).is_ok()) {
+            highlights.push((column, (i >= split) as usize, grapheme));
+        }
+        chars += len;
+        column += grapheme.width();
+    }
+    highlights
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::fuzzy::fuzzy::MATCH_RUNS;
+
+    fn test_items(names: &[&str]) -> Vec<FuzzyItem> {
+        names
+            .iter()
+            .map(|name| FuzzyItem::empty_dir(PathBuf::from("/music").join(name), 1))
+            .collect()
+    }
+
+    static GLOBAL_CALLS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+    // Stands in for the global bindings, counting the times they run.
+    fn count_global(_: &Event) -> Option<EventResult> {
+        GLOBAL_CALLS.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        Some(EventResult::Consumed(None))
+    }
+
+    fn global_calls() -> usize {
+        GLOBAL_CALLS.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    #[test]
+    fn test_typing_is_not_taken_by_global_bindings() {
+        use cursive::views::{Dialog, EditView};
+
+        let mut siv = Cursive::new();
+        siv.set_on_pre_event_inner('-', layers::global(count_global));
+        siv.set_on_pre_event_inner('=', layers::global(count_global));
+        siv.set_on_pre_event_inner(trigger(), layers::global(count_global));
+
+        let fuzzy = FuzzyView::new(test_items(&["abba", "remasters"]));
+        layers::show(&mut siv, Role::Fuzzy, fuzzy.with_name(FuzzyView::NAME));
+        let query = |siv: &mut Cursive| {
+            siv.call_on_name(FuzzyView::NAME, |f: &mut FuzzyView| f.query.to_owned())
+        };
+
+        for ch in "remaster-=Quiet".chars() {
+            siv.on_event(Event::Char(ch));
+        }
+        assert_eq!(query(&mut siv), Some("remaster-=Quiet".into()));
+        assert_eq!(global_calls(), 0);
+        assert!(siv.is_running());
+
+        // With nothing typed, a capital letter still starts a filtered search.
+        siv.on_event(Event::CtrlChar('u'));
+        siv.on_event(Event::Char('R'));
+        assert_eq!(global_calls(), 1);
+
+        // 'Ctrl + a' moves the cursor once something has been typed, and
+        // searches the artists otherwise.
+        siv.on_event(Event::Char('r'));
+        siv.on_event(Event::CtrlChar('a'));
+        let cursor = siv.call_on_name(FuzzyView::NAME, |f: &mut FuzzyView| f.cursor);
+        assert_eq!(cursor, Some(0));
+        assert_eq!(global_calls(), 1);
+        siv.on_event(Event::CtrlChar('u'));
+        siv.on_event(Event::CtrlChar('a'));
+        assert_eq!(global_calls(), 2);
+
+        // 'Tab' marks the selected item rather than searching everything.
+        siv.on_event(Event::Key(Key::Tab));
+        assert_eq!(global_calls(), 2);
+
+        // The text box of a dialog gets every character.
+        let dialog = Dialog::around(EditView::new().with_name("name"));
+        layers::show(&mut siv, Role::Dialog, dialog);
+        for ch in "-=A".chars() {
+            siv.on_event(Event::Char(ch));
+        }
+        let name = siv.call_on_name("name", |e: &mut EditView| e.get_content());
+        assert_eq!(name.as_deref().map(String::as_str), Some("-=A"));
+        assert_eq!(global_calls(), 2);
+    }
+
+    #[test]
+    fn test_apply_changes_preserves_query_and_selection() {
+        let mut fuzzy = FuzzyView::new(test_items(&["abba", "abc", "beatles", "bach"]));
+        fuzzy.insert('b');
+        fuzzy.selected = fuzzy
+            .items
+            .iter()
+            .position(|e| e.display == "bach")
+            .unwrap();
+
+        fuzzy.apply_changes(
+            test_items(&["bjork", "abc"]),
+            vec![PathBuf::from("/music/beatles")],
+        );
+
+        assert_eq!(fuzzy.query, "b");
+        assert_eq!(fuzzy.items.len(), 4);
+        assert_eq!(fuzzy.matches, 4);
+        assert_eq!(fuzzy.items[fuzzy.selected].display, "bach");
+    }
+
+    #[test]
+    fn test_render_goldens() {
+        use crate::config::theme::Preset;
+        use crate::golden;
+
+        for preset in Preset::ALL {
+            let mut items = test_items(&["abba", "abc", "beatles", "bach", "björk", "坂本 bob"]);
+            items[0].has_audio = true;
+            items[0].audio_count = 9;
+            items[2].child_count = 12;
+            let mut fuzzy = FuzzyView::new(items);
+            fuzzy.insert('b');
+
+            let rendered = golden::render(&mut fuzzy, XY::new(32, 10), preset);
+            golden::assert_golden(&format!("fuzzy_view_{}", preset.name()), &rendered);
+        }
+    }
+
+    #[test]
+    fn test_count_column() {
+        use crate::config::theme::Preset;
+        use crate::golden;
+
+        let mut items = test_items(&["a very long album name", "box set", "new"]);
+        items[0].has_audio = true;
+        items[0].audio_count = 12;
+        items[1].child_count = 60;
+        assert_eq!(count_label(&items[0]), "12");
+        assert_eq!(count_label(&items[1]), "60/");
+        assert_eq!(count_label(&items[2]), "");
+
+        // The names are cut short with "…" before the counts when they don't
+        // fit.
+        let mut fuzzy = FuzzyView::new(items);
+        let rendered = golden::render(&mut fuzzy, XY::new(20, 6), Preset::Default);
+        let rows: Vec<&str> = rendered.lines().skip(1).take(6).collect();
+        assert_eq!(rows[3], "|> a very long … 12  |");
+        assert_eq!(rows[2], "|  box set      60/  |");
+    }
+
+    #[test]
+    fn test_directories_are_colored_apart() {
+        use crate::config::theme::Preset;
+        use crate::golden;
+
+        let mut items = test_items(&["album", "box set", "new"]);
+        items[0].has_audio = true;
+        items[1].child_count = 3;
+        let mut fuzzy = FuzzyView::new(items);
+
+        // The style of the name on `row`, from the runs of styled cells.
+        fn style(rendered: &str, row: usize) -> String {
+            let runs = rendered.split(&format!("\nrow {row}:")).nth(1).unwrap();
+            let run = runs
+                .lines()
+                .find(|run| run.trim_start().split("..").next() == Some("2"))
+                .expect("the name should start a run");
+            run.trim_start().split_once(' ').unwrap().1.to_string()
+        }
+
+        let rendered = golden::render(&mut fuzzy, XY::new(20, 6), Preset::Default);
+        assert_eq!(fuzzy.items[2].display, "new");
+        let (album, box_set, new) = (
+            style(&rendered, 3),
+            style(&rendered, 2),
+            style(&rendered, 1),
+        );
+        assert_ne!(box_set, new);
+        assert_ne!(box_set, album);
+
+        // The selected row is drawn the same whatever it is.
+        fuzzy.selected = 1;
+        let rendered = golden::render(&mut fuzzy, XY::new(20, 6), Preset::Default);
+        assert_eq!(style(&rendered, 2), album);
+        assert_eq!(style(&rendered, 3), new);
+    }
+
+    #[test]
+    fn test_details() {
+        use crate::config::theme::Preset;
+        use crate::golden;
+
+        let mut items = test_items(&["album", "box set"]);
+        items[0].has_audio = true;
+        items[0].audio_count = 12;
+        items[0].size_bytes = 312 * 1024 * 1024;
+        items[1].child_count = 3;
+        for item in items.iter_mut() {
+            item.modified = 1_714_521_600;
+        }
+        let mut fuzzy = FuzzyView::new(items);
+        fuzzy.on_event(Event::AltChar('i'));
+        assert!(fuzzy.details);
+
+        // The sizes and the dates line up after the counts, and a directory
+        // of directories has no size.
+        let rendered = golden::render(&mut fuzzy, XY::new(40, 6), Preset::Default);
+        let rows: Vec<&str> = rendered.lines().skip(1).take(6).collect();
+        assert_eq!(rows[3], "|> album            12  312M 2024-05-01  |");
+        assert_eq!(rows[2], "|  box set          3/       2024-05-01  |");
+
+        // They are left out when they would crowd out the names.
+        let rendered = golden::render(&mut fuzzy, XY::new(20, 6), Preset::Default);
+        let rows: Vec<&str> = rendered.lines().skip(1).take(6).collect();
+        assert_eq!(rows[3], "|> album         12  |");
+
+        fuzzy.on_event(Event::AltChar('i'));
+        let rendered = golden::render(&mut fuzzy, XY::new(40, 6), Preset::Default);
+        assert!(!rendered.contains("2024-05-01"));
+    }
+
+    #[test]
+    fn test_long_names_are_cut_short_after_wrapping() {
+        use crate::config::theme::Preset;
+        use crate::golden;
+
+        let mut items = test_items(&["a very long album name that goes on"]);
+        items[0].has_audio = true;
+        items[0].audio_count = 12;
+        let mut fuzzy = FuzzyView::new(items);
+        fuzzy.soft_wrap = SoftWrap::Always;
+
+        // Only the second row is cut short, where the count is.
+        let rendered = golden::render(&mut fuzzy, XY::new(20, 6), Preset::Default);
+        let rows: Vec<&str> = rendered.lines().skip(1).take(6).collect();
+        assert_eq!(rows[2], "|> a very long album |");
+        assert_eq!(rows[3], "|   name that g… 12  |");
+    }
+
+    #[test]
+    fn test_preview() {
+        use crate::config::theme::Preset;
+        use crate::golden;
+
+        let dir = tempfile::tempdir().unwrap();
+        let album = dir.path().join("album");
+        std::fs::create_dir(&album).unwrap();
+        for file in ["02 two.mp3", "01 one.flac", "cover.jpg"] {
+            std::fs::write(album.join(file), "").unwrap();
+        }
+        let mut items = vec![
+            FuzzyItem::empty_dir(album.to_owned(), 1),
+            FuzzyItem::empty_dir(dir.path().join("box set"), 1),
+        ];
+        items[1].child_count = 3;
+
+        let mut fuzzy = FuzzyView::new(items);
+        fuzzy.preview = true;
+        let rendered = golden::render(&mut fuzzy, XY::new(40, 6), Preset::Default);
+        let rows: Vec<&str> = rendered.lines().skip(1).take(6).collect();
+        assert_eq!(fuzzy.tracks[&album], vec!["01 one.flac", "02 two.mp3"]);
+        assert!(
+            rows[0].ends_with(" 1/1│ 01 one.flac       |"),
+            "{}",
+            rows[0]
+        );
+        assert_eq!(rows[1], "|                    │ 02 two.mp3        |");
+        assert_eq!(rows[3], "|> album             │                   |");
+
+        fuzzy.move_up();
+        let rendered = golden::render(&mut fuzzy, XY::new(40, 6), Preset::Default);
+        assert!(rendered.lines().nth(1).unwrap().contains("│ directory"));
+
+        // There is no space for the preview in a narrow terminal.
+        let rendered = golden::render(&mut fuzzy, XY::new(30, 6), Preset::Default);
+        assert!(!rendered.contains('│'));
+    }
+
+    #[test]
+    fn test_extension_filter() {
+        let mut items = vec![];
+        for (name, ext) in [
+            ("beethoven 5", "flac"),
+            ("beethoven 9", "mp3"),
+            ("bach", "ogg"),
+        ] {
+            let mut item = FuzzyItem::empty_dir(PathBuf::from("/music").join(name), 1);
+            item.has_audio = true;
+            item.extensions = vec![ext.to_string()];
+            items.push(item);
+        }
+        let names = |fuzzy: &FuzzyView| -> Vec<String> {
+            let mut names: Vec<String> = fuzzy.items[..fuzzy.matches]
+                .iter()
+                .map(|item| item.display.to_owned())
+                .collect();
+            names.sort();
+            names
+        };
+
+        let mut fuzzy = FuzzyView::new(items);
+        for ch in "beethoven :ext FLAC,mp3".chars() {
+            fuzzy.on_event(Event::Char(ch));
+        }
+        assert_eq!(names(&fuzzy), ["beethoven 5", "beethoven 9"]);
+        assert_eq!(fuzzy.count(), "2/3 [.flac, .mp3 only] ");
+
+        for _ in 0..3 {
+            fuzzy.on_event(Event::Key(Key::Backspace));
+        }
+        assert_eq!(fuzzy.query, "beethoven :ext FLAC,");
+        assert_eq!(names(&fuzzy), ["beethoven 5"]);
+
+        // The filter by itself keeps everything with the extensions.
+        fuzzy.on_event(Event::CtrlChar('u'));
+        for ch in ":ext ogg".chars() {
+            fuzzy.on_event(Event::Char(ch));
+        }
+        assert_eq!(names(&fuzzy), ["bach"]);
+        assert_eq!(fuzzy.items[0].weight, 1);
+        // The albums aren't read to filter them.
+        assert!(fuzzy.tracks.is_empty());
+    }
+
+    #[test]
+    fn test_too_small_to_draw() {
+        use crate::config::theme::Preset;
+        use crate::golden;
+
+        for size in [XY::new(0, 0), XY::new(80, 0), XY::new(3, 20)] {
+            let mut fuzzy = FuzzyView::new(test_items(&["abba", "abc", "beatles"]));
+            let rendered = golden::render(&mut fuzzy, size, Preset::Default);
+            let rows = rendered.lines().skip(1).take(size.y);
+
+            for row in rows {
+                assert!(row.trim_matches('|').trim().is_empty(), "drew '{}'", row);
+            }
+        }
+    }
+
+    #[test]
+    fn test_page_count_on_small_terminals() {
+        use crate::config::theme::Preset;
+        use crate::golden;
+
+        let names: Vec<String> = (0..200).map(|i| format!("album {i:03}")).collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let mut fuzzy = FuzzyView::new(test_items(&names));
+
+        // One item fits on each page of a 10 column terminal, and the pages
+        // are counted from one.
+        let rendered = golden::render(&mut fuzzy, XY::new(10, 4), Preset::Default);
+        let header = rendered.lines().nth(1).unwrap();
+        assert!(header.ends_with(" 1/200 |"), "{}", header);
+
+        // The count is left out when it doesn't fit.
+        fuzzy.selected = 199;
+        let rendered = golden::render(&mut fuzzy, XY::new(10, 4), Preset::Default);
+        assert_eq!(rendered.lines().nth(1), Some("|  /music  |"));
+
+        // There is no header on a 3 row terminal.
+        let rendered = golden::render(&mut fuzzy, XY::new(40, 3), Preset::Default);
+        assert_eq!(
+            rendered.lines().nth(1),
+            Some(format!("|{}|", " ".repeat(40)).as_str())
+        );
+    }
+
+    #[test]
+    fn test_hidden_items_are_not_matched() {
+        let mut fuzzy = FuzzyView::new(test_items(&["abba", "abc", "beatles", "bach"]));
+        let displays = |fuzzy: &FuzzyView| {
+            let mut displays = fuzzy.items[..fuzzy.matches]
+                .iter()
+                .map(|e| e.display.to_owned())
+                .collect::<Vec<String>>();
+            displays.sort();
+            displays
+        };
+
+        fuzzy.selected = fuzzy.items.iter().position(|e| e.display == "abc").unwrap();
+        fuzzy.toggle_hidden();
+        assert_eq!(displays(&fuzzy), vec!["abba", "bach", "beatles"]);
+        assert_eq!(fuzzy.count(), "3/4 (1 hidden) ");
+        assert!(fuzzy.toast().is_some());
+
+        fuzzy.insert('a');
+        assert_eq!(displays(&fuzzy), vec!["abba", "bach", "beatles"]);
+
+        fuzzy.toggle_showing_hidden();
+        assert_eq!(displays(&fuzzy), vec!["abba", "abc", "bach", "beatles"]);
+
+        // Toggling a hidden item shows it again.
+        fuzzy.selected = fuzzy.items.iter().position(|e| e.display == "abc").unwrap();
+        fuzzy.toggle_hidden();
+        fuzzy.toggle_showing_hidden();
+        assert!(fuzzy.hidden.is_empty());
+        assert_eq!(fuzzy.matches, 4);
+    }
+
+    // Creates a view with items of varied lengths, laid out on a screen of `size`.
+    fn wrapping_view(size: XY<usize>, soft_wrap: SoftWrap, rng: &mut StdRng) -> FuzzyView {
+        let names = (0..rng.gen_range(1..60))
+            .map(|i| format!("{i}{}", "x".repeat(rng.gen_range(0..size.x * 2))))
+            .collect::<Vec<String>>();
+        let names = names.iter().map(|e| e.as_str()).collect::<Vec<&str>>();
+
+        let mut fuzzy = FuzzyView::new(test_items(&names));
+        fuzzy.soft_wrap = soft_wrap;
+        fuzzy.layout(size);
+        fuzzy
+    }
+
+    // Checks that the selection is valid and all of its rows are visible.
+    fn assert_selection_visible(fuzzy: &FuzzyView) {
+        assert!(fuzzy.selected < fuzzy.matches);
+        assert!(fuzzy.offset_y <= fuzzy.selected);
+
+        let rows = (fuzzy.offset_y..=fuzzy.selected)
+            .map(|index| fuzzy.item_rows(index))
+            .sum::<usize>();
+        assert!(rows <= fuzzy.list_rows() || fuzzy.offset_y == fuzzy.selected);
+
+        let from_bottom = rows - fuzzy.item_rows(fuzzy.selected);
+        assert_eq!(fuzzy.item_at_row(from_bottom), Some(fuzzy.selected));
+    }
+
+    #[test]
+    fn test_navigation_with_variable_height_rows() {
+        let mut rng = StdRng::seed_from_u64(480);
+
+        for _ in 0..200 {
+            let size = XY::new(rng.gen_range(8..100), rng.gen_range(5..30));
+            let mut fuzzy = wrapping_view(size, SoftWrap::Always, &mut rng);
+            assert_selection_visible(&fuzzy);
+
+            for _ in 0..100 {
+                match rng.gen_range(0..4) {
+                    0 => fuzzy.move_up(),
+                    1 => fuzzy.move_down(),
+                    2 => fuzzy.page_up(),
+                    _ => fuzzy.page_down(),
+                }
+                assert_selection_visible(&fuzzy);
+            }
+        }
+    }
+
+    #[test]
+    fn test_navigation_without_wrapping_is_unchanged() {
+        let mut rng = StdRng::seed_from_u64(480);
+
+        for _ in 0..100 {
+            let size = XY::new(rng.gen_range(8..100), rng.gen_range(5..30));
+            let mut fuzzy = wrapping_view(size, SoftWrap::Auto(size.x), &mut rng);
+            assert!(!fuzzy.is_wrapping());
+
+            for _ in 0..100 {
+                let (selected, offset_y) = (fuzzy.selected, fuzzy.offset_y);
+                fuzzy.move_up();
+                // One item per row, so the selection scrolls one row at a time.
+                if selected + 1 < fuzzy.matches {
+                    assert_eq!(fuzzy.selected, selected + 1);
+                    match selected - offset_y >= fuzzy.available_y {
+                        true => assert_eq!(fuzzy.offset_y, offset_y + 1),
+                        false => assert_eq!(fuzzy.offset_y, offset_y),
+                    }
+                }
+                if rng.gen_bool(0.3) {
+                    fuzzy.page_down();
+                }
+                assert_selection_visible(&fuzzy);
+            }
+        }
+    }
+
+    #[test]
+    fn test_mouse_rows_map_to_wrapped_items() {
+        let mut fuzzy = FuzzyView::new(test_items(&["a", "bbbbbbbbbbbb", "c"]));
+        fuzzy.soft_wrap = SoftWrap::Always;
+        fuzzy.layout(XY::new(10, 7));
+
+        // 'a' is on the bottom row, 'bbbbbbbbbbbb' on the two rows above it.
+        assert_eq!(fuzzy.list_rows(), 4);
+        assert_eq!(fuzzy.item_at_row(0), Some(0));
+        assert_eq!(fuzzy.item_at_row(1), Some(1));
+        assert_eq!(fuzzy.item_at_row(2), Some(1));
+        assert_eq!(fuzzy.item_at_row(3), Some(2));
+        assert_eq!(fuzzy.item_at_row(4), None);
+    }
+
+    #[test]
+    fn test_clicks_outside_the_matches() {
+        let names: Vec<String> = (0..6).map(|i| format!("album {i}")).collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let mut fuzzy = FuzzyView::new(test_items(&names));
+        fuzzy.layout(XY::new(20, 8));
+        assert_eq!(fuzzy.list_rows(), 5);
+        let click = |fuzzy: &mut FuzzyView, offset: (usize, usize), y: usize| {
+            fuzzy.on_event(Event::Mouse {
+                offset: XY::from(offset),
+                position: XY::new(offset.0 + 4, y),
+                event: MouseEvent::Press(MouseButton::Left),
+            });
+            fuzzy.selected
+        };
+
+        // The header and the border above the query are ignored, as are the
+        // rows above a view that is further down.
+        for y in [0, 1, 7, 8, 20] {
+            assert_eq!(click(&mut fuzzy, (0, 0), y), 0, "row {y}");
+        }
+        assert_eq!(click(&mut fuzzy, (0, 0), 2), 4);
+        assert_eq!(click(&mut fuzzy, (0, 0), 6), 0);
+        assert_eq!(fuzzy.item_under_mouse(XY::new(0, 3), XY::new(4, 2)), None);
+        assert_eq!(
+            fuzzy.item_under_mouse(XY::new(0, 3), XY::new(4, 9)),
+            Some(0)
+        );
+
+        // Scrolled up past the last match, the rows above it are ignored.
+        fuzzy.offset_y = 3;
+        for y in [4, 3, 2] {
+            assert_eq!(click(&mut fuzzy, (0, 0), y), 5, "row {y}");
+        }
+
+        // The items that didn't match are never selected.
+        fuzzy.on_event(Event::Char('5'));
+        assert_eq!(fuzzy.matches, 1);
+        for y in 2..=5 {
+            assert_eq!(click(&mut fuzzy, (0, 0), y), 0, "row {y}");
+        }
+    }
+
+    #[test]
+    fn test_mouse_clicks_drags_and_wheel() {
+        let names: Vec<String> = (0..20).map(|i| format!("album {i:02}")).collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let mut fuzzy = FuzzyView::new(test_items(&names));
+        fuzzy.layout(XY::new(20, 8));
+        let mouse = |fuzzy: &mut FuzzyView, event: MouseEvent, y: usize| {
+            let result = fuzzy.on_event(Event::Mouse {
+                offset: XY::new(0, 0),
+                position: XY::new(4, y),
+                event,
+            });
+            matches!(result, EventResult::Consumed(Some(_)))
+        };
+        let press = MouseEvent::Press(MouseButton::Left);
+        let hold = MouseEvent::Hold(MouseButton::Left);
+        let release = MouseEvent::Release(MouseButton::Left);
+
+        // The first click selects, even the selected item, and the second
+        // plays it.
+        assert!(!mouse(&mut fuzzy, press, 6));
+        assert_eq!(fuzzy.selected, 0);
+        assert!(mouse(&mut fuzzy, press, 6));
+
+        // Dragging up scrolls the rows up with the mouse, keeping the
+        // selection on the list.
+        assert!(!mouse(&mut fuzzy, press, 6));
+        mouse(&mut fuzzy, hold, 3);
+        mouse(&mut fuzzy, release, 3);
+        assert_eq!((fuzzy.selected, fuzzy.offset_y), (3, 3));
+        // The list moved, so clicking the selected item selects it again.
+        assert!(!mouse(&mut fuzzy, press, 6));
+        assert!(mouse(&mut fuzzy, press, 6));
+
+        // Dragging stops at either end of the list.
+        mouse(&mut fuzzy, press, 2);
+        mouse(&mut fuzzy, hold, 30);
+        assert_eq!((fuzzy.selected, fuzzy.offset_y), (4, 0));
+        fuzzy.on_event(Event::AltChar('G'));
+        mouse(&mut fuzzy, press, 6);
+        mouse(&mut fuzzy, hold, 0);
+        assert_eq!((fuzzy.selected, fuzzy.offset_y), (15, 15));
+        // Holding without pressing on an item doesn't drag.
+        mouse(&mut fuzzy, release, 0);
+        mouse(&mut fuzzy, hold, 6);
+        assert_eq!(fuzzy.offset_y, 15);
+
+        // The wheel moves the selection a few rows at a time.
+        fuzzy.on_event(Event::AltChar('g'));
+        mouse(&mut fuzzy, MouseEvent::WheelUp, 6);
+        assert_eq!(fuzzy.selected, WHEEL_ROWS);
+        mouse(&mut fuzzy, MouseEvent::WheelDown, 6);
+        assert_eq!(fuzzy.selected, 0);
+    }
+
+    #[test]
+    fn test_scrollbar_clicks_and_drags() {
+        let names: Vec<String> = (0..20).map(|i| format!("album {i:02}")).collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let mut fuzzy = FuzzyView::new(test_items(&names));
+        fuzzy.layout(XY::new(20, 8));
+        let mut mouse = |event: MouseEvent, y: usize| {
+            fuzzy.on_event(Event::Mouse {
+                offset: XY::new(0, 0),
+                position: XY::new(19, y),
+                event,
+            });
+            (fuzzy.selected, fuzzy.offset_y)
+        };
+        let press = MouseEvent::Press(MouseButton::Left);
+        let hold = MouseEvent::Hold(MouseButton::Left);
+
+        // Clicking the scrollbar jumps to that part of the list, keeping the
+        // selection on it.
+        assert_eq!(mouse(press, 2), (15, 15));
+        assert_eq!(mouse(press, 6), (4, 0));
+        assert_eq!(mouse(press, 5), (4, 4));
+
+        // Dragging moves the thumb with the mouse, as far as either end.
+        assert_eq!(mouse(hold, 4), (8, 8));
+        assert_eq!(mouse(hold, 40), (4, 0));
+        assert_eq!(mouse(hold, 0), (15, 15));
+        mouse(MouseEvent::Release(MouseButton::Left), 0);
+        assert_eq!(mouse(hold, 6), (15, 15));
+
+        // Without a scrollbar, the last column is part of the list.
+        fuzzy.insert('9');
+        fuzzy.layout(XY::new(20, 8));
+        assert_eq!(
+            fuzzy.scrollbar_under_mouse(XY::new(0, 0), XY::new(19, 6)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_delete_word_left() {
+        let mut fuzzy = typed_view("pink floyd animals", 40);
+
+        // Mid-word, the start of the word is deleted.
+        fuzzy.cursor = "pink flo".len();
+        fuzzy.delete_word_left();
+        assert_eq!(fuzzy.query, "pink yd animals");
+        assert_eq!(fuzzy.cursor, "pink ".len());
+
+        // At a word boundary, the spaces and the word before them are deleted.
+        fuzzy.delete_word_left();
+        assert_eq!(fuzzy.query, "yd animals");
+        assert_eq!(fuzzy.cursor, 0);
+
+        // Nothing is deleted at the start of the query.
+        fuzzy.delete_word_left();
+        assert_eq!(fuzzy.query, "yd animals");
+        assert_eq!(fuzzy.cursor, 0);
+
+        // Wide and combining characters are deleted whole.
+        let mut fuzzy = typed_view("sigur ro\u{301}s 坂本", 40);
+        fuzzy.on_event(Event::CtrlChar('w'));
+        assert_eq!(fuzzy.query, "sigur ro\u{301}s ");
+        fuzzy.on_event(Event::CtrlChar('w'));
+        assert_eq!(fuzzy.query, "sigur ");
+    }
+
+    #[test]
+    fn test_readline_word_editing() {
+        let mut fuzzy = typed_view("björk homogenic jóga", 40);
+
+        // Words are moved over whole, without splitting multi-byte chars.
+        fuzzy.on_event(Event::AltChar('b'));
+        fuzzy.on_event(Event::AltChar('b'));
+        assert_eq!(&fuzzy.query[fuzzy.cursor..], "homogenic jóga");
+        fuzzy.on_event(Event::AltChar('b'));
+        assert_eq!(fuzzy.cursor, 0);
+        fuzzy.on_event(Event::AltChar('f'));
+        assert_eq!(&fuzzy.query[..fuzzy.cursor], "björk");
+
+        // Killing to the end and yanking it back at the start.
+        fuzzy.on_event(Event::CtrlChar('k'));
+        assert_eq!(fuzzy.query, "björk");
+        fuzzy.on_event(Event::Key(Key::Home));
+        fuzzy.on_event(Event::CtrlChar('y'));
+        assert_eq!(fuzzy.query, " homogenic jógabjörk");
+        assert_eq!(fuzzy.cursor, " homogenic jóga".len());
+
+        // The word deleted with 'Ctrl + w' is yanked back whole.
+        fuzzy.on_event(Event::CtrlChar('w'));
+        assert_eq!(fuzzy.query, " homogenic björk");
+        fuzzy.on_event(Event::CtrlChar('y'));
+        assert_eq!(fuzzy.query, " homogenic jógabjörk");
+
+        // Killing at the end keeps the last killed text.
+        fuzzy.on_event(Event::Key(Key::End));
+        fuzzy.on_event(Event::CtrlChar('k'));
+        fuzzy.on_event(Event::CtrlChar('y'));
+        assert_eq!(fuzzy.query, " homogenic jógabjörkjóga");
+    }
+
+    #[test]
+    fn test_kill_to_end_updates_matches() {
+        let mut fuzzy = FuzzyView::new(test_items(&["abba", "abc", "beatles", "bach"]));
+        for ch in "abba".chars() {
+            fuzzy.on_event(Event::Char(ch));
+        }
+        assert_eq!(fuzzy.matches, 1);
+
+        fuzzy.cursor = 1;
+        fuzzy.on_event(Event::CtrlChar('k'));
+        assert_eq!(fuzzy.query, "a");
+        assert_eq!(fuzzy.cursor, 1);
+        assert_eq!(fuzzy.matches, 4);
+
+        // There is nothing after the cursor to delete.
+        fuzzy.on_event(Event::CtrlChar('k'));
+        assert_eq!(fuzzy.query, "a");
+    }
+
+    #[test]
+    fn test_line_start_and_end() {
+        let query = "Bjo\u{308}rk 東京 🎵";
+        let mut fuzzy = FuzzyView::new(test_items(&["a"]));
+        for ch in query.chars() {
+            fuzzy.on_event(Event::Char(ch));
+        }
+
+        fuzzy.on_event(Event::CtrlChar('a'));
+        assert_eq!(fuzzy.cursor, 0);
+        fuzzy.on_event(Event::CtrlChar('e'));
+        assert_eq!(fuzzy.cursor, query.len());
+
+        // The cursor stays on grapheme boundaries after moving from either end.
+        fuzzy.on_event(Event::CtrlChar('a'));
+        for _ in 0..3 {
+            fuzzy.on_event(Event::Key(Key::Right));
+        }
+        assert_eq!(&fuzzy.query[..fuzzy.cursor], "Bjo\u{308}");
+        fuzzy.on_event(Event::CtrlChar('e'));
+        fuzzy.on_event(Event::Key(Key::Left));
+        assert_eq!(&fuzzy.query[fuzzy.cursor..], "🎵");
+    }
+
+    #[test]
+    fn test_paste_matches_once() {
+        let mut fuzzy = FuzzyView::new(test_items(&["The Who Live at Leeds", "The Wall"]));
+        fuzzy.on_event(Event::Char('x'));
+        fuzzy.on_event(Event::Key(Key::Home));
+
+        let runs = MATCH_RUNS.with(|runs| runs.get());
+        fuzzy.paste("the who live at\nleeds");
+        let runs = MATCH_RUNS.with(|runs| runs.get()) - runs;
+
+        assert_eq!(runs, 1);
+        assert_eq!(fuzzy.query, "the who live atleedsx");
+        assert_eq!(fuzzy.cursor, 20);
+    }
+
+    #[test]
+    fn test_wrap_around() {
+        let names: Vec<String> = (0..10).map(|i| format!("album {i}")).collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+
+        for height in [4, 6, 9, 30] {
+            let mut fuzzy = FuzzyView::new(test_items(&names));
+            fuzzy.layout(XY::new(40, height));
+
+            // Without wrapping, the selection stops at the bottom.
+            fuzzy.on_event(Event::Key(Key::Down));
+            assert_eq!(fuzzy.selected, 0);
+
+            fuzzy.wrap_around = true;
+            fuzzy.on_event(Event::Key(Key::Down));
+            assert_eq!(fuzzy.selected, 9, "height {height}");
+            assert!(fuzzy.offset_y <= 9, "height {height}");
+            assert!(
+                9 < fuzzy.offset_y + fuzzy.visible_items(),
+                "the top item isn't visible at height {height}"
+            );
+
+            fuzzy.on_event(Event::Key(Key::Up));
+            assert_eq!(fuzzy.selected, 0, "height {height}");
+            assert_eq!(fuzzy.offset_y, 0, "height {height}");
+        }
+    }
+
+    #[test]
+    fn test_jumps_and_half_pages() {
+        let names: Vec<String> = (0..20).map(|i| format!("album {i:02}")).collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let mut fuzzy = FuzzyView::new(test_items(&names));
+        fuzzy.layout(XY::new(40, 10));
+        assert_eq!(fuzzy.list_rows(), 7);
+
+        // The last match is at the top of a full list.
+        fuzzy.on_event(Event::AltChar('G'));
+        assert_eq!((fuzzy.selected, fuzzy.offset_y), (19, 13));
+        assert_eq!(fuzzy.visible_items(), 7);
+        fuzzy.on_event(Event::AltChar('g'));
+        assert_eq!((fuzzy.selected, fuzzy.offset_y), (0, 0));
+
+        // With nothing typed, 'Home' and 'End' jump as well.
+        fuzzy.on_event(Event::Key(Key::End));
+        assert_eq!((fuzzy.selected, fuzzy.offset_y), (19, 13));
+        fuzzy.on_event(Event::Key(Key::Home));
+        assert_eq!((fuzzy.selected, fuzzy.offset_y), (0, 0));
+
+        // Half a page at a time, without scrolling past the last match.
+        fuzzy.on_event(Event::CtrlChar('u'));
+        assert_eq!((fuzzy.selected, fuzzy.offset_y), (3, 3));
+        fuzzy.on_event(Event::CtrlChar('d'));
+        assert_eq!((fuzzy.selected, fuzzy.offset_y), (0, 0));
+        fuzzy.on_event(Event::Ctrl(Key::End));
+        fuzzy.on_event(Event::CtrlChar('d'));
+        fuzzy.on_event(Event::CtrlChar('u'));
+        fuzzy.on_event(Event::CtrlChar('u'));
+        assert_eq!((fuzzy.selected, fuzzy.offset_y), (19, 13));
+
+        // Once something is typed, they edit the query instead.
+        fuzzy.on_event(Event::Char('9'));
+        fuzzy.on_event(Event::Key(Key::Home));
+        assert_eq!((fuzzy.cursor, fuzzy.selected), (0, 0));
+        fuzzy.on_event(Event::CtrlChar('u'));
+        assert_eq!(fuzzy.query, "");
+
+        // Without any matches, nothing moves.
+        fuzzy.on_event(Event::Char('x'));
+        assert_eq!(fuzzy.matches, 0);
+        for event in [
+            Event::AltChar('G'),
+            Event::CtrlChar('d'),
+            Event::Ctrl(Key::Home),
+        ] {
+            fuzzy.on_event(event);
+            assert_eq!((fuzzy.selected, fuzzy.offset_y), (0, 0));
+        }
+    }
+
+    #[test]
+    fn test_query_operators() {
+        let mut fuzzy = FuzzyView::new(test_items(&[
+            "The Who Live at Leeds",
+            "The Wall",
+            "Live Rust",
+            "Other Live",
+        ]));
+        for ch in "^the 'live".chars() {
+            fuzzy.on_event(Event::Char(ch));
+        }
+
+        assert_eq!(fuzzy.matches, 1);
+        let item = &fuzzy.items[fuzzy.selected];
+        assert_eq!(item.display, "The Who Live at Leeds");
+        assert_eq!(item.indices, vec![0, 1, 2, 8, 9, 10, 11]);
+    }
+
+    #[test]
+    fn test_match_paths() {
+        let mut fuzzy = FuzzyView::new(test_items(&[
+            "Mingus/1959 - Ah Um",
+            "Davis/1959 - Kind of Blue",
+        ]));
+        fuzzy.root = PathBuf::from("/music");
+        for ch in "mingus".chars() {
+            fuzzy.on_event(Event::Char(ch));
+        }
+        assert_eq!(fuzzy.matches, 0);
+
+        fuzzy.on_event(Event::CtrlChar('f'));
+        assert_eq!(fuzzy.matches, 1);
+
+        // The highlighted indices are of the path that is drawn.
+        let item = &fuzzy.items[fuzzy.selected];
+        let text = item.text(fuzzy.match_root());
+        assert_eq!(text, "Mingus/1959 - Ah Um");
+        let matched: String = item
+            .indices
+            .iter()
+            .filter_map(|i| text.chars().nth(*i))
+            .collect();
+        assert_eq!(matched, "Mingus");
+
+        fuzzy.on_event(Event::CtrlChar('f'));
+        assert_eq!(fuzzy.matches, 0);
+    }
+
+    #[test]
+    fn test_cycle_case() {
+        let mut fuzzy = FuzzyView::new(test_items(&["The Wall", "the wall", "THE WALL"]));
+        for ch in "wall".chars() {
+            fuzzy.on_event(Event::Char(ch));
+        }
+        assert_eq!(fuzzy.matches, 3);
+        assert_eq!(fuzzy.count(), "3/3 ");
+
+        fuzzy.on_event(Event::AltChar('c'));
+        assert_eq!(fuzzy.matches, 1);
+        assert_eq!(fuzzy.count(), "1/3 [match case] ");
+
+        fuzzy.on_event(Event::AltChar('c'));
+        assert_eq!(fuzzy.matches, 3);
+        assert_eq!(fuzzy.count(), "3/3 [ignore case] ");
+
+        // With smart case, an uppercase letter makes the case count.
+        fuzzy.on_event(Event::AltChar('c'));
+        fuzzy.on_event(Event::Key(Key::Home));
+        fuzzy.on_event(Event::Char('W'));
+        fuzzy.on_event(Event::Key(Key::Del));
+        assert_eq!(fuzzy.query, "Wall");
+        assert_eq!(fuzzy.matches, 1);
+    }
+
+    #[test]
+    fn test_ties_are_stable_across_queries() {
+        let mut fuzzy = FuzzyView::new(test_items(&["x/Live", "y/Live", "z/Live"]));
+        fuzzy.root = PathBuf::from("/music");
+        fuzzy.on_event(Event::CtrlChar('f'));
+
+        fuzzy.on_event(Event::Char('z'));
+        assert_eq!(fuzzy.items[0].path, PathBuf::from("/music/z/Live"));
+
+        fuzzy.on_event(Event::Key(Key::Backspace));
+        for ch in "live".chars() {
+            fuzzy.on_event(Event::Char(ch));
+        }
+        assert_eq!(fuzzy.matches, 3);
+        let order: Vec<&Path> = fuzzy.items.iter().map(|e| e.path.as_path()).collect();
+        assert_eq!(
+            order,
+            ["/music/x/Live", "/music/y/Live", "/music/z/Live"].map(Path::new)
+        );
+    }
+
+    #[test]
+    fn test_cycle_sort_mode() {
+        let mut items = test_items(&["b/beta", "Alpha", "gamma", "a/beta"]);
+        for (item, modified) in items.iter_mut().zip([20, 10, 30, 40]) {
+            item.modified = modified;
+        }
+        let mut fuzzy = FuzzyView::new(items);
+        let order = |fuzzy: &FuzzyView| -> Vec<String> {
+            let items = fuzzy.items[..fuzzy.matches].iter();
+            items.map(|e| e.path.display().to_string()).collect()
+        };
+        fuzzy.update_list("");
+        let by_name = [
+            "/music/Alpha",
+            "/music/a/beta",
+            "/music/b/beta",
+            "/music/gamma",
+        ];
+        assert_eq!(order(&fuzzy), by_name);
+        assert_eq!(fuzzy.count(), "4/4 ");
+
+        fuzzy.on_event(Event::CtrlChar('t'));
+        assert_eq!(
+            order(&fuzzy),
+            [
+                "/music/a/beta",
+                "/music/gamma",
+                "/music/b/beta",
+                "/music/Alpha"
+            ]
+        );
+        assert_eq!(fuzzy.count(), "4/4 [newest first] ");
+
+        // The mode orders the matches with equal weights.
+        for ch in "beta".chars() {
+            fuzzy.on_event(Event::Char(ch));
+        }
+        assert_eq!(order(&fuzzy), ["/music/a/beta", "/music/b/beta"]);
+
+        fuzzy.on_event(Event::CtrlChar('t'));
+        assert_eq!(order(&fuzzy), ["/music/b/beta", "/music/a/beta"]);
+        assert_eq!(fuzzy.count(), "2/4 [as found] ");
+
+        fuzzy.on_event(Event::CtrlChar('u'));
+        assert_eq!(
+            order(&fuzzy),
+            [
+                "/music/b/beta",
+                "/music/Alpha",
+                "/music/gamma",
+                "/music/a/beta"
+            ]
+        );
+
+        fuzzy.on_event(Event::CtrlChar('t'));
+        assert_eq!(order(&fuzzy), by_name);
+    }
+
+    #[test]
+    fn test_equal_weights_are_ordered_by_name_and_path() {
+        let names = ["beta", "y/Live", "Alpha", "x/live", "a/Live", "Live"];
+        let expected = [
+            "/music/Alpha",
+            "/music/beta",
+            "/music/Live",
+            "/music/a/Live",
+            "/music/x/live",
+            "/music/y/Live",
+        ];
+
+        for start in 0..names.len() {
+            let mut names = names.to_vec();
+            names.rotate_left(start);
+            let mut fuzzy = FuzzyView::new(test_items(&names));
+            // Every item has the same weight without a query.
+            fuzzy.on_event(Event::Char('x'));
+            fuzzy.on_event(Event::Key(Key::Backspace));
+
+            let order: Vec<String> = fuzzy
+                .items
+                .iter()
+                .map(|e| e.path.display().to_string())
+                .collect();
+            assert_eq!(order, expected, "starting with {:?}", names);
+        }
+    }
+
+    #[test]
+    fn test_typing_in_a_large_library() {
+        // The most items that are matched without the worker.
+        let count = match_worker::MIN_ITEMS - 1;
+        let names: Vec<String> = (0..count)
+            .map(|i| format!("Artist {:03}/Album {:04}", i / 10, i))
+            .collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let mut fuzzy = FuzzyView::new(test_items(&names));
+
+        // Each keystroke matches the items once, and clearing the query lists
+        // them all again without matching.
+        let runs = MATCH_RUNS.with(|runs| runs.get());
+        for _ in 0..10 {
+            for ch in "album 42".chars() {
+                fuzzy.on_event(Event::Char(ch));
+            }
+            fuzzy.clear();
+        }
+        assert_eq!(MATCH_RUNS.with(|runs| runs.get()) - runs, 80);
+        assert!(fuzzy.worker.is_none());
+        assert_eq!(fuzzy.matches, count);
+    }
+
+    #[test]
+    fn test_typing_matches_the_items_in_place() {
+        // Just too few items for the worker, so each keystroke is matched here.
+        let count = match_worker::MIN_ITEMS - 1;
+        let names: Vec<String> = (0..count)
+            .map(|i| format!("Artist {} - Album {i:04}", i % 7))
+            .collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let mut fuzzy = FuzzyView::new(test_items(&names));
+        fuzzy.update_list("");
+
+        // The items are matched where they are, rather than copied for each
+        // keystroke.
+        let items = fuzzy.items.as_ptr();
+        let runs = MATCH_RUNS.with(|runs| runs.get());
+        for ch in "album 4321".chars() {
+            fuzzy.on_event(Event::Char(ch));
+            assert_eq!(fuzzy.items.as_ptr(), items);
+        }
+        assert_eq!(MATCH_RUNS.with(|runs| runs.get()) - runs, 10);
+        assert_eq!(fuzzy.items.len(), count);
+
+        // The same as matching a fresh copy of the items all at once.
+        let mut expected = test_items(&names);
+        let terms = query::parse("album 4321");
+        let matches = fuzzy_match(
+            &mut expected,
+            &terms,
+            &Case::Smart.matcher(),
+            Case::Smart,
+            None,
+            0,
+        );
+        assert_eq!(fuzzy.matches, matches);
+        assert!(matches > 1);
+        assert_eq!(fuzzy.items[0].display, "Artist 2 - Album 4321");
+    }
+
+    #[test]
+    fn test_matching_on_the_worker() {
+        let names: Vec<String> = (0..5_000).map(|i| format!("Album {i:04}")).collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let mut fuzzy = FuzzyView::new(test_items(&names));
+        let (tx, rx) = std::sync::mpsc::channel();
+        fuzzy.worker = Some(MatchWorker::start(fuzzy.items.to_owned(), move |m| {
+            tx.send(m).is_ok()
+        }));
+
+        for ch in "4242".chars() {
+            fuzzy.on_event(Event::Char(ch));
+        }
+        // The previous matches are shown while the query is matched, with
+        // a spinner after the count.
+        assert_eq!(fuzzy.matches, 5_000);
+        fuzzy.spinner = utils::Spinner::new(false);
+        fuzzy.layout(XY::new(40, 10));
+        assert_eq!(fuzzy.count(), "5000/5000.   ");
+
+        let generation = fuzzy.pending.unwrap();
+        for matches in rx.iter() {
+            // The matches of the earlier queries are ignored.
+            fuzzy.apply_matches(&matches);
+            if matches.generation == generation {
+                break;
+            }
+            assert_eq!(fuzzy.matches, 5_000);
+        }
+        assert_eq!(fuzzy.matches, 1);
+        assert_eq!(fuzzy.items[0].display, "Album 4242");
+        assert_eq!(fuzzy.count(), "1/5000 ");
+
+        // Selecting while the query is being matched selects its matches.
+        fuzzy.on_event(Event::Char('x'));
+        fuzzy.on_event(Event::Key(Key::Backspace));
+        assert!(fuzzy.pending.is_some());
+        _ = fuzzy.on_select();
+        assert!(fuzzy.pending.is_none());
+        assert_eq!(fuzzy.items[fuzzy.selected].display, "Album 4242");
+    }
+
+    #[test]
+    fn test_explain_ranking() {
+        let mut fuzzy = FuzzyView::new(test_items(&["Walls", "The Wall", "Wallflowers"]));
+        for ch in "wall 'the".chars() {
+            fuzzy.on_event(Event::Char(ch));
+        }
+        let weight = fuzzy.items[0].weight;
+        let explained = fuzzy.explain_item("selected", 0);
+        let lines: Vec<&str> = explained.lines().collect();
+
+        assert_eq!(lines[0], "selected: 1 of 1, 'The Wall'");
+        assert!(lines[1].starts_with("  wall "));
+        assert!(lines[2].starts_with("  'the "));
+        // The weight is 1 more than the weights of the terms.
+        let term_weight = |line: &str| line.split_whitespace().last().unwrap().parse::<i64>();
+        let terms = term_weight(lines[1]).unwrap() + term_weight(lines[2]).unwrap();
+        assert_eq!(lines[3], format!("  weight        {: >6}", weight));
+        assert_eq!(weight, terms + 1);
+        assert_eq!(lines[4], "  matched       [The] [Wall]");
+        assert_eq!(lines[5], "  then by       the name");
+
+        // Without a query, every item has the same weight.
+        fuzzy.clear();
+        fuzzy.on_event(Event::CtrlChar('t'));
+        fuzzy.on_event(Event::CtrlChar('t'));
+        fuzzy.on_event(Event::Key(Key::Up));
+        let explained = fuzzy.explain_item("selected", fuzzy.selected);
+        assert_eq!(
+            explained.lines().collect::<Vec<_>>(),
+            vec![
+                "selected: 2 of 3, 'The Wall'",
+                "  weight             1",
+                "  then by       found 2nd",
+            ]
+        );
+
+        assert_eq!(bracketed("Walls", &[0, 1, 3]), "[Wa]l[l]s");
+        assert_eq!(ordinal(12), "12th");
+        assert_eq!(ordinal(23), "23rd");
+    }
+
+    #[test]
+    fn test_recall_history() {
+        let mut fuzzy = FuzzyView::new(test_items(&["abba", "abc", "beatles", "bach"]));
+        fuzzy.history = vec!["abba".into(), "bea".into()];
+        fuzzy.on_event(Event::Char('c'));
+
+        fuzzy.on_event(Event::AltChar('p'));
+        assert_eq!(fuzzy.query, "bea");
+        assert_eq!(fuzzy.cursor, 3);
+        assert_eq!(fuzzy.matches, 1);
+
+        fuzzy.on_event(Event::Ctrl(Key::Up));
+        assert_eq!(fuzzy.query, "abba");
+        // There is nothing older to recall.
+        fuzzy.on_event(Event::AltChar('p'));
+        assert_eq!(fuzzy.query, "abba");
+
+        fuzzy.on_event(Event::AltChar('n'));
+        assert_eq!(fuzzy.query, "bea");
+        // Going past the last query brings back what was being typed.
+        fuzzy.on_event(Event::Ctrl(Key::Down));
+        assert_eq!(fuzzy.query, "c");
+        assert_eq!(fuzzy.matches, 2);
+        fuzzy.on_event(Event::AltChar('n'));
+        assert_eq!(fuzzy.query, "c");
+
+        // Typing after a recall starts from the newest query again.
+        fuzzy.on_event(Event::Ctrl(Key::Up));
+        fuzzy.on_event(Event::Ctrl(Key::Up));
+        fuzzy.on_event(Event::Char('!'));
+        assert_eq!(fuzzy.query, "abba!");
+        fuzzy.on_event(Event::AltChar('p'));
+        assert_eq!(fuzzy.query, "bea");
+        fuzzy.on_event(Event::AltChar('n'));
+        assert_eq!(fuzzy.query, "abba!");
+    }
+
+    #[test]
+    fn test_marks() {
+        let mut items = test_items(&["abba", "abc", "beatles", "box set"]);
+        for item in items.iter_mut().take(3) {
+            item.has_audio = true;
+        }
+        let mut fuzzy = FuzzyView::new(items);
+        let marked = |fuzzy: &FuzzyView| -> Vec<String> {
+            let names = fuzzy.marked.iter().map(|path| path.file_name().unwrap());
+            names.map(|name| name.to_string_lossy().into()).collect()
+        };
+
+        fuzzy.on_event(Event::Key(Key::Tab));
+        for ch in "bea".chars() {
+            fuzzy.on_event(Event::Char(ch));
+        }
+        fuzzy.on_event(Event::Key(Key::Tab));
+        // The marks are kept in the order they were made.
+        assert_eq!(marked(&fuzzy), vec!["abba", "beatles"]);
+        assert!(fuzzy.items[0].selected);
+        assert_eq!(fuzzy.count(), "1/4 [2 marked] ");
+
+        fuzzy.on_event(Event::Key(Key::Tab));
+        assert_eq!(marked(&fuzzy), vec!["abba"]);
+        assert!(!fuzzy.items[0].selected);
+
+        // Only albums can be marked.
+        fuzzy.on_event(Event::CtrlChar('u'));
+        for ch in "box".chars() {
+            fuzzy.on_event(Event::Char(ch));
+        }
+        fuzzy.on_event(Event::Key(Key::Tab));
+        assert_eq!(marked(&fuzzy), vec!["abba"]);
+        assert_eq!(fuzzy.toast(), Some("only albums can be marked"));
+
+        // Escaping clears the marks.
+        fuzzy.on_event(Event::Key(Key::Esc));
+        assert!(fuzzy.marked.is_empty());
+        assert!(fuzzy.items.iter().all(|item| !item.selected));
+    }
+
+    #[test]
+    fn test_match_modes() {
+        let mut fuzzy = FuzzyView::new(test_items(&["abba", "abc", "beatles", "bach"]));
+        fuzzy.on_event(Event::CtrlChar('x'));
+        assert_eq!(fuzzy.toast(), Some("/ regex matching"));
+        for ch in "^b.*s$".chars() {
+            fuzzy.on_event(Event::Char(ch));
+        }
+        assert_eq!(fuzzy.matches, 1);
+        assert_eq!(fuzzy.items[0].display, "beatles");
+        assert_eq!(fuzzy.items[0].indices, (0..7).collect::<Vec<_>>());
+        assert_eq!(fuzzy.count(), "/ 1/4 ");
+
+        // An invalid pattern matches nothing and says why.
+        fuzzy.on_event(Event::CtrlChar('u'));
+        fuzzy.on_event(Event::Char('('));
+        assert_eq!(fuzzy.matches, 0);
+        assert_eq!(fuzzy.count(), "/ error: unclosed group ");
+
+        // The prefix matches all have the same weight, so they are in the
+        // sort order.
+        fuzzy.on_event(Event::CtrlChar('u'));
+        fuzzy.on_event(Event::CtrlChar('x'));
+        for ch in "ab".chars() {
+            fuzzy.on_event(Event::Char(ch));
+        }
+        let matches: Vec<&FuzzyItem> = fuzzy.items[..fuzzy.matches].iter().collect();
+        let names: Vec<_> = matches.iter().map(|item| item.display.as_str()).collect();
+        assert_eq!(names, ["abba", "abc"]);
+        assert!(matches.iter().all(|item| item.weight == 1));
+        assert_eq!(matches[1].indices, [0, 1]);
+        assert_eq!(fuzzy.count(), "^ 2/4 ");
+        // Smart case matches the case of a prefix with an uppercase letter.
+        fuzzy.on_event(Event::CtrlChar('u'));
+        fuzzy.on_event(Event::Char('B'));
+        assert_eq!(fuzzy.matches, 0);
+
+        // Back to fuzzy matching, the same query matches as a term.
+        fuzzy.on_event(Event::CtrlChar('u'));
+        fuzzy.on_event(Event::CtrlChar('x'));
+        assert_eq!(fuzzy.toast(), Some("~ fuzzy matching"));
+        for ch in "bs".chars() {
+            fuzzy.on_event(Event::Char(ch));
+        }
+        assert_eq!(fuzzy.matches, 1);
+        assert_eq!(fuzzy.count(), "1/4 ");
+    }
+
+    // Types `query` into a view on a terminal `width` columns wide.
+    fn typed_view(query: &str, width: usize) -> FuzzyView {
+        let mut fuzzy = FuzzyView::new(test_items(&["a"]));
+        fuzzy.layout(XY::new(width, 10));
+        for ch in query.chars() {
+            fuzzy.on_event(Event::Char(ch));
+        }
+        fuzzy
+    }
+
+    // Checks that the drawn part of the query fits, with the cursor visible,
+    // and returns it with the indicators.
+    fn assert_query_fits(fuzzy: &FuzzyView) -> String {
+        let window = fuzzy.query_window();
+        let available = fuzzy.size.x - 2;
+        let shown = &fuzzy.query[window.start..window.end];
+        let cursor = match fuzzy.query[fuzzy.cursor..].graphemes(true).next() {
+            Some(g) => g.width(),
+            None => 1,
+        };
+        let cursor_end = window.clipped_start as usize
+            + fuzzy.query[window.start..fuzzy.cursor].width()
+            + cursor;
+
+        assert!(window.start <= fuzzy.cursor && fuzzy.cursor <= window.end);
+        assert!(cursor_end + window.clipped_end as usize <= available);
+        assert!(
+            window.clipped_start as usize + shown.width() + window.clipped_end as usize
+                <= available
+        );
+        assert_eq!(window.clipped_start, window.start > 0);
+        assert_eq!(window.clipped_end, window.end < fuzzy.query.len());
+
+        let start = if window.clipped_start { "…" } else { "" };
+        let end = if window.clipped_end { "…" } else { "" };
+        format!("{start}{shown}{end}")
+    }
+
+    #[test]
+    fn test_long_query_scrolls_with_cursor() {
+        let mut fuzzy = typed_view("the quick brown fox jumps over", 20);
+        assert_eq!(assert_query_fits(&fuzzy), "…n fox jumps over");
+
+        fuzzy.on_event(Event::Key(Key::Home));
+        assert_eq!(assert_query_fits(&fuzzy), "the quick brown f…");
+
+        // Moving right only scrolls once the cursor reaches the end.
+        for i in 0..fuzzy.query.len() {
+            fuzzy.on_event(Event::Key(Key::Right));
+            let shown = assert_query_fits(&fuzzy);
+            if i < 16 {
+                assert_eq!(shown, "the quick brown f…");
+            }
+        }
+
+        // Word motion moves the window with the cursor.
+        for _ in 0..4 {
+            fuzzy.on_event(Event::Ctrl(Key::Left));
+            assert_query_fits(&fuzzy);
+        }
+        assert_eq!(&fuzzy.query[fuzzy.cursor..], "brown fox jumps over");
+        assert_eq!(assert_query_fits(&fuzzy), "…brown fox jumps …");
+
+        fuzzy.on_event(Event::Ctrl(Key::Right));
+        assert_eq!(&fuzzy.query[fuzzy.cursor..], " fox jumps over");
+
+        fuzzy.on_event(Event::Key(Key::End));
+        assert_eq!(assert_query_fits(&fuzzy), "…n fox jumps over");
+
+        // Deleting the query shows more of its start again.
+        for _ in 0..13 {
+            fuzzy.on_event(Event::Key(Key::Backspace));
+        }
+        assert_eq!(assert_query_fits(&fuzzy), "the quick brown f");
+    }
+
+    #[test]
+    fn test_wide_query_uses_display_width() {
+        let query = "日本語のクエリはとても長いです";
+        let mut fuzzy = typed_view(query, 20);
+        assert_eq!(assert_query_fits(&fuzzy), "…はとても長いです");
+
+        fuzzy.on_event(Event::Key(Key::Home));
+        assert_eq!(assert_query_fits(&fuzzy), "日本語のクエリは…");
+
+        for _ in 0..query.chars().count() {
+            fuzzy.on_event(Event::Key(Key::Right));
+            assert_query_fits(&fuzzy);
+        }
+        for _ in 0..query.chars().count() {
+            fuzzy.on_event(Event::Key(Key::Left));
+            assert_query_fits(&fuzzy);
+        }
+        assert_eq!(fuzzy.cursor, 0);
+    }
+
+    #[test]
+    fn test_highlights_wrap_with_display_name() {
+        // The display name starts on column 2, after the selection marker.
+        let text = "abcdefghijk";
+        assert_eq!(highlights(text, &[3], text.len()), [(5, 0, "d")]);
+        assert_eq!(highlights(text, &[9], text.len()), [(11, 0, "j")]);
+        let split = wrap_index(text, 7);
+        assert_eq!(split, 7);
+        assert_eq!(highlights(text, &[6], split), [(8, 0, "g")]);
+        assert_eq!(highlights(text, &[7], split), [(2, 1, "h")]);
+        assert_eq!(highlights(text, &[10], split), [(5, 1, "k")]);
+    }
+
+    #[test]
+    fn test_highlights_of_non_ascii_names() {
+        fn highlighted<'a>(
+            term: &str,
+            text: &'a str,
+            width: usize,
+        ) -> Vec<(usize, usize, &'a str)> {
+            let (_, indices) = query::parse(term)[0]
+                .find(&Case::Smart.matcher(), Case::Smart, text)
+                .unwrap();
+            highlights(text, &indices, wrap_index(text, width))
+        }
+
+        // The columns count the graphemes rather than the bytes.
+        assert_eq!(
+            highlighted("'rós", "Sigur Rós", 40),
+            [(8, 0, "R"), (9, 0, "ó"), (10, 0, "s")]
+        );
+        // Wide graphemes take two columns, and wrap as a whole.
+        assert_eq!(
+            highlighted("'ヒカ", "宇多田ヒカル", 40),
+            [(8, 0, "ヒ"), (10, 0, "カ")]
+        );
+        assert_eq!(wrap_index("宇多田ヒカル", 7), "宇多田".len());
+        assert_eq!(
+            highlighted("'ヒカ", "宇多田ヒカル", 7),
+            [(2, 1, "ヒ"), (4, 1, "カ")]
+        );
+
+        // A combining char is drawn with the char that it combines with.
+        let text = "Re\u{301}sume\u{301}";
+        assert_eq!(
+            highlighted("'sum", text, 40),
+            [(4, 0, "s"), (5, 0, "u"), (6, 0, "m")]
+        );
+        assert_eq!(
+            highlights(text, &[2, 7], text.len()),
+            [(3, 0, "e\u{301}"), (7, 0, "e\u{301}")]
+        );
+    }
+
+    #[test]
+    fn test_narrowing_matches_as_a_full_rescan() {
+        let names = [
+            "The Wall",
+            "The Who Live at Leeds",
+            "the white album",
+            "Who's Next",
+            "Wish You Were Here",
+            "Whole Lotta Love",
+            "Animals",
+            "wall$ street",
+        ];
+        let matched = |fuzzy: &FuzzyView| -> Vec<(String, i64, Vec<usize>)> {
+            fuzzy.items[..fuzzy.matches]
+                .iter()
+                .map(|item| {
+                    (
+                        item.display.to_owned(),
+                        item.weight,
+                        item.indices.to_owned(),
+                    )
+                })
+                .collect()
+        };
+        let rescanned = |fuzzy: &FuzzyView| {
+            let mut rescanned = FuzzyView::new(test_items(&names));
+            rescanned.match_mode = fuzzy.match_mode;
+            rescanned.paste(&fuzzy.query);
+            matched(&rescanned)
+        };
+
+        // The matches of each query as it is typed are those of the same
+        // query matched from scratch, in fuzzy and then prefix mode.
+        for (modes, query) in [
+            (0, "the wh 'live ^th"),
+            (0, "Wh e"),
+            (0, "wall$ t"),
+            (2, "the w"),
+        ] {
+            let mut fuzzy = FuzzyView::new(test_items(&names));
+            // As when the view is loaded.
+            fuzzy.update_list("");
+            for _ in 0..modes {
+                fuzzy.on_event(Event::CtrlChar('x'));
+            }
+            for ch in query.chars() {
+                let narrows = fuzzy.narrows_to(&format!("{}{ch}", fuzzy.query), &[]);
+                fuzzy.on_event(Event::Char(ch));
+                assert_eq!(matched(&fuzzy), rescanned(&fuzzy), "{:?}", fuzzy.query);
+                // Only text after a '$' is matched from scratch.
+                assert_eq!(narrows, !fuzzy.query.ends_with("$ "), "{:?}", fuzzy.query);
+            }
+            assert!(fuzzy.matches > 0, "{query:?}");
+
+            // Deleting matches from scratch, as do the other edits.
+            for _ in 0..3 {
+                assert!(!fuzzy.narrows_to(&fuzzy.query[..fuzzy.query.len() - 1], &[]));
+                fuzzy.on_event(Event::Key(Key::Backspace));
+                assert_eq!(matched(&fuzzy), rescanned(&fuzzy), "{:?}", fuzzy.query);
+            }
+        }
+
+        // Nor are the matches narrowed after matching differently.
+        let mut fuzzy = FuzzyView::new(test_items(&names));
+        fuzzy.on_event(Event::Char('W'));
+        fuzzy.set_case(Case::Ignore);
+        assert!(!fuzzy.narrows_to("Wh", &[]));
+        fuzzy.on_event(Event::CtrlChar('x'));
+        fuzzy.on_event(Event::Char('h'));
+        assert!(!fuzzy.narrows_to("Wh.", &[]));
+    }
+
+    #[test]
+    fn test_scrollbar_thumb() {
+        assert_eq!(scrollbar_thumb(10, 10, 0), None);
+        assert_eq!(scrollbar_thumb(0, 0, 0), None);
+        assert_eq!(scrollbar_thumb(10, 20, 0), Some((0, 5)));
+        assert_eq!(scrollbar_thumb(10, 20, 10), Some((5, 5)));
+        // The thumb is at least a row high, and stays on the track.
+        assert_eq!(scrollbar_thumb(10, 1000, 0), Some((0, 1)));
+        assert_eq!(scrollbar_thumb(10, 1000, 999), Some((9, 1)));
+        assert_eq!(scrollbar_thumb(10, 11, 5), Some((1, 9)));
+    }
+
+    #[test]
+    fn test_scrollbar() {
+        use crate::config::theme::Preset;
+        use crate::golden;
+
+        let names: Vec<String> = (0..20)
+            .map(|i| format!("album {i:02} {}", "x".repeat(20)))
+            .collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let mut fuzzy = FuzzyView::new(test_items(&names));
+        let rendered = golden::render(&mut fuzzy, XY::new(20, 8), Preset::Default);
+        let rows: Vec<&str> = rendered.lines().skip(1).take(8).collect();
+        // The text is cut short with "…" before the scrollbar, which is
+        // scrolled to the bottom, where the list starts.
+        assert_eq!(rows[5], "|> album 00 xxxxxxx…█|");
+        assert_eq!(rows[4], "|  album 01 xxxxxxx…│|");
+        assert_eq!(rows[1], "|  album 04 xxxxxxx…│|");
+        assert_eq!(rows[0], "|  /music        1/5 |");
+
+        fuzzy.selected = 19;
+        let rendered = golden::render(&mut fuzzy, XY::new(20, 8), Preset::Default);
+        let rows: Vec<&str> = rendered.lines().skip(1).take(8).collect();
+        assert_eq!(rows[1], "|> album 19 xxxxxxx…│|");
+        assert_eq!(rows[2], "|  album 18 xxxxxxx…█|");
+        assert_eq!(rows[5], "|  album 15 xxxxxxx…│|");
+        assert_eq!(rows[0], "|  /music        5/5 |");
+
+        // With all the matches on the screen, there is no scrollbar.
+        fuzzy.insert('9');
+        let rendered = golden::render(&mut fuzzy, XY::new(20, 8), Preset::Default);
+        assert!(!rendered.contains('█'));
+        assert!(rendered.contains("album 19 xxxxxxxx…|"));
+    }
+
+    #[test]
+    fn test_errors() {
+        use crate::config::theme::Preset;
+        use crate::golden;
+        use cursive::views::TextView;
+
+        let mut siv = Cursive::new();
+        let fuzzy = FuzzyView::new(test_items(&["abba", "abc"]));
+        layers::show(&mut siv, Role::Fuzzy, fuzzy.with_name(FuzzyView::NAME));
+        let error = |siv: &mut Cursive| {
+            siv.call_on_name(FuzzyView::NAME, |f: &mut FuzzyView| {
+                f.error().map(String::from)
+            })
+            .flatten()
+        };
+
+        // An error that doesn't need acknowledging is shown in place of the
+        // toast, even under a dialog, and takes none of the keys.
+        layers::show(&mut siv, Role::Dialog, TextView::new("New directory"));
+        ErrorView::notify(&mut siv, anyhow!("'a/b' can't contain '/'"));
+        assert_eq!(error(&mut siv).as_deref(), Some("'a/b' can't contain '/'"));
+        assert!(!layers::is_showing(&mut siv, Role::Error));
+        layers::close(&mut siv, Role::Dialog);
+
+        siv.call_on_name(FuzzyView::NAME, |fuzzy: &mut FuzzyView| {
+            let rendered = golden::render(fuzzy, XY::new(50, 6), Preset::Default);
+            let border = rendered.lines().nth(5).unwrap();
+            assert!(border.starts_with("|  2/2 ─"), "{border}");
+            assert!(
+                border.ends_with(" error: 'a/b' can't contain '/'  |"),
+                "{border}"
+            );
+
+            // Any key dismisses it, and does what it does as well.
+            fuzzy.on_event(Event::Char('c'));
+            assert_eq!(fuzzy.query, "c");
+            assert_eq!(fuzzy.error(), None);
+
+            // Otherwise it is dismissed after a few seconds.
+            fuzzy.error = Some(("gone".into(), Instant::now() - layers::ERROR_TIME));
+            assert_eq!(fuzzy.error(), None);
+        });
+
+        // An error that has to be acknowledged is shown on a layer of its own.
+        ErrorView::load(&mut siv, anyhow!("Nothing to play"));
+        assert!(layers::is_showing(&mut siv, Role::Error));
+        assert_eq!(error(&mut siv), None);
+    }
+
+    #[test]
+    fn test_copy_path_without_a_match() {
+        let mut fuzzy = FuzzyView::new(test_items(&["abba", "abc"]));
+        fuzzy.on_event(Event::Char('x'));
+        assert_eq!(fuzzy.matches, 0);
+
+        // There is nothing to copy, so nothing is tried.
+        fuzzy.on_event(Event::AltChar('y'));
+        assert!(fuzzy.toast.is_none());
+        assert_eq!(fuzzy.error(), None);
+    }
+
+    #[test]
+    fn test_reload() {
+        let mut fuzzy = FuzzyView::new(test_items(&["one", "two", "three"]));
+        fuzzy.on_event(Event::Char('t'));
+        fuzzy.selected = 1;
+
+        let result = fuzzy.on_event(Event::AltChar('r'));
+        assert!(matches!(result, EventResult::Consumed(Some(_))));
+        assert!(fuzzy.count().starts_with("reloading… 2/3"));
+
+        // The query is matched with the new items, from the first match.
+        fuzzy.reloaded(Ok(test_items(&["two", "three", "four", "twenty"])));
+        assert_eq!(fuzzy.count(), "3/4 ");
+        assert_eq!((fuzzy.selected, fuzzy.offset_y), (0, 0));
+
+        fuzzy.reloaded(Err(anyhow!("not found")));
+        assert_eq!(fuzzy.items.len(), 4);
+        assert_eq!(fuzzy.error(), Some("can't reload the directory: not found"));
+    }
+
+    #[test]
+    fn test_fuzzy_search_depth() {
+        let items = vec![
+            FuzzyItem::empty_dir(PathBuf::from("/music/rock"), 1),
+            FuzzyItem::empty_dir(PathBuf::from("/music/rock/artist"), 2),
+            FuzzyItem::empty_dir(PathBuf::from("/music/rock/artist/album"), 3),
+            FuzzyItem::empty_dir(PathBuf::from("/music/rock/artist/album/cd 1"), 4),
+        ];
+        let tab = Event::Key(Key::Tab);
+        assert_eq!(filter_items(&tab, &items, None).0.len(), 4);
+
+        // The albums are listed without the discs in them.
+        let (shallow, _) = filter_items(&tab, &items, Some(3));
+        assert_eq!(shallow.last().unwrap().display, "album");
+        assert_eq!(shallow.len(), 3);
+
+        // The depth searches aren't limited.
+        assert_eq!(
+            filter_items(&Event::Key(Key::F4), &items, Some(1)).0.len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_toggle_hidden_dirs() {
+        let dir = tempfile::Builder::new()
+            .prefix("tap-tests")
+            .tempdir()
+            .unwrap();
+        for album in ["album", ".albums"] {
+            let album = dir.path().join(album);
+            fs::create_dir(&album).unwrap();
+            fs::write(album.join("01.mp3"), "").unwrap();
+        }
+        let root = dir.path().to_path_buf();
+
+        let mut fuzzy = FuzzyView::new(create_items(&root, false).unwrap());
+        fuzzy.on_event(Event::Char('a'));
+        assert_eq!(fuzzy.count(), "1/1 ");
+
+        // The directory is scanned again with the hidden directories, and
+        // the query is kept.
+        let result = fuzzy.on_event(Event::AltChar('.'));
+        assert!(matches!(result, EventResult::Consumed(Some(_))));
+        assert!(fuzzy.hidden_dirs);
+        fuzzy.reloaded(create_items(&root, fuzzy.hidden_dirs));
+        assert_eq!(fuzzy.query, "a");
+        assert_eq!(fuzzy.count(), "2/2 ");
+
+        fuzzy.on_event(Event::AltChar('.'));
+        assert!(!fuzzy.hidden_dirs);
+        fuzzy.reloaded(create_items(&root, fuzzy.hidden_dirs));
+        assert_eq!(fuzzy.count(), "1/1 ");
+    }
+
+    #[test]
+    fn test_scanned_items() {
+        // Hidden directories aren't scanned, so the name mustn't start with '.'.
+        let dir = tempfile::Builder::new()
+            .prefix("tap-tests")
+            .tempdir()
+            .unwrap();
+        for album in ["one", "two", "three"] {
+            let album = dir.path().join("artist").join(album);
+            fs::create_dir_all(&album).unwrap();
+            fs::write(album.join("01.mp3"), "").unwrap();
+        }
+        let scanned: Vec<FuzzyItem> = crate::fuzzy::scan_items(&dir.path().to_path_buf(), false)
+            .iter()
+            .flatten()
+            .collect();
+        assert_eq!(scanned.len(), 4);
+
+        let siv = Cursive::new();
+        let mut fuzzy = FuzzyView::new(vec![]);
+        fuzzy.scanning = Some(1);
+        fuzzy.on_event(Event::Char('b'));
+        fuzzy.add_scanned(1, &mut test_items(&["abba", "beatles"]), siv.cb_sink());
+        assert_eq!(fuzzy.count(), "Scanning… 2 files ");
+        fuzzy.selected = 1;
+        let selected = fuzzy.items[1].path.to_owned();
+
+        // The selection is kept as the items are added.
+        fuzzy.add_scanned(1, &mut test_items(&["bach", "abc", "queen"]), siv.cb_sink());
+        assert_eq!(fuzzy.items[fuzzy.selected].path, selected);
+        assert_eq!(fuzzy.matches, 4);
+        assert_eq!(
+            fuzzy.items.iter().map(|item| item.original_index).max(),
+            Some(4)
+        );
+
+        // The items of another scan aren't added.
+        fuzzy.add_scanned(2, &mut test_items(&["björk"]), siv.cb_sink());
+        fuzzy.scanned(2);
+        assert_eq!(fuzzy.count(), "Scanning… 5 files ");
+
+        fuzzy.scanned(1);
+        assert_eq!(fuzzy.count(), "4/5 ");
+    }
+
+    #[test]
+    fn test_rename_albums_and_files() {
+        // Albums and files have no subdirectories, and are renamed too.
+        let mut fuzzy = FuzzyView::new(test_items(&["album"]));
+        let result = fuzzy.on_event(Event::CtrlChar('r'));
+        assert!(matches!(result, EventResult::Consumed(Some(_))));
+
+        fuzzy.rename(
+            &PathBuf::from("/music/album"),
+            &PathBuf::from("/music/renamed"),
+        );
+        let item = fuzzy.selected_item().unwrap();
+        assert_eq!(item.path, PathBuf::from("/music/renamed"));
+        assert_eq!(item.display, "renamed");
+
+        // There is nothing to rename without a match.
+        fuzzy.on_event(Event::Char('x'));
+        let result = fuzzy.on_event(Event::CtrlChar('r'));
+        assert!(matches!(result, EventResult::Consumed(None)));
+    }
+
+    #[test]
+    fn test_restore_last_search() {
+        let search = LastSearch {
+            query: String::from("b"),
+            selected: PathBuf::from("/music/bach"),
+            offset_y: 1,
+        };
+        let mut fuzzy = FuzzyView::new(test_items(&["abba", "abc", "beatles", "bach"]));
+        fuzzy.restore(&search);
+        assert_eq!(fuzzy.query, "b");
+        assert_eq!(fuzzy.cursor, 1);
+        assert_eq!(fuzzy.matches, 4);
+        assert_eq!(fuzzy.items[fuzzy.selected].display, "bach");
+        assert!(fuzzy.offset_y <= fuzzy.selected);
+
+        // The first match is selected when the item doesn't match any more.
+        let mut fuzzy = FuzzyView::new(test_items(&["abba", "abc", "beatles", "bach"]));
+        fuzzy.restore(&LastSearch {
+            query: String::from("abb"),
+            ..search
+        });
+        assert_eq!(fuzzy.selected, 0);
+        assert_eq!(fuzzy.items[0].display, "abba");
+        assert_eq!(items_dir(&fuzzy.items), Some(PathBuf::from("/music")));
+    }
+
+    #[test]
+    fn test_clear_marks_without_cancelling() {
+        let mut items = test_items(&["one", "two", "three"]);
+        for item in items.iter_mut() {
+            item.has_audio = true;
+        }
+        let mut fuzzy = FuzzyView::new(items);
+        fuzzy.on_event(Event::Key(Key::Tab));
+        fuzzy.on_event(Event::Key(Key::Up));
+        fuzzy.on_event(Event::Key(Key::Tab));
+        assert_eq!(fuzzy.marked.len(), 2);
+        assert_eq!(fuzzy.count(), "3/3 [2 marked] ");
+
+        fuzzy.on_event(Event::Char('t'));
+        let result = fuzzy.on_event(Event::Shift(Key::Tab));
+        assert!(matches!(result, EventResult::Consumed(None)));
+        assert!(fuzzy.marked.is_empty());
+        assert!(fuzzy.items.iter().all(|item| !item.selected));
+        assert_eq!(fuzzy.query, "t");
+    }
+
+    #[test]
+    fn test_score_threshold() {
+        let mut fuzzy = FuzzyView::new(test_items(&["abc", "a big cat", "xaxbxc", "dog"]));
+        for ch in "abc".chars() {
+            fuzzy.on_event(Event::Char(ch));
+        }
+        assert_eq!(fuzzy.matches, 3);
+        assert_eq!(fuzzy.count(), "3/4 ");
+
+        // Only the best match weighs as much as itself.
+        fuzzy.threshold = fuzzy.items[0].weight - THRESHOLD_STEP;
+        fuzzy.on_event(Event::AltChar('+'));
+        assert_eq!(fuzzy.matches, 1);
+        assert_eq!(fuzzy.items[0].display, "abc");
+        let count = format!("1/4 (threshold {}) ", fuzzy.threshold);
+        assert_eq!(fuzzy.count(), count);
+
+        // Lowering it to zero keeps every match again.
+        while fuzzy.threshold > 0 {
+            fuzzy.on_event(Event::AltChar('-'));
+        }
+        assert_eq!(fuzzy.matches, 3);
+        assert_eq!(fuzzy.count(), "3/4 ");
+        let result = fuzzy.on_event(Event::AltChar('-'));
+        assert!(matches!(result, EventResult::Consumed(None)));
+    }
+
+    #[test]
+    fn test_delete_items() {
+        let mut fuzzy = FuzzyView::new(test_items(&["abba", "abc", "beatles", "bach"]));
+        let result = fuzzy.on_event(Event::Shift(Key::Del));
+        assert!(matches!(result, EventResult::Consumed(Some(_))));
+
+        // The match after the deleted one is selected in its place.
+        fuzzy.insert('a');
+        fuzzy.insert('b');
+        assert_eq!(fuzzy.matches, 2);
+        fuzzy.selected = 1;
+        let deleted = fuzzy.items[1].path.to_owned();
+        let other = fuzzy.items[0].path.to_owned();
+        fuzzy.remove(&deleted);
+        assert_eq!(fuzzy.items.len(), 3);
+        assert_eq!(fuzzy.matches, 1);
+        assert_eq!(fuzzy.selected_item().unwrap().path, other);
+
+        // Deleting a folder removes the items inside it too.
+        fuzzy
+            .items
+            .push(FuzzyItem::empty_dir(PathBuf::from("/music/bach/suites"), 2));
+        fuzzy.remove(Path::new("/music/bach"));
+        assert_eq!(fuzzy.items.len(), 2);
+        assert_eq!(fuzzy.matches, 1);
+    }
+
+    #[test]
+    fn test_merge_library() {
+        let mut fuzzy = FuzzyView::new(test_items(&["abba", "beatles", "bach"]));
+        fuzzy.insert('b');
+        fuzzy.selected = fuzzy
+            .items
+            .iter()
+            .position(|e| e.display == "bach")
+            .unwrap();
+
+        let mut library = test_items(&["abba", "bach", "bjork"]);
+        library[0].audio_count = 12;
+        let changes = Changes::Batch {
+            added: vec![PathBuf::from("/music/bjork")],
+            removed: vec![PathBuf::from("/music/beatles")],
+            refreshed: vec![PathBuf::from("/music/abba")],
+        };
+        fuzzy.merge_library(&changes, &library);
+        assert_eq!(fuzzy.query, "b");
+        assert_eq!(fuzzy.items.len(), 3);
+        assert_eq!(fuzzy.items[fuzzy.selected].display, "bach");
+        let abba = fuzzy.items.iter().find(|e| e.display == "abba").unwrap();
+        assert_eq!(abba.audio_count, 12);
+
+        // The changes outside the current directory aren't merged.
+        library.extend(test_items(&["cars"]));
+        let elsewhere = Changes::Batch {
+            added: vec![PathBuf::from("/podcasts/show")],
+            removed: vec![],
+            refreshed: vec![],
+        };
+        fuzzy.merge_library(&elsewhere, &library);
+        assert_eq!(fuzzy.items.len(), 3);
+
+        // Only some of the items are listed, so there is nothing to add.
+        fuzzy.set_partial(true);
+        fuzzy.merge_library(&Changes::Rescan, &library);
+        assert_eq!(fuzzy.items.len(), 3);
+        fuzzy.set_partial(false);
+        fuzzy.merge_library(&Changes::Rescan, &library);
+        assert_eq!(fuzzy.items.len(), 4);
+    }
+
+    #[test]
+    fn test_favorites() {
+        use crate::config::theme::Preset;
+        use crate::golden;
+
+        let mut fuzzy = FuzzyView::new(test_items(&["one", "two", "three"]));
+        let result = fuzzy.on_event(Event::CtrlChar('b'));
+        assert!(matches!(result, EventResult::Consumed(Some(_))));
+        assert!(fuzzy.favorites.contains(Path::new("/music/one")));
+
+        // Favorites are starred unless they are marked.
+        let rendered = golden::render(&mut fuzzy, XY::new(20, 8), Preset::Default);
+        assert!(rendered.contains("|>*one"), "{rendered}");
+        assert!(rendered.contains("|  three"), "{rendered}");
+        fuzzy.items[0].has_audio = true;
+        fuzzy.on_event(Event::Key(Key::Tab));
+        let rendered = golden::render(&mut fuzzy, XY::new(20, 8), Preset::Default);
+        assert!(rendered.contains("|>+one"), "{rendered}");
+        fuzzy.clear_marks();
+
+        // Only the favorites are matched until it is toggled back.
+        fuzzy.on_event(Event::AltChar('B'));
+        assert_eq!(fuzzy.count(), "1/3 [favorites] ");
+        fuzzy.on_event(Event::Char('t'));
+        assert_eq!(fuzzy.matches, 0);
+        fuzzy.on_event(Event::Key(Key::Backspace));
+        assert_eq!(fuzzy.matches, 1);
+
+        // Unstarring a favorite removes it from the matches.
+        fuzzy.on_event(Event::CtrlChar('b'));
+        assert_eq!(fuzzy.matches, 0);
+        fuzzy.on_event(Event::AltChar('B'));
+        assert_eq!(fuzzy.count(), "3/3 ");
+    }
+
+    #[test]
+    fn test_write_matches() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("results.txt");
+        let mut fuzzy = FuzzyView::new(test_items(&["one", "two", "three"]));
+        fuzzy.on_event(Event::Char('o'));
+
+        assert_eq!(fuzzy.write_matches(&file).unwrap(), 2);
+        let text = std::fs::read_to_string(&file).unwrap();
+        assert_eq!(text, "/music/one\n/music/two\n");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&file).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        // Without matches, the file is emptied.
+        fuzzy.on_event(Event::Char('x'));
+        assert_eq!(fuzzy.write_matches(&file).unwrap(), 0);
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "");
+        assert!(fuzzy
+            .write_matches(&dir.path().join("missing/results.txt"))
+            .is_err());
+    }
+
+    #[test]
+    fn test_parent_search() {
+        let dir = |path: &str| FuzzyItem::empty_dir(PathBuf::from(path), 1);
+        // The albums of an artist that was selected in the search of the
+        // artists, at depth 1.
+        let mut fuzzy = FuzzyView::new(vec![dir("/music/artist/album")]);
+        fuzzy.root = PathBuf::from("/music");
+        assert_eq!(fuzzy.parent_search(), None);
+        fuzzy.depth_search = Some(1);
+        assert_eq!(fuzzy.parent_search(), Some(1));
+
+        // Deeper folders and the search itself have a parent folder instead.
+        fuzzy.items = vec![dir("/music/artist/album/disc 1")];
+        assert_eq!(fuzzy.parent_search(), None);
+        fuzzy.items = vec![dir("/music/artist")];
+        assert_eq!(fuzzy.parent_search(), None);
+    }
+
+    #[test]
+    fn test_indices() {
+        use crate::config::theme::Preset;
+        use crate::golden;
+
+        let names: Vec<String> = (0..12).map(|i| format!("album {i:02}")).collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let mut fuzzy = FuzzyView::new(test_items(&names));
+        fuzzy.on_event(Event::AltChar('#'));
+        let rendered = golden::render(&mut fuzzy, XY::new(24, 16), Preset::Default);
+        assert!(rendered.contains("|>  1 album 00 "), "{rendered}");
+        assert!(rendered.contains("|  12 album 11 "), "{rendered}");
+
+        // The positions are of the matches, rather than of all the items.
+        fuzzy.insert('1');
+        let rendered = golden::render(&mut fuzzy, XY::new(24, 16), Preset::Default);
+        assert_eq!(fuzzy.matches, 3);
+        assert!(rendered.contains("|> 1 album "), "{rendered}");
+        assert!(rendered.contains("|  3 album "), "{rendered}");
+
+        fuzzy.on_event(Event::AltChar('#'));
+        let rendered = golden::render(&mut fuzzy, XY::new(24, 16), Preset::Default);
+        assert!(rendered.contains("|> album "), "{rendered}");
+    }
+
+    #[test]
+    fn test_random_pages() {
+        let names: Vec<String> = (0..20).map(|i| format!("album {i:02}")).collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let mut fuzzy = FuzzyView::new(test_items(&names));
+        fuzzy.layout(XY::new(20, 8));
+        assert_eq!(fuzzy.page_len(), 4);
+        let random_page = |fuzzy: &mut FuzzyView| {
+            fuzzy.on_event(Event::CtrlChar('z'));
+            assert_eq!(fuzzy.selected, fuzzy.offset_y);
+            fuzzy.offset_y / 4
+        };
+
+        // Each of the other pages is visited before any is visited again,
+        // and the page that is shown is never picked.
+        let mut pages: Vec<usize> = (0..4).map(|_| random_page(&mut fuzzy)).collect();
+        pages.sort();
+        assert_eq!(pages, vec![1, 2, 3, 4]);
+        let mut page = random_page(&mut fuzzy);
+        for _ in 0..20 {
+            let next = random_page(&mut fuzzy);
+            assert_ne!(next, page);
+            page = next;
+        }
+
+        // Only the pages of the matches are picked.
+        for name in &names[..12] {
+            fuzzy.hidden.insert(PathBuf::from("/music").join(name));
+        }
+        for _ in 0..10 {
+            assert!(random_page(&mut fuzzy) < 2);
+        }
+        assert_eq!(fuzzy.matches, 8);
+    }
+
+    #[test]
+    fn test_breadcrumb() {
+        let path = "/music/artist/album";
+        assert_eq!(breadcrumb(path, 40), path);
+        assert_eq!(breadcrumb(path, 14), "…/artist/album");
+        assert_eq!(breadcrumb(path, 8), "…/album");
+        // The last directory is cut when it doesn't fit by itself.
+        assert_eq!(breadcrumb(path, 5), "…/bum");
+        assert_eq!(breadcrumb("/音楽/宇多田ヒカル", 10), "…/田ヒカル");
+    }
+
+    #[test]
+    fn test_header_shows_the_directory() {
+        use crate::config::theme::Preset;
+        use crate::golden;
+
+        // The items of a directory are one deep.
+        let items = vec![FuzzyItem::empty_dir(
+            PathBuf::from("/music/Artist/Album"),
+            1,
+        )];
+        let mut fuzzy = FuzzyView::new(items);
+        let rendered = golden::render(&mut fuzzy, XY::new(30, 6), Preset::Default);
+        assert_eq!(
+            rendered.lines().nth(1),
+            Some("|  /music/Artist            1/1|")
+        );
+        assert_eq!(
+            rendered.lines().nth(4),
+            Some("|> Album                       |")
+        );
+        let rendered = golden::render(&mut fuzzy, XY::new(16, 6), Preset::Default);
+        assert_eq!(rendered.lines().nth(1), Some("|  …/Artist   1/1|"));
+
+        // Deeper items are in the directory that they were searched from.
+        let items = vec![FuzzyItem::empty_dir(
+            PathBuf::from("/music/Artist/Album"),
+            2,
+        )];
+        let mut fuzzy = FuzzyView::new(items);
+        let rendered = golden::render(&mut fuzzy, XY::new(30, 6), Preset::Default);
+        assert_eq!(
+            rendered.lines().nth(1),
+            Some("|  /music                   1/1|")
+        );
     }
 }
@@ -0,0 +1,234 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    thread,
+};
+
+use super::{
+    fuzzy_match, prefix_match,
+    query::{self, Case},
+    regex_match, FuzzyItem, MatchMode,
+};
+
+// The number of items from which the query is matched on the worker, rather
+// than on each keystroke.
+pub const MIN_ITEMS: usize = 5_000;
+
+// The number of items that are matched between checks for a newer query.
+const CHUNK_LEN: usize = 1_000;
+
+// The generation of the latest query sent to any worker, so that the matches
+// of one fuzzy finder are never taken for those of another.
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+// What to match the items with.
+#[derive(Clone, Debug)]
+pub struct Query {
+    pub pattern: String,
+    pub case: Case,
+    pub mode: MatchMode,
+    // The root that the paths are matched relative to, if the paths are
+    // matched instead of the display names.
+    pub root: Option<PathBuf>,
+    // The weight that the fuzzy matches need at least.
+    pub threshold: i64,
+}
+
+// The matches of a query.
+#[derive(Debug)]
+pub struct Matches {
+    pub generation: u64,
+    // The weight and the matched indices of each matched item, by path.
+    pub matched: HashMap<PathBuf, (i64, Vec<usize>)>,
+    // Why the pattern isn't a valid regex, in regex mode.
+    pub regex_error: Option<String>,
+}
+
+enum Job {
+    Items(Vec<FuzzyItem>),
+    Match(u64, Query),
+}
+
+// Matches the queries on a thread of its own, so that typing doesn't wait
+// for the matches in a large library. A query that is replaced by a newer one
+// is abandoned. The thread stops when the worker is dropped.
+pub struct MatchWorker {
+    jobs: Sender<Job>,
+    // The generation of the latest query sent to the thread.
+    latest: Arc<AtomicU64>,
+}
+
+impl MatchWorker {
+    // Starts matching a copy of `items`, passing the matches of each query
+    // that isn't abandoned to `send`, until it returns false.
+    pub fn start(items: Vec<FuzzyItem>, send: impl Fn(Matches) -> bool + Send + 'static) -> Self {
+        let (jobs, rx) = mpsc::channel();
+        let latest = Arc::new(AtomicU64::new(0));
+        let worker = MatchWorker {
+            jobs,
+            latest: latest.clone(),
+        };
+
+        worker.set_items(items);
+        thread::spawn(move || run(rx, latest, send));
+        worker
+    }
+
+    // Replaces the items that are matched, after they have changed.
+    pub fn set_items(&self, items: Vec<FuzzyItem>) {
+        _ = self.jobs.send(Job::Items(items));
+    }
+
+    // Sends `query` to be matched, abandoning any earlier one. Returns the
+    // generation of its matches.
+    pub fn send(&self, query: Query) -> u64 {
+        let generation = GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+        self.latest.store(generation, Ordering::SeqCst);
+        _ = self.jobs.send(Job::Match(generation, query));
+        generation
+    }
+}
+
+fn run(jobs: Receiver<Job>, latest: Arc<AtomicU64>, send: impl Fn(Matches) -> bool) {
+    let mut items = vec![];
+
+    while let Ok(job) = jobs.recv() {
+        // Only the latest of the waiting queries is matched.
+        let mut query = None;
+        for job in std::iter::once(job).chain(jobs.try_iter()) {
+            match job {
+                Job::Items(new_items) => items = new_items,
+                Job::Match(generation, new_query) => query = Some((generation, new_query)),
+            }
+        }
+
+        if let Some((generation, query)) = query {
+            let is_current = || latest.load(Ordering::SeqCst) == generation;
+            let matches = match_items(&mut items, generation, &query, is_current);
+            if matches.is_some_and(|matches| !send(matches)) {
+                return;
+            }
+        }
+    }
+}
+
+// Matches `items` with `query` a chunk at a time, or returns `None` as soon
+// as `is_current` is false between chunks.
+fn match_items(
+    items: &mut [FuzzyItem],
+    generation: u64,
+    query: &Query,
+    is_current: impl Fn() -> bool,
+) -> Option<Matches> {
+    let root = query.root.as_deref();
+    let regex = match query.mode {
+        MatchMode::Regex => match query.case.regex(&query.pattern) {
+            Ok(regex) => Some(regex),
+            // Nothing matches until the pattern is valid again.
+            Err(e) => {
+                return Some(Matches {
+                    generation,
+                    matched: HashMap::new(),
+                    regex_error: Some(e),
+                })
+            }
+        },
+        _ => None,
+    };
+    let terms = query::parse(&query.pattern);
+    let matcher = query.case.matcher();
+
+    for chunk in items.chunks_mut(CHUNK_LEN) {
+        if !is_current() {
+            return None;
+        }
+        match (query.mode, &regex) {
+            (_, Some(regex)) => regex_match(chunk, regex, root),
+            (MatchMode::Prefix, _) => prefix_match(chunk, &query.pattern, query.case, root),
+            _ => fuzzy_match(chunk, &terms, &matcher, query.case, root, query.threshold),
+        };
+    }
+
+    let matched = items
+        .iter()
+        .filter(|item| item.weight != 0)
+        .map(|item| (item.path.to_owned(), (item.weight, item.indices.to_owned())))
+        .collect();
+    Some(Matches {
+        generation,
+        matched,
+        regex_error: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn test_items(count: usize) -> Vec<FuzzyItem> {
+        (0..count)
+            .map(|i| FuzzyItem::empty_dir(PathBuf::from(format!("/music/album {i:05}")), 1))
+            .collect()
+    }
+
+    fn query(pattern: &str) -> Query {
+        Query {
+            pattern: pattern.to_string(),
+            case: Case::Smart,
+            mode: MatchMode::Fuzzy,
+            root: None,
+            threshold: 0,
+        }
+    }
+
+    #[test]
+    fn test_only_the_latest_query_is_sent() {
+        let (tx, rx) = mpsc::channel();
+        let worker = MatchWorker::start(test_items(MIN_ITEMS), move |m| tx.send(m).is_ok());
+
+        for pattern in ["a", "al", "alb", "album 0001"] {
+            worker.send(query(pattern));
+        }
+        let latest = worker.send(query("album 00012"));
+
+        // Earlier queries may be sent before they are replaced, but never
+        // after the latest one.
+        let matches = rx
+            .iter()
+            .find(|matches| matches.generation == latest)
+            .expect("the latest query should be matched");
+        assert_eq!(matches.matched.len(), 1);
+        assert!(matches
+            .matched
+            .contains_key(&PathBuf::from("/music/album 00012")));
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+
+        // The items can be replaced.
+        worker.set_items(test_items(3));
+        let generation = worker.send(query("album"));
+        let matches = rx.recv().unwrap();
+        assert_eq!(matches.generation, generation);
+        assert_eq!(matches.matched.len(), 3);
+    }
+
+    #[test]
+    fn test_abandoned_and_invalid_queries() {
+        let mut items = test_items(CHUNK_LEN * 2);
+        assert!(match_items(&mut items, 1, &query("album"), || false).is_none());
+
+        let regex = Query {
+            mode: MatchMode::Regex,
+            ..query("album (")
+        };
+        let matches = match_items(&mut items, 2, &regex, || true).unwrap();
+        assert!(matches.matched.is_empty());
+        assert!(matches.regex_error.is_some());
+    }
+}
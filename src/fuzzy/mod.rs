@@ -1,9 +1,16 @@
+pub mod changes;
 pub mod error_view;
+pub mod file_ops;
 pub mod fuzzy;
 pub mod fuzzy_view;
+pub mod match_worker;
+pub mod query;
+pub mod two_pane;
+pub mod watch;
 
 pub use self::{
     error_view::ErrorView,
     fuzzy::*,
     fuzzy_view::{fuzzy_finder, trigger, FuzzyView},
+    two_pane::TwoPaneView,
 };
@@ -0,0 +1,304 @@
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use regex::{Regex, RegexBuilder};
+
+// A term of the fuzzy query. The terms are separated by spaces, and an item
+// has to match all of them.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Term {
+    Fuzzy(String),
+    // `'text`, matched as a substring.
+    Exact(String),
+    // `^text`, matched at the start.
+    Prefix(String),
+    // `text$`, matched at the end.
+    Suffix(String),
+    // `^text$`, matched as the whole text.
+    Whole(String),
+}
+
+// How the case of the letters is matched.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Case {
+    // The case is ignored unless a term has uppercase letters.
+    #[default]
+    Smart,
+    // The case always counts.
+    Respect,
+    // The case never counts.
+    Ignore,
+}
+
+impl Case {
+    // The mode after this one, when cycling through them.
+    pub fn next(self) -> Self {
+        match self {
+            Case::Smart => Case::Respect,
+            Case::Respect => Case::Ignore,
+            Case::Ignore => Case::Smart,
+        }
+    }
+
+    // The name of the mode, shown with the match count when it isn't smart.
+    pub fn name(self) -> &'static str {
+        match self {
+            Case::Smart => "smart case",
+            Case::Respect => "match case",
+            Case::Ignore => "ignore case",
+        }
+    }
+
+    // The fuzzy matcher for the mode.
+    pub fn matcher(self) -> SkimMatcherV2 {
+        let matcher = SkimMatcherV2::default();
+        match self {
+            Case::Smart => matcher.smart_case(),
+            Case::Respect => matcher.respect_case(),
+            Case::Ignore => matcher.ignore_case(),
+        }
+    }
+
+    // Whether or not `text` starts with `prefix`, matched with the mode.
+    pub fn is_prefix(self, prefix: &str, text: &str) -> bool {
+        starts_with(text, prefix, 0, self.ignores(prefix)).is_some()
+    }
+
+    // The regex of `pattern`, matched with the mode, or a short description of
+    // why it isn't valid.
+    pub fn regex(self, pattern: &str) -> Result<Regex, String> {
+        RegexBuilder::new(pattern)
+            .case_insensitive(self.ignores(pattern))
+            .build()
+            .map_err(|e| {
+                // The last line of a syntax error says what is wrong, after
+                // the pattern with the position marked.
+                let msg = e.to_string();
+                msg.lines().last().unwrap_or_default().to_owned()
+            })
+    }
+
+    // Whether or not the case is ignored when matching `term`.
+    fn ignores(self, term: &str) -> bool {
+        match self {
+            Case::Smart => !term.chars().any(char::is_uppercase),
+            Case::Respect => false,
+            Case::Ignore => true,
+        }
+    }
+}
+
+// Splits `query` into its terms, leaving out the operators without any text.
+pub fn parse(query: &str) -> Vec<Term> {
+    query
+        .split_whitespace()
+        .filter_map(|word| {
+            let term = match word.strip_prefix('\'') {
+                Some(text) => Term::Exact(text.into()),
+                None => match (word.strip_prefix('^'), word.strip_suffix('$')) {
+                    (Some(_), Some(_)) if word.len() > 1 => {
+                        Term::Whole(word[1..word.len() - 1].into())
+                    }
+                    (Some(text), _) => Term::Prefix(text.into()),
+                    (None, Some(text)) => Term::Suffix(text.into()),
+                    (None, None) => Term::Fuzzy(word.into()),
+                },
+            };
+            match term.text().is_empty() {
+                true => None,
+                false => Some(term),
+            }
+        })
+        .collect()
+}
+
+// Splits the extension filter off the end of `query`, as in
+// `beethoven :ext flac,ogg`, into the rest of the query and the extensions,
+// lowercased and without any dots.
+pub fn split_extensions(query: &str) -> (&str, Vec<String>) {
+    // Whether or not the `:ext` at `i` is a word of its own.
+    let is_word = |&i: &usize| {
+        (i == 0 || query[..i].ends_with(char::is_whitespace))
+            && (i + 4 == query.len() || query[i + 4..].starts_with(char::is_whitespace))
+    };
+    let start = query
+        .match_indices(":ext")
+        .map(|(i, _)| i)
+        .filter(is_word)
+        .last();
+    match start {
+        Some(i) => {
+            let extensions = query[i + 4..]
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .map(|ext| ext.trim_start_matches('.').to_lowercase())
+                .filter(|ext| !ext.is_empty())
+                .collect();
+            (query[..i].trim_end(), extensions)
+        }
+        None => (query, vec![]),
+    }
+}
+
+impl Term {
+    // The term as it is typed, with its operators.
+    pub fn typed(&self) -> String {
+        match self {
+            Term::Fuzzy(text) => text.to_owned(),
+            Term::Exact(text) => format!("'{text}"),
+            Term::Prefix(text) => format!("^{text}"),
+            Term::Suffix(text) => format!("{text}$"),
+            Term::Whole(text) => format!("^{text}$"),
+        }
+    }
+
+    fn text(&self) -> &str {
+        match self {
+            Term::Fuzzy(text)
+            | Term::Exact(text)
+            | Term::Prefix(text)
+            | Term::Suffix(text)
+            | Term::Whole(text) => text,
+        }
+    }
+
+    // The weight of the match of the term in `text` and the char indices that
+    // are matched, or `None` if it doesn't match. `matcher` is the matcher of
+    // `case`.
+    pub fn find(
+        &self,
+        matcher: &SkimMatcherV2,
+        case: Case,
+        text: &str,
+    ) -> Option<(i64, Vec<usize>)> {
+        let term = self.text();
+        let ignore_case = case.ignores(term);
+        let start = match self {
+            Term::Fuzzy(_) => return matcher.fuzzy_indices(text, term),
+            Term::Exact(_) => find_chars(text, term, ignore_case),
+            Term::Prefix(_) => starts_with(text, term, 0, ignore_case),
+            Term::Suffix(_) => {
+                let start = text.chars().count().checked_sub(term.chars().count())?;
+                starts_with(text, term, start, ignore_case)
+            }
+            Term::Whole(_) => match text.chars().count() == term.chars().count() {
+                true => starts_with(text, term, 0, ignore_case),
+                false => None,
+            },
+        }?;
+
+        // The exact terms are weighted as if they had been fuzzy matched, so
+        // that they rank with the fuzzy terms.
+        let weight = matcher.fuzzy_match(text, term).unwrap_or(1).max(1);
+        Some((weight, (start..start + term.chars().count()).collect()))
+    }
+}
+
+// The char index of the first place that `term` is in `text`.
+fn find_chars(text: &str, term: &str, ignore_case: bool) -> Option<usize> {
+    (0..=text.chars().count()).find_map(|start| starts_with(text, term, start, ignore_case))
+}
+
+// Returns `start` if `text` has `term` at the char index `start`.
+fn starts_with(text: &str, term: &str, start: usize, ignore_case: bool) -> Option<usize> {
+    let mut chars = text.chars().skip(start);
+
+    let matches = term.chars().all(|t| match chars.next() {
+        Some(c) if ignore_case => c.to_lowercase().eq(t.to_lowercase()),
+        Some(c) => c == t,
+        None => false,
+    });
+    match matches {
+        true => Some(start),
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse("^the  'live blue$ ^kind$ fuzzy ' ^"),
+            vec![
+                Term::Prefix("the".into()),
+                Term::Exact("live".into()),
+                Term::Suffix("blue".into()),
+                Term::Whole("kind".into()),
+                Term::Fuzzy("fuzzy".into()),
+            ]
+        );
+        assert_eq!(parse("'^the"), vec![Term::Exact("^the".into())]);
+        assert_eq!(parse("$"), vec![]);
+
+        let typed: Vec<String> = parse("^the  'live blue$ ^kind$ fuzzy")
+            .iter()
+            .map(Term::typed)
+            .collect();
+        assert_eq!(typed.join(" "), "^the 'live blue$ ^kind$ fuzzy");
+    }
+
+    #[test]
+    fn test_split_extensions() {
+        assert_eq!(
+            split_extensions("beethoven :ext flac,.OGG"),
+            ("beethoven", vec!["flac".to_string(), "ogg".to_string()])
+        );
+        assert_eq!(split_extensions(":ext mp3"), ("", vec!["mp3".to_string()]));
+        // The filter is being typed.
+        assert_eq!(split_extensions("bach :ext"), ("bach", vec![]));
+        // Only a word of its own starts the filter.
+        assert_eq!(split_extensions("a:ext mp3"), ("a:ext mp3", vec![]));
+        assert_eq!(split_extensions(":extra"), (":extra", vec![]));
+    }
+
+    #[test]
+    fn test_find() {
+        let matcher = Case::Smart.matcher();
+        let indices = |term: &str, text: &str| {
+            let terms = parse(term);
+            terms[0]
+                .find(&matcher, Case::Smart, text)
+                .map(|(_, indices)| indices)
+        };
+
+        assert_eq!(indices("'live", "Alive At Leeds"), Some(vec![1, 2, 3, 4]));
+        assert_eq!(indices("'lve", "Alive At Leeds"), None);
+        assert_eq!(indices("^the", "The Wall"), Some(vec![0, 1, 2]));
+        assert_eq!(indices("^wall", "The Wall"), None);
+        assert_eq!(indices("wall$", "The Wall"), Some(vec![4, 5, 6, 7]));
+        assert_eq!(indices("^the$", "The Wall"), None);
+        assert_eq!(indices("^the$", "The"), Some(vec![0, 1, 2]));
+
+        // Uppercase letters make the case count.
+        assert_eq!(indices("'Wall", "the wall"), None);
+        // The indices count chars, not bytes.
+        assert_eq!(indices("'été", "L'été indien"), Some(vec![2, 3, 4]));
+        assert_eq!(indices("'x", "ab"), None);
+    }
+
+    #[test]
+    fn test_case() {
+        let matches = |case: Case, term: &str, text: &str| {
+            parse(term)[0].find(&case.matcher(), case, text).is_some()
+        };
+
+        for term in ["'wall", "wall"] {
+            assert!(matches(Case::Smart, term, "The Wall"));
+            assert!(!matches(Case::Respect, term, "The Wall"));
+            assert!(matches(Case::Ignore, term, "The Wall"));
+        }
+        for term in ["'Wall", "Wall"] {
+            assert!(!matches(Case::Smart, term, "the wall"));
+            assert!(!matches(Case::Respect, term, "the wall"));
+            assert!(matches(Case::Ignore, term, "the wall"));
+        }
+    }
+
+    #[test]
+    fn test_regex() {
+        assert!(Case::Smart.regex("^the w").unwrap().is_match("The Wall"));
+        assert!(!Case::Smart.regex("^The w").unwrap().is_match("the wall"));
+        assert!(!Case::Respect.regex("wall").unwrap().is_match("The Wall"));
+        assert_eq!(Case::Smart.regex("(").unwrap_err(), "error: unclosed group");
+    }
+}
@@ -0,0 +1,256 @@
+use std::path::{Path, PathBuf};
+
+use cursive::{
+    event::{Event, EventResult, Key, MouseEvent},
+    view::{Nameable, Resizable},
+    Cursive, Printer, View, XY,
+};
+
+use crate::config::theme;
+use crate::layers::{self, Role};
+
+use super::{audio_file_names, replace_prefix, FuzzyItem, FuzzyView};
+
+// The fuzzy-finder in two panes, with the folders on the left and the audio
+// files of the selected folder on the right.
+pub struct TwoPaneView {
+    // The folders, and the items they were made from, to go back to one pane.
+    dirs: FuzzyView,
+    items: Vec<FuzzyItem>,
+    // The audio files of the folder `files_of`.
+    files: FuzzyView,
+    files_of: Option<PathBuf>,
+    // Whether or not the keys go to the files, rather than the folders.
+    files_focused: bool,
+    // The size of the view.
+    size: XY<usize>,
+}
+
+impl TwoPaneView {
+    // The name used to find the two panes in the view stack.
+    pub const NAME: &'static str = "two_pane";
+
+    fn new(dirs: FuzzyView, items: Vec<FuzzyItem>) -> Self {
+        let mut view = TwoPaneView {
+            dirs,
+            items,
+            files: FuzzyView::new(vec![]),
+            files_of: None,
+            files_focused: false,
+            size: XY { x: 0, y: 0 },
+        };
+        view.update_files();
+        view
+    }
+
+    // Shows `items` in two panes, in place of the fuzzy-finder.
+    pub fn load(items: Vec<FuzzyItem>, siv: &mut Cursive) {
+        let dirs = FuzzyView::with_session(items.to_owned(), siv);
+        let view = TwoPaneView::new(dirs, items);
+        layers::show(siv, Role::Fuzzy, view.with_name(Self::NAME).full_screen());
+    }
+
+    // Lists the audio files of the selected folder, if another one has been
+    // selected.
+    fn update_files(&mut self) {
+        let selected = self.dirs.selected_item().map(|item| item.path.to_owned());
+        if selected == self.files_of {
+            return;
+        }
+        let files = match &selected {
+            Some(dir) => audio_file_names(dir)
+                .into_iter()
+                .map(|name| FuzzyItem::audio_file(dir.join(name), 1))
+                .collect(),
+            None => vec![],
+        };
+        self.files = FuzzyView::new(files);
+        self.files_of = selected;
+    }
+
+    // Updates the items after the directory or the file at `old` has been
+    // renamed to `new`.
+    pub fn rename(&mut self, old: &PathBuf, new: &PathBuf) {
+        self.dirs.rename(old, new);
+        self.files.rename(old, new);
+        for item in self.items.iter_mut() {
+            item.rename(old, new);
+        }
+        if let Some(files_of) = self.files_of.as_mut() {
+            replace_prefix(files_of, old, new);
+        }
+    }
+
+    // Removes the items at or inside `path` after it has been deleted.
+    pub fn remove(&mut self, path: &Path) {
+        self.dirs.remove(path);
+        self.files.remove(path);
+        self.items.retain(|item| !item.path.starts_with(path));
+        self.update_files();
+    }
+
+    // The number of columns of the folders, left of the line between the
+    // panes.
+    fn dirs_width(&self) -> usize {
+        self.size.x / 2
+    }
+
+    // Passes the mouse `event` to the pane that it is over, or to the pane
+    // that is being dragged in, focusing the pane that is clicked.
+    fn on_mouse(
+        &mut self,
+        offset: XY<usize>,
+        position: XY<usize>,
+        event: MouseEvent,
+    ) -> EventResult {
+        let split = offset.x + self.dirs_width();
+        let on_files = match event {
+            MouseEvent::Hold(_) | MouseEvent::Release(_) => self.files_focused,
+            _ => position.x > split,
+        };
+        if let MouseEvent::Press(_) = event {
+            self.files_focused = on_files;
+        }
+
+        match on_files {
+            true => self.files.on_event(Event::Mouse {
+                offset: XY::new(split + 1, offset.y),
+                position,
+                event,
+            }),
+            false => self.dirs.on_event(Event::Mouse {
+                offset,
+                position,
+                event,
+            }),
+        }
+    }
+}
+
+impl View for TwoPaneView {
+    fn layout(&mut self, size: XY<usize>) {
+        self.size = size;
+        let dirs_w = self.dirs_width();
+        self.dirs.layout(XY::new(dirs_w, size.y));
+        self.files
+            .layout(XY::new(size.x.saturating_sub(dirs_w + 1), size.y));
+    }
+
+    fn draw(&self, p: &Printer) {
+        let dirs_w = self.dirs_width();
+        if dirs_w == 0 {
+            return;
+        }
+        self.dirs
+            .draw(&p.cropped((dirs_w, p.size.y)).focused(!self.files_focused));
+        p.with_color(theme::progress(), |p| {
+            p.print_vline((dirs_w, 0), p.size.y, "│")
+        });
+        self.files
+            .draw(&p.offset((dirs_w + 1, 0)).focused(self.files_focused));
+    }
+
+    fn on_event(&mut self, event: Event) -> EventResult {
+        let result = match event {
+            Event::Key(Key::Tab) => {
+                self.files_focused = !self.files_focused;
+                EventResult::Consumed(None)
+            }
+            // Selecting a folder moves to its files, which are played when
+            // they are selected.
+            Event::Key(Key::Enter) if !self.files_focused => {
+                self.files_focused = true;
+                EventResult::Consumed(None)
+            }
+            Event::AltChar('t') => {
+                let items = self.items.to_owned();
+                EventResult::with_cb(move |siv| FuzzyView::load(items.to_owned(), None, siv))
+            }
+            // The panes are scanned again by name, which they don't have.
+            Event::AltChar('r') | Event::AltChar('.') => EventResult::Ignored,
+            Event::Mouse {
+                offset,
+                position,
+                event,
+            } => self.on_mouse(offset, position, event),
+            event => match self.files_focused {
+                true => self.files.on_event(event),
+                false => self.dirs.on_event(event),
+            },
+        };
+        self.update_files();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_two_panes() {
+        use crate::config::theme::Preset;
+        use crate::golden;
+
+        let dir = tempfile::Builder::new()
+            .prefix("tap-tests")
+            .tempdir()
+            .unwrap();
+        let mut items = vec![];
+        for (album, tracks) in [
+            ("one", ["01 a.mp3", "02 b.mp3"]),
+            ("two", ["01 c.mp3", "02 d.mp3"]),
+        ] {
+            let album = dir.path().join(album);
+            fs::create_dir(&album).unwrap();
+            for track in tracks {
+                fs::write(album.join(track), "").unwrap();
+            }
+            items.push(FuzzyItem::empty_dir(album, 1));
+        }
+
+        let mut view = TwoPaneView::new(FuzzyView::new(items.to_owned()), items.to_owned());
+        assert_eq!(view.files_of, Some(items[0].path.to_owned()));
+        let rendered = golden::render(&mut view, XY::new(40, 6), Preset::Default);
+        let rows: Vec<&str> = rendered.lines().skip(1).take(6).collect();
+        assert!(rows[3].starts_with("|> one "), "{}", rows[3]);
+        assert!(rows[3].contains("│> 01 a.mp3"), "{}", rows[3]);
+        assert!(rows[2].contains("│  02 b.mp3"), "{}", rows[2]);
+
+        // Moving on the folders lists the files of the selected one.
+        view.on_event(Event::Key(Key::Up));
+        assert_eq!(view.files_of, Some(items[1].path.to_owned()));
+
+        // The files are focused by selecting the folder or with Tab, which
+        // moves back again.
+        view.on_event(Event::Key(Key::Enter));
+        assert!(view.files_focused);
+        view.on_event(Event::Key(Key::Up));
+        assert_eq!(view.files_of, Some(items[1].path.to_owned()));
+        view.on_event(Event::Key(Key::Tab));
+        assert!(!view.files_focused);
+
+        // The renamed files are renamed in their pane.
+        let track = items[1].path.join("02 d.mp3");
+        let renamed = items[1].path.join("02 e.mp3");
+        view.rename(&track, &renamed);
+        assert_eq!(view.files.selected_item().unwrap().display, "02 e.mp3");
+
+        // The deleted files are removed from their pane, and the files of a
+        // deleted folder with it.
+        view.remove(&renamed);
+        assert_eq!(view.files.selected_item().unwrap().display, "01 c.mp3");
+        view.remove(&items[1].path);
+        assert_eq!(view.files_of, Some(items[0].path.to_owned()));
+
+        // Clicking a pane focuses it.
+        view.on_event(Event::Mouse {
+            offset: XY::new(0, 0),
+            position: XY::new(30, 3),
+            event: MouseEvent::Press(cursive::event::MouseButton::Left),
+        });
+        assert!(view.files_focused);
+    }
+}
@@ -0,0 +1,324 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use cursive::{CbSink, Cursive};
+use walkdir::WalkDir;
+
+use crate::config::{args, config_file};
+use crate::data::session_data::SessionData;
+use crate::utils::InnerType;
+
+use super::{
+    audio_items,
+    changes::{Changes, Coalescer, FsEvent},
+    create_items,
+    fuzzy::is_non_hidden_dir,
+    leaf_paths, rescan_items, FuzzyItem, FuzzyView,
+};
+
+// The time between checks for directories that were added or removed.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+// The modification times of the directories of the library. A
+// directory's time changes when an entry is added to or removed from it, so
+// only the directories themselves are checked on each poll, rather than
+// reading all of them.
+#[derive(Debug, Default)]
+pub struct DirTree {
+    modified: HashMap<PathBuf, SystemTime>,
+    // Whether or not the hidden directories are included.
+    hidden_dirs: bool,
+}
+
+impl DirTree {
+    pub fn scan(root: &Path, hidden_dirs: bool) -> Self {
+        let mut tree = DirTree {
+            hidden_dirs,
+            ..DirTree::default()
+        };
+        tree.add(root);
+        tree
+    }
+
+    // Adds `dir` and its subdirectories. Returns whether or not `dir` was
+    // added, which it isn't when it is hidden.
+    fn add(&mut self, dir: &Path) -> bool {
+        let hidden_dirs = self.hidden_dirs;
+        let entries = WalkDir::new(dir)
+            .into_iter()
+            .filter_entry(|entry| is_non_hidden_dir(entry, hidden_dirs))
+            .flatten();
+
+        for entry in entries {
+            if let Some(modified) = entry.metadata().ok().and_then(|m| m.modified().ok()) {
+                self.modified.insert(entry.into_path(), modified);
+            }
+        }
+        self.modified.contains_key(dir)
+    }
+
+    // The directories that were added, removed or changed since the last
+    // poll.
+    pub fn poll(&mut self) -> Vec<FsEvent> {
+        let mut changed = vec![];
+        let mut removed = vec![];
+        for (dir, modified) in self.modified.iter_mut() {
+            match dir.metadata().and_then(|m| m.modified()) {
+                Ok(now) if now == *modified => (),
+                Ok(now) => {
+                    *modified = now;
+                    changed.push(dir.to_owned());
+                }
+                Err(_) => removed.push(dir.to_owned()),
+            }
+        }
+
+        let mut events = vec![];
+
+        // The parents come first, so their subdirectories are removed with them.
+        removed.sort();
+        for dir in removed {
+            if self.modified.contains_key(&dir) {
+                self.modified.retain(|path, _| !path.starts_with(&dir));
+                events.push(FsEvent::Removed(dir));
+            }
+        }
+
+        changed.sort();
+        for dir in changed {
+            if !self.modified.contains_key(&dir) {
+                continue;
+            }
+            let entries = match dir.read_dir() {
+                Ok(entries) => entries.flatten(),
+                Err(_) => continue,
+            };
+            for entry in entries {
+                let path = entry.path();
+                let is_new = entry.file_type().is_ok_and(|t| t.is_dir())
+                    && !self.modified.contains_key(&path);
+                if is_new && self.add(&path) {
+                    events.push(FsEvent::Created(path));
+                }
+            }
+            events.push(FsEvent::Changed(dir));
+        }
+        events
+    }
+}
+
+// Watches the search root for directories that are added or removed while
+// 'watch-library' is true, and merges them into the fuzzy views. The changes
+// are only applied once a poll finds nothing new, so that an album that is
+// still being copied is scanned once, when it is done.
+pub fn start(cb_sink: CbSink) {
+    thread::spawn(move || {
+        let root = args::search_root();
+        // The library is watched as it was first scanned, whether or not a
+        // view has switched to scanning hidden directories since.
+        let hidden_dirs = args::hidden_dirs();
+        let mut tree = None;
+        let mut coalescer = Coalescer::default();
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            if !config_file::get().watch_library {
+                tree = None;
+                continue;
+            }
+            let events = tree
+                .get_or_insert_with(|| DirTree::scan(&root, hidden_dirs))
+                .poll();
+            let settled = events.is_empty();
+            for event in events {
+                coalescer.push(event);
+            }
+            let changes = match settled {
+                true => coalescer.flush(Instant::now()),
+                false => None,
+            };
+
+            if let Some(changes) = changes {
+                let scanned = match scan_changes(&root, &changes, hidden_dirs) {
+                    Ok(items) => items,
+                    Err(_) => continue,
+                };
+                let apply = move |siv: &mut Cursive| merge(siv, &changes, scanned);
+                if cb_sink.send(Box::new(apply)).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+}
+
+// Scans the directories that `changes` added or changed: the added ones with
+// their subdirectories, and the changed ones by themselves, since their new
+// subdirectories are added too. The whole library is scanned again after a
+// rescan.
+fn scan_changes(
+    root: &Path,
+    changes: &Changes,
+    hidden_dirs: bool,
+) -> Result<Vec<FuzzyItem>, anyhow::Error> {
+    match changes {
+        Changes::Batch {
+            added, refreshed, ..
+        } => {
+            let refreshed = refreshed
+                .iter()
+                .filter(|dir| !added.iter().any(|path| dir.starts_with(path)))
+                .flat_map(|dir| rescan_items(root, dir, false, hidden_dirs));
+            let added = added
+                .iter()
+                .flat_map(|dir| rescan_items(root, dir, true, hidden_dirs));
+            Ok(added.chain(refreshed).collect())
+        }
+        Changes::Rescan => create_items(&root.to_path_buf(), hidden_dirs),
+    }
+}
+
+// The items of `library` after `changes`, with the `scanned` items in place
+// of those of the directories that were added, removed or changed.
+fn updated_library(
+    mut library: Vec<FuzzyItem>,
+    changes: &Changes,
+    scanned: Vec<FuzzyItem>,
+) -> Vec<FuzzyItem> {
+    let (added, removed, refreshed) = match changes {
+        Changes::Batch {
+            added,
+            removed,
+            refreshed,
+        } => (added, removed, refreshed),
+        Changes::Rescan => return scanned,
+    };
+    library.retain(|item| {
+        !added
+            .iter()
+            .chain(removed)
+            .any(|dir| item.path.starts_with(dir))
+            && !refreshed.contains(&item.path)
+    });
+    library.extend(scanned);
+    for (index, item) in library.iter_mut().enumerate() {
+        item.original_index = index;
+    }
+    library
+}
+
+// Replaces the session's items, and the albums picked from at random, with
+// the library updated with the items `scanned` after `changes`, and merges
+// them into the fuzzy views that they affect.
+fn merge(siv: &mut Cursive, changes: &Changes, scanned: Vec<FuzzyItem>) {
+    let library = siv
+        .with_user_data(|(_, _, _, data, _, _): &mut InnerType<SessionData>| {
+            std::mem::take(&mut data.items)
+        })
+        .unwrap_or_default();
+    let library = match updated_library(library, changes, scanned) {
+        library if args::audio_only() => audio_items(&library),
+        library => library,
+    };
+
+    siv.call_on_all_named(FuzzyView::NAME, |fuzzy: &mut FuzzyView| {
+        fuzzy.merge_library(changes, &library)
+    });
+
+    siv.with_user_data(|(_, paths, _, data, _, _): &mut InnerType<SessionData>| {
+        *paths = leaf_paths(&library);
+        data.items = library;
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_added_and_removed_dirs_are_polled() {
+        let dir = tempfile::Builder::new()
+            .prefix("tap-tests")
+            .tempdir()
+            .unwrap();
+        let root = dir.path();
+        let artist = root.join("artist");
+        fs::create_dir_all(artist.join("album")).unwrap();
+
+        let mut tree = DirTree::scan(root, false);
+        assert_eq!(tree.modified.len(), 3);
+        assert!(tree.poll().is_empty());
+
+        // An album copied with its discs is found at once.
+        fs::create_dir_all(artist.join("new/disc 1")).unwrap();
+        fs::create_dir(root.join(".hidden")).unwrap();
+        let events = tree.poll();
+        assert!(events.contains(&FsEvent::Created(artist.join("new"))));
+        assert!(events.contains(&FsEvent::Changed(artist.to_owned())));
+        assert!(tree.modified.contains_key(&artist.join("new/disc 1")));
+        assert!(!tree.modified.contains_key(&root.join(".hidden")));
+        assert!(tree.poll().is_empty());
+
+        // A removed artist is removed with its albums.
+        fs::remove_dir_all(&artist).unwrap();
+        let events = tree.poll();
+        assert!(events.contains(&FsEvent::Removed(artist.to_owned())));
+        assert!(!events.contains(&FsEvent::Removed(artist.join("album"))));
+        assert_eq!(tree.modified.len(), 1);
+    }
+
+    #[test]
+    fn test_only_the_changed_dirs_are_scanned() {
+        let dir = tempfile::Builder::new()
+            .prefix("tap-tests")
+            .tempdir()
+            .unwrap();
+        let root = dir.path();
+        let artist = root.join("artist");
+        for album in ["album", "other"] {
+            fs::create_dir_all(artist.join(album)).unwrap();
+            fs::write(artist.join(album).join("01.mp3"), "").unwrap();
+        }
+        let library = create_items(&root.to_path_buf(), false).unwrap();
+
+        fs::remove_dir_all(artist.join("album")).unwrap();
+        fs::create_dir_all(artist.join("new/disc 1")).unwrap();
+        fs::write(artist.join("new/disc 1/01.mp3"), "").unwrap();
+        let changes = Changes::Batch {
+            added: vec![artist.join("new")],
+            removed: vec![artist.join("album")],
+            refreshed: vec![artist.to_owned()],
+        };
+
+        // The other album isn't scanned again.
+        let scanned = scan_changes(root, &changes, false).unwrap();
+        let mut paths: Vec<&Path> = scanned.iter().map(|item| item.path.as_path()).collect();
+        paths.sort();
+        assert_eq!(
+            paths,
+            [&artist, &artist.join("new"), &artist.join("new/disc 1")]
+        );
+
+        // The library is the same as when it is scanned again.
+        let without_index = |items: Vec<FuzzyItem>| {
+            let mut items: Vec<FuzzyItem> = items
+                .into_iter()
+                .map(|item| FuzzyItem {
+                    original_index: 0,
+                    ..item
+                })
+                .collect();
+            items.sort_by(|a, b| a.path.cmp(&b.path));
+            items
+        };
+        assert_eq!(
+            without_index(updated_library(library, &changes, scanned)),
+            without_index(create_items(&root.to_path_buf(), false).unwrap())
+        );
+    }
+}
@@ -0,0 +1,236 @@
+// Golden-file tests for the views. A view is drawn into an in-memory backend
+// with the colors of a theme preset, and the styled cells are compared with a
+// text file in 'tests/goldens'. To accept a change to the drawing code or the
+// theme, regenerate the files with:
+//
+//     UPDATE_GOLDENS=1 cargo test
+//
+// and review the diff.
+
+use std::{cell::RefCell, env, fs, path::PathBuf};
+
+use cursive::{
+    backend::Backend,
+    event::Event,
+    theme::{Color, ColorPair, Effect, PaletteColor},
+    Printer, Vec2, View,
+};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::config::theme::{self, Preset};
+
+#[derive(Clone, PartialEq)]
+struct Cell {
+    text: String,
+    colors: ColorPair,
+    effects: Vec<Effect>,
+}
+
+// A backend that records what is printed to each cell of the screen.
+struct RecordingBackend {
+    size: Vec2,
+    cells: RefCell<Vec<Vec<Cell>>>,
+    colors: RefCell<ColorPair>,
+    effects: RefCell<Vec<Effect>>,
+}
+
+impl RecordingBackend {
+    fn new(size: Vec2) -> Self {
+        let colors = ColorPair {
+            front: Color::TerminalDefault,
+            back: Color::TerminalDefault,
+        };
+        let blank = Cell {
+            text: " ".into(),
+            colors,
+            effects: vec![],
+        };
+
+        RecordingBackend {
+            size,
+            cells: RefCell::new(vec![vec![blank; size.x]; size.y]),
+            colors: RefCell::new(colors),
+            effects: RefCell::new(vec![]),
+        }
+    }
+
+    // The text of each row, followed by the runs of cells with the same style.
+    fn dump(&self) -> String {
+        let cells = self.cells.borrow();
+        let mut out = String::new();
+
+        for row in cells.iter() {
+            let text: String = row.iter().map(|cell| cell.text.as_str()).collect();
+            out.push_str(&format!("|{}|\n", text));
+        }
+
+        for (y, row) in cells.iter().enumerate() {
+            out.push_str(&format!("\nrow {}:", y));
+            let mut start = 0;
+            for x in 1..=row.len() {
+                if x < row.len() && same_style(&row[x], &row[start]) {
+                    continue;
+                }
+                let cell = &row[start];
+                out.push_str(&format!(
+                    "\n  {}..{} fg={:?} bg={:?}",
+                    start, x, cell.colors.front, cell.colors.back
+                ));
+                for effect in cell.effects.iter().filter(|e| **e != Effect::Simple) {
+                    out.push_str(&format!(" +{:?}", effect).to_lowercase());
+                }
+                start = x;
+            }
+        }
+        out.push('\n');
+        out
+    }
+}
+
+fn same_style(a: &Cell, b: &Cell) -> bool {
+    a.colors == b.colors && a.effects == b.effects
+}
+
+impl Backend for RecordingBackend {
+    fn poll_event(&mut self) -> Option<Event> {
+        None
+    }
+
+    fn set_title(&mut self, _title: String) {}
+
+    fn refresh(&mut self) {}
+
+    fn has_colors(&self) -> bool {
+        true
+    }
+
+    fn screen_size(&self) -> Vec2 {
+        self.size
+    }
+
+    fn print_at(&self, pos: Vec2, text: &str) {
+        let mut cells = self.cells.borrow_mut();
+        let row = match cells.get_mut(pos.y) {
+            Some(row) => row,
+            None => return,
+        };
+        let mut effects = self.effects.borrow().to_owned();
+        effects.sort_by_key(|e| format!("{:?}", e));
+
+        let mut x = pos.x;
+        for grapheme in text.graphemes(true) {
+            let width = grapheme.width();
+            if x + width > row.len() {
+                break;
+            }
+            row[x] = Cell {
+                text: grapheme.into(),
+                colors: *self.colors.borrow(),
+                effects: effects.to_owned(),
+            };
+            // The cells covered by a wide character are left empty.
+            for cell in row.iter_mut().take(x + width).skip(x + 1) {
+                cell.text = String::new();
+            }
+            x += width;
+        }
+    }
+
+    fn clear(&self, color: Color) {
+        for row in self.cells.borrow_mut().iter_mut() {
+            for cell in row.iter_mut() {
+                *cell = Cell {
+                    text: " ".into(),
+                    colors: ColorPair {
+                        front: color,
+                        back: color,
+                    },
+                    effects: vec![],
+                };
+            }
+        }
+    }
+
+    fn set_color(&self, colors: ColorPair) -> ColorPair {
+        self.colors.replace(colors)
+    }
+
+    fn set_effect(&self, effect: Effect) {
+        self.effects.borrow_mut().push(effect);
+    }
+
+    fn unset_effect(&self, effect: Effect) {
+        let mut effects = self.effects.borrow_mut();
+        if let Some(i) = effects.iter().position(|e| *e == effect) {
+            effects.remove(i);
+        }
+    }
+
+    fn name(&self) -> &str {
+        "recording"
+    }
+}
+
+// Lays out and draws `view` at `size` with the colors of `preset`.
+pub fn render<V: View>(view: &mut V, size: Vec2, preset: Preset) -> String {
+    theme::with_preset(preset, || {
+        let backend = RecordingBackend::new(size);
+        let theme = theme::with_palette(&preset.palette());
+        backend.clear(theme.palette[PaletteColor::Background]);
+
+        view.layout(size);
+        let printer = Printer::new(size, &theme, &backend);
+        view.draw(&printer);
+
+        format!(
+            "# {} {}x{}\n{}",
+            preset.name(),
+            size.x,
+            size.y,
+            backend.dump()
+        )
+    })
+}
+
+// Compares `rendered` with the golden file `name`, which is written instead
+// with `UPDATE_GOLDENS` set. A missing file fails the test, so that a golden
+// that was never committed can't pass unnoticed.
+pub fn assert_golden(name: &str, rendered: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/goldens")
+        .join(format!("{}.txt", name));
+
+    if env::var_os("UPDATE_GOLDENS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).expect("create goldens dir");
+        fs::write(&path, rendered).expect("write golden file");
+        eprintln!("wrote {}", path.display());
+        return;
+    }
+
+    let expected = match fs::read_to_string(&path) {
+        Ok(expected) => expected,
+        Err(e) => panic!(
+            "could not read the golden file '{}', rerun with UPDATE_GOLDENS=1 \
+            to create it\n- `{}`",
+            path.display(),
+            e
+        ),
+    };
+    if expected != rendered {
+        let diff = expected
+            .lines()
+            .zip(rendered.lines())
+            .enumerate()
+            .find(|(_, (a, b))| a != b)
+            .map(|(i, (a, b))| format!("line {}:\n- {}\n+ {}", i + 1, a, b))
+            .unwrap_or("the number of lines differs".into());
+
+        panic!(
+            "'{}' doesn't match its golden file, rerun with UPDATE_GOLDENS=1 \
+            if the change is intended\n{}",
+            path.display(),
+            diff
+        );
+    }
+}
@@ -0,0 +1,287 @@
+use std::time::Duration;
+
+use cursive::{
+    event::{Event, EventResult, Key},
+    view::{Nameable, Selector},
+    views::LayerPosition,
+    Cursive, View,
+};
+
+#[cfg(any(debug_assertions, feature = "frame_stats"))]
+use crate::frame_stats;
+use crate::fuzzy::FuzzyView;
+use crate::player::PlayerView;
+
+// How long an error that doesn't need acknowledging is shown for, unless a
+// key is pressed first.
+pub const ERROR_TIME: Duration = Duration::from_secs(8);
+
+// The role of a layer on the screen. Each layer is named after its role, so
+// layers can be found and removed by what they are rather than by where they
+// are in the stack.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    // The player, which is always at the bottom of the stack.
+    Player,
+    // The fuzzy finder, which is shown over the player.
+    Fuzzy,
+    // A dialog, such as the dialogs for creating and renaming directories.
+    Dialog,
+    // The keybindings popup.
+    Help,
+    // An error message.
+    Error,
+}
+
+impl Role {
+    const ALL: [Role; 5] = [
+        Role::Player,
+        Role::Fuzzy,
+        Role::Dialog,
+        Role::Help,
+        Role::Error,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Role::Player => "layer_player",
+            Role::Fuzzy => "layer_fuzzy",
+            Role::Dialog => "layer_dialog",
+            Role::Help => "layer_help",
+            Role::Error => "layer_error",
+        }
+    }
+
+    // Whether or not the layer is closed when the fuzzy finder or player
+    // is replaced.
+    fn is_transient(&self) -> bool {
+        matches!(self, Role::Dialog | Role::Help | Role::Error)
+    }
+}
+
+// Adds `view` to the top of the screen as a layer with `role`:
+// - A player replaces all of the layers.
+// - A fuzzy finder replaces the fuzzy finder and the transient layers, but
+//   keeps the player.
+// - Transient layers are added on top of the current layers. Errors are
+//   transparent, so the layer below stays visible.
+pub fn show<V: View>(siv: &mut Cursive, role: Role, view: V) {
+    match role {
+        Role::Player => {
+            while siv.screen().len() > 0 {
+                siv.screen_mut().remove_layer(LayerPosition::FromFront(0));
+            }
+        }
+        Role::Fuzzy => {
+            close(siv, Role::Fuzzy);
+            close_transients(siv);
+        }
+        _ => (),
+    }
+
+    // Each layer is timed as the view it shows, when the frames are timed.
+    #[cfg(any(debug_assertions, feature = "frame_stats"))]
+    let view = frame_stats::Timed::new(role.name().trim_start_matches("layer_"), view);
+    let view = view.with_name(role.name());
+
+    match role {
+        Role::Error => siv.screen_mut().add_transparent_layer(view),
+        _ => siv.add_layer(view),
+    }
+}
+
+// Removes every layer with `role`.
+pub fn close(siv: &mut Cursive, role: Role) {
+    while let Some(position) = siv.screen_mut().find_layer_from_name(role.name()) {
+        siv.screen_mut().remove_layer(position);
+    }
+}
+
+// Removes the dialogs, popups and errors.
+pub fn close_transients(siv: &mut Cursive) {
+    for role in Role::ALL.into_iter().filter(Role::is_transient) {
+        close(siv, role);
+    }
+}
+
+// Whether or not there is a layer with `role`.
+pub fn is_showing(siv: &mut Cursive, role: Role) -> bool {
+    siv.screen_mut().find_layer_from_name(role.name()).is_some()
+}
+
+// The roles of the layers on the screen, from the bottom of the stack up.
+// Layers that weren't added with `show` have no role.
+pub fn roles(siv: &mut Cursive) -> Vec<Option<Role>> {
+    let screen = siv.screen_mut();
+
+    (0..screen.len())
+        .map(|i| {
+            let layer = screen.get_mut(LayerPosition::FromBack(i))?;
+            Role::ALL.into_iter().find(|role| {
+                let mut found = false;
+                layer.call_on_any(&Selector::Name(role.name()), &mut |_: &mut dyn View| {
+                    found = true
+                });
+                found
+            })
+        })
+        .collect()
+}
+
+// Whether or not the characters that are typed go to a text input on the top
+// layer: the text box of a dialog, or the query of the fuzzy finder once
+// something has been typed into it.
+pub fn is_typing(siv: &mut Cursive) -> bool {
+    match roles(siv).last() {
+        Some(Some(Role::Dialog)) => true,
+        Some(Some(Role::Fuzzy)) => siv
+            .call_on_name(FuzzyView::NAME, |fuzzy: &mut FuzzyView| fuzzy.has_query())
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
+// The fuzzy finder or the player, whichever is higher on the screen, below
+// any dialogs and popups.
+fn top_view(siv: &mut Cursive) -> Option<Role> {
+    roles(siv)
+        .into_iter()
+        .flatten()
+        .filter(|role| matches!(role, Role::Fuzzy | Role::Player))
+        .last()
+}
+
+// Shows `msg` for a few seconds on the fuzzy finder or the player, whichever
+// is on top.
+pub fn notify(siv: &mut Cursive, msg: &str) {
+    match top_view(siv) {
+        Some(Role::Fuzzy) => {
+            siv.call_on_name(FuzzyView::NAME, |fuzzy: &mut FuzzyView| {
+                fuzzy.show_toast(msg)
+            });
+        }
+        _ => {
+            siv.call_on_name(PlayerView::NAME, |player: &mut PlayerView| {
+                player.show_note(msg)
+            });
+        }
+    }
+}
+
+// Shows the error `msg` in place of the notes on the fuzzy finder or the
+// player, whichever is on top, until a key is pressed or `ERROR_TIME` has
+// passed. Unlike an error layer, it takes none of the keys.
+pub fn notify_error(siv: &mut Cursive, msg: &str) {
+    match top_view(siv) {
+        Some(Role::Fuzzy) => {
+            siv.call_on_name(FuzzyView::NAME, |fuzzy: &mut FuzzyView| {
+                fuzzy.show_error(msg)
+            });
+        }
+        _ => {
+            siv.call_on_name(PlayerView::NAME, |player: &mut PlayerView| {
+                player.show_error(msg)
+            });
+        }
+    }
+}
+
+// Wraps the global key binding `cb`, which runs before the views see the
+// event, so that characters typed into a text input reach the input instead.
+// So does 'Ctrl + a', which moves the cursor to the start of the input, while
+// it searches the artists when nothing has been typed, and 'Tab', which marks
+// an item in the fuzzy finder while it opens the fuzzy finder everywhere else.
+pub fn global<F>(cb: F) -> impl Fn(&Event) -> Option<EventResult>
+where
+    F: Fn(&Event) -> Option<EventResult> + Copy + 'static,
+{
+    move |event| {
+        let event = event.to_owned();
+        Some(EventResult::with_cb(move |siv| {
+            let result = match event {
+                Event::Char(_) | Event::CtrlChar('a') if is_typing(siv) => {
+                    Some(siv.screen_mut().on_event(event.clone()))
+                }
+                Event::Key(Key::Tab) if roles(siv).last() == Some(&Some(Role::Fuzzy)) => {
+                    Some(siv.screen_mut().on_event(event.clone()))
+                }
+                _ => cb(&event),
+            };
+            if let Some(result) = result {
+                result.process(siv);
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cursive::views::{Dialog, TextView};
+
+    use super::*;
+
+    fn stack(siv: &mut Cursive) -> Vec<Role> {
+        roles(siv).into_iter().flatten().collect()
+    }
+
+    #[test]
+    fn test_fuzzy_finder_over_player() {
+        let mut siv = Cursive::new();
+
+        show(&mut siv, Role::Player, TextView::new("player"));
+        show(&mut siv, Role::Fuzzy, TextView::new("fuzzy"));
+        show(&mut siv, Role::Help, TextView::new("keys"));
+        assert_eq!(stack(&mut siv), vec![Role::Player, Role::Fuzzy, Role::Help]);
+
+        // Refreshing the fuzzy finder replaces it, and closes the popup.
+        show(&mut siv, Role::Fuzzy, TextView::new("fuzzy"));
+        assert_eq!(stack(&mut siv), vec![Role::Player, Role::Fuzzy]);
+
+        // Cancelling the fuzzy finder shows the player.
+        close(&mut siv, Role::Fuzzy);
+        assert_eq!(stack(&mut siv), vec![Role::Player]);
+    }
+
+    #[test]
+    fn test_dialogs_and_errors() {
+        let mut siv = Cursive::new();
+
+        show(&mut siv, Role::Player, TextView::new("player"));
+        show(&mut siv, Role::Fuzzy, TextView::new("fuzzy"));
+        show(&mut siv, Role::Dialog, Dialog::text("name"));
+        show(&mut siv, Role::Dialog, Dialog::text("confirm"));
+        show(&mut siv, Role::Error, TextView::new("error"));
+        assert_eq!(
+            stack(&mut siv),
+            vec![
+                Role::Player,
+                Role::Fuzzy,
+                Role::Dialog,
+                Role::Dialog,
+                Role::Error
+            ]
+        );
+
+        // Dismissing the error leaves the dialogs open.
+        close(&mut siv, Role::Error);
+        assert!(is_showing(&mut siv, Role::Dialog));
+
+        close(&mut siv, Role::Dialog);
+        assert_eq!(stack(&mut siv), vec![Role::Player, Role::Fuzzy]);
+    }
+
+    #[test]
+    fn test_player_replaces_all_layers() {
+        let mut siv = Cursive::new();
+
+        // The fuzzy finder is shown first when there is no player.
+        show(&mut siv, Role::Fuzzy, TextView::new("fuzzy"));
+        show(&mut siv, Role::Error, TextView::new("error"));
+        show(&mut siv, Role::Player, TextView::new("player"));
+        assert_eq!(stack(&mut siv), vec![Role::Player]);
+
+        show(&mut siv, Role::Fuzzy, TextView::new("fuzzy"));
+        show(&mut siv, Role::Player, TextView::new("player"));
+        assert_eq!(roles(&mut siv), vec![Some(Role::Player)]);
+    }
+}
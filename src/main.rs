@@ -1,23 +1,39 @@
+mod accessible;
+mod command;
 mod config;
 mod data;
 mod fuzzy;
+#[cfg(any(debug_assertions, feature = "frame_stats"))]
+mod frame_stats;
+#[cfg(test)]
+mod golden;
+mod layers;
 mod player;
+mod profile;
+mod tty;
 mod utils;
+mod watchdog;
 
 use std::path::PathBuf;
 
-use cursive::{event::Event, CursiveRunnable};
+use cursive::{
+    event::{Event, Key},
+    CursiveRunnable,
+};
 
 use config::{
     args::{self, Opts},
-    theme,
+    config_file, theme,
 };
-use data::{persistent_data, session_data, SessionData};
+use data::{persistent_data, scan_limit, session_data, SessionData};
 use fuzzy::{FuzzyItem, FuzzyView};
 use player::{PlayerBuilder, PlayerView};
+use profile::Phase;
 use utils::IntoInner;
 
 fn main() {
+    profile::start();
+
     let result = setup_and_run();
 
     match result {
@@ -28,26 +44,52 @@ fn main() {
 
 // Run the app.
 fn setup_and_run() -> Result<(), anyhow::Error> {
-    let (path, opts) = args::parse()?;
+    let (path, opts) = profile::span(Phase::Args, args::parse)?;
+
+    if args::watchdog() {
+        return watchdog::run();
+    }
+
+    profile::span(Phase::Config, || config_file::load(args::strict_config()))?;
+    utils::set_path_display(args::path_display());
 
+    // Bring the saved state up to date before anything reads it. The modes
+    // without the UI print the message, and the UI shows it once loaded.
+    let state_msg = persistent_data::migrate_state();
+    let shows_ui = matches!(opts, Opts::Default | Opts::None) && !args::accessible();
+    if let (Some(msg), false) = (&state_msg, shows_ui) {
+        eprintln!("[tap]: {msg}");
+    }
+
+    // The modes without the UI. The audio output is only opened once a player
+    // is created, so these don't wait on the audio server unless they play.
     match opts {
         Opts::Automate => {
-            let path = fuzzy::first_audio_path(&path)?;
+            let path = fuzzy::first_audio_path(&path, args::hidden_dirs())?;
             return player::run_automated(path);
         }
-        Opts::Set => return persistent_data::set_default_path(path),
-        Opts::Print => return persistent_data::print_default_path(),
+        Opts::Set => return without_ui(|| persistent_data::set_default_path(path)),
+        Opts::Print => return without_ui(persistent_data::print_default_path),
         _ => (),
     }
 
     // The items to fuzzy search on.
     let items = get_items(&path, opts)?;
 
-    // The cursive root.
-    let mut siv = cursive::ncurses();
+    if args::accessible() {
+        return accessible::AccessibleMode::run(items);
+    }
+
+    // The cursive root. Profiling with '--dry-run' renders the first layer
+    // without a terminal.
+    let mut siv = match args::profile_startup() && args::dry_run() {
+        true => CursiveRunnable::dummy(),
+        false => cursive::ncurses(),
+    };
 
     siv.set_theme(theme::custom());
     siv.set_fps(15);
+    siv.set_on_pre_event(Event::Key(Key::F5), config::watch::reload);
 
     // Don't load the fuzzy-finder if there is only one audio item.
     if let Some(path) = fuzzy::only_audio_path(&path, &items) {
@@ -56,14 +98,39 @@ fn setup_and_run() -> Result<(), anyhow::Error> {
         load_fuzzy_finder(items, &mut siv, path)?;
     }
 
+    if let Some(msg) = state_msg {
+        layers::notify(&mut siv, &msg);
+    }
+
+    // Initialise everything, then quit before the event loop.
+    if args::profile_startup() && args::dry_run() {
+        return profile_dry_run(siv);
+    }
+
     run_or_test(siv)
 }
 
+// Runs a mode that neither plays audio nor draws, reporting the startup
+// phases when profiling.
+fn without_ui(f: impl FnOnce() -> Result<(), anyhow::Error>) -> Result<(), anyhow::Error> {
+    let result = f();
+    if args::profile_startup() {
+        profile::report();
+    }
+    result
+}
+
 fn get_items(path: &PathBuf, opts: Opts) -> Result<Vec<FuzzyItem>, anyhow::Error> {
-    let items = if opts == Opts::Default || persistent_data::uses_default(path) {
-        persistent_data::get_cached_items(path)?
+    // The cached items don't include the hidden directories.
+    let cached = opts == Opts::Default || persistent_data::uses_default(path);
+    let items = if cached && !args::hidden_dirs() {
+        profile::span(Phase::Cache, || persistent_data::get_cached_items(path))?
     } else {
-        utils::display_with_spinner(fuzzy::create_items, path, "loading")?
+        profile::span(Phase::Scan, || {
+            scan_limit::scan(path, "loading", |path, limit| {
+                fuzzy::create_items_within(path, limit, args::hidden_dirs())
+            })
+        })?
     };
 
     if args::audio_only() {
@@ -77,7 +144,7 @@ fn load_standalone_player(
     path: std::path::PathBuf,
     siv: &mut CursiveRunnable,
 ) -> Result<(), anyhow::Error> {
-    let player = PlayerBuilder::new(path)?;
+    let player = profile::span(Phase::Player, || PlayerBuilder::new(path))?;
     PlayerView::load(player, siv);
     Ok(())
 }
@@ -87,17 +154,28 @@ fn load_fuzzy_finder(
     siv: &mut CursiveRunnable,
     path: PathBuf,
 ) -> Result<(), anyhow::Error> {
-    FuzzyView::load(items.to_owned(), None, siv);
+    FuzzyView::load(fuzzy::top_items(args::depth(), &items), None, siv);
+    siv.call_on_name(FuzzyView::NAME, |fuzzy: &mut FuzzyView| {
+        fuzzy.set_partial(args::audio_only() || args::depth().is_some())
+    });
 
     let session_data = SessionData::new(&path, &items)?;
     siv.set_user_data(session_data.into_inner());
 
-    siv.set_on_pre_event_inner('-', player::previous_album);
-    siv.set_on_pre_event_inner('=', player::random_album);
+    // The global bindings. Characters go to a text input first, when one is
+    // being typed into.
+    siv.set_on_pre_event_inner('-', layers::global(player::previous_album));
+    siv.set_on_pre_event_inner('=', layers::global(player::random_album));
 
-    siv.set_on_pre_event_inner(fuzzy::trigger(), move |event: &Event| {
-        fuzzy::fuzzy_finder(event, &items)
-    });
+    siv.set_on_pre_event_inner(fuzzy::trigger(), layers::global(fuzzy::fuzzy_finder));
+
+    if args::shuffle_albums() {
+        player::shuffle_albums(siv);
+    }
+
+    if let Some(file) = args::session_restore() {
+        player::restore_session(file, siv);
+    }
 
     Ok(())
 }
@@ -116,7 +194,36 @@ fn run_or_test(mut siv: CursiveRunnable) -> Result<(), anyhow::Error> {
     // Run the Cursive event loop in non-test builds.
     #[cfg(not(feature = "run_tests"))]
     {
-        siv.run();
+        run(siv);
         Ok(())
     }
 }
+
+// Runs the event loop, timing the first render when profiling startup.
+#[cfg(not(feature = "run_tests"))]
+fn run(mut siv: CursiveRunnable) {
+    config::watch::start(siv.cb_sink().clone());
+    fuzzy::watch::start(siv.cb_sink().clone());
+
+    tty::run(&mut siv, |runner| match args::profile_startup() {
+        true => profile::span(Phase::FirstRender, || runner.refresh()),
+        false => runner.refresh(),
+    });
+
+    if args::profile_startup() {
+        profile::report();
+    }
+
+    #[cfg(any(debug_assertions, feature = "frame_stats"))]
+    frame_stats::report();
+}
+
+// Renders the first layer and reports the startup phases, without running
+// the event loop.
+fn profile_dry_run(mut siv: CursiveRunnable) -> Result<(), anyhow::Error> {
+    let mut runner = siv.runner();
+    profile::span(Phase::FirstRender, || runner.refresh());
+    drop(runner);
+    profile::report();
+    Ok(())
+}
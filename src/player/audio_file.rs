@@ -4,6 +4,10 @@ use std::{collections::HashSet, path::PathBuf};
 use anyhow::bail;
 use lofty::{Accessor, AudioFile as LoftyAudioFile, Probe, TaggedFileExt};
 
+use crate::utils::display_path;
+
+use super::{mixdown::output_channels, mp4};
+
 // The set of valid audio file extensions.
 lazy_static::lazy_static! {
     pub static ref AUDIO_FORMATS: HashSet<&'static str> = create_set();
@@ -16,27 +20,72 @@ pub struct AudioFile {
     pub artist: String,
     pub album: String,
     pub year: Option<u32>,
+    pub genre: Option<String>,
     pub track: u32,
     pub duration: usize,
+    // The sample rate in Hz, if known.
+    pub sample_rate: Option<u32>,
+    // The number of channels, if known.
+    pub channels: Option<u8>,
+    // The reason that the file can't be played, if any.
+    pub unplayable: Option<Unplayable>,
+}
+
+// The reasons that an audio file is skipped during playback.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Unplayable {
+    DrmProtected,
+    NoAudioStream,
+}
+
+impl Unplayable {
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Unplayable::DrmProtected => "DRM-protected",
+            Unplayable::NoAudioStream => "no audio stream",
+        }
+    }
 }
 
 impl AudioFile {
+    // Reads the tags of the file at `path`. Files that can't be played are
+    // still returned, with the reason set and a duration of zero so that
+    // they aren't counted in the album length.
     pub fn new(path: PathBuf) -> Result<Self, anyhow::Error> {
+        let unplayable = match is_mp4(&path) {
+            true => mp4::probe(&path),
+            false => None,
+        };
+
+        match (Self::read(path.clone()), unplayable) {
+            (Ok(file), None) => Ok(file),
+            (Ok(file), Some(reason)) => Ok(Self {
+                duration: 0,
+                unplayable: Some(reason),
+                ..file
+            }),
+            // The tags of unplayable files may also be unreadable.
+            (Err(_), Some(reason)) => Ok(Self::untagged(path, reason)),
+            (Err(e), None) => Err(e),
+        }
+    }
+
+    fn read(path: PathBuf) -> Result<Self, anyhow::Error> {
         let file = match Probe::open(&path) {
             Ok(f) => f,
-            Err(e) => bail!("could not probe '{}'\n-`{}`", path.display(), e),
+            Err(e) => bail!("could not probe '{}'\n-`{}`", display_path(&path), e),
         };
 
         let tagged_file = match file.read() {
             Ok(f) => f,
-            Err(e) => bail!("failed to read '{}'\n- `{}`", path.display(), e),
+            Err(e) => bail!("failed to read '{}'\n- `{}`", display_path(&path), e),
         };
 
         let tag = match tagged_file.primary_tag() {
             Some(primary_tag) => primary_tag,
             None => match tagged_file.first_tag().ok_or(()) {
                 Ok(t) => t,
-                Err(_) => bail!("no tags found for '{}'", path.display()),
+                Err(_) => bail!("no tags found for '{}'", display_path(&path)),
             },
         };
 
@@ -48,14 +97,63 @@ impl AudioFile {
             album: tag.album().as_deref().unwrap_or("None").trim().to_string(),
             title: tag.title().as_deref().unwrap_or("None").trim().to_string(),
             year: tag.year(),
+            genre: tag.genre().map(|g| g.trim().to_string()),
             track: tag.track().unwrap_or(0),
             artist,
             path,
             duration,
+            sample_rate: properties.sample_rate(),
+            channels: properties.channels(),
+            unplayable: None,
         };
 
         Ok(audio_file)
     }
+
+    // An unplayable file titled with its file name.
+    fn untagged(path: PathBuf, reason: Unplayable) -> Self {
+        let title = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        Self {
+            title,
+            artist: String::from("None"),
+            album: String::from("None"),
+            year: None,
+            genre: None,
+            track: 0,
+            duration: 0,
+            sample_rate: None,
+            channels: None,
+            unplayable: Some(reason),
+            path,
+        }
+    }
+
+    pub fn is_playable(&self) -> bool {
+        self.unplayable.is_none()
+    }
+
+    // Whether or not `next` can be queued to play gaplessly after this file.
+    // Gapless playback is disabled across a change in sample rate, or in the
+    // number of channels that are played, so that the output renegotiates
+    // them at the track boundary.
+    pub fn is_gapless_with(&self, next: &AudioFile, downmix: bool) -> bool {
+        let same_rate = match (self.sample_rate, next.sample_rate) {
+            (Some(rate), Some(next_rate)) => rate == next_rate,
+            _ => true,
+        };
+        let same_channels = match (self.channels, next.channels) {
+            (Some(channels), Some(next_channels)) => {
+                output_channels(channels as u16, downmix)
+                    == output_channels(next_channels as u16, downmix)
+            }
+            _ => true,
+        };
+        same_rate && same_channels
+    }
 }
 
 // Order by Album -> Track / Title
@@ -78,12 +176,21 @@ pub fn valid_audio_ext(p: &PathBuf) -> bool {
     AUDIO_FORMATS.contains(&ext)
 }
 
+// Returns true if the file is in an MP4 container.
+fn is_mp4(p: &PathBuf) -> bool {
+    matches!(
+        p.extension().unwrap_or_default().to_str(),
+        Some("m4a" | "m4p" | "mp4")
+    )
+}
+
 fn create_set() -> HashSet<&'static str> {
     let mut m = HashSet::new();
     m.insert("aac");
     m.insert("flac");
     m.insert("mp3");
     m.insert("m4a");
+    m.insert("m4p");
     m.insert("ogg");
     m.insert("wav");
     m.insert("wma");
@@ -0,0 +1,125 @@
+use std::{
+    io::{BufRead, BufReader},
+    process::{self, Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Once,
+    },
+    thread,
+};
+
+use anyhow::{anyhow, bail};
+
+use crate::utils;
+
+// The name of the log for the audio server listener.
+const LOG: &str = "audio_focus";
+
+// Whether or not another application is playing audio.
+static OTHERS_PLAYING: AtomicBool = AtomicBool::new(false);
+
+// Makes sure that only one listener is started.
+static STARTED: Once = Once::new();
+
+// Listens for the audio streams of other applications starting and stopping.
+// This uses 'pactl', which works with both PulseAudio and PipeWire. Without
+// it the listener exits, and other applications are never detected. Only the
+// first call starts the listener.
+pub fn start() {
+    STARTED.call_once(|| {
+        thread::spawn(|| {
+            if let Err(e) = listen() {
+                utils::log(LOG, &e.to_string());
+            }
+        });
+    });
+}
+
+// Whether or not another application is playing audio.
+pub fn others_playing() -> bool {
+    OTHERS_PLAYING.load(Ordering::Relaxed)
+}
+
+fn listen() -> Result<(), anyhow::Error> {
+    let mut child = Command::new("pactl")
+        .arg("subscribe")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| anyhow!("could not run 'pactl subscribe'\n- `{}`", e))?;
+
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or(anyhow!("'pactl subscribe' has no output"))?;
+
+    update()?;
+
+    // The events are lines like "Event 'new' on sink-input #42".
+    for line in BufReader::new(stdout).lines() {
+        if line?.contains("sink-input") {
+            update()?;
+        }
+    }
+
+    bail!("'pactl subscribe' exited")
+}
+
+fn update() -> Result<(), anyhow::Error> {
+    let output = Command::new("pactl")
+        .args(["list", "sink-inputs"])
+        .env("LC_ALL", "C")
+        .stderr(Stdio::null())
+        .output()?;
+
+    let list = String::from_utf8_lossy(&output.stdout);
+    let playing = others_are_playing(&list, process::id());
+    OTHERS_PLAYING.store(playing, Ordering::Relaxed);
+
+    Ok(())
+}
+
+// Whether any of the streams in the output of 'pactl list sink-inputs' is
+// playing, i.e. isn't corked, and belongs to a process other than `pid`.
+fn others_are_playing(list: &str, pid: u32) -> bool {
+    let ours = format!("application.process.id = \"{}\"", pid);
+
+    list.split("Sink Input #").skip(1).any(|input| {
+        let corked = input.lines().any(|line| line.trim() == "Corked: yes");
+        let is_ours = input.lines().any(|line| line.trim() == ours);
+        !corked && !is_ours
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sink_input(index: usize, pid: u32, corked: bool) -> String {
+        format!(
+            "Sink Input #{index}\n\
+            \tDriver: protocol-native.c\n\
+            \tCorked: {}\n\
+            \tMute: no\n\
+            \tProperties:\n\
+            \t\tapplication.name = \"app\"\n\
+            \t\tapplication.process.id = \"{pid}\"\n\
+            \t\tmedia.role = \"phone\"\n\n",
+            if corked { "yes" } else { "no" }
+        )
+    }
+
+    #[test]
+    fn test_others_are_playing() {
+        let ours = sink_input(1, 100, false);
+        let call = sink_input(2, 200, false);
+        let paused_video = sink_input(3, 300, true);
+
+        assert!(!others_are_playing("", 100));
+        assert!(!others_are_playing(&ours, 100));
+        assert!(!others_are_playing(&(ours.clone() + &paused_video), 100));
+        assert!(others_are_playing(&(ours + &call), 100));
+        // Process 1000 isn't process 100.
+        assert!(others_are_playing(&sink_input(4, 1000, false), 100));
+    }
+}
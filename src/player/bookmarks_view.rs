@@ -0,0 +1,131 @@
+use std::path::{Path, PathBuf};
+
+use cursive::{
+    view::{Nameable, Resizable, Scrollable},
+    views::{Dialog, EditView, SelectView},
+    Cursive,
+};
+
+use crate::data::{bookmarks::Bookmark, Bookmarks};
+use crate::layers::{self, Role};
+
+use super::PlayerView;
+
+// The name of the list of bookmarks in the popup.
+const LIST: &str = "bookmarks";
+
+// Shows the bookmarks of `file`, the file in the player, to jump to, rename
+// or delete.
+pub fn load(file: PathBuf, siv: &mut Cursive) {
+    let marks = Bookmarks::of(&file);
+    if marks.is_empty() {
+        return layers::notify(siv, "no bookmarks, 'b' adds one");
+    }
+
+    let mut list = SelectView::new().on_submit(|siv, secs: &usize| {
+        layers::close(siv, Role::Dialog);
+        let secs = *secs;
+        siv.call_on_name(PlayerView::NAME, |player: &mut PlayerView| {
+            player.seek_to(secs)
+        });
+    });
+    for mark in marks.iter() {
+        list.add_item(label(mark), mark.secs);
+    }
+
+    let rename_file = file.to_owned();
+    let dialog = Dialog::new()
+        .title("Bookmarks")
+        .content(list.with_name(LIST).scrollable().max_height(12))
+        .button("Rename", move |siv| {
+            if let Some(mark) = selected(siv, &marks) {
+                rename_dialog(rename_file.to_owned(), mark, siv);
+            }
+        })
+        .button("Delete", move |siv| {
+            if let Some(secs) = selected_secs(siv) {
+                Bookmarks::delete(&file, secs);
+                reload(file.to_owned(), siv);
+            }
+        })
+        .dismiss_button("Close");
+
+    layers::show(siv, Role::Dialog, dialog);
+}
+
+// The text of a bookmark in the list: its position and its name.
+fn label(mark: &Bookmark) -> String {
+    let time = match mark.secs / 3600 {
+        0 => format!("{:02}:{:02}", mark.secs / 60, mark.secs % 60),
+        hours => format!("{}:{:02}:{:02}", hours, mark.secs / 60 % 60, mark.secs % 60),
+    };
+    format!("{time}  {}", mark.name)
+}
+
+// The position of the selected bookmark, if any.
+fn selected_secs(siv: &mut Cursive) -> Option<usize> {
+    siv.call_on_name(LIST, |list: &mut SelectView<usize>| list.selection())
+        .flatten()
+        .map(|secs| *secs)
+}
+
+// The selected bookmark, if any.
+fn selected(siv: &mut Cursive, marks: &[Bookmark]) -> Option<Bookmark> {
+    let secs = selected_secs(siv)?;
+    marks.iter().find(|mark| mark.secs == secs).cloned()
+}
+
+// Shows a dialog to rename `mark` in `file`.
+fn rename_dialog(file: PathBuf, mark: Bookmark, siv: &mut Cursive) {
+    let submit_file = file.to_owned();
+    let secs = mark.secs;
+
+    let dialog = Dialog::new()
+        .title(format!("Rename {}", label(&mark).trim_end()))
+        .content(
+            EditView::new()
+                .content(mark.name)
+                .on_submit(move |siv, name| rename(&submit_file, secs, name, siv))
+                .with_name("bookmark_name")
+                .fixed_width(32),
+        )
+        .button("Ok", move |siv| {
+            let name = siv
+                .call_on_name("bookmark_name", |view: &mut EditView| view.get_content())
+                .unwrap_or_default();
+            rename(&file, secs, &name, siv)
+        })
+        .dismiss_button("Cancel");
+
+    layers::show(siv, Role::Dialog, dialog);
+}
+
+fn rename(file: &Path, secs: usize, name: &str, siv: &mut Cursive) {
+    Bookmarks::save_name(file, secs, name);
+    reload(file.to_owned(), siv);
+}
+
+// Shows the bookmarks of `file` again after they have been changed, and
+// updates the ticks on the progress bar.
+fn reload(file: PathBuf, siv: &mut Cursive) {
+    layers::close(siv, Role::Dialog);
+    siv.call_on_name(PlayerView::NAME, |player: &mut PlayerView| {
+        player.reload_bookmarks()
+    });
+    load(file, siv);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_label() {
+        let mark = |secs, name: &str| Bookmark {
+            secs,
+            name: name.into(),
+        };
+        assert_eq!(label(&mark(75, "")), "01:15  ");
+        assert_eq!(label(&mark(3 * 3600 + 62, "encore")), "3:01:02  encore");
+    }
+}
@@ -1,14 +1,15 @@
-use std::path::PathBuf;
+use std::{collections::VecDeque, path::PathBuf};
 
 use anyhow::bail;
 use cursive::Cursive;
 
+use crate::data::persistent_data;
 use crate::session_data::SessionData;
 use crate::utils::{self, InnerType};
 
 use super::{
-    player::{playlist, PlayerResult},
-    Player, PlayerOpts,
+    player::{track_count, PlayerResult},
+    BytesToStatus, Player, PlayerOpts,
 };
 
 #[derive(PartialEq)]
@@ -18,14 +19,25 @@ pub enum PlayerBuilder {
     PreviousTrack,
     RandomAlbum,
     RandomTrack,
+    ShuffledAlbum,
+    NextShuffled,
+    PreviousShuffled,
+    NextStation,
+    PreviousStation,
+    Session,
 }
 
 impl PlayerBuilder {
     pub fn from(&self, path: Option<PathBuf>, siv: &mut Cursive) -> PlayerResult {
         match self {
             Self::FuzzyFinder => Self::fuzzy(path, siv),
+            Self::Session => Self::session(path, siv),
             Self::PreviousAlbum | Self::PreviousTrack => Self::previous(&self, siv),
             Self::RandomAlbum | Self::RandomTrack => Self::random(&self, siv),
+            Self::ShuffledAlbum | Self::NextShuffled | Self::PreviousShuffled => {
+                Self::shuffled(&self, siv)
+            }
+            Self::NextStation | Self::PreviousStation => Self::station(&self, siv),
         }
     }
 
@@ -36,7 +48,7 @@ impl PlayerBuilder {
 
     fn previous(&self, siv: &mut Cursive) -> PlayerResult {
         let ((path, mut index), opts) = siv
-            .with_user_data(|(opts, _, queue): &mut InnerType<SessionData>| {
+            .with_user_data(|(opts, _, queue, _, _, _): &mut InnerType<SessionData>| {
                 let (path, index) = queue.front().expect("should always exist").to_owned();
                 let opts: PlayerOpts = (*opts).into();
 
@@ -63,31 +75,33 @@ impl PlayerBuilder {
 
     fn random(&self, siv: &mut Cursive) -> PlayerResult {
         let ((path, mut index), opts) = siv
-            .with_user_data(|(opts, paths, queue): &mut InnerType<SessionData>| {
-                let opts: PlayerOpts = (*opts).into();
-                let (path, index) = queue.back().expect("should always exist").to_owned();
-
-                if queue.len() == 1 {
-                    let front = queue.front().expect("should always exist").to_owned();
-                    queue.push_back(front);
-                } else {
-                    queue.pop_front();
-                }
-
-                let next_random = match Player::randomized(&paths) {
-                    Some(track) => track,
-                    None => {
-                        let path = path.to_owned();
-                        let upper_bound = playlist(&path).expect("should always exist").0.len();
-                        let index = utils::random(0..upper_bound);
-                        (path, index)
+            .with_user_data(
+                |(opts, paths, queue, data, _, _): &mut InnerType<SessionData>| {
+                    let opts: PlayerOpts = (*opts).into();
+                    let (path, index) = queue.back().expect("should always exist").to_owned();
+
+                    if queue.len() == 1 {
+                        let front = queue.front().expect("should always exist").to_owned();
+                        queue.push_back(front);
+                    } else {
+                        queue.pop_front();
                     }
-                };
-
-                queue.push_back(next_random);
 
-                ((path, index), opts)
-            })
+                    // The hidden albums are never selected at random.
+                    let next_random = match Player::randomized(&data.visible(paths)) {
+                        Some(track) => track,
+                        None => {
+                            let path = path.to_owned();
+                            let index = utils::random(0..track_count(&path).max(1));
+                            (path, index)
+                        }
+                    };
+
+                    queue.push_back(next_random);
+
+                    ((path, index), opts)
+                },
+            )
             .expect("should be set on init");
 
         if Self::RandomAlbum.eq(self) {
@@ -97,25 +111,140 @@ impl PlayerBuilder {
         Player::new(path, index, opts, Self::RandomTrack.eq(self))
     }
 
+    fn shuffled(&self, siv: &mut Cursive) -> PlayerResult {
+        let current = siv
+            .with_user_data(
+                |(opts, _, queue, _, shuffle, _): &mut InnerType<SessionData>| {
+                    let opts: PlayerOpts = (*opts).into();
+                    let shuffle = shuffle.as_mut()?;
+
+                    match self {
+                        Self::NextShuffled => shuffle.next(),
+                        Self::PreviousShuffled => shuffle.previous(),
+                        _ => (),
+                    }
+
+                    shuffle.save();
+
+                    let path = shuffle.current()?.to_owned();
+                    push_current(queue, &path);
+
+                    Some((path, opts))
+                },
+            )
+            .flatten();
+
+        match current {
+            Some((path, opts)) => Player::new(path, 0, opts, false),
+            None => bail!("albums are not being shuffled"),
+        }
+    }
+
+    fn station(&self, siv: &mut Cursive) -> PlayerResult {
+        let left_at = saved_position();
+
+        let current = siv
+            .with_user_data(
+                |(opts, paths, queue, data, _, station): &mut InnerType<SessionData>| {
+                    let opts: PlayerOpts = (*opts).into();
+                    let station = station.as_mut()?;
+
+                    // Remember where the current album was left, so that it
+                    // can be resumed when going back to it.
+                    if let Some((album, index)) = left_at {
+                        if album.eq(station.current()) {
+                            station.leave_at(index);
+                        }
+                    }
+
+                    let (path, index) = match self {
+                        Self::PreviousStation => station
+                            .previous()
+                            .map(|(path, index)| (path.to_owned(), index))?,
+                        _ => (station.next(&data.visible(paths))?.to_owned(), 0),
+                    };
+                    push_current(queue, &path);
+
+                    Some((path, index, opts))
+                },
+            )
+            .flatten();
+
+        match current {
+            Some((path, index, opts)) => Player::new(path, index, opts, false),
+            None if Self::PreviousStation.eq(self) => bail!("start of queue"),
+            None => bail!("the station has no albums queued"),
+        }
+    }
+
+    fn session(file: Option<PathBuf>, siv: &mut Cursive) -> PlayerResult {
+        let file = file.expect("session file should be provided");
+        let (path, index, status) = persistent_data::cached_session(&file)?;
+
+        let opts: PlayerOpts = siv
+            .with_user_data(|(opts, _, queue, _, _, _): &mut InnerType<SessionData>| {
+                push_current(queue, &path);
+                // Resume with the status that the session was saved with.
+                opts.0 = status;
+                (*opts).into()
+            })
+            .expect("should be set on init");
+
+        Player::new(path, index, opts, false)
+    }
+
     fn fuzzy(path: Option<PathBuf>, siv: &mut Cursive) -> PlayerResult {
         let path = path.expect("path should be provided by fuzzy-finder");
 
-        let opts = siv
-            .with_user_data(|(opts, _, queue): &mut InnerType<SessionData>| {
-                let opts: PlayerOpts = (*opts).into();
+        let opts: PlayerOpts = siv
+            .with_user_data(|(opts, _, queue, _, _, _): &mut InnerType<SessionData>| {
+                push_current(queue, &path);
+                (*opts).into()
+            })
+            .expect("should be set on init");
 
-                if queue.len() == 1 {
-                    queue.push_front((path.clone(), 0));
-                    queue.push_front((path.clone(), 0));
-                } else {
-                    queue.pop_front();
-                    queue.insert(1, (path.clone(), 0));
-                }
+        Player::new(path, 0, opts, false)
+    }
 
-                opts
+    // A player for the albums at `paths`, such as those marked in the
+    // fuzzy-finder, which play one after another.
+    pub fn albums(paths: Vec<PathBuf>, siv: &mut Cursive) -> PlayerResult {
+        let first = match paths.first() {
+            Some(first) => first.to_owned(),
+            None => bail!("no albums to play"),
+        };
+
+        let opts: PlayerOpts = siv
+            .with_user_data(|(opts, _, queue, _, _, _): &mut InnerType<SessionData>| {
+                push_current(queue, &first);
+                (*opts).into()
             })
             .expect("should be set on init");
 
-        Player::new(path, 0, opts, false)
+        Player::with_albums(&paths, opts)
+    }
+}
+
+// The album and track index that the player was last saved with. The index
+// is zero if the album was played to the end.
+fn saved_position() -> Option<(PathBuf, usize)> {
+    let file = persistent_data::session_file().ok()?;
+    let (album, index, status) = persistent_data::cached_session(&file).ok()?;
+
+    match status.from_u8().is_resumable() {
+        true => Some((album, index)),
+        false => Some((album, 0)),
+    }
+}
+
+// Makes `path` the current track in the queue, keeping the previous current
+// track as the previous track.
+fn push_current(queue: &mut VecDeque<(PathBuf, usize)>, path: &PathBuf) {
+    if queue.len() == 1 {
+        queue.push_front((path.clone(), 0));
+        queue.push_front((path.clone(), 0));
+    } else {
+        queue.pop_front();
+        queue.insert(1, (path.clone(), 0));
     }
 }
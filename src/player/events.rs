@@ -0,0 +1,213 @@
+use std::{
+    collections::VecDeque,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex, MutexGuard,
+    },
+    time::Duration,
+};
+
+// The number of events held for each subscriber. When a subscriber falls
+// this far behind, its oldest events are dropped.
+pub const EVENT_CAPACITY: usize = 64;
+
+// The playback events sent to the subscribers of a player.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PlayerEvent {
+    // The track at `index` in the playlist started playing.
+    TrackStarted { path: PathBuf, index: usize },
+    // The current track played to the end.
+    TrackEnded,
+    Paused,
+    Resumed,
+    // The playback position moved from `from` to `to`.
+    Seeked { from: Duration, to: Duration },
+    VolumeChanged { volume: u8, is_muted: bool },
+    // The last track in the playlist played to the end.
+    AlbumEnded,
+    Error { msg: String },
+}
+
+// The state shared by a subscriber and the bus that sends to it.
+struct Shared<T> {
+    queue: Mutex<VecDeque<T>>,
+    ready: Condvar,
+    // The number of events that were dropped because the queue was full.
+    dropped: AtomicUsize,
+    // Whether or not the bus has been dropped.
+    closed: AtomicBool,
+}
+
+impl<T> Shared<T> {
+    // Locks the queue, ignoring poisoning so that sending never panics.
+    fn lock(&self) -> MutexGuard<VecDeque<T>> {
+        self.queue.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+// Sends events to any number of subscribers. Sending never blocks on a
+// slow subscriber: each subscriber has a bounded queue and loses its
+// oldest events first when the queue is full.
+pub struct EventBus<T> {
+    subscribers: Vec<Arc<Shared<T>>>,
+    capacity: usize,
+}
+
+impl<T: Clone> EventBus<T> {
+    pub fn new(capacity: usize) -> Self {
+        EventBus {
+            subscribers: vec![],
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn subscribe(&mut self) -> Receiver<T> {
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(VecDeque::with_capacity(self.capacity)),
+            ready: Condvar::new(),
+            dropped: AtomicUsize::new(0),
+            closed: AtomicBool::new(false),
+        });
+        self.subscribers.push(shared.clone());
+
+        Receiver { shared }
+    }
+
+    // Sends `event` to every subscriber, in the order that events are sent.
+    pub fn send(&mut self, event: T) {
+        // Forget the subscribers whose receivers have been dropped.
+        self.subscribers
+            .retain(|shared| Arc::strong_count(shared) > 1);
+
+        for shared in self.subscribers.iter() {
+            let mut queue = shared.lock();
+            if queue.len() == self.capacity {
+                queue.pop_front();
+                shared.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+            queue.push_back(event.clone());
+            shared.ready.notify_one();
+        }
+    }
+}
+
+impl<T> Drop for EventBus<T> {
+    fn drop(&mut self) {
+        for shared in self.subscribers.iter() {
+            shared.closed.store(true, Ordering::Release);
+            shared.ready.notify_all();
+        }
+    }
+}
+
+// Receives the events sent by an `EventBus`, in the order they were sent.
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Receiver<T> {
+    // The next event, if there is one.
+    pub fn try_recv(&self) -> Option<T> {
+        self.shared.lock().pop_front()
+    }
+
+    // Waits up to `timeout` for the next event. Returns `None` on timeout,
+    // or once the bus has been dropped and all its events received.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<T> {
+        let queue = self.shared.lock();
+        let (mut queue, _) = self
+            .shared
+            .ready
+            .wait_timeout_while(queue, timeout, |queue| {
+                queue.is_empty() && !self.shared.closed.load(Ordering::Acquire)
+            })
+            .unwrap_or_else(|e| e.into_inner());
+
+        queue.pop_front()
+    }
+
+    // The events that have been sent and not yet received.
+    pub fn try_iter(&self) -> impl Iterator<Item = T> + '_ {
+        std::iter::from_fn(|| self.try_recv())
+    }
+
+    // The number of events that were dropped because this receiver fell behind.
+    pub fn dropped(&self) -> usize {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+
+    // Whether or not the bus has been dropped, i.e. no more events will be sent.
+    pub fn is_closed(&self) -> bool {
+        self.shared.closed.load(Ordering::Acquire)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn test_events_are_received_in_order() {
+        let mut bus = EventBus::new(8);
+        let first = bus.subscribe();
+        let second = bus.subscribe();
+
+        for i in 0..5 {
+            bus.send(i);
+        }
+
+        assert_eq!(first.try_iter().collect::<Vec<_>>(), vec![0, 1, 2, 3, 4]);
+        assert_eq!(second.try_iter().collect::<Vec<_>>(), vec![0, 1, 2, 3, 4]);
+        assert_eq!(first.try_recv(), None);
+    }
+
+    #[test]
+    fn test_slow_subscribers_drop_oldest_events() {
+        let mut bus = EventBus::new(3);
+        let slow = bus.subscribe();
+
+        for i in 0..10 {
+            bus.send(i);
+        }
+
+        assert_eq!(slow.dropped(), 7);
+        assert_eq!(slow.try_iter().collect::<Vec<_>>(), vec![7, 8, 9]);
+    }
+
+    #[test]
+    fn test_dropped_subscribers_are_removed() {
+        let mut bus = EventBus::new(3);
+        let kept = bus.subscribe();
+        drop(bus.subscribe());
+
+        bus.send(1);
+
+        assert_eq!(bus.subscribers.len(), 1);
+        assert_eq!(kept.try_recv(), Some(1));
+    }
+
+    #[test]
+    fn test_recv_timeout() {
+        let mut bus = EventBus::new(3);
+        let receiver = bus.subscribe();
+
+        assert_eq!(receiver.recv_timeout(Duration::from_millis(10)), None);
+
+        let handle = thread::spawn(move || {
+            bus.send(PlayerEvent::Paused);
+        });
+
+        assert_eq!(
+            receiver.recv_timeout(Duration::from_secs(5)),
+            Some(PlayerEvent::Paused)
+        );
+        handle.join().unwrap();
+
+        // The bus was dropped with the thread.
+        assert!(receiver.is_closed());
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(5)), None);
+    }
+}
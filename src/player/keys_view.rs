@@ -7,6 +7,8 @@ use cursive::{
     Cursive,
 };
 
+use crate::layers::{self, Role};
+
 pub struct KeysView {}
 
 impl KeysView {
@@ -28,7 +30,8 @@ impl KeysView {
                             .child("parent search:", TextView::new("Ctrl + p"))
                             .child("previous album:", TextView::new("-"))
                             .child("random album:", TextView::new("="))
-                            .child("open file manager:", TextView::new("Ctrl + o")),
+                            .child("open file manager:", TextView::new("Ctrl + o"))
+                            .child("reload config:", TextView::new("F5")),
                     ),
                 )
                 .child(DummyView.fixed_height(1))
@@ -43,11 +46,20 @@ impl KeysView {
                             .child("step backward:", TextView::new(","))
                             .child("seek to sec", TextView::new("0..9 + \""))
                             .child("seek to min", TextView::new("0..9 + \'"))
+                            .child("add bookmark:", TextView::new("b"))
+                            .child("bookmarks:", TextView::new("Alt + b"))
                             .child("random:", TextView::new("r or *"))
+                            .child("shuffle albums:", TextView::new("a"))
+                            .child("reshuffle albums:", TextView::new("x"))
+                            .child("station:", TextView::new("s"))
+                            .child("back in the station:", TextView::new("<"))
                             .child("volume up:", TextView::new("]"))
                             .child("volume down:", TextView::new("["))
                             .child("show volume:", TextView::new("v"))
                             .child("mute:", TextView::new("m"))
+                            .child("album gain up:", TextView::new("Alt + +"))
+                            .child("album gain down:", TextView::new("Alt + -"))
+                            .child("clear album gain:", TextView::new("Alt + 0"))
                             .child("go to first track:", TextView::new("gg"))
                             .child("go to last track:", TextView::new("Ctrl + g"))
                             .child("go to track number:", TextView::new("0...9 + g"))
@@ -60,10 +72,49 @@ impl KeysView {
                     Dialog::new().title("Fuzzy").content(
                         ListView::new()
                             .child("clear search:", TextView::new("Ctrl + u"))
+                            .child("delete word:", TextView::new("Ctrl + w"))
+                            .child("delete to end:", TextView::new("Ctrl + k"))
+                            .child("paste deleted text:", TextView::new("Ctrl + y"))
+                            .child("previous search:", TextView::new("Ctrl + ↑ or Alt + p"))
+                            .child("next search:", TextView::new("Ctrl + ↓ or Alt + n"))
+                            .child("back / forward:", TextView::new("Alt + ← or →"))
                             .child("cancel search:", TextView::new("Esc"))
+                            .child(
+                                "move cursor by word:",
+                                TextView::new("Ctrl + ← or →, Alt + b or f"),
+                            )
+                            .child("cursor to start:", TextView::new("Ctrl + a or Home"))
+                            .child("cursor to end:", TextView::new("Ctrl + e or End"))
                             .child("page up:", TextView::new("Ctrl + h or PgUp"))
                             .child("page down:", TextView::new("Ctrl + l or PgDn"))
-                            .child("random page:", TextView::new("Ctrl + z")),
+                            .child("half page up:", TextView::new("Ctrl + u"))
+                            .child("half page down:", TextView::new("Ctrl + d"))
+                            .child("first match:", TextView::new("Alt + g or Ctrl + Home"))
+                            .child("last match:", TextView::new("Alt + G or Ctrl + End"))
+                            .child("random page:", TextView::new("Ctrl + z"))
+                            .child("new directory:", TextView::new("Ctrl + n"))
+                            .child("rename item:", TextView::new("Ctrl + r"))
+                            .child("delete item:", TextView::new("Shift + Del"))
+                            .child("reload directory:", TextView::new("Alt + r"))
+                            .child("hidden directories:", TextView::new("Alt + ."))
+                            .child("hide item:", TextView::new("Alt + d"))
+                            .child("show hidden items:", TextView::new("Alt + h"))
+                            .child("star item:", TextView::new("Ctrl + b"))
+                            .child("show only favorites:", TextView::new("Alt + B"))
+                            .child("match paths / names:", TextView::new("Ctrl + f"))
+                            .child("case matching:", TextView::new("Alt + c"))
+                            .child("sort order:", TextView::new("Ctrl + t"))
+                            .child("track preview:", TextView::new("Ctrl + v"))
+                            .child("two panes:", TextView::new("Alt + t"))
+                            .child("size and date:", TextView::new("Alt + i"))
+                            .child("match positions:", TextView::new("Alt + #"))
+                            .child("match mode:", TextView::new("Ctrl + x"))
+                            .child("match threshold:", TextView::new("Alt + + or -"))
+                            .child("mark album:", TextView::new("Tab"))
+                            .child("clear marks:", TextView::new("Shift + Tab"))
+                            .child("explain ranking:", TextView::new("Alt + ?"))
+                            .child("copy path:", TextView::new("Alt + y"))
+                            .child("save matches:", TextView::new("Alt + s")),
                     ),
                 ),
         ))
@@ -71,9 +122,11 @@ impl KeysView {
     }
 
     pub fn load(siv: &mut Cursive) {
-        siv.add_layer(
+        layers::show(
+            siv,
+            Role::Help,
             OnEventView::new(KeysView::new()).on_event(KeysView::trigger(), |siv| {
-                siv.pop_layer();
+                layers::close(siv, Role::Help);
             }),
         )
     }
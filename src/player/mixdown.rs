@@ -0,0 +1,223 @@
+use std::time::Duration;
+
+use rodio::{source::SeekError, Source};
+
+// The gain of a channel that is shared equally between the left and the right,
+// which keeps its power the same.
+const EQUAL_POWER: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+// Plays a source in stereo. Mono is shared equally between the left and the
+// right and, when downmixing, more than two channels are mixed down. Other
+// sources are played as they are.
+pub struct Mixdown<S> {
+    source: S,
+    // The gains of each channel of the source in the left and the right, or
+    // `None` when the samples are played as they are.
+    gains: Option<Vec<[f32; 2]>>,
+    // The mixed frame that is being played, and the channel of it to play next.
+    frame: [i16; 2],
+    channel: usize,
+}
+
+impl<S: Source<Item = i16>> Mixdown<S> {
+    pub fn new(source: S, downmix: bool) -> Self {
+        let gains = match source.channels() {
+            1 => Some(gains(1)),
+            channels if channels > 2 && downmix => Some(gains(channels)),
+            _ => None,
+        };
+
+        Mixdown {
+            source,
+            gains,
+            frame: [0; 2],
+            channel: 2,
+        }
+    }
+}
+
+// The number of channels that a source with `channels` is played with.
+pub fn output_channels(channels: u16, downmix: bool) -> u16 {
+    match channels {
+        1 => 2,
+        channels if channels > 2 && downmix => 2,
+        channels => channels,
+    }
+}
+
+// The gains of each of `channels` in the left and the right, for channels in
+// the order of WAV and FLAC files: left, right, centre, LFE and then the
+// surrounds in left and right pairs. Quad and 5.0 have no LFE, and quad has no
+// centre. The LFE is left out, and the gains are scaled down so that the mix
+// can't clip.
+pub fn gains(channels: u16) -> Vec<[f32; 2]> {
+    if channels == 1 {
+        return vec![[EQUAL_POWER, EQUAL_POWER]];
+    }
+    let channels = channels as usize;
+    let has_centre = channels != 2 && channels != 4;
+    let has_lfe = channels >= 6;
+
+    let mut gains = vec![[1.0, 0.0], [0.0, 1.0]];
+    if has_centre {
+        gains.push([EQUAL_POWER, EQUAL_POWER]);
+    }
+    if has_lfe {
+        gains.push([0.0, 0.0]);
+    }
+    // An odd surround is a centre, such as the back centre of 6.1.
+    let surrounds = channels.saturating_sub(gains.len());
+    if surrounds % 2 == 1 {
+        gains.push([EQUAL_POWER, EQUAL_POWER]);
+    }
+    while gains.len() < channels {
+        gains.push([EQUAL_POWER, 0.0]);
+        gains.push([0.0, EQUAL_POWER]);
+    }
+
+    let loudest: f32 = gains.iter().map(|gain| gain[0]).sum();
+    if loudest > 1.0 {
+        for gain in gains.iter_mut() {
+            *gain = gain.map(|g| g / loudest);
+        }
+    }
+    gains
+}
+
+impl<S: Source<Item = i16>> Iterator for Mixdown<S> {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        let gains = match &self.gains {
+            Some(gains) => gains,
+            None => return self.source.next(),
+        };
+
+        if self.channel == 2 {
+            let mut mixed = [0.0; 2];
+            for (i, gain) in gains.iter().enumerate() {
+                let sample = match self.source.next() {
+                    Some(sample) => sample as f32,
+                    None if i == 0 => return None,
+                    // The rest of a frame that was cut short is silent.
+                    None => break,
+                };
+                mixed[0] += sample * gain[0];
+                mixed[1] += sample * gain[1];
+            }
+            self.frame = mixed.map(|s| s.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16);
+            self.channel = 0;
+        }
+
+        self.channel += 1;
+        Some(self.frame[self.channel - 1])
+    }
+}
+
+impl<S: Source<Item = i16>> Source for Mixdown<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        let len = self.source.current_frame_len()?;
+        match &self.gains {
+            Some(gains) => Some(len / gains.len() * 2 + 2 - self.channel),
+            None => Some(len),
+        }
+    }
+
+    fn channels(&self) -> u16 {
+        match self.gains {
+            Some(_) => 2,
+            None => self.source.channels(),
+        }
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+
+    // The rest of the frame being played is kept, so that the left and the
+    // right don't swap.
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.source.try_seek(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rodio::buffer::SamplesBuffer;
+
+    use super::*;
+
+    fn mixed(channels: u16, samples: &[i16], downmix: bool) -> (u16, Vec<i16>) {
+        let source = SamplesBuffer::new(channels, 44_100, samples.to_vec());
+        let mixdown = Mixdown::new(source, downmix);
+        (mixdown.channels(), mixdown.collect())
+    }
+
+    #[test]
+    fn test_gains() {
+        let rounded = |channels| -> Vec<[f32; 2]> {
+            let round = |g: f32| (g * 1000.0).round() / 1000.0;
+            gains(channels).iter().map(|gain| gain.map(round)).collect()
+        };
+
+        assert_eq!(rounded(1), vec![[0.707, 0.707]]);
+        assert_eq!(rounded(2), vec![[1.0, 0.0], [0.0, 1.0]]);
+        // 5.1: the centre and the surrounds are 3 dB down, without the LFE,
+        // scaled so that the mix of a full scale sample is full scale.
+        assert_eq!(
+            rounded(6),
+            vec![
+                [0.414, 0.0],
+                [0.0, 0.414],
+                [0.293, 0.293],
+                [0.0, 0.0],
+                [0.293, 0.0],
+                [0.0, 0.293]
+            ]
+        );
+        assert_eq!(rounded(4)[2], [0.414, 0.0]);
+        assert_eq!(rounded(7)[4], rounded(7)[2]);
+        for channels in 1..=8 {
+            let left: f32 = gains(channels).iter().map(|gain| gain[0]).sum();
+            assert!(left <= 1.0 + f32::EPSILON, "{channels} channels");
+        }
+    }
+
+    #[test]
+    fn test_mono_is_shared_equally() {
+        assert_eq!(
+            mixed(1, &[1000, -2000], true),
+            (2, vec![707, 707, -1414, -1414])
+        );
+        // Mono is shared even without downmixing.
+        assert_eq!(mixed(1, &[1000], false).0, 2);
+    }
+
+    #[test]
+    fn test_surround_is_mixed_down() {
+        let frame = [10_000, 0, 10_000, 10_000, 10_000, 0];
+        let (channels, samples) = mixed(6, &frame.repeat(2), true);
+        assert_eq!(channels, 2);
+        assert_eq!(samples, vec![10_000, 2929, 10_000, 2929]);
+
+        // The loudest mix doesn't clip.
+        let (_, samples) = mixed(6, &[i16::MAX; 6], true);
+        assert_eq!(samples, vec![i16::MAX, i16::MAX]);
+
+        // Without downmixing, the channels are played as they are.
+        assert_eq!(mixed(6, &frame, false), (6, frame.to_vec()));
+        assert_eq!(mixed(2, &[1, 2], true), (2, vec![1, 2]));
+    }
+
+    #[test]
+    fn test_output_channels() {
+        assert_eq!(output_channels(1, false), 2);
+        assert_eq!(output_channels(2, true), 2);
+        assert_eq!(output_channels(6, true), 2);
+        assert_eq!(output_channels(6, false), 6);
+    }
+}
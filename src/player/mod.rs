@@ -1,7 +1,13 @@
 pub mod audio_file;
+pub mod audio_focus;
+pub mod bookmarks_view;
 pub mod builder;
+pub mod events;
 pub mod keys_view;
+pub mod mixdown;
+pub mod mp4;
 pub mod opts;
+pub mod output;
 pub mod player;
 pub mod player_view;
 pub mod status;
@@ -9,9 +15,10 @@ pub mod status;
 pub use self::{
     audio_file::{valid_audio_ext, AudioFile},
     builder::PlayerBuilder,
+    events::PlayerEvent,
     keys_view::KeysView,
     opts::PlayerOpts,
     player::{run_automated, Player},
-    player_view::{previous_album, random_album, PlayerView},
-    status::{BytesToStatus, PlayerStatus, StatusToBytes},
+    player_view::{previous_album, random_album, restore_session, shuffle_albums, PlayerView},
+    status::{BytesToStatus, Halt, PlayerStatus, StatusToBytes, StopReason, Transition},
 };
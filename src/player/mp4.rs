@@ -0,0 +1,213 @@
+use std::{
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use super::audio_file::Unplayable;
+
+// The largest 'moov' atom that will be read, to guard against corrupt sizes.
+const MAX_MOOV_LEN: u64 = 64 * 1024 * 1024;
+
+// Checks the atoms of the MP4 file at `path` for the reasons it can't be
+// played, without decoding any audio. Returns `None` if the file looks
+// playable, or if it can't be read, leaving those errors to the decoder.
+pub fn probe(path: &Path) -> Option<Unplayable> {
+    let file = File::open(path).ok()?;
+    let moov = read_moov(&mut BufReader::new(file))?;
+    check_moov(&moov)
+}
+
+// Finds the top-level 'moov' atom and reads its payload.
+fn read_moov(reader: &mut (impl Read + Seek)) -> Option<Vec<u8>> {
+    loop {
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header).ok()?;
+
+        let (size, header_len) = match u32::from_be_bytes(header[..4].try_into().ok()?) {
+            // The size is in the 64-bit field after the type.
+            1 => {
+                let mut large = [0u8; 8];
+                reader.read_exact(&mut large).ok()?;
+                (u64::from_be_bytes(large), 16)
+            }
+            // The atom extends to the end of the file.
+            0 => (u64::MAX, 8),
+            size => (size as u64, 8),
+        };
+
+        if size < header_len {
+            return None;
+        }
+
+        let len = size - header_len;
+
+        if &header[4..] == b"moov" {
+            if len > MAX_MOOV_LEN {
+                return None;
+            }
+            let mut payload = vec![0u8; len as usize];
+            reader.read_exact(&mut payload).ok()?;
+            return Some(payload);
+        }
+
+        reader
+            .seek(SeekFrom::Current(i64::try_from(len).ok()?))
+            .ok()?;
+    }
+}
+
+// The reason that a file with the 'moov' payload `moov` can't be played.
+fn check_moov(moov: &[u8]) -> Option<Unplayable> {
+    let audio_tracks = atoms(moov)
+        .filter(|(kind, _)| kind == b"trak")
+        .filter_map(|(_, trak)| child(trak, &[b"mdia"]))
+        .filter(|mdia| handler_type(mdia) == Some(*b"soun"))
+        .collect::<Vec<&[u8]>>();
+
+    if audio_tracks.is_empty() {
+        return Some(Unplayable::NoAudioStream);
+    }
+
+    // Protected sample entries are 'drms' for iTunes purchases, or 'enca'
+    // for other encrypted audio.
+    let is_protected = |mdia: &&[u8]| {
+        child(mdia, &[b"minf", b"stbl", b"stsd"])
+            .and_then(|stsd| stsd.get(8..))
+            .map_or(false, |entries| {
+                atoms(entries).any(|(kind, _)| &kind == b"drms" || &kind == b"enca")
+            })
+    };
+
+    match audio_tracks.iter().all(is_protected) {
+        true => Some(Unplayable::DrmProtected),
+        false => None,
+    }
+}
+
+// The handler type of a 'mdia' payload, i.e. 'soun' for audio.
+fn handler_type(mdia: &[u8]) -> Option<[u8; 4]> {
+    // After the version, flags and pre-defined fields.
+    child(mdia, &[b"hdlr"])?.get(8..12)?.try_into().ok()
+}
+
+// The payload of the atom at `path` below `payload`.
+fn child<'a>(payload: &'a [u8], path: &[&[u8; 4]]) -> Option<&'a [u8]> {
+    path.iter().try_fold(payload, |payload, kind| {
+        atoms(payload)
+            .find(|(k, _)| k == *kind)
+            .map(|(_, child)| child)
+    })
+}
+
+// The types and payloads of the atoms in `data`.
+fn atoms(mut data: &[u8]) -> impl Iterator<Item = ([u8; 4], &[u8])> {
+    std::iter::from_fn(move || {
+        let size = u32::from_be_bytes(data.get(..4)?.try_into().ok()?) as usize;
+        let kind: [u8; 4] = data.get(4..8)?.try_into().ok()?;
+
+        let (start, end) = match size {
+            0 => (8, data.len()),
+            1 => {
+                let large = u64::from_be_bytes(data.get(8..16)?.try_into().ok()?);
+                (16, usize::try_from(large).ok()?)
+            }
+            size => (8, size),
+        };
+
+        let payload = data.get(start..end)?;
+        data = &data[end..];
+        Some((kind, payload))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn atom(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut atom = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        atom.extend(kind);
+        atom.extend(payload);
+        atom
+    }
+
+    // A minimal MP4 file with a track for each of the (handler type,
+    // sample entry type) pairs in `tracks`.
+    fn mp4(tracks: &[(&[u8; 4], &[u8; 4])]) -> Vec<u8> {
+        let traks = tracks
+            .iter()
+            .flat_map(|(handler, entry)| {
+                let mut hdlr = vec![0u8; 8];
+                hdlr.extend(*handler);
+                hdlr.extend([0u8; 13]);
+
+                let mut stsd = vec![0, 0, 0, 0, 0, 0, 0, 1];
+                stsd.extend(atom(entry, &[0u8; 28]));
+
+                let stbl = atom(b"stbl", &atom(b"stsd", &stsd));
+                let minf = atom(b"minf", &stbl);
+                let mdia = atom(b"mdia", &[atom(b"hdlr", &hdlr), minf].concat());
+                atom(b"trak", &mdia)
+            })
+            .collect::<Vec<u8>>();
+
+        [
+            atom(b"ftyp", b"M4A \0\0\0\0"),
+            atom(b"mdat", &[0u8; 64]),
+            atom(b"moov", &[atom(b"mvhd", &[0u8; 100]), traks].concat()),
+        ]
+        .concat()
+    }
+
+    fn check(data: &[u8]) -> Option<Unplayable> {
+        let moov = read_moov(&mut std::io::Cursor::new(data))?;
+        check_moov(&moov)
+    }
+
+    #[test]
+    fn test_playable_audio() {
+        assert_eq!(check(&mp4(&[(b"soun", b"mp4a")])), None);
+        assert_eq!(check(&mp4(&[(b"soun", b"alac")])), None);
+    }
+
+    #[test]
+    fn test_drm_protected() {
+        assert_eq!(
+            check(&mp4(&[(b"soun", b"drms")])),
+            Some(Unplayable::DrmProtected)
+        );
+        assert_eq!(
+            check(&mp4(&[(b"soun", b"enca")])),
+            Some(Unplayable::DrmProtected)
+        );
+        // A file with an unprotected audio track can still be played.
+        assert_eq!(check(&mp4(&[(b"soun", b"drms"), (b"soun", b"mp4a")])), None);
+    }
+
+    #[test]
+    fn test_no_audio_stream() {
+        assert_eq!(check(&mp4(&[])), Some(Unplayable::NoAudioStream));
+        assert_eq!(
+            check(&mp4(&[(b"vide", b"avc1")])),
+            Some(Unplayable::NoAudioStream)
+        );
+    }
+
+    #[test]
+    fn test_probe_fixture_files() {
+        let temp_dir = crate::utils::create_working_dir(&[], &[], &[]).unwrap();
+        let path = temp_dir.path().join("protected.m4p");
+        std::fs::write(&path, mp4(&[(b"soun", b"drms")])).unwrap();
+        assert_eq!(probe(&path), Some(Unplayable::DrmProtected));
+
+        let path = temp_dir.path().join("video.m4a");
+        std::fs::write(&path, mp4(&[(b"vide", b"avc1")])).unwrap();
+        assert_eq!(probe(&path), Some(Unplayable::NoAudioStream));
+
+        // Files that aren't MP4 are left to the decoder.
+        let path = crate::utils::find_assets_dir().join("test_m4a_audio.m4a");
+        assert_eq!(probe(&path), None);
+        assert_eq!(probe(&temp_dir.path().join("missing.m4a")), None);
+    }
+}
@@ -0,0 +1,36 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rodio::{OutputStream, OutputStreamHandle, Sink};
+
+use crate::config::args::{self, AutoPause};
+use crate::profile::{self, Phase};
+
+use super::audio_focus;
+
+// The number of times that the audio output has been opened. Modes that don't
+// play audio never open it, which the startup profile reports.
+static OPENED: AtomicUsize = AtomicUsize::new(0);
+
+// Opens the default audio output and a sink that plays to it. This is the
+// only place that the audio backend is started, so that it is only started
+// once something is about to play, rather than when tap starts.
+pub fn open() -> Result<(Sink, (OutputStream, OutputStreamHandle)), anyhow::Error> {
+    OPENED.fetch_add(1, Ordering::Relaxed);
+
+    let (sink, output) = profile::span(Phase::Audio, || {
+        let (stream, stream_handle) = OutputStream::try_default()?;
+        let sink = Sink::try_new(&stream_handle)?;
+        Ok::<_, anyhow::Error>((sink, (stream, stream_handle)))
+    })?;
+
+    if args::auto_pause() != AutoPause::Off {
+        audio_focus::start();
+    }
+
+    Ok((sink, output))
+}
+
+// The number of times that the audio output has been opened.
+pub fn times_opened() -> usize {
+    OPENED.load(Ordering::Relaxed)
+}
@@ -1,8 +1,14 @@
 use std::{
     cmp::{max, min},
+    collections::HashMap,
     fs::File,
     io::BufReader,
     path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
     time::{Duration, Instant},
 };
 
@@ -11,14 +17,39 @@ use cursive::XY;
 use expiring_bool::ExpiringBool;
 use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink};
 
+use crate::config::args::{self, AutoPause};
+use crate::data::{album_gain, AlbumGains};
+use crate::profile::{self, Phase};
 use crate::utils;
 
-use super::{valid_audio_ext, AudioFile, PlayerOpts, PlayerStatus, StatusToBytes};
+use super::{
+    events::{EventBus, Receiver, EVENT_CAPACITY},
+    mixdown::Mixdown,
+    output, valid_audio_ext, AudioFile, Halt, PlayerEvent, PlayerOpts, PlayerStatus, StatusToBytes,
+    StopReason, Transition,
+};
 
 pub type PlayerResult = Result<(Player, bool, XY<usize>), anyhow::Error>;
 
 const SEEK_TIME: Duration = Duration::from_secs(10);
 
+// The fraction of the volume that is played while another application is
+// playing audio, when ducking.
+const DUCK_VOLUME: f32 = 0.2;
+
+// The name of the log that unplayable files are written to.
+const LOG: &str = "player";
+
+// The name of the log that pauses and stops are written to.
+const PLAYBACK_LOG: &str = "playback";
+
+// The most threads used to read the tags of a playlist.
+const MAX_READERS: usize = 8;
+
+// The number of audio files that can be read ahead of the playlist being
+// collected.
+const READ_QUEUE_LEN: usize = 64;
+
 pub struct Player {
     // The list of audio files for the player.
     pub playlist: Vec<AudioFile>,
@@ -30,56 +61,130 @@ pub struct Player {
     pub volume: u8,
     // Whether the player is muted or not.
     pub is_muted: bool,
+    // The gain of the album in dB, applied on top of the volume.
+    pub album_gain: i8,
+    // The album that `album_gain` was loaded for.
+    gain_album: Option<PathBuf>,
     // Whether or not the next track will be selected randomly.
     pub is_randomized: bool,
     // Whether or not the next track is queued.
     pub next_track_queued: bool,
     // Whether the player is playing, paused or stopped.
     pub status: PlayerStatus,
+    // Whether playback was paused or ducked because another application
+    // started playing audio.
+    pub system_pause: Option<AutoPause>,
+    // Why and when playback was last paused or stopped.
+    pub last_halt: Option<(Halt, Instant)>,
     // The list of numbers from last keyboard input.
     pub num_keys: Vec<usize>,
+    // The index of the first file with each track number.
+    track_index: HashMap<u32, usize>,
     // Whether or not a double-tap event was registered.
     pub timer_bool: ExpiringBool,
     // The instant that playback started or resumed.
     last_started: Instant,
     // The instant that the player was paused. Reset when player is stopped.
     last_elapsed: Duration,
+    // The subscribers to playback events.
+    events: EventBus<PlayerEvent>,
     // Handle to audio sink.
     sink: Sink,
-    // The open flow of audio data.
-    _stream: OutputStream,
-    // Handle to stream.
-    _stream_handle: OutputStreamHandle,
+    // The open flow of audio data and its handle. `None` with a null sink.
+    _stream: Option<(OutputStream, OutputStreamHandle)>,
 }
 
 impl Player {
     pub fn new(path: PathBuf, index: usize, opts: PlayerOpts, is_randomized: bool) -> PlayerResult {
-        let (playlist, size) = playlist(&path)?;
-        let (_stream, _stream_handle) = OutputStream::try_default()?;
-        let sink = Sink::try_new(&_stream_handle)?;
+        Self::from_playlist(playlist(&path)?, index, opts, is_randomized)
+    }
+
+    // A player for the albums at `paths`, which play one after another.
+    pub fn with_albums(paths: &[PathBuf], opts: PlayerOpts) -> PlayerResult {
+        Self::from_playlist(albums_playlist(paths)?, 0, opts, false)
+    }
+
+    // A player for the playlist, with the size it is drawn at, starting at
+    // `index`.
+    fn from_playlist(
+        (playlist, size): (Vec<AudioFile>, XY<usize>),
+        index: usize,
+        opts: PlayerOpts,
+        is_randomized: bool,
+    ) -> PlayerResult {
+        // The album may have changed since the index was saved.
+        let index = index.min(playlist.len() - 1);
+        let (sink, output) = output::open()?;
+        let showing_volume = opts.showing_volume;
+
+        let mut player = Self::with_sink(playlist, index, opts, is_randomized, sink, Some(output));
+        player.load_album_gain();
+
+        Ok((player, showing_volume, size))
+    }
+
+    // A stopped player for `playlist` with a null sink, so that the player
+    // can be drawn without any audio files or output device.
+    #[cfg(test)]
+    pub fn with_playlist(playlist: Vec<AudioFile>, index: usize) -> Self {
+        let opts = PlayerOpts {
+            status: PlayerStatus::Stopped(StopReason::User),
+            ..Default::default()
+        };
+        Self::with_sink(playlist, index, opts, false, Sink::new_idle().0, None)
+    }
+
+    fn with_sink(
+        playlist: Vec<AudioFile>,
+        index: usize,
+        opts: PlayerOpts,
+        is_randomized: bool,
+        sink: Sink,
+        _stream: Option<(OutputStream, OutputStreamHandle)>,
+    ) -> Self {
+        let mut track_index = HashMap::new();
+        for (i, file) in playlist.iter().enumerate() {
+            track_index.entry(file.track).or_insert(i);
+        }
 
         let mut player = Self {
+            track_index,
+            system_pause: None,
+            last_halt: None,
             last_started: Instant::now(),
             last_elapsed: Duration::ZERO,
             previous: 0,
             num_keys: vec![],
             next_track_queued: false,
             timer_bool: ExpiringBool::new(false, Duration::from_millis(500)),
-            status: opts.status,
+            // A new player hasn't played its album to the end.
+            status: match opts.status {
+                PlayerStatus::Stopped(_) => PlayerStatus::Stopped(StopReason::User),
+                status => status,
+            },
             volume: opts.volume,
             is_muted: opts.is_muted,
+            album_gain: 0,
+            gain_album: None,
             index,
             playlist,
             is_randomized,
+            events: EventBus::new(EVENT_CAPACITY),
             sink,
             _stream,
-            _stream_handle,
         };
 
         player.set_volume();
         player.set_playback();
 
-        Ok((player, opts.showing_volume, size))
+        player
+    }
+
+    // Subscribes to the playback events of this player. Events are received
+    // in the order they happen. A subscriber that falls behind loses its
+    // oldest events rather than holding up playback.
+    pub fn subscribe(&mut self) -> Receiver<PlayerEvent> {
+        self.events.subscribe()
     }
 
     // The current audio file.
@@ -94,47 +199,110 @@ impl Player {
 
     // Resumes a paused sink and records the start time.
     pub fn resume(&mut self) {
+        self.user_override();
         self.sink.play();
-        self.status = PlayerStatus::Playing;
+        self.status = self.status.after(Transition::Resume);
         self.last_started = Instant::now();
+        self.events.send(PlayerEvent::Resumed);
     }
 
     // Pauses the sink and records the elapsed time.
     pub fn pause(&mut self) {
+        self.user_override();
+        if self.is_playing() {
+            self.record_halt(Halt::Paused);
+        }
         self.last_elapsed = self.elapsed();
         self.sink.pause();
-        self.status = PlayerStatus::Paused;
+        self.status = self.status.after(Transition::Pause);
+        self.events.send(PlayerEvent::Paused);
     }
 
     // Empties the sink, clears the current inputs and elapsed time.
     pub fn stop(&mut self) -> u8 {
+        self.stop_because(StopReason::User)
+    }
+
+    // Stops the player, recording why it stopped.
+    fn stop_because(&mut self, reason: StopReason) -> u8 {
+        if !self.status.is_stopped() {
+            self.record_halt(Halt::Stopped(reason));
+        }
+        self.stop_sink(reason)
+    }
+
+    // Stops the player before playback is started again, such as when
+    // changing tracks, so it isn't recorded as a stop.
+    fn stop_to_restart(&mut self) {
+        self.stop_sink(StopReason::User);
+    }
+
+    fn stop_sink(&mut self, reason: StopReason) -> u8 {
+        self.user_override();
         self.clear();
-        if self.status != PlayerStatus::Stopped {
+        if !self.status.is_stopped() {
             self.sink.stop();
-            self.status = PlayerStatus::Stopped;
             self.last_elapsed = Duration::ZERO;
         }
+        self.status = self.status.after(Transition::Stop(reason));
         self.status.to_u8()
     }
 
     // Decodes and appends `file` to the sink, starts playback and records start time.
     pub fn play(&mut self) {
-        if let Ok(source) = decode(self.path()) {
-            self.sink.append(source);
-            self.sink.play();
-            self.status = PlayerStatus::Playing;
-            self.last_started = Instant::now();
-        } else {
-            self.next()
+        // Play the next playable track instead, if there is one.
+        if !self.skip_unplayable() {
+            return;
+        }
+        match mixed(self.path()) {
+            Ok(source) => {
+                self.sink.append(source);
+                self.sink.play();
+                self.status = self.status.after(Transition::Play);
+                self.last_started = Instant::now();
+                self.track_started();
+            }
+            Err(e) => {
+                self.error(e);
+                self.next()
+            }
         }
     }
 
+    // Pauses or ducks playback while another application plays audio. Only a
+    // playing player is affected, so a player paused by the user stays paused.
+    pub fn system_pause(&mut self, mode: AutoPause) {
+        if self.system_pause.is_some() || !self.is_playing() {
+            return;
+        }
+        match mode {
+            AutoPause::Off => return,
+            AutoPause::Pause => {
+                self.pause();
+                self.record_halt(Halt::SystemPaused);
+            }
+            AutoPause::Duck => (),
+        }
+        self.system_pause = Some(mode);
+        self.set_volume();
+    }
+
+    // Undoes `system_pause` once the other application has stopped playing.
+    // Playback isn't resumed if the user has paused or played since.
+    pub fn system_resume(&mut self) {
+        match self.system_pause.take() {
+            Some(AutoPause::Pause) if self.status == PlayerStatus::Paused => self.resume(),
+            _ => (),
+        }
+        self.set_volume();
+    }
+
     // Starts playback if not playing, pauses otherwise.
     pub fn play_or_pause(&mut self) -> u8 {
         match self.status {
             PlayerStatus::Paused => self.resume(),
             PlayerStatus::Playing => self.pause(),
-            PlayerStatus::Stopped => self.play(),
+            PlayerStatus::Stopped(_) => self.play(),
         };
         self.status.to_u8()
     }
@@ -151,8 +319,8 @@ impl Player {
         // Play the track from number key inputs.
         } else {
             let track_number = utils::concatenate(&self.num_keys) as u32;
-            if let Some(index) = self.playlist.iter().position(|f| f.track == track_number) {
-                self.play_index(index.clone());
+            if let Some(&index) = self.track_index.get(&track_number) {
+                self.play_index(index);
             } else {
                 self.clear();
             }
@@ -164,6 +332,11 @@ impl Player {
         self.play_index(selected);
     }
 
+    // Play the first track in the current playlist.
+    pub fn play_first_track(&mut self) {
+        self.play_index(0);
+    }
+
     // Play the last track in the current playlist.
     pub fn play_last_track(&mut self) {
         self.play_index(self.last_index());
@@ -183,8 +356,12 @@ impl Player {
     // Skip to previous track in the playlist.
     pub fn previous(&mut self) {
         self.clear();
-        if self.index > 0 {
+        // Skip back over the tracks that can't be played.
+        while self.index > 0 {
             self.index -= 1;
+            if self.file().is_playable() {
+                break;
+            }
         }
         self.set_playback();
     }
@@ -193,9 +370,8 @@ impl Player {
     pub fn increase_volume(&mut self) -> u8 {
         if self.volume < 120 {
             self.volume += 10;
-            if !self.is_muted {
-                self.sink.set_volume(self.volume as f32 / 100.0);
-            }
+            self.set_volume();
+            self.volume_changed();
         }
         self.volume
     }
@@ -204,9 +380,8 @@ impl Player {
     pub fn decrease_volume(&mut self) -> u8 {
         if self.volume > 0 {
             self.volume -= 10;
-            if !self.is_muted {
-                self.sink.set_volume(self.volume as f32 / 100.0);
-            }
+            self.set_volume();
+            self.volume_changed();
         }
         self.volume
     }
@@ -215,11 +390,8 @@ impl Player {
     // this change. Returns the updated `is_muted`.
     pub fn toggle_mute(&mut self) -> bool {
         self.is_muted ^= true;
-        self.sink.set_volume(if self.is_muted {
-            0.0
-        } else {
-            self.volume as f32 / 100.0
-        });
+        self.set_volume();
+        self.volume_changed();
         self.is_muted
     }
 
@@ -243,12 +415,11 @@ impl Player {
         while count < 10 {
             let target = utils::random(0..paths.len());
             let path = paths[target].to_owned();
-            if let Ok((playlist, _)) = playlist(&path) {
-                let index = utils::random(0..playlist.len());
-                return Some((path, index));
-            } else {
-                count += 1;
-                continue;
+            // Counting the tracks is enough here, since the tags of every
+            // track are read when the player is created.
+            match track_count(&path) {
+                0 => count += 1,
+                len => return Some((path, utils::random(0..len))),
             }
         }
         None
@@ -337,6 +508,10 @@ impl Player {
             let future = elapsed + time;
             if let Ok(_) = self.sink.try_seek(future) {
                 self.last_started -= time;
+                self.events.send(PlayerEvent::Seeked {
+                    from: elapsed,
+                    to: future,
+                });
             }
         }
     }
@@ -348,7 +523,7 @@ impl Player {
             self.play_or_pause();
         }
         if elapsed < time + Duration::new(0, 500) {
-            self.stop();
+            self.stop_to_restart();
             self.play();
         } else {
             let past = elapsed - time;
@@ -362,6 +537,10 @@ impl Player {
                     self.last_elapsed = Duration::ZERO;
                     self.last_started += diff;
                 }
+                self.events.send(PlayerEvent::Seeked {
+                    from: elapsed,
+                    to: past,
+                });
             }
         }
     }
@@ -384,23 +563,22 @@ impl Player {
     // track in the playlist, the next track is queued before the
     // current track completes. This is to ensure gapless playback.
     //
-    // If playback is randomized, the next track is queued when
-    // the current track completes.
+    // If playback is randomized, or the next track has a different
+    // sample rate, the next track is queued when the current track
+    // completes.
     //
     // Finally, playback is stopped when the sink is emptied.
     //
-    // Return values are for the automated player, where:
-    // 0 => the player has completed.
-    // 1 => the player has changed.
-    // 2 => the player is unchanged.
+    // Changes to the current track are sent to the subscribers.
     #[inline]
-    pub fn poll(&mut self) -> usize {
+    pub fn poll(&mut self) {
         if !self.is_playing() {
-            return 0;
+            return;
         }
         if self.is_randomized {
-            if self.sink.empty() {
+            if self.sink.empty() && !self.next_track_queued {
                 self.next_track_queued = true;
+                self.events.send(PlayerEvent::TrackEnded);
             }
         } else if self.sink.len() == 1 {
             if self.next_track_queued {
@@ -408,19 +586,39 @@ impl Player {
                 self.last_elapsed = Duration::ZERO;
                 self.index += 1;
                 self.next_track_queued = false;
-                return 1;
-            } else if let Some(next) = self.playlist.get(self.index + 1) {
-                if let Ok(source) = decode(&next.path) {
-                    self.sink.append(source);
-                    self.next_track_queued = true;
-                } else {
-                    self.next();
+                self.events.send(PlayerEvent::TrackEnded);
+                self.track_started();
+            } else if let Some(next) = self.gapless_next().filter(|f| f.is_playable()) {
+                match mixed(&next.path) {
+                    Ok(source) => {
+                        self.sink.append(source);
+                        self.next_track_queued = true;
+                    }
+                    Err(e) => {
+                        self.error(e);
+                        self.next();
+                    }
                 }
             }
         } else if self.sink.empty() {
-            self.stop();
+            self.events.send(PlayerEvent::TrackEnded);
+            if self.index < self.last_index() {
+                // The next track wasn't queued because of a change in sample rate
+                // or channels, or because it can't be played.
+                self.index += 1;
+                self.set_playback();
+            } else {
+                self.events.send(PlayerEvent::AlbumEnded);
+                self.stop_because(StopReason::AlbumEnded);
+            }
         }
-        2
+    }
+
+    // The next track in the playlist, if it can be queued for gapless playback.
+    fn gapless_next(&self) -> Option<&AudioFile> {
+        self.playlist
+            .get(self.index + 1)
+            .filter(|next| self.file().is_gapless_with(next, args::downmix()))
     }
 
     // Stdout for the automated player.
@@ -456,7 +654,7 @@ impl Player {
 
     // Play the track at `index` in playlist.
     fn play_index(&mut self, index: usize) {
-        self.stop();
+        self.stop_to_restart();
         self.index = index;
         self.clear();
         self.play();
@@ -467,10 +665,18 @@ impl Player {
         self.sink.stop();
         self.last_elapsed = Duration::ZERO;
 
-        if self.status != PlayerStatus::Stopped {
-            if let Ok(source) = decode(self.path()) {
-                self.sink.append(source);
-                self.last_started = Instant::now();
+        if !self.status.is_stopped() {
+            // Skip to the next playable track, keeping the status.
+            if !self.skip_unplayable() {
+                return;
+            }
+            match mixed(self.path()) {
+                Ok(source) => {
+                    self.sink.append(source);
+                    self.last_started = Instant::now();
+                    self.track_started();
+                }
+                Err(e) => self.error(e),
             }
             if self.status == PlayerStatus::Paused {
                 self.sink.pause()
@@ -478,12 +684,95 @@ impl Player {
         }
     }
 
+    // Moves forward past the tracks that can't be played, sending an error for
+    // each one. Returns false, with the player stopped, if none are left.
+    fn skip_unplayable(&mut self) -> bool {
+        while let Some(e) = self.unplayable() {
+            self.error(e);
+            if self.index == self.last_index() {
+                self.stop_because(StopReason::Unplayable);
+                return false;
+            }
+            self.index += 1;
+        }
+        true
+    }
+
+    // The error for the current file, if it can't be played.
+    fn unplayable(&self) -> Option<anyhow::Error> {
+        self.file().unplayable.map(|reason| {
+            anyhow::anyhow!(
+                "skipped '{}': {}",
+                utils::display_path(self.path()),
+                reason.reason()
+            )
+        })
+    }
+
+    fn track_started(&mut self) {
+        profile::mark(Phase::FirstAudio);
+        self.load_album_gain();
+        let (path, index) = (self.path().to_owned(), self.index);
+        self.events.send(PlayerEvent::TrackStarted { path, index });
+    }
+
+    fn volume_changed(&mut self) {
+        let (volume, is_muted) = (self.volume, self.is_muted);
+        self.events
+            .send(PlayerEvent::VolumeChanged { volume, is_muted });
+    }
+
+    fn error(&mut self, e: anyhow::Error) {
+        let msg = e.to_string();
+        self.events.send(PlayerEvent::Error { msg });
+    }
+
+    // Loads the saved gain of the album of the current file, unless it was
+    // loaded for that album already, such as when the playlist moves into
+    // the next album.
+    pub fn load_album_gain(&mut self) {
+        let album = match self.path().parent() {
+            Some(album) => album.to_owned(),
+            None => return,
+        };
+        if self.gain_album.as_ref() != Some(&album) {
+            self.set_album_gain(AlbumGains::cached().get(&album));
+            self.gain_album = Some(album);
+        }
+    }
+
+    // Sets the gain of the album in dB.
+    pub fn set_album_gain(&mut self, gain: i8) {
+        self.album_gain = gain;
+        self.set_volume();
+    }
+
     // Apply volume setting to the audio sink.
     fn set_volume(&mut self) {
-        if self.is_muted {
-            self.sink.set_volume(0.0)
-        } else {
-            self.sink.set_volume(self.volume as f32 / 100.0);
+        // A boost is limited to full volume, or to the volume if that is
+        // higher already, so that a loud album doesn't clip.
+        let volume = self.volume as f32 / 100.0;
+        let volume = (volume * album_gain::amplitude(self.album_gain)).min(volume.max(1.0));
+        let volume = match (self.is_muted, self.system_pause) {
+            (true, _) => 0.0,
+            (false, Some(AutoPause::Duck)) => volume * DUCK_VOLUME,
+            (false, _) => volume,
+        };
+        self.sink.set_volume(volume);
+    }
+
+    // Records why playback was paused or stopped, and logs it.
+    fn record_halt(&mut self, halt: Halt) {
+        self.last_halt = Some((halt, Instant::now()));
+        let path = utils::display_path(self.path());
+        utils::log(PLAYBACK_LOG, &format!("{}: '{path}'", halt.name()));
+    }
+
+    // Stops `system_resume` from resuming playback after the user has paused,
+    // played or stopped the player.
+    fn user_override(&mut self) {
+        if self.system_pause == Some(AutoPause::Pause) {
+            self.system_pause = None;
         }
     }
 }
@@ -494,6 +783,7 @@ pub fn run_automated(path: PathBuf) -> Result<(), anyhow::Error> {
     use std::thread::sleep;
 
     let (mut player, _, _) = super::PlayerBuilder::new(path)?;
+    let events = player.subscribe();
     let (mut line, mut length) = player.stdout();
 
     print!("{}", line);
@@ -506,20 +796,31 @@ pub fn run_automated(path: PathBuf) -> Result<(), anyhow::Error> {
             return Ok(());
         }
 
-        match player.poll() {
-            0 => {
-                println!();
-                return Ok(());
-            }
-            1 => {
-                // Print the number of spaces required to clear the previous line.
-                print!("\r{: <1$}", "", length);
-                (line, length) = player.stdout();
-                print!("\r{}", line);
-                stdout().flush()?;
+        player.poll();
+
+        for event in events.try_iter() {
+            match event {
+                PlayerEvent::TrackStarted { .. } => {
+                    // Print the number of spaces required to clear the previous line.
+                    print!("\r{: <1$}", "", length);
+                    (line, length) = player.stdout();
+                    print!("\r{}", line);
+                    stdout().flush()?;
+                }
+                PlayerEvent::AlbumEnded => {
+                    println!();
+                    return Ok(());
+                }
+                _ => (),
             }
-            _ => sleep(Duration::from_millis(60)),
         }
+
+        if player.status.is_stopped() {
+            println!();
+            return Ok(());
+        }
+
+        sleep(Duration::from_millis(60));
     }
 }
 
@@ -543,16 +844,28 @@ pub fn playlist(path: &PathBuf) -> Result<(Vec<AudioFile>, XY<usize>), anyhow::E
     };
 
     if paths.is_empty() {
-        bail!("'{}' is empty", path.display())
+        bail!("'{}' is empty", utils::display_path(path))
     }
 
+    let paths = paths
+        .into_iter()
+        .filter(|path| valid_audio_ext(path))
+        .collect::<Vec<PathBuf>>();
+
     // The audio files comprising our playlist.
     let mut list = {
-        paths
+        read_audio_files(paths)
             .into_iter()
-            .filter(|path| valid_audio_ext(path))
-            .filter_map(|path| match AudioFile::new(path) {
+            .filter_map(|file| match file {
                 Ok(file) => {
+                    if let Some(reason) = file.unplayable {
+                        let msg = format!(
+                            "'{}' is unplayable: {}",
+                            utils::display_path(&file.path),
+                            reason.reason()
+                        );
+                        utils::log(LOG, &msg);
+                    }
                     width = max(width, file.title.len());
                     Some(file)
                 }
@@ -566,14 +879,22 @@ pub fn playlist(path: &PathBuf) -> Result<(Vec<AudioFile>, XY<usize>), anyhow::E
     }
     .collect::<Vec<AudioFile>>();
 
-    // Check the first track can be decoded and calculate the required width.
+    // Check the first playable track can be decoded and calculate the required width.
     if let Some(first) = list.first() {
         width = max(width, first.album.len() + first.artist.len() + 1);
-        _ = decode(&first.path)?;
+        match list.iter().find(|f| f.is_playable()) {
+            Some(playable) => _ = decode(&playable.path)?,
+            None => bail!(
+                "no playable audio files in '{}', '{}' is {}",
+                utils::display_path(path),
+                utils::display_path(&first.path),
+                first.unplayable.map_or("", |u| u.reason())
+            ),
+        }
     } else {
         match error {
             Some(e) => bail!(e),
-            None => bail!("no audio files detected in '{}'", path.display()),
+            None => bail!("no audio files detected in '{}'", utils::display_path(path)),
         }
     }
 
@@ -587,22 +908,603 @@ pub fn playlist(path: &PathBuf) -> Result<(Vec<AudioFile>, XY<usize>), anyhow::E
     Ok((list, size))
 }
 
+// The playlists of the albums at `paths`, one after another, and the size
+// that fits the widest of them.
+pub fn albums_playlist(paths: &[PathBuf]) -> Result<(Vec<AudioFile>, XY<usize>), anyhow::Error> {
+    let mut list = vec![];
+    let mut width = 0;
+
+    for path in paths {
+        let (album, size) = playlist(path)?;
+        list.extend(album);
+        width = max(width, size.x);
+    }
+
+    if list.is_empty() {
+        bail!("no albums to play");
+    }
+
+    let size = XY {
+        x: width,
+        y: min(45, list.len() + 3),
+    };
+
+    Ok((list, size))
+}
+
+// The number of audio files in the directory at `path`, or 1 if `path` is
+// an audio file. The files aren't read, so they may not be playable.
+pub fn track_count(path: &PathBuf) -> usize {
+    match path.read_dir() {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| valid_audio_ext(path) && path.is_file())
+            .count(),
+        Err(_) => valid_audio_ext(path) as usize,
+    }
+}
+
+// Reads the audio files at `paths` on a pool of threads, which is faster than
+// reading them one by one for large directories. The results are in the same
+// order as `paths`.
+fn read_audio_files(paths: Vec<PathBuf>) -> Vec<Result<AudioFile, anyhow::Error>> {
+    let readers = thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(MAX_READERS)
+        .min(paths.len());
+
+    if readers <= 1 {
+        return paths.into_iter().map(AudioFile::new).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let mut files = Vec::with_capacity(paths.len());
+    files.resize_with(paths.len(), || None);
+
+    thread::scope(|s| {
+        // A bounded queue, so that the readers can't get far ahead of the results.
+        let (tx, rx) = mpsc::sync_channel(READ_QUEUE_LEN);
+
+        for _ in 0..readers {
+            let (tx, next, paths) = (tx.clone(), &next, &paths);
+            s.spawn(move || loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                match paths.get(i) {
+                    Some(path) => {
+                        if tx.send((i, AudioFile::new(path.to_owned()))).is_err() {
+                            return;
+                        }
+                    }
+                    None => return,
+                }
+            });
+        }
+        drop(tx);
+
+        for (i, file) in rx {
+            files[i] = Some(file);
+        }
+    });
+
+    files.into_iter().flatten().collect()
+}
+
 pub fn decode(path: &PathBuf) -> Result<Decoder<BufReader<File>>, anyhow::Error> {
     let source = match File::open(path.as_path()) {
         Ok(inner) => match Decoder::new(BufReader::new(inner)) {
             Ok(s) => s,
-            Err(_) => bail!("could not decode '{}'", path.display()),
+            Err(_) => bail!("could not decode '{}'", utils::display_path(path)),
         },
-        Err(_) => bail!("could not open '{}'", path.display()),
+        Err(_) => bail!("could not open '{}'", utils::display_path(path)),
     };
     Ok(source)
 }
 
+// Decodes the file at `path` to be played in stereo, or with all of its
+// channels when surround isn't downmixed.
+pub fn mixed(path: &PathBuf) -> Result<Mixdown<Decoder<BufReader<File>>>, anyhow::Error> {
+    Ok(Mixdown::new(decode(path)?, args::downmix()))
+}
+
 #[cfg(test)]
 mod tests {
+    use rodio::{queue::SourcesQueueOutput, Source};
+
     use super::*;
+    use crate::player::audio_file::Unplayable;
     use crate::utils::{create_working_dir, find_assets_dir};
 
+    // Creates a stopped player for `path` with a null sink. Playback is
+    // driven by pulling samples from the returned sink output.
+    fn null_player(path: &PathBuf) -> (Player, SourcesQueueOutput<f32>) {
+        let (playlist, _) = playlist(path).expect("should create a valid playlist");
+        let (sink, output) = Sink::new_idle();
+        let opts = PlayerOpts {
+            status: PlayerStatus::Stopped(StopReason::User),
+            ..Default::default()
+        };
+
+        (
+            Player::with_sink(playlist, 0, opts, false, sink, None),
+            output,
+        )
+    }
+
+    // Plays the null sink until the player stops, polling the player as
+    // the player view would.
+    fn play_to_end(player: &mut Player, output: &mut SourcesQueueOutput<f32>) {
+        for _ in 0..10_000 {
+            output.by_ref().take(1024).for_each(drop);
+            player.poll();
+            if player.status.is_stopped() {
+                return;
+            }
+        }
+        panic!("the player should stop at the end of the playlist");
+    }
+
+    #[test]
+    fn test_events_for_album_played_to_end() {
+        let temp_dir = create_working_dir(
+            &["album"],
+            &[
+                ("album/one.wav", "test_wav_audio.wav"),
+                ("album/two.ogg", "test_ogg_audio.ogg"),
+            ],
+            &[],
+        )
+        .expect("create temp dir");
+        let (mut player, mut output) = null_player(&temp_dir.path().join("album"));
+        let events = player.subscribe();
+
+        player.play();
+        play_to_end(&mut player, &mut output);
+
+        let started = |index: usize| PlayerEvent::TrackStarted {
+            path: player.playlist[index].path.to_owned(),
+            index,
+        };
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            vec![
+                started(0),
+                PlayerEvent::TrackEnded,
+                started(1),
+                PlayerEvent::TrackEnded,
+                PlayerEvent::AlbumEnded,
+            ]
+        );
+        assert_eq!(player.status, PlayerStatus::Stopped(StopReason::AlbumEnded));
+    }
+
+    // Writes a tagged, mono, 16-bit WAV file with `secs` of a cosine at `freq` Hz.
+    fn write_tone(path: &PathBuf, title: &str, sample_rate: u32, secs: f32, freq: f32) {
+        let samples = (sample_rate as f32 * secs) as u32;
+        let samples = (0..samples)
+            .map(|n| {
+                let t = n as f32 / sample_rate as f32;
+                (0.5 * (2.0 * std::f32::consts::PI * freq * t).cos() * i16::MAX as f32) as i16
+            })
+            .collect::<Vec<i16>>();
+
+        write_wav(path, title, sample_rate, 1, &samples);
+    }
+
+    // Writes a tagged, 16-bit WAV file with the interleaved `samples` of
+    // `channels`. Files with more than two channels use the extensible format,
+    // with the channels of 5.1 and so on.
+    fn write_wav(path: &PathBuf, title: &str, sample_rate: u32, channels: u16, samples: &[i16]) {
+        let data = samples
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect::<Vec<u8>>();
+
+        // The title, as a RIFF INFO chunk, so that the file has tags.
+        let mut name = title.as_bytes().to_vec();
+        name.push(0);
+        if name.len() % 2 == 1 {
+            name.push(0);
+        }
+        let mut info = b"INFOINAM".to_vec();
+        info.extend((name.len() as u32).to_le_bytes());
+        info.extend(name);
+
+        let mut fmt = vec![];
+        fmt.extend(
+            match channels > 2 {
+                true => 0xfffeu16,
+                false => 1,
+            }
+            .to_le_bytes(),
+        );
+        fmt.extend(channels.to_le_bytes());
+        fmt.extend(sample_rate.to_le_bytes());
+        fmt.extend((sample_rate * channels as u32 * 2).to_le_bytes());
+        fmt.extend((channels * 2).to_le_bytes());
+        fmt.extend(16u16.to_le_bytes());
+        if channels > 2 {
+            // The size of the extension, the valid bits, the channel mask
+            // and the PCM subformat.
+            fmt.extend(22u16.to_le_bytes());
+            fmt.extend(16u16.to_le_bytes());
+            fmt.extend(((1u32 << channels) - 1).to_le_bytes());
+            fmt.extend([
+                1, 0, 0, 0, 0, 0, 0x10, 0, 0x80, 0, 0, 0xaa, 0, 0x38, 0x9b, 0x71,
+            ]);
+        }
+
+        let mut wav = b"RIFF".to_vec();
+        wav.extend(
+            (4 + 8 + fmt.len() as u32 + 8 + data.len() as u32 + 8 + info.len() as u32)
+                .to_le_bytes(),
+        );
+        wav.extend(b"WAVEfmt ");
+        wav.extend((fmt.len() as u32).to_le_bytes());
+        wav.extend(fmt);
+        wav.extend(b"data");
+        wav.extend((data.len() as u32).to_le_bytes());
+        wav.extend(data);
+        wav.extend(b"LIST");
+        wav.extend((info.len() as u32).to_le_bytes());
+        wav.extend(info);
+
+        std::fs::write(path, wav).expect("write tone");
+    }
+
+    #[test]
+    fn test_mixed_sample_rates() {
+        let temp_dir = create_working_dir(&["album"], &[], &[]).expect("create temp dir");
+        let album = temp_dir.path().join("album");
+        write_tone(&album.join("a.wav"), "a", 44_100, 0.25, 440.0);
+        write_tone(&album.join("b.wav"), "b", 48_000, 0.25, 440.0);
+
+        let (mut player, mut output) = null_player(&album);
+        let events = player.subscribe();
+        assert_eq!(player.playlist[0].sample_rate, Some(44_100));
+        assert_eq!(player.playlist[1].sample_rate, Some(48_000));
+
+        // The runs of consecutive audible samples with the same sample
+        // rate, as (sample rate, sample count, sign changes).
+        let mut runs: Vec<(u32, usize, usize)> = vec![];
+        let mut previous: f32 = 0.0;
+
+        player.play();
+        for _ in 0..10_000 {
+            if player.status.is_stopped() {
+                break;
+            }
+            // Gapless playback is disabled across the rate change.
+            assert!(player.sink.len() <= 1);
+
+            for _ in 0..256 {
+                // The rate is of the track that the sample was taken from.
+                let sample = output.next().unwrap_or_default();
+                let rate = output.sample_rate();
+                // Skip the silence between tracks.
+                if sample == 0.0 {
+                    continue;
+                }
+                match runs.last_mut() {
+                    Some(run) if run.0 == rate => {
+                        run.1 += 1;
+                        run.2 += (sample.signum() != previous.signum()) as usize;
+                    }
+                    _ => runs.push((rate, 1, 0)),
+                }
+                previous = sample;
+            }
+            player.poll();
+        }
+
+        assert_eq!(player.status, PlayerStatus::Stopped(StopReason::AlbumEnded));
+        assert_eq!(runs.len(), 2);
+        for (rate, count, sign_changes) in runs {
+            // Each track plays for its duration at its own rate, in stereo...
+            let secs = count as f32 / 2.0 / rate as f32;
+            assert!(
+                (secs - 0.25).abs() < 0.01,
+                "{rate} Hz played for {secs} secs"
+            );
+            // ...and at its own pitch.
+            let freq = sign_changes as f32 / 2.0 / secs;
+            assert!((freq - 440.0).abs() < 10.0, "{rate} Hz played at {freq} Hz");
+        }
+
+        let started = events
+            .try_iter()
+            .filter(|e| matches!(e, PlayerEvent::TrackStarted { .. }))
+            .count();
+        assert_eq!(started, 2);
+    }
+
+    #[test]
+    fn test_mixed_channel_counts() {
+        let temp_dir = create_working_dir(&["album"], &[], &[]).expect("create temp dir");
+        let album = temp_dir.path().join("album");
+        // A sine, which starts and ends near silence, in every channel.
+        let sine = |channels: usize| {
+            (0..11_025)
+                .map(|n| {
+                    let t = n as f32 / 44_100.0;
+                    (0.5 * (2.0 * std::f32::consts::PI * 400.0 * t).sin() * i16::MAX as f32) as i16
+                })
+                .flat_map(|sample| vec![sample; channels])
+                .collect::<Vec<i16>>()
+        };
+        write_wav(&album.join("a.wav"), "mono", 44_100, 1, &sine(1));
+        write_wav(&album.join("b.wav"), "stereo", 44_100, 2, &sine(2));
+        write_wav(&album.join("c.wav"), "surround", 44_100, 6, &sine(6));
+
+        let (mut player, mut output) = null_player(&album);
+        let channels: Vec<_> = player.playlist.iter().map(|f| f.channels).collect();
+        assert_eq!(channels, vec![Some(1), Some(2), Some(6)]);
+
+        let mut audible = 0;
+        let mut peak: f32 = 0.0;
+        let mut previous: f32 = 0.0;
+
+        player.play();
+        for _ in 0..10_000 {
+            if player.status.is_stopped() {
+                break;
+            }
+            for _ in 0..256 {
+                let channels = output.channels();
+                let sample = output.next().unwrap_or_default();
+                // Skip the silence between tracks.
+                if sample == 0.0 {
+                    continue;
+                }
+                // Every track is played in stereo...
+                assert_eq!(channels, 2);
+                // ...without a jump in level between the channels or the
+                // tracks, such as mono played on the left only. A step of
+                // the sine is doubled where a zero in between is skipped.
+                assert!(
+                    (sample - previous).abs() < 0.06,
+                    "jumped from {previous} to {sample}"
+                );
+                audible += 1;
+                peak = peak.max(sample.abs());
+                previous = sample;
+            }
+            player.poll();
+        }
+
+        assert_eq!(player.status, PlayerStatus::Stopped(StopReason::AlbumEnded));
+        // Each track played both channels for its duration.
+        assert!(audible > 3 * 2 * 10_500, "played {audible} samples");
+        // The 5.1 mix is as loud as its channels, without clipping.
+        assert!(peak > 0.49 && peak < 0.51, "peaked at {peak}");
+    }
+
+    #[test]
+    fn test_unplayable_tracks_are_skipped() {
+        let temp_dir = create_working_dir(&["album"], &[], &[]).expect("create temp dir");
+        let album = temp_dir.path().join("album");
+        write_tone(&album.join("a.wav"), "A", 44_100, 0.1, 440.0);
+        write_tone(&album.join("c.wav"), "c", 44_100, 0.1, 440.0);
+        // An MP4 file with an empty 'moov' atom, i.e. no tracks.
+        let no_audio = [
+            &[0, 0, 0, 16][..],
+            b"ftypM4A \0\0\0\0",
+            &[0, 0, 0, 8],
+            b"moov",
+        ]
+        .concat();
+        std::fs::write(album.join("b.m4a"), no_audio).expect("write m4a");
+
+        let (mut player, mut output) = null_player(&album);
+        let events = player.subscribe();
+        assert_eq!(player.playlist[1].duration, 0);
+        assert_eq!(
+            player.playlist[1].unplayable,
+            Some(Unplayable::NoAudioStream)
+        );
+
+        player.play();
+        play_to_end(&mut player, &mut output);
+
+        let events = events.try_iter().collect::<Vec<_>>();
+        let started = events
+            .iter()
+            .filter_map(|e| match e {
+                PlayerEvent::TrackStarted { index, .. } => Some(*index),
+                _ => None,
+            })
+            .collect::<Vec<usize>>();
+        assert_eq!(started, vec![0, 2]);
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, PlayerEvent::Error { msg } if msg.ends_with("no audio stream"))));
+        assert_eq!(events.last(), Some(&PlayerEvent::AlbumEnded));
+
+        // Playing the unplayable track plays the next one instead.
+        player.play_mouse_selected(1);
+        assert_eq!(player.index, 2);
+        assert_eq!(player.status, PlayerStatus::Playing);
+    }
+
+    #[test]
+    fn test_large_flat_directory() {
+        let temp_dir = create_working_dir(&["podcasts"], &[], &[]).expect("create temp dir");
+        let dir = temp_dir.path().join("podcasts");
+        for i in 0..5_000 {
+            let title = format!("episode {:04}", i);
+            write_tone(
+                &dir.join(format!("{:04}.wav", i)),
+                &title,
+                8_000,
+                0.01,
+                440.0,
+            );
+        }
+
+        let start = Instant::now();
+        let (mut player, _output) = null_player(&dir);
+        player.play();
+        for _ in 0..10 {
+            player.next();
+        }
+        let elapsed = start.elapsed();
+
+        assert_eq!(player.playlist.len(), 5_000);
+        assert_eq!(player.file().title, "episode 0010");
+        // A generous limit, since the build may be unoptimized.
+        assert!(
+            elapsed < Duration::from_secs(30),
+            "took {:?} to create the player and play ten tracks",
+            elapsed
+        );
+    }
+
+    #[test]
+    fn test_system_pause() {
+        let root = find_assets_dir().join("test_wav_audio.wav");
+        let (mut player, _output) = null_player(&root);
+
+        player.play();
+        player.system_pause(AutoPause::Pause);
+        assert_eq!(player.status, PlayerStatus::Paused);
+        player.system_resume();
+        assert_eq!(player.status, PlayerStatus::Playing);
+
+        // A pause by the user during the system pause isn't undone.
+        player.system_pause(AutoPause::Pause);
+        player.play_or_pause();
+        player.play_or_pause();
+        player.system_resume();
+        assert_eq!(player.status, PlayerStatus::Paused);
+
+        // A player paused by the user isn't affected.
+        player.system_pause(AutoPause::Pause);
+        assert_eq!(player.system_pause, None);
+
+        player.play_or_pause();
+        player.system_pause(AutoPause::Duck);
+        assert_eq!(player.status, PlayerStatus::Playing);
+        assert!((player.sink.volume() - DUCK_VOLUME).abs() < f32::EPSILON);
+        player.system_resume();
+        assert_eq!(player.sink.volume(), 1.0);
+    }
+
+    #[test]
+    fn test_last_halt() {
+        let root = find_assets_dir().join("test_wav_audio.wav");
+        let (mut player, _output) = null_player(&root);
+        let last_halt = |player: &Player| player.last_halt.map(|(halt, _)| halt);
+
+        player.play();
+        // Changing tracks isn't recorded as a stop.
+        player.play_index(0);
+        assert_eq!(last_halt(&player), None);
+
+        player.pause();
+        assert_eq!(last_halt(&player), Some(Halt::Paused));
+        player.stop();
+        assert_eq!(last_halt(&player), Some(Halt::Stopped(StopReason::User)));
+
+        player.play();
+        player.system_pause(AutoPause::Pause);
+        assert_eq!(last_halt(&player), Some(Halt::SystemPaused));
+    }
+
+    #[test]
+    fn test_album_gain() {
+        let root = find_assets_dir().join("test_wav_audio.wav");
+        let (mut player, _output) = null_player(&root);
+
+        player.set_album_gain(-6);
+        assert!((player.sink.volume() - 0.501).abs() < 0.001);
+
+        // The gain is kept when the volume changes, and muting still silences.
+        player.decrease_volume();
+        assert!(player.sink.volume() < 0.501);
+        player.toggle_mute();
+        assert_eq!(player.sink.volume(), 0.0);
+        player.toggle_mute();
+
+        player.set_album_gain(0);
+        assert_eq!(player.sink.volume(), player.volume as f32 / 100.0);
+
+        // A boost stops at full volume, but not below it.
+        player.volume = 100;
+        player.set_album_gain(6);
+        assert_eq!(player.sink.volume(), 1.0);
+        player.volume = 40;
+        player.set_album_gain(6);
+        assert!((player.sink.volume() - 0.798).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_album_gain_is_loaded_for_each_album() {
+        let root = find_assets_dir().join("test_wav_audio.wav");
+        let (mut player, _output) = null_player(&root);
+
+        // The gain isn't loaded again while the album plays.
+        player.load_album_gain();
+        player.set_album_gain(3);
+        player.load_album_gain();
+        assert_eq!(player.album_gain, 3);
+
+        // It is once the playlist moves into another album, which has none.
+        player.gain_album = Some(PathBuf::from("/another/album"));
+        player.load_album_gain();
+        assert_eq!(player.album_gain, 0);
+    }
+
+    #[test]
+    fn test_events_for_controls() {
+        let root = find_assets_dir().join("test_wav_audio.wav");
+        let (mut player, _output) = null_player(&root);
+        let events = player.subscribe();
+
+        player.play();
+        player.pause();
+        player.play_or_pause();
+        player.increase_volume();
+        player.toggle_mute();
+
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            vec![
+                PlayerEvent::TrackStarted {
+                    path: root,
+                    index: 0
+                },
+                PlayerEvent::Paused,
+                PlayerEvent::Resumed,
+                PlayerEvent::VolumeChanged {
+                    volume: 110,
+                    is_muted: false
+                },
+                PlayerEvent::VolumeChanged {
+                    volume: 110,
+                    is_muted: true
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_events_for_decode_error() {
+        let temp_dir =
+            create_working_dir(&["album"], &[("album/one.wav", "test_wav_audio.wav")], &[])
+                .expect("create temp dir");
+        let (mut player, _output) = null_player(&temp_dir.path().join("album"));
+        let events = player.subscribe();
+
+        std::fs::remove_file(temp_dir.path().join("album/one.wav")).expect("remove track");
+        player.play();
+
+        match events.try_recv() {
+            Some(PlayerEvent::Error { msg }) => assert!(msg.contains("could not open")),
+            event => panic!("expected an error event, got {event:?}"),
+        }
+        assert_eq!(player.status, PlayerStatus::Stopped(StopReason::User));
+    }
+
     #[test]
     fn test_playlist_mp3_success() {
         let root = find_assets_dir().join("test_mp3_audio.mp3");
@@ -666,6 +1568,19 @@ mod tests {
         assert_eq!((size.x, size.y), (53, 8));
     }
 
+    #[test]
+    fn test_albums_playlist() {
+        let root = find_assets_dir();
+        let paths = [root.join("test_mp3_audio.mp3"), root.to_owned()];
+        let (playlist, size) = albums_playlist(&paths).expect("should create a valid playlist");
+
+        // Each album keeps its own order, after the albums before it.
+        assert_eq!(playlist.len(), 6);
+        assert_eq!(playlist[0].title, "test_audio_mp3");
+        assert_eq!((size.x, size.y), (53, 9));
+        assert!(albums_playlist(&[]).is_err());
+    }
+
     #[test]
     fn test_playlist_empty_error() {
         let root = create_working_dir(&["one"], &[], &[])
@@ -1,25 +1,52 @@
-use std::time::Duration;
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
 
+use anyhow::anyhow;
 use cursive::{
     event::{Event, EventResult, Key, MouseButton, MouseEvent},
     reexports::crossbeam_channel::Sender,
     theme::{ColorStyle, Effect},
     traits::View,
-    view::Resizable,
+    view::{Nameable, Resizable},
     Cursive, Printer, XY,
 };
 use expiring_bool::ExpiringBool;
 
-use crate::config::{args, theme};
-use crate::fuzzy::{self, FuzzyView};
+use crate::command;
+use crate::config::{
+    args::{self, AutoPause},
+    config_file, theme,
+};
+use crate::data::{
+    bookmarks::Bookmark, persistent_data, AlbumGains, AlbumShuffle, Bookmarks, PlayHistory, Station,
+};
+use crate::fuzzy::{self, ErrorView, FuzzyView};
+use crate::layers::{self, Role};
 use crate::session_data::SessionData;
 use crate::utils::{self, InnerType};
 
-use super::{AudioFile, KeysView, Player, PlayerBuilder, PlayerStatus};
+use super::{
+    audio_focus, bookmarks_view, events::Receiver, AudioFile, BytesToStatus, KeysView, Player,
+    PlayerBuilder, PlayerEvent, PlayerStatus, StatusToBytes, StopReason,
+};
+
+// The narrowest terminal that the player is drawn on.
+const MIN_WIDTH: usize = 8;
+
+// The time from the start of an album within which going back in the queue
+// loads the previous album, rather than restarting the current one.
+const QUEUE_BACK_SECS: usize = 5;
+
+// How long a note is shown in the header for.
+const NOTE_TIME: Duration = Duration::from_secs(3);
 
 pub struct PlayerView {
     // The currently loaded player.
     player: Player,
+    // The playback events from the current player.
+    events: Receiver<PlayerEvent>,
     // The time to seek to, in seconds. `Some` when seeking has been initiated.
     mouse_seek_time: Option<usize>,
     // The vertical offset required to show relevant playlist rows.
@@ -28,40 +55,98 @@ pub struct PlayerView {
     showing_volume: ExpiringBool,
     // Callback to access the cursive root. `None` if standalone player.
     cb: Option<Sender<Box<dyn FnOnce(&mut Cursive) + Send>>>,
+    // The position of the album in the album shuffle and the album count.
+    // `Some` when the album was loaded from the album shuffle.
+    shuffle: Option<(usize, usize)>,
+    // The name of the seed album of the station. `Some` when the album was
+    // loaded from the station.
+    station: Option<String>,
+    // Whether or not the station played an album before this one.
+    station_has_previous: bool,
+    // Whether or not "start of queue" is shown in the header.
+    showing_queue_start: ExpiringBool,
+    // A message shown in the header for a short time, and when it was shown.
+    note: Option<(String, Instant)>,
+    // An error shown in place of the note until a key is pressed, and when it
+    // was shown.
+    error: Option<(String, Instant)>,
+    // The index of the track and the status last saved to the session file.
+    saved: Option<(usize, u8)>,
+    // Whether or not another application was playing audio at the last layout.
+    others_playing: bool,
+    // The bookmarks of the current file, and the file that they were loaded
+    // for.
+    bookmarks: (Vec<Bookmark>, PathBuf),
     // The size of the view.
     size: XY<usize>,
 }
 
 impl PlayerView {
+    // The name used to find the player view in the view stack.
+    pub const NAME: &'static str = "player";
+
     pub fn new(
-        player: Player,
+        mut player: Player,
         showing_volume: bool,
         cb: Option<Sender<Box<dyn FnOnce(&mut Cursive) + Send>>>,
+        shuffle: Option<(usize, usize)>,
+        station: Option<String>,
     ) -> Self {
         Self {
+            events: player.subscribe(),
             player,
             cb,
+            shuffle,
+            station,
+            station_has_previous: false,
+            showing_queue_start: ExpiringBool::new(false, Duration::from_millis(3000)),
+            note: None,
+            error: None,
+            saved: None,
             mouse_seek_time: None,
             offset: 0,
             showing_volume: ExpiringBool::new(showing_volume, Duration::from_millis(1500)),
+            others_playing: audio_focus::others_playing(),
+            bookmarks: (Vec::new(), PathBuf::new()),
             size: XY { x: 0, y: 0 },
         }
     }
 
     pub fn load((player, showing_volume, size): (Player, bool, XY<usize>), siv: &mut Cursive) {
-        let cb = match siv.user_data::<InnerType<SessionData>>() {
-            Some(_) => Some(siv.cb_sink().clone()),
-            None => None,
+        let (cb, shuffle, station) = match siv.user_data::<InnerType<SessionData>>() {
+            Some((_, _, _, _, shuffle, station)) => {
+                let album = player.path().parent();
+                let shuffle = shuffle
+                    .as_ref()
+                    .filter(|shuffle| shuffle.current().map(|p| p.as_path()) == album)
+                    .map(|shuffle| shuffle.progress());
+                let station = station
+                    .as_ref()
+                    .filter(|station| Some(station.current().as_path()) == album)
+                    .map(|station| (station.name(), station.has_previous()));
+                if let Some(album) = album {
+                    PlayHistory::record(&album.to_path_buf());
+                }
+                (Some(siv.cb_sink().clone()), shuffle, station)
+            }
+            None => (None, None, None),
+        };
+
+        let (station, station_has_previous) = match station {
+            Some((name, has_previous)) => (Some(name), has_previous),
+            None => (None, false),
         };
+        let mut view = PlayerView::new(player, showing_volume, cb, shuffle, station);
+        view.station_has_previous = station_has_previous;
 
-        siv.add_layer(
-            PlayerView::new(player, showing_volume, cb)
+        layers::show(
+            siv,
+            Role::Player,
+            view.with_name(Self::NAME)
                 .full_width()
                 .max_width(size.x)
                 .fixed_height(size.y),
         );
-
-        remove_layers_to_top(siv);
     }
 
     // Draw methods
@@ -71,7 +156,7 @@ impl PlayerView {
         match self.player.status {
             PlayerStatus::Paused => ("|", theme::hl(), Effect::Simple),
             PlayerStatus::Playing => (">", theme::header2(), Effect::Simple),
-            PlayerStatus::Stopped => (".", theme::err(), Effect::Simple),
+            PlayerStatus::Stopped(_) => (".", theme::err(), Effect::Simple),
         }
     }
 
@@ -94,9 +179,89 @@ impl PlayerView {
         }
     }
 
+    // Formats the reason that the player stopped and how long ago, or that it
+    // was paused or ducked while another application played audio. A player
+    // that hasn't played yet only shows why it stopped, unless it was stopped
+    // by the user.
+    fn status_note(&self) -> Option<String> {
+        let status = self.player.status;
+        let system_paused = self.player.system_pause == Some(AutoPause::Pause);
+        if let Some((halt, at)) = self.player.last_halt {
+            if status.is_stopped() || (status == PlayerStatus::Paused && system_paused) {
+                return Some(format!("{} {}", halt.name(), ago(at.elapsed())));
+            }
+        }
+
+        let note = match (status, self.player.system_pause) {
+            (PlayerStatus::Paused, Some(AutoPause::Pause)) => Some("paused by system"),
+            (_, Some(AutoPause::Duck)) => Some("ducked by system"),
+            (PlayerStatus::Stopped(StopReason::User), _) => None,
+            (status, _) if status.is_stopped() => Some(status.name()),
+            _ => None,
+        };
+        note.map(String::from)
+    }
+
+    // Shows `msg` in the header for a few seconds.
+    pub fn show_note(&mut self, msg: &str) {
+        self.note = Some((msg.to_string(), Instant::now()));
+    }
+
+    // The message to show in the header, if any.
+    fn note(&self) -> Option<&str> {
+        match &self.note {
+            Some((msg, shown)) if shown.elapsed() < NOTE_TIME => Some(msg.as_str()),
+            _ => None,
+        }
+    }
+
+    // Shows the error `msg` in the header, in place of the note.
+    pub fn show_error(&mut self, msg: &str) {
+        self.error = Some((msg.to_string(), Instant::now()));
+    }
+
+    // The error to show in the header, if any.
+    fn error(&self) -> Option<&str> {
+        match &self.error {
+            Some((msg, shown)) if shown.elapsed() < layers::ERROR_TIME => Some(msg.as_str()),
+            _ => None,
+        }
+    }
+
+    // The note shown when there is no album before the current one.
+    fn queue_start_note(&self) -> Option<&'static str> {
+        match self.showing_queue_start.is_true() {
+            true => Some("start of queue"),
+            false => None,
+        }
+    }
+
+    // Formats the album shuffle display.
+    fn shuffle_progress(&self, w: usize) -> Option<String> {
+        let (position, count) = self.shuffle?;
+        match w > 40 {
+            true => Some(format!("album {} of {}", position, count)),
+            false => Some(format!("{}/{}", position, count)),
+        }
+    }
+
+    // Formats the station display.
+    fn station_name(&self, w: usize) -> Option<String> {
+        let name = self.station.as_ref()?;
+        match w > 40 {
+            true => Some(format!("station: {}", name)),
+            false => Some(name.to_owned()),
+        }
+    }
+
     // Formats the volume display.
     fn volume(&self, w: usize) -> String {
+        let gain = self.player.album_gain;
         match w > 14 {
+            true if gain != 0 && w > 40 => format!(
+                "  vol: {:>3} %  album gain: {:+} dB  ",
+                self.player.volume, gain
+            ),
             true => format!("  vol: {:>3} %  ", self.player.volume),
             false => format!("  {:>3} %  ", self.player.volume),
         }
@@ -112,6 +277,31 @@ impl PlayerView {
         }
     }
 
+    // Loads the bookmarks of the current file again.
+    pub fn reload_bookmarks(&mut self) {
+        let file = self.player.path().to_owned();
+        self.bookmarks = (Bookmarks::of(&file), file);
+    }
+
+    // Adds a bookmark at the elapsed time in the current file.
+    fn add_bookmark(&mut self) {
+        let secs = self.elapsed();
+        Bookmarks::save(self.player.path(), secs);
+        self.reload_bookmarks();
+        self.show_note(&format!("bookmark at {}", mins_and_secs(secs).trim()));
+    }
+
+    // Shows the bookmarks of the current file.
+    fn show_bookmarks(&self) -> EventResult {
+        let file = self.player.path().to_owned();
+        EventResult::with_cb(move |siv| bookmarks_view::load(file.to_owned(), siv))
+    }
+
+    // Seeks to `secs` in the current file, such as to a bookmark.
+    pub fn seek_to(&mut self, secs: usize) {
+        self.player.seek_to_time(Duration::from_secs(secs as u64));
+    }
+
     // Computes the y offset needed to show the results of the fuzzy match.
     #[inline]
     fn update_offset(&self) -> usize {
@@ -134,6 +324,21 @@ impl PlayerView {
 
     // Event methods
 
+    // Pauses or ducks the player when another application starts playing
+    // audio, and undoes it when the application stops.
+    fn auto_pause(&mut self) {
+        let others_playing = audio_focus::others_playing();
+        if others_playing == self.others_playing {
+            return;
+        }
+        self.others_playing = others_playing;
+
+        match others_playing {
+            true => self.player.system_pause(args::auto_pause()),
+            false => self.player.system_resume(),
+        }
+    }
+
     // Loads the next random track.
     fn random_track(&mut self) {
         match &self.cb {
@@ -149,6 +354,40 @@ impl PlayerView {
         }
     }
 
+    // Saves the current album, track and status so that the session can be restored.
+    fn save_session(&mut self) {
+        if let Some(album) = self.player.path().parent() {
+            let (index, status) = (self.player.index, self.player.status.to_u8());
+            if persistent_data::save_session(&album.to_path_buf(), index, status).is_ok() {
+                self.saved = Some((index, status));
+            }
+        }
+    }
+
+    // Loads the next album in the album shuffle.
+    fn next_shuffled(&mut self) {
+        if let Some(cb) = &self.cb {
+            cb.send(Box::new(move |siv| {
+                if let Ok(player) = PlayerBuilder::NextShuffled.from(None, siv) {
+                    PlayerView::load(player, siv);
+                }
+            }))
+            .unwrap_or_default();
+        }
+    }
+
+    // Loads the next album from the station.
+    fn next_station(&mut self) {
+        if let Some(cb) = &self.cb {
+            cb.send(Box::new(move |siv| {
+                if let Ok(player) = PlayerBuilder::NextStation.from(None, siv) {
+                    PlayerView::load(player, siv);
+                }
+            }))
+            .unwrap_or_default();
+        }
+    }
+
     // Loads the previous random track.
     fn previous_random(&mut self) {
         match &self.cb {
@@ -171,7 +410,7 @@ impl PlayerView {
 
         if self.cb.is_some() {
             EventResult::with_cb(move |siv| {
-                siv.with_user_data(|(opts, _, _): &mut InnerType<SessionData>| {
+                siv.with_user_data(|(opts, _, _, _, _, _): &mut InnerType<SessionData>| {
                     opts.1 = volume;
                 });
             })
@@ -184,7 +423,7 @@ impl PlayerView {
     fn set_status(&mut self, status: u8) -> EventResult {
         if self.cb.is_some() {
             EventResult::with_cb(move |siv| {
-                siv.with_user_data(|(opts, _, _): &mut InnerType<SessionData>| {
+                siv.with_user_data(|(opts, _, _, _, _, _): &mut InnerType<SessionData>| {
                     opts.0 = status;
                 });
             })
@@ -199,7 +438,7 @@ impl PlayerView {
             let curr_index = self.player.index;
             if self.cb.is_some() {
                 return EventResult::with_cb(move |siv| {
-                    siv.with_user_data(|(_, _, queue): &mut InnerType<SessionData>| {
+                    siv.with_user_data(|(_, _, queue, _, _, _): &mut InnerType<SessionData>| {
                         if let Some((_, index)) = queue.get_mut(1) {
                             *index = curr_index;
                         }
@@ -212,6 +451,78 @@ impl PlayerView {
         EventResult::Consumed(None)
     }
 
+    // Starts shuffling albums if the current album isn't from the album
+    // shuffle, stops shuffling albums otherwise.
+    fn toggle_album_shuffle(&mut self) -> EventResult {
+        if self.cb.is_none() {
+            return EventResult::Consumed(None);
+        }
+
+        match self.shuffle.take() {
+            Some(_) => EventResult::with_cb(|siv| {
+                siv.with_user_data(|(_, _, _, _, shuffle, _): &mut InnerType<SessionData>| {
+                    *shuffle = None;
+                });
+            }),
+            None => EventResult::with_cb(shuffle_albums),
+        }
+    }
+
+    // Starts a station seeded with the current album, or stops the station
+    // if the current album is from one. Starting a station stops the album
+    // shuffle.
+    fn toggle_station(&mut self) -> EventResult {
+        if self.cb.is_none() {
+            return EventResult::Consumed(None);
+        }
+
+        if self.station.take().is_some() {
+            return EventResult::with_cb(|siv| {
+                siv.with_user_data(|(_, _, _, _, _, station): &mut InnerType<SessionData>| {
+                    *station = None;
+                });
+            });
+        }
+
+        let seed = match self.player.path().parent() {
+            Some(album) => album.to_path_buf(),
+            None => return EventResult::Consumed(None),
+        };
+
+        self.station = seed
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string());
+        self.shuffle = None;
+
+        EventResult::with_cb(move |siv| {
+            siv.with_user_data(
+                |(_, paths, _, data, shuffle, station): &mut InnerType<SessionData>| {
+                    *shuffle = None;
+                    *station = Some(Station::new(&seed, &data.visible(paths)));
+                },
+            );
+        })
+    }
+
+    // Starts the album shuffle over with a new order.
+    fn reshuffle(&mut self) -> EventResult {
+        if self.shuffle.is_none() {
+            return EventResult::Consumed(None);
+        }
+
+        EventResult::with_cb(|siv| {
+            siv.with_user_data(|(_, _, _, _, shuffle, _): &mut InnerType<SessionData>| {
+                if let Some(shuffle) = shuffle {
+                    shuffle.reshuffle();
+                }
+            });
+
+            if let Ok(player) = PlayerBuilder::ShuffledAlbum.from(None, siv) {
+                PlayerView::load(player, siv);
+            }
+        })
+    }
+
     // Loads a fuzzy view for the parent of the current audio file.
     fn parent(&self) -> EventResult {
         let mut parent = self.player.path().to_owned();
@@ -222,8 +533,8 @@ impl PlayerView {
             if parent != root {
                 parent.pop();
                 return EventResult::with_cb(move |siv| {
-                    let items = fuzzy::create_items(&parent).expect("should always exist");
-                    FuzzyView::load(items, None, siv)
+                    let hidden_dirs = fuzzy::fuzzy_view::scans_hidden_dirs(siv);
+                    FuzzyView::load_scanned(fuzzy::scan_items(&parent, hidden_dirs), siv)
                 });
             }
         }
@@ -235,7 +546,7 @@ impl PlayerView {
         let is_muted = self.player.toggle_mute();
         if self.cb.is_some() {
             EventResult::with_cb(move |siv| {
-                siv.with_user_data(|(opts, _, _): &mut InnerType<SessionData>| {
+                siv.with_user_data(|(opts, _, _, _, _, _): &mut InnerType<SessionData>| {
                     opts.2 = is_muted;
                 });
             })
@@ -249,7 +560,7 @@ impl PlayerView {
         let showing_volume = self.showing_volume.toggle();
         if self.cb.is_some() {
             EventResult::with_cb(move |siv| {
-                siv.with_user_data(|(opts, _, _): &mut InnerType<SessionData>| {
+                siv.with_user_data(|(opts, _, _, _, _, _): &mut InnerType<SessionData>| {
                     opts.3 = showing_volume;
                 });
             })
@@ -278,9 +589,15 @@ impl PlayerView {
 
     // Opens the parent of the current audio file in the
     // preferred file manager.
-    fn open_file_manager(&self) {
+    fn open_file_manager(&self) -> EventResult {
         let path = self.player.path().to_owned();
-        _ = utils::open_file_manager(path);
+        match command::open_file_manager(path) {
+            Ok(_) => EventResult::Consumed(None),
+            Err(err) => {
+                let msg = err.to_string();
+                EventResult::with_cb(move |siv| ErrorView::notify(siv, anyhow!(msg.clone())))
+            }
+        }
     }
 
     // Increments the volume and updates user data.
@@ -295,12 +612,56 @@ impl PlayerView {
         return self.set_volume(volume);
     }
 
+    // Changes the gain of the current album by `change` dB, or clears it when
+    // `change` is `None`, and remembers it for the next time the album plays.
+    fn change_album_gain(&mut self, change: Option<i8>) {
+        self.player.load_album_gain();
+        if let Some(album) = self.player.path().parent().map(PathBuf::from) {
+            let gain = match change {
+                Some(change) => self.player.album_gain.saturating_add(change),
+                None => 0,
+            };
+            let gain = AlbumGains::save(&album, gain);
+            self.player.set_album_gain(gain);
+            self.showing_volume.set();
+        }
+    }
+
     // Stops the player and updates user data.
     fn stop(&mut self) -> EventResult {
         let status = self.player.stop();
         return self.set_status(status);
     }
 
+    // Goes back to the previous album of the station within the first
+    // seconds of an album, and restarts the album otherwise. Without a
+    // station, loads the previous album from the history like '-'.
+    fn back_in_queue(&mut self) -> EventResult {
+        if self.cb.is_none() {
+            return EventResult::Consumed(None);
+        }
+
+        if self.station.is_none() {
+            return EventResult::with_cb(load_previous_album);
+        }
+
+        if self.player.index > 0 || self.elapsed() >= QUEUE_BACK_SECS {
+            self.player.play_first_track();
+            return EventResult::Consumed(None);
+        }
+
+        if !self.station_has_previous {
+            self.showing_queue_start.set();
+            return EventResult::Consumed(None);
+        }
+
+        EventResult::with_cb(|siv| {
+            if let Ok(player) = PlayerBuilder::PreviousStation.from(None, siv) {
+                PlayerView::load(player, siv);
+            }
+        })
+    }
+
     // Plays or pauses the player and updates user data.
     fn play_or_pause(&mut self) -> EventResult {
         let status = self.player.play_or_pause();
@@ -346,7 +707,7 @@ impl PlayerView {
     // Updates the seek position from mouse input.
     fn mouse_hold_seek(&mut self, offset: XY<usize>, position: XY<usize>) {
         if self.size.x > 16 && position.x > offset.x {
-            if self.player.status == PlayerStatus::Stopped {
+            if self.player.status.is_stopped() {
                 self.player.play();
             }
             self.player.pause();
@@ -395,9 +756,26 @@ impl PlayerView {
 impl View for PlayerView {
     fn layout(&mut self, size: cursive::Vec2) {
         self.player.poll();
+        self.auto_pause();
         if self.player.is_randomized && self.player.next_track_queued {
             self.random_track();
         }
+        while let Some(event) = self.events.try_recv() {
+            match event {
+                // Continue with the next album when shuffling albums.
+                PlayerEvent::AlbumEnded if self.shuffle.is_some() => self.next_shuffled(),
+                // Continue with the next album from the station.
+                PlayerEvent::AlbumEnded if self.station.is_some() => self.next_station(),
+                _ => (),
+            }
+        }
+        let saved = (self.player.index, self.player.status.to_u8());
+        if self.cb.is_some() && self.saved != Some(saved) {
+            self.save_session();
+        }
+        if &self.bookmarks.1 != self.player.path() {
+            self.reload_bookmarks();
+        }
         self.size = size;
         self.offset = self.update_offset();
     }
@@ -405,6 +783,13 @@ impl View for PlayerView {
     fn draw(&self, p: &Printer) {
         // The size of the screen we can draw on.
         let (w, h) = (p.size.x, p.size.y);
+
+        // Draw nothing when the terminal is too small, such as after a zero
+        // size resize from a multiplexer. The player is still polled in
+        // `layout`, so playback continues.
+        if w < MIN_WIDTH || h == 0 {
+            return;
+        }
         // The file currently loaded in the player.
         let f = self.player.file();
         // The start of the duration column.
@@ -418,12 +803,9 @@ impl View for PlayerView {
 
         // Draw the playlist, with rows: 'Track, Title, Duration'.
         if h > 2 {
-            for (i, f) in self.player.playlist.iter().enumerate() {
-                // Skip rows that are not visible.
-                if i < self.offset {
-                    continue;
-                }
-
+            // Skip rows that are not visible.
+            let visible = self.player.playlist.iter().enumerate().skip(self.offset);
+            for (i, f) in visible.take(h) {
                 let row = i + 1 - self.offset;
 
                 if i == self.player.index {
@@ -434,7 +816,17 @@ impl View for PlayerView {
                     });
                     // Draw the active row.
                     p.with_color(theme::hl(), |p| {
-                        p.print((6, row), format!("{:02}  {}", f.track, f.title).as_str());
+                        let title = format!("{:02}  {}", f.track, f.title);
+                        p.print((6, row), title.as_str());
+                        // Draw the sample rate and the channels of the source, if
+                        // there is space after the title.
+                        if let Some(format) = audio_format(f) {
+                            if 6 + title.len() + format.len() + 2 < column.saturating_sub(4) {
+                                p.with_color(theme::prompt(), |p| {
+                                    p.print((column - 4 - format.len(), row), format.as_str())
+                                })
+                            }
+                        }
                         if column > 11 && (self.player.is_randomized || self.player.is_muted) {
                             // Draw the player options.
                             p.with_color(theme::info(), |p| {
@@ -443,16 +835,29 @@ impl View for PlayerView {
                                 })
                             })
                         }
-                        p.print((column, row), mins_and_secs(f.duration).as_str());
+                        if f.is_playable() {
+                            p.print((column, row), mins_and_secs(f.duration).as_str());
+                        }
                     })
                 } else if i + 2 - self.offset < h {
                     // Draw the inactive rows.
                     p.with_color(theme::fg(), |p| {
                         p.print((6, row), format!("{:02}  {}", f.track, f.title).as_str());
-                        p.print((column, row), mins_and_secs(f.duration).as_str());
+                        if f.is_playable() {
+                            p.print((column, row), mins_and_secs(f.duration).as_str());
+                        }
                     })
                 }
 
+                // Draw the reason that the track can't be played, in place of its duration.
+                if let Some(reason) = f.unplayable.map(|u| u.reason()) {
+                    if row < h - 1 && 6 + f.title.len() + reason.len() + 8 < w {
+                        p.with_color(theme::err(), |p| {
+                            p.print((w - reason.len() - 2, row), reason)
+                        })
+                    }
+                }
+
                 // The active row has been drawn so we can exit early.
                 if h == 3 {
                     break;
@@ -471,11 +876,39 @@ impl View for PlayerView {
                 })
             });
 
-            if self.showing_volume.is_true() {
-                let column = if w > 14 { column - 5 } else { column };
+            let header_end = f.artist.len() + self.album_and_year(f).len() + 6;
+
+            if let Some(msg) = self.error() {
+                // Draw an error after the header, or over it when it doesn't fit.
+                let msg = format!("error: {msg}");
+                let column = match header_end + msg.len() + 2 < w {
+                    true => w - msg.len() - 2,
+                    false => 2,
+                };
+                p.with_color(theme::err(), |p| {
+                    p.print_hline((column, 0), w - column, " ");
+                    p.print((column, 0), &msg)
+                });
+            } else if self.showing_volume.is_true() {
+                let volume = self.volume(w);
                 p.with_color(theme::prompt(), |p| {
-                    p.print((column, 0), &self.volume(w).as_str())
+                    p.print((w.saturating_sub(volume.len()), 0), volume.as_str())
                 });
+            } else if let Some(progress) = self
+                .note()
+                .or(self.queue_start_note())
+                .map(String::from)
+                .or(self.status_note())
+                .or(self.shuffle_progress(w))
+                .or(self.station_name(w))
+            {
+                // Draw why the player isn't playing, the album shuffle progress
+                // or the station, if there is space after the header.
+                if header_end + progress.len() + 2 < w {
+                    p.with_color(theme::info(), |p| {
+                        p.print((w - progress.len() - 2, 0), progress.as_str())
+                    });
+                }
             };
         }
 
@@ -505,6 +938,15 @@ impl View for PlayerView {
                     p.print_hline((8, last_row), length, "█");
                 });
 
+            // Draw the bookmarks as ticks on the progress bar.
+            if f.duration > 0 && w > 16 {
+                p.with_color(theme::header2(), |p| {
+                    for mark in self.bookmarks.0.iter().filter(|m| m.secs < f.duration) {
+                        p.print((8 + mark.secs * (w - 16) / f.duration, last_row), "│");
+                    }
+                });
+            }
+
             // Draw spaces to maintain consistent padding when resizing.
             p.print((w - 2, 0), "  ");
             p.print((w - 2, last_row), "  ");
@@ -513,6 +955,10 @@ impl View for PlayerView {
 
     // Keybindings for the player view.
     fn on_event(&mut self, event: Event) -> EventResult {
+        // Any key dismisses an error, and does what it does as well.
+        if ErrorView::trigger().apply(&event) {
+            self.error = None;
+        }
         match event {
             Event::Char('h' | ' ') | Event::Key(Key::Left) => return self.play_or_pause(),
             Event::Char('j') | Event::Key(Key::Down) => self.next(),
@@ -523,6 +969,9 @@ impl View for PlayerView {
             Event::Char('[') => return self.decrease_volume(),
             Event::Char('v') => return self.toggle_volume_display(),
             Event::Char('m') => return self.toggle_mute(),
+            Event::AltChar('+' | '=') => self.change_album_gain(Some(1)),
+            Event::AltChar('-') => self.change_album_gain(Some(-1)),
+            Event::AltChar('0') => self.change_album_gain(None),
 
             Event::Char('\'') => self.player.seek_to_min(),
             Event::Char('"') => self.player.seek_to_sec(),
@@ -530,8 +979,14 @@ impl View for PlayerView {
             Event::Char(',') => self.player.step_backward(),
 
             Event::Char('*' | 'r') => return self.toggle_randomization(),
+            Event::Char('a') => return self.toggle_album_shuffle(),
+            Event::Char('x') => return self.reshuffle(),
+            Event::Char('s') => return self.toggle_station(),
+            Event::Char('<') => return self.back_in_queue(),
             Event::Char('g') => self.player.play_key_selection(),
             Event::CtrlChar('g') => self.player.play_last_track(),
+            Event::Char('b') => self.add_bookmark(),
+            Event::AltChar('b') => return self.show_bookmarks(),
 
             Event::Char('0') => self.player.num_keys.push(0),
             Event::Char('1') => self.player.num_keys.push(1),
@@ -545,7 +1000,7 @@ impl View for PlayerView {
             Event::Char('9') => self.player.num_keys.push(9),
 
             Event::CtrlChar('p') => return self.parent(),
-            Event::CtrlChar('o') => self.open_file_manager(),
+            Event::CtrlChar('o') => return self.open_file_manager(),
             Event::Char('?') => return load_keys_view(),
             Event::Char('q') => return quit(),
 
@@ -576,24 +1031,85 @@ impl View for PlayerView {
     }
 }
 
-// Callback to select the previous album.
+// Callback to select the previous album, or the previous album in the
+// album shuffle when shuffling albums.
 pub fn previous_album(_: &Event) -> Option<EventResult> {
-    Some(EventResult::with_cb(|siv| {
-        if let Ok(player) = PlayerBuilder::PreviousAlbum.from(None, siv) {
-            PlayerView::load(player, siv);
-        }
-    }))
+    Some(EventResult::with_cb(load_previous_album))
 }
 
-// Callback to select a random album.
+fn load_previous_album(siv: &mut Cursive) {
+    let builder = match is_shuffling(siv) {
+        true => PlayerBuilder::PreviousShuffled,
+        false => PlayerBuilder::PreviousAlbum,
+    };
+    if let Ok(player) = builder.from(None, siv) {
+        PlayerView::load(player, siv);
+    }
+}
+
+// Callback to select a random album, or the next album in the album
+// shuffle or the station when shuffling albums or playing a station.
 pub fn random_album(_: &Event) -> Option<EventResult> {
     Some(EventResult::with_cb(|siv| {
-        if let Ok(player) = PlayerBuilder::RandomAlbum.from(None, siv) {
+        let builder = match (is_shuffling(siv), is_playing_station(siv)) {
+            (true, _) => PlayerBuilder::NextShuffled,
+            (false, true) => PlayerBuilder::NextStation,
+            (false, false) => PlayerBuilder::RandomAlbum,
+        };
+        if let Ok(player) = builder.from(None, siv) {
             PlayerView::load(player, siv);
         }
     }))
 }
 
+// Starts shuffling the albums under the search root, continuing the
+// previous album shuffle if there is one, and loads the current album.
+pub fn shuffle_albums(siv: &mut Cursive) {
+    siv.with_user_data(
+        |(_, paths, _, _, shuffle, station): &mut InnerType<SessionData>| {
+            if shuffle.is_none() {
+                *shuffle = Some(AlbumShuffle::restore(&args::search_root(), paths));
+            }
+            *station = None;
+        },
+    );
+
+    if let Ok(player) = PlayerBuilder::ShuffledAlbum.from(None, siv) {
+        PlayerView::load(player, siv);
+    }
+}
+
+// Loads the player saved in the session `file`. An album that was played to
+// the end isn't reopened, unless the config file says to.
+pub fn restore_session(file: PathBuf, siv: &mut Cursive) {
+    if let Ok((_, _, status)) = persistent_data::cached_session(&file) {
+        if !status.from_u8().is_resumable() && !config_file::get().resume_finished_album {
+            return;
+        }
+    }
+
+    match PlayerBuilder::Session.from(Some(file), siv) {
+        Ok(player) => PlayerView::load(player, siv),
+        Err(err) => ErrorView::load(siv, err),
+    }
+}
+
+// Whether or not the albums are being shuffled.
+fn is_shuffling(siv: &mut Cursive) -> bool {
+    match siv.user_data::<InnerType<SessionData>>() {
+        Some((_, _, _, _, shuffle, _)) => shuffle.is_some(),
+        None => false,
+    }
+}
+
+// Whether or not a station is filling the queue.
+fn is_playing_station(siv: &mut Cursive) -> bool {
+    match siv.user_data::<InnerType<SessionData>>() {
+        Some((_, _, _, _, _, station)) => station.is_some(),
+        None => false,
+    }
+}
+
 // Quit the app.
 fn quit() -> EventResult {
     return EventResult::with_cb(|siv| {
@@ -635,15 +1151,144 @@ fn sub_block(extra: usize) -> &'static str {
     }
 }
 
+// Formats a sample rate in Hz as kHz, i.e. `44100` -> `44.1k`.
+fn khz(rate: u32) -> String {
+    match rate % 1000 {
+        0 => format!("{}k", rate / 1000),
+        _ => format!("{:.1}k", rate as f32 / 1000.0),
+    }
+}
+
+// Formats a number of channels as its layout, i.e. `6` -> `5.1`.
+fn channel_layout(channels: u8) -> String {
+    match channels {
+        1 => String::from("mono"),
+        2 => String::from("stereo"),
+        6 => String::from("5.1"),
+        8 => String::from("7.1"),
+        n => format!("{n}ch"),
+    }
+}
+
+// The sample rate and the channels of `file`, i.e. `44.1k stereo`, if known.
+fn audio_format(file: &AudioFile) -> Option<String> {
+    match (file.sample_rate.map(khz), file.channels.map(channel_layout)) {
+        (Some(rate), Some(layout)) => Some(format!("{rate} {layout}")),
+        (rate, layout) => rate.or(layout),
+    }
+}
+
 // Formats the playback time.
 fn mins_and_secs(secs: usize) -> String {
     format!("  {:02}:{:02}  ", secs / 60, secs % 60)
 }
 
-// Remove all layers from the view stack except the top layer.
-fn remove_layers_to_top(siv: &mut Cursive) {
-    while siv.screen().len() > 1 {
-        siv.screen_mut()
-            .remove_layer(cursive::views::LayerPosition::FromBack(0));
+// Formats how long ago something happened, to the minute.
+fn ago(elapsed: Duration) -> String {
+    match elapsed.as_secs() / 60 {
+        0 => String::from("just now"),
+        mins @ 1..=59 => format!("{mins} min ago"),
+        mins => format!("{} h ago", mins / 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::theme::Preset;
+    use crate::golden;
+    use crate::player::audio_file::Unplayable;
+
+    fn test_file(track: u32, title: &str, duration: usize) -> AudioFile {
+        AudioFile {
+            path: PathBuf::from(format!("/music/artist/album/{:02} {}.flac", track, title)),
+            title: title.to_string(),
+            artist: "The Artist".to_string(),
+            album: "The Album".to_string(),
+            year: Some(1999),
+            genre: None,
+            track,
+            duration,
+            sample_rate: Some(44100),
+            channels: Some(2),
+            unplayable: None,
+        }
+    }
+
+    #[test]
+    fn test_render_goldens() {
+        for preset in Preset::ALL {
+            let mut playlist = vec![
+                test_file(1, "Opening", 95),
+                test_file(2, "A Much Longer Title That Is Cut Short", 3725),
+                test_file(3, "Locked", 200),
+                test_file(4, "Closing", 0),
+            ];
+            playlist[2].unplayable = Some(Unplayable::DrmProtected);
+            let player = Player::with_playlist(playlist, 1);
+            let mut view = PlayerView::new(player, false, None, Some((2, 5)), None);
+
+            let rendered = golden::render(&mut view, XY::new(48, 8), preset);
+            golden::assert_golden(&format!("player_view_{}", preset.name()), &rendered);
+        }
+    }
+
+    #[test]
+    fn test_ago() {
+        let mins = |mins: u64| Duration::from_secs(mins * 60);
+        assert_eq!(ago(Duration::from_secs(59)), "just now");
+        assert_eq!(ago(mins(23)), "23 min ago");
+        assert_eq!(ago(mins(150)), "2 h ago");
+    }
+
+    #[test]
+    fn test_audio_format() {
+        let mut file = test_file(1, "Opening", 95);
+        assert_eq!(audio_format(&file).as_deref(), Some("44.1k stereo"));
+        file.channels = Some(6);
+        assert_eq!(audio_format(&file).as_deref(), Some("44.1k 5.1"));
+        file.sample_rate = None;
+        file.channels = Some(3);
+        assert_eq!(audio_format(&file).as_deref(), Some("3ch"));
+        file.channels = None;
+        assert_eq!(audio_format(&file), None);
+    }
+
+    #[test]
+    fn test_errors_are_shown_in_the_header() {
+        let player = Player::with_playlist(vec![test_file(1, "Opening", 95)], 0);
+        let mut view = PlayerView::new(player, false, None, None, None);
+        let status = view.player.status;
+        view.show_error("no file manager");
+
+        let rendered = golden::render(&mut view, XY::new(60, 6), Preset::Default);
+        let header = rendered.lines().nth(1).unwrap();
+        assert!(header.contains("The Album"), "{header}");
+        assert!(header.ends_with("error: no file manager  |"), "{header}");
+        // Over the header, when it doesn't fit beside it.
+        let rendered = golden::render(&mut view, XY::new(30, 6), Preset::Default);
+        assert_eq!(
+            rendered.lines().nth(1),
+            Some("|  error: no file manager      |")
+        );
+
+        // Any key dismisses it, leaving the player as it was.
+        _ = view.on_event(Event::Char('v'));
+        assert_eq!(view.error(), None);
+        assert_eq!(view.player.status, status);
+    }
+
+    #[test]
+    fn test_too_small_to_draw() {
+        for size in [XY::new(0, 0), XY::new(80, 0), XY::new(1, 1)] {
+            let player = Player::with_playlist(vec![test_file(1, "Opening", 95)], 0);
+            let mut view = PlayerView::new(player, false, None, None, None);
+            let rendered = golden::render(&mut view, size, Preset::Default);
+            let rows = rendered.lines().skip(1).take(size.y);
+
+            for row in rows {
+                assert!(row.trim_matches('|').trim().is_empty(), "drew '{}'", row);
+            }
+        }
     }
 }
@@ -1,8 +1,82 @@
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum PlayerStatus {
     Paused,
     Playing,
-    Stopped,
+    Stopped(StopReason),
+}
+
+// Why the player is stopped.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StopReason {
+    // Stopped by the user, or not started yet.
+    User,
+    // The last track of the album played to the end.
+    AlbumEnded,
+    // None of the remaining tracks could be played.
+    Unplayable,
+}
+
+// Why playback was last paused or stopped.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Halt {
+    Paused,
+    // Paused while another application played audio.
+    SystemPaused,
+    Stopped(StopReason),
+}
+
+// The actions that change the playback status.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Transition {
+    Play,
+    Pause,
+    Resume,
+    Stop(StopReason),
+}
+
+impl PlayerStatus {
+    // The status after `transition`. A stopped player can only be started
+    // by playing, and stopping it again keeps the reason that it stopped.
+    pub fn after(self, transition: Transition) -> Self {
+        match (self, transition) {
+            (_, Transition::Play) => PlayerStatus::Playing,
+            (PlayerStatus::Playing, Transition::Pause) => PlayerStatus::Paused,
+            (PlayerStatus::Paused, Transition::Resume) => PlayerStatus::Playing,
+            (PlayerStatus::Stopped(_), Transition::Stop(_)) => self,
+            (_, Transition::Stop(reason)) => PlayerStatus::Stopped(reason),
+            (status, _) => status,
+        }
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        matches!(self, PlayerStatus::Stopped(_))
+    }
+
+    // Whether or not a session that was saved with this status can be
+    // resumed, i.e. the album wasn't played to the end.
+    pub fn is_resumable(&self) -> bool {
+        *self != PlayerStatus::Stopped(StopReason::AlbumEnded)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PlayerStatus::Playing => "playing",
+            PlayerStatus::Paused => "paused",
+            PlayerStatus::Stopped(StopReason::User) => "stopped",
+            PlayerStatus::Stopped(StopReason::AlbumEnded) => "album finished",
+            PlayerStatus::Stopped(StopReason::Unplayable) => "nothing playable",
+        }
+    }
+}
+
+impl Halt {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Halt::Paused => "paused",
+            Halt::SystemPaused => "paused by system",
+            Halt::Stopped(reason) => PlayerStatus::Stopped(*reason).name(),
+        }
+    }
 }
 
 pub trait BytesToStatus {
@@ -18,7 +92,9 @@ impl BytesToStatus for u8 {
         match self {
             0 => PlayerStatus::Playing,
             1 => PlayerStatus::Paused,
-            _ => PlayerStatus::Stopped,
+            3 => PlayerStatus::Stopped(StopReason::AlbumEnded),
+            4 => PlayerStatus::Stopped(StopReason::Unplayable),
+            _ => PlayerStatus::Stopped(StopReason::User),
         }
     }
 }
@@ -28,7 +104,78 @@ impl StatusToBytes for PlayerStatus {
         match self {
             PlayerStatus::Playing => 0,
             PlayerStatus::Paused => 1,
-            PlayerStatus::Stopped => 2,
+            PlayerStatus::Stopped(StopReason::User) => 2,
+            PlayerStatus::Stopped(StopReason::AlbumEnded) => 3,
+            PlayerStatus::Stopped(StopReason::Unplayable) => 4,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [PlayerStatus; 5] = [
+        PlayerStatus::Playing,
+        PlayerStatus::Paused,
+        PlayerStatus::Stopped(StopReason::User),
+        PlayerStatus::Stopped(StopReason::AlbumEnded),
+        PlayerStatus::Stopped(StopReason::Unplayable),
+    ];
+
+    #[test]
+    fn test_transitions() {
+        use PlayerStatus::*;
+
+        let ended = Stopped(StopReason::AlbumEnded);
+        let cases = [
+            (Playing, Transition::Pause, Paused),
+            (Paused, Transition::Resume, Playing),
+            (Paused, Transition::Play, Playing),
+            (ended, Transition::Play, Playing),
+            (Playing, Transition::Stop(StopReason::AlbumEnded), ended),
+            (
+                Paused,
+                Transition::Stop(StopReason::User),
+                Stopped(StopReason::User),
+            ),
+            // Stopping again keeps the reason.
+            (ended, Transition::Stop(StopReason::User), ended),
+            // Pausing or resuming has no effect when stopped.
+            (ended, Transition::Pause, ended),
+            (ended, Transition::Resume, ended),
+            (Playing, Transition::Resume, Playing),
+            (Paused, Transition::Pause, Paused),
+        ];
+
+        for (status, transition, expected) in cases {
+            assert_eq!(
+                status.after(transition),
+                expected,
+                "{:?} after {:?}",
+                status,
+                transition
+            );
+        }
+    }
+
+    #[test]
+    fn test_status_bytes_round_trip() {
+        for status in ALL {
+            assert_eq!(status.to_u8().from_u8(), status);
+        }
+    }
+
+    #[test]
+    fn test_only_finished_albums_are_not_resumable() {
+        let not_resumable = ALL
+            .iter()
+            .filter(|status| !status.is_resumable())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            not_resumable,
+            vec![&PlayerStatus::Stopped(StopReason::AlbumEnded)]
+        );
+    }
+}
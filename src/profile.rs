@@ -0,0 +1,171 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::player::output;
+use crate::utils;
+
+lazy_static::lazy_static! {
+    // The instant that tap started.
+    static ref START: Instant = Instant::now();
+    static ref SPANS: Mutex<Vec<Span>> = Mutex::new(vec![]);
+}
+
+// The name of the log that the spans are written to.
+const LOG: &str = "debug";
+
+// The phases of startup, in the order they happen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Phase {
+    Args,
+    Config,
+    // Loading the items from the cache, for the default directory.
+    Cache,
+    // Walking the search root for the items.
+    Scan,
+    // Constructing the standalone player.
+    Player,
+    // Opening the audio output, the first time a player is created.
+    Audio,
+    FirstRender,
+    // Queuing the first decoded audio to the sink.
+    FirstAudio,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 8] = [
+        Phase::Args,
+        Phase::Config,
+        Phase::Cache,
+        Phase::Scan,
+        Phase::Player,
+        Phase::Audio,
+        Phase::FirstRender,
+        Phase::FirstAudio,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Phase::Args => "arg parsing",
+            Phase::Config => "config load",
+            Phase::Cache => "cache load",
+            Phase::Scan => "scan",
+            Phase::Player => "player",
+            Phase::Audio => "audio init",
+            Phase::FirstRender => "first render",
+            Phase::FirstAudio => "first audio",
+        }
+    }
+}
+
+// A timed phase, relative to the start of tap.
+#[derive(Clone, Copy, Debug)]
+struct Span {
+    phase: Phase,
+    start: Duration,
+    // `None` for phases that are a single point in time.
+    duration: Option<Duration>,
+}
+
+// Starts the clock that the phases are timed against.
+pub fn start() {
+    lazy_static::initialize(&START);
+}
+
+// Times `f` as `phase`.
+pub fn span<T>(phase: Phase, f: impl FnOnce() -> T) -> T {
+    let start = START.elapsed();
+    let result = f();
+    record(Span {
+        phase,
+        start,
+        duration: Some(START.elapsed() - start),
+    });
+    result
+}
+
+// Records the first time that `phase` is reached.
+pub fn mark(phase: Phase) {
+    record(Span {
+        phase,
+        start: START.elapsed(),
+        duration: None,
+    });
+}
+
+// Only the first span for each phase is kept.
+fn record(span: Span) {
+    let mut spans = SPANS.lock().unwrap_or_else(|e| e.into_inner());
+    if !spans.iter().any(|s| s.phase == span.phase) {
+        spans.push(span);
+    }
+}
+
+// Prints the phases to stderr as a table, and writes them to the debug log.
+pub fn report() {
+    let spans = SPANS.lock().unwrap_or_else(|e| e.into_inner());
+    let table = table(&spans, START.elapsed(), output::times_opened());
+
+    for line in table.lines() {
+        utils::log(LOG, &format!("startup: {}", line.trim_end()));
+    }
+    eprint!("{table}");
+}
+
+// Formats the `spans` as a table, with a row for each phase. Phases that
+// weren't reached are shown as 'skipped'. The last row is the number of times
+// that the audio output was opened.
+fn table(spans: &[Span], total: Duration, audio_opens: usize) -> String {
+    let ms = |d: Duration| format!("{:.1} ms", d.as_secs_f64() * 1000.0);
+    let mut table = format!("{: <14}{: >12}{: >12}\n", "phase", "start", "duration");
+
+    for phase in Phase::ALL {
+        let row = match spans.iter().find(|s| s.phase == phase) {
+            Some(span) => format!(
+                "{: <14}{: >12}{: >12}\n",
+                phase.name(),
+                ms(span.start),
+                span.duration.map_or(String::from("-"), ms),
+            ),
+            None => format!("{: <14}{: >12}{: >12}\n", phase.name(), "skipped", "-"),
+        };
+        table.push_str(&row);
+    }
+
+    table.push_str(&format!("{: <14}{: >12}{: >12}\n", "total", "", ms(total)));
+    table.push_str(&format!("{: <14}{: >24}\n", "audio opens", audio_opens));
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_has_a_row_for_each_phase() {
+        let spans = [
+            Span {
+                phase: Phase::Scan,
+                start: Duration::from_millis(2),
+                duration: Some(Duration::from_micros(12_340)),
+            },
+            Span {
+                phase: Phase::FirstRender,
+                start: Duration::from_millis(20),
+                duration: None,
+            },
+        ];
+
+        let table = table(&spans, Duration::from_millis(25), 0);
+        let rows = table.lines().collect::<Vec<&str>>();
+
+        assert_eq!(rows.len(), Phase::ALL.len() + 3);
+        assert!(rows[4].starts_with("scan") && rows[4].ends_with("12.3 ms"));
+        assert!(rows[3].starts_with("cache load") && rows[3].contains("skipped"));
+        assert!(rows[6].starts_with("audio init") && rows[6].contains("skipped"));
+        assert!(rows[7].starts_with("first render") && rows[7].ends_with("-"));
+        assert!(rows[9].starts_with("total") && rows[9].ends_with("25.0 ms"));
+        assert!(rows[10].starts_with("audio opens") && rows[10].ends_with(" 0"));
+    }
+}
@@ -0,0 +1,238 @@
+use std::{
+    fs::OpenOptions,
+    time::{Duration, Instant},
+};
+
+use cursive::{backend, Cursive, CursiveRunnable, CursiveRunner};
+
+use crate::utils;
+
+// The name of the log for losing and regaining the terminal.
+const LOG: &str = "tty";
+
+// The time between checks that the terminal is still there.
+const PROBE_INTERVAL: Duration = Duration::from_secs(1);
+
+// The time between attempts to reattach, which doubles after each failed
+// attempt up to `MAX_REATTACH_DELAY`.
+const REATTACH_DELAY: Duration = Duration::from_secs(1);
+const MAX_REATTACH_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TtyState {
+    Attached,
+    // Running without a terminal. Audio continues and the session is still
+    // saved, but nothing is drawn and there is no input.
+    Detached,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TtyEvent {
+    Lost,
+    Reattached,
+}
+
+// Tracks whether tap has a terminal, probing it at most once per interval.
+pub struct TtyMonitor {
+    state: TtyState,
+    // The failed attempts to reattach since the terminal was lost.
+    attempts: u32,
+    // The instant of the next probe.
+    next_probe: Instant,
+}
+
+impl TtyMonitor {
+    pub fn new(now: Instant) -> Self {
+        TtyMonitor {
+            state: TtyState::Attached,
+            attempts: 0,
+            next_probe: now + PROBE_INTERVAL,
+        }
+    }
+
+    // Runs `probe`, if it is time to, and returns whether the terminal was
+    // lost or reattached.
+    pub fn check(&mut self, now: Instant, probe: impl FnOnce() -> bool) -> Option<TtyEvent> {
+        if now < self.next_probe {
+            return None;
+        }
+
+        match (self.state, probe()) {
+            (TtyState::Attached, true) => {
+                self.next_probe = now + PROBE_INTERVAL;
+                None
+            }
+            (TtyState::Attached, false) => {
+                self.state = TtyState::Detached;
+                self.attempts = 0;
+                self.next_probe = now + REATTACH_DELAY;
+                Some(TtyEvent::Lost)
+            }
+            (TtyState::Detached, true) => {
+                self.state = TtyState::Attached;
+                self.next_probe = now + PROBE_INTERVAL;
+                Some(TtyEvent::Reattached)
+            }
+            (TtyState::Detached, false) => {
+                self.retry_later(now);
+                None
+            }
+        }
+    }
+
+    // Goes back to running detached when the terminal came back but the
+    // backend couldn't be started on it.
+    pub fn reattach_failed(&mut self, now: Instant) {
+        self.state = TtyState::Detached;
+        self.retry_later(now);
+    }
+
+    fn retry_later(&mut self, now: Instant) {
+        self.attempts += 1;
+        self.next_probe = now + reattach_delay(self.attempts);
+    }
+}
+
+// The time to wait after `attempts` failed attempts to reattach.
+fn reattach_delay(attempts: u32) -> Duration {
+    REATTACH_DELAY
+        .saturating_mul(2u32.saturating_pow(attempts))
+        .min(MAX_REATTACH_DELAY)
+}
+
+// Whether or not tap still has a controlling terminal. Opening '/dev/tty'
+// fails once a multiplexer has detached it or the terminal has hung up.
+fn is_attached() -> bool {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .is_ok()
+}
+
+// Runs the event loop until the user quits. When the terminal is lost, the
+// views keep being laid out without it, so that audio continues, until the
+// terminal comes back and the view stack is drawn on it again.
+pub fn run(
+    siv: &mut CursiveRunnable,
+    first_refresh: impl FnOnce(&mut CursiveRunner<&mut Cursive>),
+) {
+    let mut monitor = TtyMonitor::new(Instant::now());
+
+    let mut runner = siv.runner();
+    first_refresh(&mut runner);
+    if !run_attached(&mut runner, &mut monitor) {
+        return;
+    }
+    drop(runner);
+
+    loop {
+        utils::log(LOG, "lost the terminal, continuing without it");
+        if !run_detached(siv, &mut monitor) {
+            return;
+        }
+
+        match siv.try_runner() {
+            Ok(mut runner) => {
+                utils::log(LOG, "reattached to the terminal");
+                runner.refresh();
+                if !run_attached(&mut runner, &mut monitor) {
+                    return;
+                }
+            }
+            Err(e) => {
+                utils::log(LOG, &format!("could not reattach: {e}"));
+                monitor.reattach_failed(Instant::now());
+            }
+        }
+    }
+}
+
+// Runs the event loop on the terminal. Returns false when the user quits,
+// or true when the terminal is lost.
+fn run_attached(runner: &mut CursiveRunner<&mut Cursive>, monitor: &mut TtyMonitor) -> bool {
+    while runner.is_running() {
+        runner.step();
+        if monitor.check(Instant::now(), is_attached) == Some(TtyEvent::Lost) {
+            return true;
+        }
+    }
+    false
+}
+
+// Runs the event loop without a terminal. Returns false when tap quits, or
+// true when the terminal is back.
+fn run_detached(siv: &mut Cursive, monitor: &mut TtyMonitor) -> bool {
+    let mut runner = siv.runner(backend::Dummy::init());
+    while runner.is_running() {
+        runner.step();
+        if monitor.check(Instant::now(), is_attached) == Some(TtyEvent::Reattached) {
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lost_and_reattached() {
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let mut monitor = TtyMonitor::new(start);
+
+        // The terminal isn't probed again within the interval.
+        assert_eq!(monitor.check(start, || panic!("probed too soon")), None);
+        assert_eq!(monitor.check(at(1), || true), None);
+        assert_eq!(monitor.check(at(2), || false), Some(TtyEvent::Lost));
+        assert_eq!(monitor.state, TtyState::Detached);
+
+        assert_eq!(monitor.check(at(3), || false), None);
+        assert_eq!(monitor.state, TtyState::Detached);
+        assert_eq!(monitor.check(at(5), || true), Some(TtyEvent::Reattached));
+        assert_eq!(monitor.state, TtyState::Attached);
+    }
+
+    #[test]
+    fn test_reattach_attempts_back_off() {
+        let start = Instant::now();
+        let mut monitor = TtyMonitor::new(start);
+        let mut now = start + PROBE_INTERVAL;
+        assert_eq!(monitor.check(now, || false), Some(TtyEvent::Lost));
+
+        // Count the probes over ten minutes without a terminal.
+        let mut probes = 0;
+        for _ in 0..600 {
+            now += Duration::from_secs(1);
+            monitor.check(now, || {
+                probes += 1;
+                false
+            });
+        }
+        assert!(probes < 30, "probed {} times", probes);
+        assert!(probes > 10, "probed {} times", probes);
+        assert_eq!(reattach_delay(100), MAX_REATTACH_DELAY);
+    }
+
+    #[test]
+    fn test_failed_reattach_stays_detached() {
+        let start = Instant::now();
+        let mut monitor = TtyMonitor::new(start);
+        let now = start + PROBE_INTERVAL;
+        monitor.check(now, || false);
+
+        let now = now + REATTACH_DELAY;
+        assert_eq!(monitor.check(now, || true), Some(TtyEvent::Reattached));
+        monitor.reattach_failed(now);
+        assert_eq!(monitor.state, TtyState::Detached);
+
+        // The next attempt is after the backoff, not the probe interval.
+        assert_eq!(monitor.check(now + PROBE_INTERVAL, || true), None);
+        assert_eq!(
+            monitor.check(now + MAX_REATTACH_DELAY, || true),
+            Some(TtyEvent::Reattached)
+        );
+    }
+}
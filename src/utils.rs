@@ -1,14 +1,22 @@
 use std::{
-    io::{stdout, Write},
+    env,
+    ffi::OsString,
+    fs::{DirBuilder, File, OpenOptions},
+    io::{self, stdout, Write},
     ops::Range,
-    path::PathBuf,
-    sync::mpsc,
+    path::{Component, Path, PathBuf},
+    sync::{mpsc, RwLock},
     thread,
-    time::{Duration, Instant, SystemTime},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+#[cfg(unix)]
+use std::{
+    fs::Permissions,
+    os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt},
 };
 
 use anyhow::bail;
-use rand::{thread_rng, Rng};
+use rand::{seq::SliceRandom, thread_rng, Rng};
 
 pub trait IntoInner {
     type T;
@@ -27,6 +35,11 @@ pub fn random(range: Range<usize>) -> usize {
     thread_rng().gen_range(range)
 }
 
+// Shuffles `items` into a random order.
+pub fn shuffle<T>(items: &mut [T]) {
+    items.shuffle(&mut thread_rng());
+}
+
 // Bounds a value by a minimum and maximum value.
 pub fn clamp<T: PartialOrd>(input: T, min: T, max: T) -> T {
     if input < min {
@@ -38,6 +51,41 @@ pub fn clamp<T: PartialOrd>(input: T, min: T, max: T) -> T {
     }
 }
 
+// `bytes` in the largest unit that keeps it at least 1, as `ls -h` does, i.e.
+// `1536` -> `1.5K`, with a decimal only below 10.
+pub fn human_size(bytes: u64) -> String {
+    let mut size = bytes as f64;
+    for unit in ["B", "K", "M", "G"] {
+        let rounded = match size < 10.0 && unit != "B" {
+            true => (size * 10.0).round() / 10.0,
+            false => size.round(),
+        };
+        if rounded < 1024.0 {
+            return match rounded < 10.0 && unit != "B" {
+                true => format!("{rounded:.1}{unit}"),
+                false => format!("{rounded}{unit}"),
+            };
+        }
+        size /= 1024.0;
+    }
+    format!("{}T", size.round())
+}
+
+// The UTC date of `secs` since the Unix epoch, i.e. `2024-05-01`.
+pub fn date(secs: u64) -> String {
+    // The days since 0000-03-01, so that the leap day ends each year.
+    let days = secs / 86_400 + 719_468;
+    let (era, day_of_era) = (days / 146_097, days % 146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = era * 400 + year_of_era + (month <= 2) as u64;
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
 // Gets the last modification time listed in the metadata for the path.
 pub fn last_modified(path: &PathBuf) -> Result<SystemTime, anyhow::Error> {
     match std::fs::metadata(&path) {
@@ -49,45 +97,318 @@ pub fn last_modified(path: &PathBuf) -> Result<SystemTime, anyhow::Error> {
     }
 }
 
-// Attempts to open the path with the default file manager.
-// Requires 'xdg-open' on linux systems. Uses 'open' on macos.
-pub fn open_file_manager(path: PathBuf) -> Result<(), anyhow::Error> {
-    let p = match std::fs::metadata(&path) {
-        Ok(meta) => match meta.is_dir() {
-            true => path,
-            // false => path.parent().unwrap().to_path_buf(),
-            false => match path.parent() {
-                Some(parent) => parent.to_path_buf(),
-                None => bail!("No parent"),
-            },
-        },
-        Err(err) => bail!(err),
+// Whether or not `path` is read-only, either because it isn't writable
+// or because it is on a filesystem that is mounted read-only.
+pub fn is_read_only(path: &Path) -> bool {
+    let mounts = std::fs::read_to_string("/proc/self/mounts").unwrap_or_default();
+    is_read_only_in(path, &mounts)
+}
+
+// As `is_read_only`, with the list of `mounts` in the format of
+// '/proc/self/mounts'.
+pub fn is_read_only_in(path: &Path, mounts: &str) -> bool {
+    let readonly = match std::fs::metadata(path) {
+        Ok(data) => data.permissions().readonly(),
+        Err(_) => false,
     };
 
-    let s = p
-        .as_os_str()
-        .to_str()
-        .expect("should be a valid UTF-8 path");
+    readonly || mounted_read_only(path, mounts)
+}
 
-    #[cfg(target_os = "macos")]
-    {
-        let status = std::process::Command::new("open").arg(s).status();
-        match status {
-            Ok(_) => Ok(()),
-            Err(err) => bail!(err),
+// Whether or not the mount containing `path`, from the list of `mounts`
+// in the format of '/proc/self/mounts', is mounted read-only.
+fn mounted_read_only(path: &Path, mounts: &str) -> bool {
+    let mut mount: Option<(PathBuf, bool)> = None;
+
+    for line in mounts.lines() {
+        let fields = line.split_whitespace().collect::<Vec<&str>>();
+        if fields.len() < 4 {
+            continue;
+        }
+
+        // Spaces in mount points are escaped as '\040'.
+        let mount_point = PathBuf::from(fields[1].replace("\\040", " "));
+        let is_read_only = fields[3].split(',').any(|opt| opt == "ro");
+
+        let is_longer = match &mount {
+            Some((current, _)) => mount_point.as_os_str().len() >= current.as_os_str().len(),
+            None => true,
+        };
+
+        if path.starts_with(&mount_point) && is_longer {
+            mount = Some((mount_point, is_read_only));
         }
     }
 
-    #[cfg(target_os = "linux")]
+    mount.map_or(false, |(_, is_read_only)| is_read_only)
+}
+
+// How paths are shown to the user.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PathStyle {
+    // Relative to the root that contains the path.
+    Relative,
+    Absolute,
+    // Relative, with each directory but the last shortened to its first
+    // character.
+    Short,
+}
+
+// The style and roots used by `display_path`.
+#[derive(Debug)]
+pub struct PathDisplay {
+    style: PathStyle,
+    // Each root, with its canonical path if that is different, and nickname.
+    roots: Vec<(Vec<PathBuf>, Option<String>)>,
+    home: Option<PathBuf>,
+}
+
+static PATH_DISPLAY: RwLock<Option<PathDisplay>> = RwLock::new(None);
+
+impl PathDisplay {
+    pub fn new(
+        style: PathStyle,
+        roots: Vec<(PathBuf, Option<String>)>,
+        home: Option<PathBuf>,
+    ) -> Self {
+        let roots = roots
+            .into_iter()
+            .map(|(root, nickname)| {
+                let mut paths = vec![root.components().collect::<PathBuf>()];
+                if let Ok(canonical) = root.canonicalize() {
+                    if canonical != paths[0] {
+                        paths.push(canonical);
+                    }
+                }
+                (paths, nickname)
+            })
+            .collect();
+
+        PathDisplay { style, roots, home }
+    }
+
+    // Formats `path` relative to the deepest root that contains it, prefixed
+    // with the root's nickname if it has one. Paths outside of the roots are
+    // abbreviated with '~' when they are in the home directory.
+    pub fn format(&self, path: &Path) -> String {
+        if self.style == PathStyle::Absolute {
+            return path.display().to_string();
+        }
+
+        let mut found: Option<(usize, &Path, Option<&str>)> = None;
+        for (paths, nickname) in &self.roots {
+            for root in paths {
+                let depth = root.components().count();
+                let is_deeper = match found {
+                    Some((found_depth, _, _)) => depth > found_depth,
+                    None => true,
+                };
+                if let (Ok(rest), true) = (path.strip_prefix(root), is_deeper) {
+                    found = Some((depth, rest, nickname.as_deref()));
+                }
+            }
+        }
+
+        let (prefix, rest) = match found {
+            Some((_, rest, nickname)) => (nickname.map(|n| n.to_string()), rest),
+            None => match self
+                .home
+                .as_ref()
+                .and_then(|home| path.strip_prefix(home).ok())
+            {
+                Some(rest) => (Some("~".to_string()), rest),
+                None => return path.display().to_string(),
+            },
+        };
+
+        let mut parts = rest
+            .components()
+            .filter_map(|c| match c {
+                Component::Normal(name) => Some(name.to_string_lossy().to_string()),
+                _ => None,
+            })
+            .collect::<Vec<String>>();
+
+        if self.style == PathStyle::Short && parts.len() > 1 {
+            let last = parts.len() - 1;
+            for part in parts[..last].iter_mut() {
+                *part = part.chars().take(1).collect();
+            }
+        }
+
+        match (prefix, parts.is_empty()) {
+            (Some(prefix), true) => prefix,
+            (Some(prefix), false) => format!("{}/{}", prefix, parts.join("/")),
+            (None, true) => ".".to_string(),
+            (None, false) => parts.join("/"),
+        }
+    }
+}
+
+// Sets how `display_path` shows paths. Paths are absolute until this is set.
+pub fn set_path_display(display: PathDisplay) {
+    *PATH_DISPLAY.write().unwrap_or_else(|e| e.into_inner()) = Some(display);
+}
+
+// Formats `path` to be shown to the user.
+pub fn display_path(path: &Path) -> String {
+    match PATH_DISPLAY
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
     {
-        let status = std::process::Command::new("xdg-open").arg(s).status();
-        match status {
-            Ok(_) => Ok(()),
-            Err(err) => bail!(err),
+        Some(display) => display.format(path),
+        None => path.display().to_string(),
+    }
+}
+
+// Appends `msg` to the log `name` in the data directory, ignoring any errors.
+pub fn log(name: &str, msg: &str) {
+    if let Ok(dir) = app_dir(AppDir::Data) {
+        let path = dir.join(format!("{name}.log"));
+        if let Ok(mut file) = open_private(&path, OpenOptions::new().append(true)) {
+            let secs = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            _ = writeln!(file, "[{secs}]: {msg}");
         }
     }
 }
 
+// The directories that tap keeps its files in, each a 'tap' directory in an
+// XDG base directory.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AppDir {
+    // The config file, in '$XDG_CONFIG_HOME' or '~/.config'.
+    Config,
+    // The saved state, in '$XDG_CACHE_HOME' or '~/.cache'.
+    Cache,
+    // The logs, in '$XDG_DATA_HOME' or '~/.local/share'.
+    Data,
+}
+
+impl AppDir {
+    // The XDG variable of the base directory, and its default in the home
+    // directory.
+    fn base(self) -> (&'static str, &'static str) {
+        match self {
+            AppDir::Config => ("XDG_CONFIG_HOME", ".config"),
+            AppDir::Cache => ("XDG_CACHE_HOME", ".cache"),
+            AppDir::Data => ("XDG_DATA_HOME", ".local/share"),
+        }
+    }
+
+    // The path of the directory in `xdg`, the value of the XDG variable, or
+    // else in `home`. As in the XDG spec, a relative `xdg` is ignored.
+    fn path(self, xdg: Option<OsString>, home: Option<PathBuf>) -> Option<PathBuf> {
+        let base = match xdg.map(PathBuf::from).filter(|dir| dir.is_absolute()) {
+            Some(dir) => dir,
+            None => home?.join(self.base().1),
+        };
+        Some(base.join("tap"))
+    }
+}
+
+// The path of the directory `dir`, without creating it. The home directory is
+// `$HOME`, or `%USERPROFILE%` on Windows.
+pub fn app_path(dir: AppDir) -> Result<PathBuf, anyhow::Error> {
+    let home = env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .map(PathBuf::from);
+    match dir.path(env::var_os(dir.base().0), home) {
+        Some(path) => Ok(path),
+        None => bail!("could not find the home directory, set $HOME"),
+    }
+}
+
+// The path of the directory `dir`, which is created with any missing parents
+// if it doesn't exist. Only the user can read the directories that are
+// created.
+pub fn app_dir(dir: AppDir) -> Result<PathBuf, anyhow::Error> {
+    let path = app_path(dir)?;
+    if let Err(e) = create_private_dir(&path) {
+        bail!("could not create '{}'\n- `{}`", path.display(), e);
+    }
+    Ok(path)
+}
+
+fn create_private_dir(path: &Path) -> io::Result<()> {
+    let mut builder = DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    builder.mode(0o700);
+    builder.create(path)
+}
+
+// Opens the file at `path` with `options`, creating it so that only the user
+// can read and write it, which the umask can't widen. The mode only applies
+// to a new file, so an existing one that others can read is narrowed too.
+fn open_private(path: &Path, options: &mut OpenOptions) -> io::Result<File> {
+    options.create(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let file = options.open(path)?;
+
+    #[cfg(unix)]
+    if file.metadata()?.permissions().mode() & 0o177 != 0 {
+        file.set_permissions(Permissions::from_mode(0o600))?;
+    }
+    Ok(file)
+}
+
+// Writes `data` to the file at `path`, replacing what was in it. Only the user
+// can read the file afterwards.
+pub fn write_private(path: &Path, data: &[u8]) -> io::Result<()> {
+    open_private(path, OpenOptions::new().write(true).truncate(true))?.write_all(data)
+}
+
+// The frames of a spinner, with Braille dots where the locale is UTF-8, and
+// dots that fill up otherwise. Each frame follows straight on from the text.
+const BRAILLE_FRAMES: [&str; 8] = [" ⠋", " ⠙", " ⠸", " ⠴", " ⠦", " ⠇", " ⠏", " ⠹"];
+const ASCII_FRAMES: [&str; 4] = ["   ", ".  ", ".. ", "..."];
+
+// A spinner for work that takes a while. It has no timer of its own, and
+// moves on a frame each time that the caller ticks it.
+pub struct Spinner {
+    frames: &'static [&'static str],
+    tick: usize,
+}
+
+impl Spinner {
+    pub fn new(unicode: bool) -> Self {
+        let frames: &'static [&'static str] = match unicode {
+            true => &BRAILLE_FRAMES,
+            false => &ASCII_FRAMES,
+        };
+        Self { frames, tick: 0 }
+    }
+
+    // Moves on to the next frame.
+    pub fn tick(&mut self) {
+        self.tick = (self.tick + 1) % self.frames.len();
+    }
+
+    pub fn frame(&self) -> &'static str {
+        self.frames[self.tick]
+    }
+}
+
+// Whether or not the locale can show characters outside of ASCII, going by
+// the first of `LC_ALL`, `LC_CTYPE` and `LANG` that is set.
+pub fn supports_unicode() -> bool {
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty());
+    locale.map_or(false, |locale| is_utf8(&locale))
+}
+
+fn is_utf8(locale: &str) -> bool {
+    let locale = locale.to_lowercase();
+    locale.ends_with(".utf-8") || locale.ends_with(".utf8")
+}
+
 pub fn display_with_spinner<F, T>(
     action: F,
     path: &PathBuf,
@@ -101,8 +422,7 @@ where
     let start_time = Instant::now();
 
     let stdout_handle = thread::spawn(move || {
-        let ellipses = vec!["   ", ".  ", ".. ", "..."];
-        let mut spinner = ellipses.iter().cycle();
+        let mut spinner = Spinner::new(supports_unicode());
         let mut is_showing = false;
 
         loop {
@@ -116,8 +436,9 @@ where
                 }
                 Err(_) => {
                     if is_showing {
-                        print!("\r[tap]: {}{} ", msg, spinner.next().unwrap());
+                        print!("\r[tap]: {}{} ", msg, spinner.frame());
                         stdout().flush().unwrap();
+                        spinner.tick();
                     }
                     thread::sleep(Duration::from_millis(300));
                 }
@@ -187,3 +508,180 @@ pub fn create_working_dir(
 
     Ok(temp_dir)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_human_size() {
+        assert_eq!(human_size(0), "0B");
+        assert_eq!(human_size(1023), "1023B");
+        assert_eq!(human_size(1536), "1.5K");
+        assert_eq!(human_size(10 * 1024 - 1), "10K");
+        assert_eq!(human_size(312 * 1024 * 1024), "312M");
+        assert_eq!(human_size(1024 * 1024 * 1024 - 1), "1.0G");
+        assert_eq!(human_size(5 << 40), "5T");
+    }
+
+    #[test]
+    fn test_date() {
+        assert_eq!(date(0), "1970-01-01");
+        assert_eq!(date(951_782_400), "2000-02-29");
+        assert_eq!(date(951_868_799), "2000-02-29");
+        assert_eq!(date(1_714_521_600), "2024-05-01");
+        assert_eq!(date(4_107_542_400), "2100-03-01");
+    }
+
+    const MOUNTS: &str = "\
+        /dev/sda1 / ext4 rw,relatime 0 0\n\
+        /dev/sdb1 /mnt/archive ext4 ro,relatime 0 0\n\
+        /dev/sdb2 /mnt/archive/incoming ext4 rw,relatime 0 0\n\
+        /dev/sdc1 /mnt/old\\040music vfat ro 0 0\n";
+
+    #[test]
+    fn test_app_dir_paths() {
+        let home = env::temp_dir().join("home");
+        let xdg = env::temp_dir().join("xdg");
+        let path = |dir: AppDir, xdg: Option<&Path>| {
+            dir.path(xdg.map(OsString::from), Some(home.to_owned()))
+        };
+
+        assert_eq!(path(AppDir::Cache, None), Some(home.join(".cache/tap")));
+        assert_eq!(
+            path(AppDir::Data, None),
+            Some(home.join(".local/share/tap"))
+        );
+        assert_eq!(path(AppDir::Config, Some(&xdg)), Some(xdg.join("tap")));
+        // An empty or relative XDG directory is ignored.
+        let config = Some(home.join(".config/tap"));
+        assert_eq!(path(AppDir::Config, Some(Path::new(""))), config);
+        assert_eq!(path(AppDir::Config, Some(Path::new("config"))), config);
+        // Without a home directory, only the XDG directory can be used.
+        assert_eq!(AppDir::Cache.path(None, None), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_private_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+        let app_dir = dir.path().join("cache").join("tap");
+        create_private_dir(&app_dir).unwrap();
+        assert_eq!(mode(&app_dir), 0o700);
+        assert_eq!(mode(&dir.path().join("cache")), 0o700);
+
+        let file = app_dir.join("history");
+        write_private(&file, b"older and longer").unwrap();
+        write_private(&file, b"newer").unwrap();
+        assert_eq!(mode(&file), 0o600);
+        assert_eq!(std::fs::read(&file).unwrap(), b"newer");
+
+        // A file that was written before, that others can read, is narrowed.
+        let file = app_dir.join("favorites");
+        std::fs::write(&file, b"older").unwrap();
+        std::fs::set_permissions(&file, Permissions::from_mode(0o644)).unwrap();
+        write_private(&file, b"newer").unwrap();
+        assert_eq!(mode(&file), 0o600);
+        assert_eq!(std::fs::read(&file).unwrap(), b"newer");
+    }
+
+    #[test]
+    fn test_spinner() {
+        let mut spinner = Spinner::new(false);
+        let frames: Vec<&str> = (0..5)
+            .map(|_| {
+                let frame = spinner.frame();
+                spinner.tick();
+                frame
+            })
+            .collect();
+        assert_eq!(frames, vec!["   ", ".  ", ".. ", "...", "   "]);
+        assert_eq!(Spinner::new(true).frame(), " ⠋");
+
+        assert!(is_utf8("en_GB.UTF-8"));
+        assert!(is_utf8("C.utf8"));
+        assert!(!is_utf8("C"));
+        assert!(!is_utf8("POSIX"));
+    }
+
+    #[test]
+    fn test_mounted_read_only() {
+        assert!(!mounted_read_only(Path::new("/home/music"), MOUNTS));
+        assert!(mounted_read_only(Path::new("/mnt/archive/jazz"), MOUNTS));
+        assert!(!mounted_read_only(
+            Path::new("/mnt/archive/incoming/new"),
+            MOUNTS
+        ));
+        assert!(mounted_read_only(Path::new("/mnt/old music/rock"), MOUNTS));
+        assert!(!mounted_read_only(Path::new("/mnt/archived"), MOUNTS));
+    }
+
+    #[test]
+    fn test_relative_paths() {
+        let roots = vec![
+            (PathBuf::from("/music/"), None),
+            (
+                PathBuf::from("/music/podcasts"),
+                Some("Podcasts".to_string()),
+            ),
+        ];
+        let display = PathDisplay::new(PathStyle::Relative, roots, Some("/home/user".into()));
+        let format = |path: &str| display.format(Path::new(path));
+
+        assert_eq!(format("/music/artist/album"), "artist/album");
+        assert_eq!(format("/music"), ".");
+        // The deepest root that contains the path is used.
+        assert_eq!(
+            format("/music/podcasts/show/episode.mp3"),
+            "Podcasts/show/episode.mp3"
+        );
+        assert_eq!(format("/music/podcasts"), "Podcasts");
+        // Paths outside of the roots.
+        assert_eq!(format("/home/user/downloads/album"), "~/downloads/album");
+        assert_eq!(format("/mnt/archive/album"), "/mnt/archive/album");
+        assert_eq!(format("/musical/album"), "/musical/album");
+    }
+
+    #[test]
+    fn test_short_and_absolute_paths() {
+        let roots = vec![(PathBuf::from("/music"), Some("Music".to_string()))];
+        let short = PathDisplay::new(
+            PathStyle::Short,
+            roots.to_owned(),
+            Some("/home/user".into()),
+        );
+        let absolute = PathDisplay::new(PathStyle::Absolute, roots, Some("/home/user".into()));
+        let path = Path::new("/music/artist/the album/01 track.flac");
+
+        assert_eq!(short.format(path), "Music/a/t/01 track.flac");
+        assert_eq!(short.format(Path::new("/home/user/music/jazz")), "~/m/jazz");
+        assert_eq!(
+            absolute.format(path),
+            "/music/artist/the album/01 track.flac"
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_symlinked_root() {
+        let temp_dir = tempfile::tempdir().expect("create temp dir");
+        let real = temp_dir.path().join("real");
+        let link = temp_dir.path().join("link");
+        std::fs::create_dir_all(real.join("album")).expect("create dir");
+        std::os::unix::fs::symlink(&real, &link).expect("create symlink");
+
+        let display = PathDisplay::new(PathStyle::Relative, vec![(link.to_owned(), None)], None);
+
+        // Paths through the link and through the real directory are both
+        // relative to the root.
+        assert_eq!(display.format(&link.join("album")), "album");
+        assert_eq!(
+            display.format(&real.canonicalize().unwrap().join("album")),
+            "album"
+        );
+    }
+}
@@ -0,0 +1,119 @@
+use std::{
+    ffi::OsString,
+    process::{Command, ExitStatus},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::bail;
+
+use crate::data::persistent_data;
+use crate::utils;
+
+// The name of the log for restart events.
+const LOG: &str = "watchdog";
+
+// The time to wait before restarting a crashed player.
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+
+// A player that crashes within this time of starting counts towards
+// `MAX_QUICK_RESTARTS`.
+const QUICK_EXIT: Duration = Duration::from_secs(10);
+
+// The number of consecutive quick crashes after which we stop restarting,
+// so that a player that crashes on startup isn't restarted forever.
+const MAX_QUICK_RESTARTS: usize = 5;
+
+// Counts the crashes in a row of a player that ran for less than
+// `QUICK_EXIT`.
+#[derive(Default)]
+struct QuickCrashes {
+    count: usize,
+}
+
+impl QuickCrashes {
+    // Records a crash of a player that ran for `ran_for`, and returns whether
+    // it should be restarted, which it isn't after `MAX_QUICK_RESTARTS` quick
+    // crashes in a row.
+    fn record(&mut self, ran_for: Duration) -> bool {
+        match ran_for < QUICK_EXIT {
+            true => self.count += 1,
+            false => self.count = 0,
+        }
+        self.count < MAX_QUICK_RESTARTS
+    }
+}
+
+// Runs tap as a child process, restarting it with the last session
+// whenever it exits with a non-zero status.
+pub fn run() -> Result<(), anyhow::Error> {
+    let exe = std::env::current_exe()?;
+    let mut args = child_args();
+    let mut quick_crashes = QuickCrashes::default();
+
+    loop {
+        let started = Instant::now();
+        // The player needs the terminal and runs until it is quit, so it
+        // is run directly rather than as a `CommandTemplate`.
+        let status = Command::new(&exe).args(&args).status()?;
+
+        if status.success() {
+            return Ok(());
+        }
+
+        if !quick_crashes.record(started.elapsed()) {
+            utils::log(LOG, &format!("{}, giving up", exit_reason(status)));
+            bail!("the player crashed {MAX_QUICK_RESTARTS} times in a row, see the watchdog log")
+        }
+
+        utils::log(LOG, &format!("{}, restarting", exit_reason(status)));
+        thread::sleep(RESTART_DELAY);
+
+        if !args.iter().any(|arg| arg == "--session-restore") {
+            args.push("--session-restore".into());
+            args.push(persistent_data::session_file()?.into());
+        }
+    }
+}
+
+// The arguments for the child process: the arguments that tap was run
+// with, without `--watchdog`.
+fn child_args() -> Vec<OsString> {
+    std::env::args_os()
+        .skip(1)
+        .filter(|arg| arg != "--watchdog")
+        .collect()
+}
+
+// Describes how the child process exited.
+fn exit_reason(status: ExitStatus) -> String {
+    match status.code() {
+        Some(code) => format!("player exited with status {code}"),
+        None => String::from("player was terminated by a signal"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quick_crashes() {
+        let quick = QUICK_EXIT - Duration::from_secs(1);
+        let mut crashes = QuickCrashes::default();
+
+        for _ in 1..MAX_QUICK_RESTARTS {
+            assert!(crashes.record(quick));
+        }
+        // A player that ran for a while starts the count again.
+        assert!(crashes.record(QUICK_EXIT));
+        assert_eq!(crashes.count, 0);
+
+        for _ in 1..MAX_QUICK_RESTARTS {
+            assert!(crashes.record(quick));
+        }
+        // It gives up on the crash that the message counts to.
+        assert!(!crashes.record(quick));
+        assert_eq!(crashes.count, MAX_QUICK_RESTARTS);
+    }
+}
@@ -0,0 +1,487 @@
+// Generates a synthetic music library for the integration tests and for trying
+// out tap, with tiny but valid audio files and the layouts that real libraries
+// have: multi-disc albums, cue + flac rips, unicode names, broken files, deep
+// nesting and huge flat folders. The same seed always generates the same
+// library, byte for byte.
+
+use std::{
+    f64::consts::PI,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+// The sample rate of the generated audio. Low, to keep the files small.
+const SAMPLE_RATE: u32 = 8000;
+
+// The most samples in a FLAC frame.
+const FLAC_BLOCK_SIZE: usize = 4096;
+
+const WORDS: [&str; 24] = [
+    "blue", "night", "river", "glass", "echo", "summer", "iron", "velvet", "ghost", "paper",
+    "signal", "static", "harbour", "lantern", "winter", "orbit", "copper", "hollow", "silver",
+    "tide", "ember", "north", "garden", "radio",
+];
+
+// Names that exercise unicode handling: accents, combining marks, wide
+// characters, right-to-left text and emoji.
+const UNICODE_NAMES: [(&str, &str); 6] = [
+    ("Sigur Rós", "Ágætis byrjun"),
+    ("Motörhead", "Ace of Spades"),
+    ("坂本龍一", "音楽図鑑"),
+    ("Zoe\u{301}", "Reptilectric"),
+    ("فيروز", "وحدن"),
+    ("Café Tacvba 🎸", "Re"),
+];
+
+#[derive(Clone, Copy, Debug)]
+pub enum Format {
+    Wav,
+    Flac,
+}
+
+impl Format {
+    fn ext(&self) -> &'static str {
+        match self {
+            Format::Wav => "wav",
+            Format::Flac => "flac",
+        }
+    }
+}
+
+// The shape of the generated library.
+#[derive(Clone, Debug)]
+pub struct LibraryConfig {
+    pub seed: u64,
+    // The artists with ordinary albums.
+    pub artists: usize,
+    pub albums_per_artist: usize,
+    pub tracks_per_album: usize,
+    // The number of tracks in the huge flat folder.
+    pub flat_tracks: usize,
+    // The number of directories above the deeply nested album.
+    pub depth: usize,
+    // The length of each track.
+    pub seconds: f64,
+}
+
+impl Default for LibraryConfig {
+    fn default() -> Self {
+        LibraryConfig {
+            seed: 0,
+            artists: 6,
+            albums_per_artist: 2,
+            tracks_per_album: 4,
+            flat_tracks: 500,
+            depth: 12,
+            seconds: 1.0,
+        }
+    }
+}
+
+// What was generated, with paths relative to the root of the library.
+#[derive(Debug, Default)]
+pub struct Library {
+    // The directories that contain playable audio.
+    pub albums: Vec<PathBuf>,
+    // The audio files that can't be played.
+    pub broken: Vec<PathBuf>,
+    // The number of playable audio files.
+    pub tracks: usize,
+}
+
+// Generates a library in `root`, which is created if it doesn't exist.
+pub fn generate(root: &Path, config: &LibraryConfig) -> io::Result<Library> {
+    let mut gen = Generator {
+        root: root.to_owned(),
+        rng: StdRng::seed_from_u64(config.seed),
+        config: config.to_owned(),
+        library: Library::default(),
+    };
+    fs::create_dir_all(root)?;
+
+    for _ in 0..config.artists {
+        let artist = gen.name(2);
+        for _ in 0..config.albums_per_artist {
+            let album = gen.name(3);
+            let year = gen.rng.gen_range(1960..2024);
+            let dir = PathBuf::from(&artist).join(format!("{} ({})", album, year));
+            gen.album(&dir, &artist, &album, config.tracks_per_album)?;
+        }
+    }
+
+    gen.multi_disc()?;
+    gen.cue_rip()?;
+    for (artist, album) in UNICODE_NAMES {
+        let dir = PathBuf::from(artist).join(album);
+        gen.album(&dir, artist, album, 2)?;
+    }
+    gen.broken()?;
+    gen.deep()?;
+    gen.flat()?;
+
+    Ok(gen.library)
+}
+
+struct Generator {
+    root: PathBuf,
+    rng: StdRng,
+    config: LibraryConfig,
+    library: Library,
+}
+
+impl Generator {
+    // A title case name of `words` random words.
+    fn name(&mut self, words: usize) -> String {
+        (0..words)
+            .map(|_| {
+                let word = WORDS.choose(&mut self.rng).expect("words");
+                word[..1].to_uppercase() + &word[1..]
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn format(&mut self) -> Format {
+        match self.rng.gen_bool(0.5) {
+            true => Format::Wav,
+            false => Format::Flac,
+        }
+    }
+
+    // An album of `tracks` tracks in `dir`, with a cover and a text file.
+    fn album(&mut self, dir: &Path, artist: &str, album: &str, tracks: usize) -> io::Result<()> {
+        fs::create_dir_all(self.root.join(dir))?;
+        let format = self.format();
+
+        for track in 1..=tracks {
+            let title = self.name(2);
+            let tags = Tags::new(artist, album, &title, track);
+            let path = dir.join(format!("{:02} {}.{}", track, title, format.ext()));
+            self.track(&path, format, &tags)?;
+        }
+
+        let cover: Vec<u8> = (0..64).map(|_| self.rng.gen()).collect();
+        fs::write(self.root.join(dir).join("cover.jpg"), cover)?;
+        fs::write(
+            self.root.join(dir).join("notes.txt"),
+            "ripped by gen_library\n",
+        )?;
+
+        self.library.albums.push(dir.to_owned());
+        Ok(())
+    }
+
+    fn track(&mut self, path: &Path, format: Format, tags: &Tags) -> io::Result<()> {
+        let frequency = self.rng.gen_range(220.0..880.0);
+        let samples = sine(frequency, self.config.seconds);
+        let bytes = match format {
+            Format::Wav => wav(&samples, tags),
+            Format::Flac => flac(&samples, tags),
+        };
+        fs::write(self.root.join(path), bytes)?;
+        self.library.tracks += 1;
+        Ok(())
+    }
+
+    // An album split over two discs, with the tracks in 'CD1' and 'CD2'.
+    fn multi_disc(&mut self) -> io::Result<()> {
+        let artist = self.name(2);
+        let album = self.name(2) + " (Deluxe Edition)";
+        for disc in 1..=2 {
+            let dir = PathBuf::from(&artist)
+                .join(&album)
+                .join(format!("CD{}", disc));
+            let tracks = self.config.tracks_per_album;
+            self.album(&dir, &artist, &album, tracks)?;
+        }
+        Ok(())
+    }
+
+    // A whole album ripped to one flac file, with a cue sheet of its tracks.
+    fn cue_rip(&mut self) -> io::Result<()> {
+        let artist = self.name(2);
+        let album = self.name(2);
+        let dir = PathBuf::from(&artist).join(format!("{} [FLAC]", album));
+        fs::create_dir_all(self.root.join(&dir))?;
+
+        let tracks = self.config.tracks_per_album;
+        let tags = Tags::new(&artist, &album, &album, 1);
+        let samples = sine(440.0, self.config.seconds * tracks as f64);
+        fs::write(
+            self.root.join(&dir).join("album.flac"),
+            flac(&samples, &tags),
+        )?;
+        self.library.tracks += 1;
+
+        let mut cue = format!(
+            "PERFORMER \"{}\"\nTITLE \"{}\"\nFILE \"album.flac\" WAVE\n",
+            artist, album
+        );
+        for track in 1..=tracks {
+            let start = self.config.seconds * (track - 1) as f64;
+            cue.push_str(&format!(
+                "  TRACK {:02} AUDIO\n    TITLE \"{}\"\n    INDEX 01 {:02}:{:02}:00\n",
+                track,
+                self.name(2),
+                start as u64 / 60,
+                start as u64 % 60,
+            ));
+        }
+        fs::write(self.root.join(&dir).join("album.cue"), cue)?;
+
+        self.library.albums.push(dir);
+        Ok(())
+    }
+
+    // An album with a playable track followed by files that can't be played:
+    // an empty file, a truncated file and random bytes with an audio extension.
+    fn broken(&mut self) -> io::Result<()> {
+        let dir = PathBuf::from("Various Artists").join("Damaged Goods");
+        fs::create_dir_all(self.root.join(&dir))?;
+
+        let tags = Tags::new("Various Artists", "Damaged Goods", "Fine", 1);
+        self.track(&dir.join("01 Fine.wav"), Format::Wav, &tags)?;
+
+        let tags = Tags::new("Various Artists", "Damaged Goods", "Truncated", 3);
+        let mut truncated = flac(&sine(440.0, self.config.seconds), &tags);
+        truncated.truncate(truncated.len() / 2);
+        let noise: Vec<u8> = (0..512).map(|_| self.rng.gen()).collect();
+
+        let broken: [(&str, &[u8]); 3] = [
+            ("02 Empty.mp3", &[]),
+            ("03 Truncated.flac", &truncated),
+            ("04 Noise.ogg", &noise),
+        ];
+        for (name, bytes) in broken {
+            fs::write(self.root.join(&dir).join(name), bytes)?;
+            self.library.broken.push(dir.join(name));
+        }
+
+        self.library.albums.push(dir);
+        Ok(())
+    }
+
+    // An album `depth` directories below the root.
+    fn deep(&mut self) -> io::Result<()> {
+        let mut dir = PathBuf::from("Deep");
+        for level in 1..self.config.depth {
+            dir.push(format!("level {}", level));
+        }
+        let artist = self.name(2);
+        let album = self.name(2);
+        self.album(&dir.join(&album), &artist, &album, 2)
+    }
+
+    // One folder with a great many tracks.
+    fn flat(&mut self) -> io::Result<()> {
+        let dir = PathBuf::from("Singles");
+        fs::create_dir_all(self.root.join(&dir))?;
+
+        for track in 1..=self.config.flat_tracks {
+            let artist = self.name(2);
+            let title = self.name(2);
+            let tags = Tags::new(&artist, "Singles", &title, track);
+            let format = self.format();
+            let path = dir.join(format!("{} - {}.{}", artist, title, format.ext()));
+            // Word lists repeat, so keep the first of any duplicate names.
+            if !self.root.join(&path).exists() {
+                self.track(&path, format, &tags)?;
+            }
+        }
+
+        self.library.albums.push(dir);
+        Ok(())
+    }
+}
+
+struct Tags {
+    artist: String,
+    album: String,
+    title: String,
+    track: usize,
+}
+
+impl Tags {
+    fn new(artist: &str, album: &str, title: &str, track: usize) -> Self {
+        Tags {
+            artist: artist.into(),
+            album: album.into(),
+            title: title.into(),
+            track,
+        }
+    }
+}
+
+// A sine wave at `frequency` Hz, at half of full scale.
+fn sine(frequency: f64, seconds: f64) -> Vec<i16> {
+    let len = (SAMPLE_RATE as f64 * seconds) as usize;
+    (0..len)
+        .map(|i| {
+            let t = i as f64 / SAMPLE_RATE as f64;
+            ((2.0 * PI * frequency * t).sin() * i16::MAX as f64 / 2.0) as i16
+        })
+        .collect()
+}
+
+// A mono 16-bit PCM WAV file, with the tags in a RIFF INFO list.
+fn wav(samples: &[i16], tags: &Tags) -> Vec<u8> {
+    let mut info = b"INFO".to_vec();
+    for (id, value) in [
+        (b"IART", &tags.artist),
+        (b"IPRD", &tags.album),
+        (b"INAM", &tags.title),
+        (b"ITRK", &tags.track.to_string()),
+    ] {
+        let mut value = value.as_bytes().to_vec();
+        value.push(0);
+        info.extend(id);
+        info.extend((value.len() as u32).to_le_bytes());
+        info.extend(&value);
+        if value.len() % 2 == 1 {
+            info.push(0);
+        }
+    }
+
+    let mut fmt = vec![];
+    fmt.extend(1u16.to_le_bytes()); // PCM
+    fmt.extend(1u16.to_le_bytes()); // mono
+    fmt.extend(SAMPLE_RATE.to_le_bytes());
+    fmt.extend((SAMPLE_RATE * 2).to_le_bytes()); // bytes per second
+    fmt.extend(2u16.to_le_bytes()); // bytes per frame
+    fmt.extend(16u16.to_le_bytes()); // bits per sample
+
+    let data: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+
+    let mut body = b"WAVE".to_vec();
+    for (id, chunk) in [(b"fmt ", &fmt), (b"LIST", &info), (b"data", &data)] {
+        body.extend(id);
+        body.extend((chunk.len() as u32).to_le_bytes());
+        body.extend(chunk);
+    }
+
+    let mut wav = b"RIFF".to_vec();
+    wav.extend((body.len() as u32).to_le_bytes());
+    wav.extend(body);
+    wav
+}
+
+// A mono 16-bit FLAC file of verbatim frames, with the tags in a Vorbis
+// comment block. Verbatim frames aren't compressed, but are simple to write.
+fn flac(samples: &[i16], tags: &Tags) -> Vec<u8> {
+    let block_size = samples.len().clamp(16, FLAC_BLOCK_SIZE);
+
+    let mut stream_info = vec![];
+    stream_info.extend((block_size as u16).to_be_bytes()); // min block size
+    stream_info.extend((block_size as u16).to_be_bytes()); // max block size
+    stream_info.extend([0; 6]); // unknown min and max frame sizes
+                                // 20 bits of sample rate, 3 bits of channels - 1, 5 bits of bits per
+                                // sample - 1 and 36 bits of total samples.
+    let packed = (SAMPLE_RATE as u64) << 44 | 15 << 36 | samples.len() as u64;
+    stream_info.extend(packed.to_be_bytes());
+    stream_info.extend([0; 16]); // unknown MD5
+
+    let mut comments = vec![];
+    let vendor = b"gen_library";
+    comments.extend((vendor.len() as u32).to_le_bytes());
+    comments.extend(vendor);
+    let fields = [
+        format!("ARTIST={}", tags.artist),
+        format!("ALBUM={}", tags.album),
+        format!("TITLE={}", tags.title),
+        format!("TRACKNUMBER={}", tags.track),
+    ];
+    comments.extend((fields.len() as u32).to_le_bytes());
+    for field in fields {
+        comments.extend((field.len() as u32).to_le_bytes());
+        comments.extend(field.as_bytes());
+    }
+
+    let mut flac = b"fLaC".to_vec();
+    for (kind, block) in [(0u8, &stream_info), (0x84, &comments)] {
+        flac.push(kind);
+        flac.extend(&(block.len() as u32).to_be_bytes()[1..]);
+        flac.extend(block);
+    }
+
+    for (number, block) in samples.chunks(block_size).enumerate() {
+        let mut frame = vec![0xff, 0xf8];
+        // The block size is in the header, after the frame number, and the
+        // sample rate is 8 kHz.
+        frame.push(0x74);
+        // Mono, 16 bits per sample.
+        frame.push(0x08);
+        frame.extend(utf8_number(number as u32));
+        frame.extend(((block.len() - 1) as u16).to_be_bytes());
+        frame.push(crc8(&frame));
+
+        // A verbatim subframe.
+        frame.push(0x02);
+        frame.extend(block.iter().flat_map(|s| s.to_be_bytes()));
+        frame.extend(crc16(&frame).to_be_bytes());
+
+        flac.extend(frame);
+    }
+    flac
+}
+
+// The frame number coded as in UTF-8, as FLAC frame headers have it.
+fn utf8_number(number: u32) -> Vec<u8> {
+    let ch = char::from_u32(number).unwrap_or('\0');
+    let mut buf = [0; 4];
+    ch.encode_utf8(&mut buf).as_bytes().to_vec()
+}
+
+// The CRC-8 of a FLAC frame header, with polynomial x^8 + x^2 + x + 1.
+fn crc8(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0, |crc, byte| {
+        (0..8).fold(crc ^ byte, |crc, _| match crc & 0x80 {
+            0 => crc << 1,
+            _ => (crc << 1) ^ 0x07,
+        })
+    })
+}
+
+// The CRC-16 of a FLAC frame, with polynomial x^16 + x^15 + x^2 + 1.
+fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0, |crc, byte| {
+        (0..8).fold(crc ^ ((*byte as u16) << 8), |crc, _| match crc & 0x8000 {
+            0 => crc << 1,
+            _ => (crc << 1) ^ 0x8005,
+        })
+    })
+}
+
+// Writes the paths and contents of the files in `root`, in order, so that two
+// libraries can be compared.
+pub fn fingerprint(root: &Path, out: &mut impl Write) -> io::Result<()> {
+    let mut entries = fs::read_dir(root)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+
+    for path in entries {
+        writeln!(
+            out,
+            "{}",
+            path.strip_prefix(root).unwrap_or(&path).display()
+        )?;
+        match path.is_dir() {
+            true => {
+                let mut inner = vec![];
+                fingerprint(&path, &mut inner)?;
+                out.write_all(&inner)?;
+            }
+            false => out.write_all(&fs::read(&path)?)?,
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_crc_check_values() {
+    assert_eq!(crc8(b"123456789"), 0xf4);
+    assert_eq!(crc16(b"123456789"), 0xfee8);
+}
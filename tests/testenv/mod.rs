@@ -10,6 +10,8 @@ use tempfile::TempDir;
 
 use utils::create_working_dir;
 
+use crate::library::{self, Library, LibraryConfig};
+
 // Environment for the integration tests.
 pub struct TestEnv {
     // Temporary working directory.
@@ -30,6 +32,17 @@ impl TestEnv {
         TestEnv { temp_dir, tap_exe }
     }
 
+    // A test environment with a generated library as the working directory.
+    pub fn with_library(config: &LibraryConfig) -> (TestEnv, Library) {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("tap-tests")
+            .tempdir()
+            .expect("temporary directory");
+        let library = library::generate(temp_dir.path(), config).expect("generated library");
+        let tap_exe = find_exe();
+        (TestEnv { temp_dir, tap_exe }, library)
+    }
+
     // Assert that calling tap with the specified arguments produces the expected error.
     pub fn assert_success(&self, args: &[&str]) {
         let output = self.run_command(".".as_ref(), args);
@@ -50,6 +63,38 @@ impl TestEnv {
         );
     }
 
+    // Assert that profiling the startup with the specified arguments times each of the `phases`.
+    pub fn assert_phases_reached(&self, args: &[&str], phases: &[&str]) {
+        let output = self.run_command(".".as_ref(), args);
+        let stderr = String::from_utf8(output.stderr).expect("profile should be utf8");
+
+        for phase in phases {
+            let row = stderr.lines().find(|line| line.starts_with(phase));
+            assert!(
+                row.is_some_and(|row| !row.contains("skipped")),
+                "\nThe startup profile:\n`{}`\n\
+                does not time the expected phase:\n`{}`\n",
+                stderr,
+                phase
+            );
+        }
+    }
+
+    // Assert that profiling the startup with the specified arguments shows that the audio
+    // output was never opened.
+    pub fn assert_audio_not_opened(&self, args: &[&str]) {
+        let output = self.run_command(".".as_ref(), args);
+        let stderr = String::from_utf8(output.stderr).expect("profile should be utf8");
+        let row = stderr.lines().find(|line| line.starts_with("audio opens"));
+
+        assert!(
+            row.is_some_and(|row| row.ends_with(" 0")),
+            "\nThe startup profile:\n`{}`\n\
+            shows that the audio output was opened\n",
+            stderr
+        );
+    }
+
     pub fn assert_normalized_paths(&self, expected: &[&str]) {
         let output = self.run_command(".".as_ref(), &[]);
         let stderr = normalize(output);
@@ -1,8 +1,11 @@
+#[allow(dead_code)]
+mod library;
 mod testenv;
 #[path = "../src/utils.rs"]
 #[allow(dead_code)]
 mod utils;
 
+use crate::library::LibraryConfig;
 use crate::testenv::TestEnv;
 
 #[test]
@@ -49,6 +52,69 @@ fn test_automate_success() {
     te.assert_success(&["--automate"]);
 }
 
+#[test]
+fn test_profile_startup_dry_run() {
+    let te = TestEnv::new(
+        &["one", "two"],
+        &[
+            ("one/a.mp3", "test_mp3_audio.mp3"),
+            ("two/b.mp3", "test_mp3_audio.mp3"),
+        ],
+        &[],
+    );
+    te.assert_phases_reached(
+        &["--profile-startup", "--dry-run"],
+        &["arg parsing", "config load", "scan", "first render"],
+    );
+    // The fuzzy-finder doesn't open the audio output until an album is played.
+    te.assert_audio_not_opened(&["--profile-startup", "--dry-run"]);
+}
+
+#[test]
+fn test_print_does_not_open_audio() {
+    let te = TestEnv::new(&["one"], &[("one/a.mp3", "test_mp3_audio.mp3")], &[]);
+    te.assert_audio_not_opened(&["--print-default", "--profile-startup"]);
+}
+
+#[test]
+fn test_generated_library_success() {
+    let config = LibraryConfig {
+        flat_tracks: 40,
+        ..Default::default()
+    };
+    let (te, library) = TestEnv::with_library(&config);
+    assert!(!library.broken.is_empty());
+
+    te.assert_success(&[]);
+    te.assert_phases_reached(
+        &["--profile-startup", "--dry-run"],
+        &["arg parsing", "config load", "scan", "first render"],
+    );
+}
+
+#[test]
+fn test_generated_library_is_deterministic() {
+    let config = LibraryConfig {
+        seed: 42,
+        flat_tracks: 20,
+        ..Default::default()
+    };
+    let fingerprint = |config: &LibraryConfig| {
+        let (te, _) = TestEnv::with_library(config);
+        let mut out = vec![];
+        library::fingerprint(te.temp_dir.path(), &mut out).expect("fingerprint");
+        out
+    };
+
+    assert!(fingerprint(&config) == fingerprint(&config));
+
+    let other = LibraryConfig {
+        seed: 43,
+        ..config.to_owned()
+    };
+    assert!(fingerprint(&config) != fingerprint(&other));
+}
+
 #[test]
 fn test_default_is_not_set_error() {
     let te = TestEnv::new(